@@ -0,0 +1,115 @@
+use crate::errors::Result;
+use crate::{Request, Response};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Summary of one request attempt, see [`Client::recent_activity`](crate::Client::recent_activity).
+#[derive(Debug, Clone)]
+pub struct Activity {
+  /// When the attempt finished.
+  pub at: SystemTime,
+  /// The request method.
+  pub method: http::Method,
+  /// The request URI, before redirects.
+  pub uri: http::Uri,
+  /// Which attempt this was, starting at `1`; retries count up.
+  pub attempt: usize,
+  /// How long the attempt took, redirects included.
+  pub elapsed: Duration,
+  /// The final status code, when a response was received.
+  pub status: Option<http::StatusCode>,
+  /// The error message, when the attempt failed.
+  pub error: Option<String>,
+  /// The extensions of the request, such as tags set with
+  /// [`RequestBuilder::extension`](crate::RequestBuilder::extension).
+  pub extensions: http::Extensions,
+}
+
+impl std::fmt::Display for Activity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "#{} {} {} {:?} ",
+      self.attempt, self.method, self.uri, self.elapsed
+    )?;
+    match (&self.status, &self.error) {
+      (_, Some(error)) => write!(f, "error: {}", error),
+      (Some(status), None) => write!(f, "{}", status),
+      (None, None) => Ok(()),
+    }
+  }
+}
+
+/// 最近请求的环形缓冲区，所有克隆的 `Client` 共用
+#[derive(Debug)]
+pub(crate) struct ActivityLog {
+  capacity: usize,
+  entries: Mutex<VecDeque<Activity>>,
+}
+
+impl ActivityLog {
+  pub(crate) fn new(capacity: usize) -> Self {
+    ActivityLog {
+      capacity,
+      entries: Mutex::new(VecDeque::with_capacity(capacity)),
+    }
+  }
+  pub(crate) fn push(
+    &self,
+    request: &Request,
+    result: &Result<Response>,
+    attempt: usize,
+    elapsed: Duration,
+  ) {
+    if self.capacity == 0 {
+      return;
+    }
+    let (status, error) = match result {
+      Ok(response) => (Some(response.status_code()), None),
+      Err(err) => (None, Some(err.to_string())),
+    };
+    let activity = Activity {
+      at: SystemTime::now(),
+      method: request.method().clone(),
+      uri: request.uri().clone(),
+      attempt,
+      elapsed,
+      status,
+      error,
+      extensions: request.extensions().clone(),
+    };
+    if let Ok(mut entries) = self.entries.lock() {
+      if entries.len() == self.capacity {
+        entries.pop_front();
+      }
+      entries.push_back(activity);
+    }
+  }
+  pub(crate) fn snapshot(&self) -> Vec<Activity> {
+    match self.entries.lock() {
+      Ok(entries) => entries.iter().cloned().collect(),
+      Err(_) => Vec::new(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ActivityLog;
+  use crate::{Error, Request};
+  use std::time::Duration;
+
+  #[test]
+  fn keeps_last_entries() {
+    let log = ActivityLog::new(2);
+    let request: Request = http::Request::get("http://example.com/").body("").unwrap().into();
+    for attempt in 1..=3 {
+      log.push(&request, &Err(Error::Other("boom".into())), attempt, Duration::ZERO);
+    }
+    let entries = log.snapshot();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].attempt, 2);
+    assert_eq!(entries[1].error.as_deref(), Some("other"));
+  }
+}
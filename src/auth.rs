@@ -0,0 +1,459 @@
+//! Authentication helpers
+use crate::errors::{new_io_error, Result};
+use crate::Request;
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{HeaderMap, HeaderValue};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "ntlm")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ntlm")))]
+pub use crate::ntlm::NtlmAuth;
+
+static CNONCE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn encode_basic_auth<U, P>(username: U, password: Option<P>) -> HeaderValue
+  where
+    U: std::fmt::Display,
+    P: std::fmt::Display,
+{
+  use base64::prelude::BASE64_STANDARD;
+  use base64::write::EncoderWriter;
+  use std::io::Write;
+
+  let mut buf = b"Basic ".to_vec();
+  {
+    let mut encoder = EncoderWriter::new(&mut buf, &BASE64_STANDARD);
+    let _ = write!(encoder, "{username}:");
+    if let Some(password) = password {
+      let _ = write!(encoder, "{password}");
+    }
+  }
+  let mut header = HeaderValue::from_bytes(&buf).expect("base64 is always valid HeaderValue");
+  header.set_sensitive(true);
+  header
+}
+
+/// Credentials attached automatically to requests targeting a given host.
+///
+/// Registered with [`ClientBuilder::auth_for_host`](crate::ClientBuilder::auth_for_host).
+#[derive(Clone, PartialEq)]
+pub enum Credentials {
+  /// HTTP basic authentication
+  Basic {
+    /// username
+    username: String,
+    /// optional password
+    password: Option<String>,
+  },
+  /// HTTP bearer authentication
+  Bearer(String),
+  /// HTTP digest authentication
+  Digest(DigestAuth),
+  /// NTLM authentication
+  #[cfg(feature = "ntlm")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "ntlm")))]
+  Ntlm(NtlmAuth),
+}
+
+impl std::fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Credentials::Basic { username, .. } => f
+        .debug_struct("Basic")
+        .field("username", username)
+        .finish_non_exhaustive(),
+      Credentials::Bearer(_) => f.write_str("Bearer"),
+      Credentials::Digest(digest) => digest.fmt(f),
+      #[cfg(feature = "ntlm")]
+      Credentials::Ntlm(ntlm) => ntlm.fmt(f),
+    }
+  }
+}
+
+impl Credentials {
+  /// Basic credentials
+  pub fn basic<U: Into<String>>(username: U, password: Option<String>) -> Self {
+    Credentials::Basic {
+      username: username.into(),
+      password,
+    }
+  }
+  /// Bearer token
+  pub fn bearer<T: Into<String>>(token: T) -> Self {
+    Credentials::Bearer(token.into())
+  }
+  /// Digest credentials
+  pub fn digest<U: Into<String>, P: Into<String>>(username: U, password: P) -> Self {
+    Credentials::Digest(DigestAuth::new(username, password))
+  }
+  /// NTLM credentials, the username may carry the domain as `DOMAIN\user`.
+  #[cfg(feature = "ntlm")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "ntlm")))]
+  pub fn ntlm<U: Into<String>, P: Into<String>>(username: U, password: P) -> Self {
+    Credentials::Ntlm(NtlmAuth::new(username, password))
+  }
+  /// 请求本身没有认证信息时才添加，返回是否添加
+  pub(crate) fn apply(&self, request: &mut Request) -> Result<bool> {
+    if request.headers().contains_key(AUTHORIZATION)
+      || request.extensions().get::<DigestAuth>().is_some()
+    {
+      return Ok(false);
+    }
+    #[cfg(feature = "ntlm")]
+    if request.extensions().get::<NtlmAuth>().is_some() {
+      return Ok(false);
+    }
+    match self {
+      Credentials::Basic { username, password } => {
+        request
+          .headers_mut()
+          .insert(AUTHORIZATION, encode_basic_auth(username, password.as_ref()));
+      }
+      Credentials::Bearer(token) => {
+        let mut value = HeaderValue::try_from(format!("Bearer {token}"))?;
+        value.set_sensitive(true);
+        request.headers_mut().insert(AUTHORIZATION, value);
+      }
+      Credentials::Digest(digest) => {
+        request.extensions_mut().insert(digest.clone());
+      }
+      #[cfg(feature = "ntlm")]
+      Credentials::Ntlm(ntlm) => {
+        request.extensions_mut().insert(ntlm.clone());
+      }
+    }
+    Ok(true)
+  }
+  /// 移除 apply 添加的认证信息
+  pub(crate) fn remove(request: &mut Request) {
+    request.headers_mut().remove(AUTHORIZATION);
+    request.extensions_mut().remove::<DigestAuth>();
+    #[cfg(feature = "ntlm")]
+    request.extensions_mut().remove::<NtlmAuth>();
+  }
+}
+
+/// `host` 可以是 `example.com` 或带端口的 `example.com:8080`
+pub(crate) fn host_matches(host: &str, uri: &http::Uri) -> bool {
+  let uri_host = match uri.host() {
+    Some(h) => h,
+    None => return false,
+  };
+  if host.eq_ignore_ascii_case(uri_host) {
+    return true;
+  }
+  let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+    Some("https") => 443,
+    _ => 80,
+  });
+  host.eq_ignore_ascii_case(&format!("{}:{}", uri_host, port))
+}
+
+/// Digest access authentication credentials.
+///
+/// Attached to a request with [`RequestBuilder::digest_auth`](crate::RequestBuilder::digest_auth),
+/// the client answers a `401 Unauthorized` carrying a `WWW-Authenticate: Digest` challenge by
+/// resending the request once with the computed `Authorization` header.
+///
+/// Supports the `MD5`, `MD5-sess`, `SHA-256` and `SHA-256-sess` algorithms with `qop=auth`
+/// (or no `qop` for RFC 2069 servers).
+#[derive(Clone, PartialEq)]
+pub struct DigestAuth {
+  username: String,
+  password: String,
+}
+
+impl std::fmt::Debug for DigestAuth {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("DigestAuth")
+      .field("username", &self.username)
+      .finish()
+  }
+}
+
+impl DigestAuth {
+  /// new digest credentials
+  pub fn new<U: Into<String>, P: Into<String>>(username: U, password: P) -> Self {
+    DigestAuth {
+      username: username.into(),
+      password: password.into(),
+    }
+  }
+  /// Build the `Authorization` header answering the `Digest` challenge found in `headers`.
+  ///
+  /// Returns `Ok(None)` when the headers carry no supported digest challenge.
+  pub fn respond(&self, request: &Request, headers: &HeaderMap) -> Result<Option<HeaderValue>> {
+    let challenge = match headers
+      .get_all(WWW_AUTHENTICATE)
+      .iter()
+      .filter_map(|v| v.to_str().ok())
+      .find_map(DigestChallenge::parse)
+    {
+      Some(challenge) => challenge,
+      None => return Ok(None),
+    };
+    let uri = request
+      .uri()
+      .path_and_query()
+      .map(|p| p.as_str())
+      .unwrap_or("/");
+    let mut header = HeaderValue::try_from(self.authorization(
+      &challenge,
+      request.method().as_str(),
+      uri,
+      &cnonce(),
+    )?)?;
+    header.set_sensitive(true);
+    Ok(Some(header))
+  }
+  fn authorization(
+    &self,
+    challenge: &DigestChallenge,
+    method: &str,
+    uri: &str,
+    cnonce: &str,
+  ) -> Result<String> {
+    let (hash, sess): (fn(&str) -> String, bool) =
+      match challenge.algorithm.to_ascii_uppercase().as_str() {
+        "MD5" => (hex_md5, false),
+        "MD5-SESS" => (hex_md5, true),
+        "SHA-256" => (hex_sha256, false),
+        "SHA-256-SESS" => (hex_sha256, true),
+        _ => {
+          return Err(new_io_error(
+            std::io::ErrorKind::Unsupported,
+            "unsupported digest algorithm",
+          ));
+        }
+      };
+    let nc = "00000001";
+    let mut ha1 = hash(&format!(
+      "{}:{}:{}",
+      self.username, challenge.realm, self.password
+    ));
+    if sess {
+      ha1 = hash(&format!("{}:{}:{}", ha1, challenge.nonce, cnonce));
+    }
+    let ha2 = hash(&format!("{}:{}", method, uri));
+    let mut authorization = format!(
+      "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}",
+      self.username, challenge.realm, challenge.nonce, uri, challenge.algorithm
+    );
+    let response = if challenge.qop_auth {
+      authorization.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+      hash(&format!(
+        "{}:{}:{}:{}:auth:{}",
+        ha1, challenge.nonce, nc, cnonce, ha2
+      ))
+    } else {
+      hash(&format!("{}:{}:{}", ha1, challenge.nonce, ha2))
+    };
+    authorization.push_str(&format!(", response=\"{}\"", response));
+    if let Some(opaque) = &challenge.opaque {
+      authorization.push_str(&format!(", opaque=\"{}\"", opaque));
+    }
+    Ok(authorization)
+  }
+}
+
+/// `WWW-Authenticate: Digest` challenge
+#[derive(Debug, Clone, PartialEq)]
+struct DigestChallenge {
+  realm: String,
+  nonce: String,
+  opaque: Option<String>,
+  algorithm: String,
+  qop_auth: bool,
+}
+
+impl DigestChallenge {
+  fn parse(value: &str) -> Option<Self> {
+    let (scheme, params) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("digest") {
+      return None;
+    }
+    let params = parse_auth_params(params);
+    let qop_auth = match params.get("qop") {
+      // 服务器只支持auth-int时无法处理
+      Some(qop) => {
+        if qop.split(',').any(|q| q.trim().eq_ignore_ascii_case("auth")) {
+          true
+        } else {
+          return None;
+        }
+      }
+      None => false,
+    };
+    Some(DigestChallenge {
+      realm: params.get("realm").cloned().unwrap_or_default(),
+      nonce: params.get("nonce")?.clone(),
+      opaque: params.get("opaque").cloned(),
+      algorithm: params
+        .get("algorithm")
+        .cloned()
+        .unwrap_or_else(|| "MD5".to_string()),
+      qop_auth,
+    })
+  }
+}
+
+/// 解析 `key=value, key="quoted, value"` 形式的认证参数
+fn parse_auth_params(params: &str) -> HashMap<String, String> {
+  let mut map = HashMap::new();
+  let mut chars = params.chars().peekable();
+  loop {
+    while matches!(chars.peek(), Some(c) if *c == ',' || c.is_whitespace()) {
+      chars.next();
+    }
+    let mut key = String::new();
+    while let Some(c) = chars.next_if(|c| *c != '=' && *c != ',') {
+      key.push(c);
+    }
+    if key.is_empty() {
+      break;
+    }
+    let mut value = String::new();
+    if chars.next_if_eq(&'=').is_some() {
+      if chars.next_if_eq(&'"').is_some() {
+        while let Some(c) = chars.next() {
+          match c {
+            '\\' => {
+              if let Some(escaped) = chars.next() {
+                value.push(escaped);
+              }
+            }
+            '"' => break,
+            _ => value.push(c),
+          }
+        }
+      } else {
+        while let Some(c) = chars.next_if(|c| *c != ',') {
+          value.push(c);
+        }
+      }
+    }
+    map.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+  }
+  map
+}
+
+fn hex_md5(input: &str) -> String {
+  to_hex(&Md5::digest(input.as_bytes()))
+}
+
+fn hex_sha256(input: &str) -> String {
+  to_hex(&Sha256::digest(input.as_bytes()))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn cnonce() -> String {
+  let nanos = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_nanos())
+    .unwrap_or_default();
+  let counter = CNONCE_COUNTER.fetch_add(1, Ordering::Relaxed);
+  hex_md5(&format!("{}:{}:{}", nanos, counter, std::process::id()))[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn host_matching() {
+    let uri = http::Uri::from_static("https://Api.Example.com/v1");
+    assert!(host_matches("api.example.com", &uri));
+    assert!(host_matches("api.example.com:443", &uri));
+    assert!(!host_matches("api.example.com:8443", &uri));
+    assert!(!host_matches("example.com", &uri));
+  }
+
+  #[test]
+  fn rfc2617_md5_qop_auth() {
+    let challenge = DigestChallenge::parse(
+      r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+    )
+      .unwrap();
+    let auth = DigestAuth::new("Mufasa", "Circle Of Life");
+    let value = auth
+      .authorization(&challenge, "GET", "/dir/index.html", "0a4f113b")
+      .unwrap();
+    assert!(value.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+    assert!(value.contains(r#"opaque="5ccc069c403ebaf9f0171e9517f40e41""#));
+  }
+
+  #[test]
+  fn rfc7616_sha256() {
+    let challenge = DigestChallenge::parse(
+      r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=SHA-256, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+    )
+      .unwrap();
+    let auth = DigestAuth::new("Mufasa", "Circle of Life");
+    let value = auth
+      .authorization(&challenge, "GET", "/dir/index.html", "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ")
+      .unwrap();
+    assert!(value.contains(
+      r#"response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1""#
+    ));
+  }
+
+  #[test]
+  fn digest_not_sent_after_cross_host_redirect() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    // 第三方主机对每个请求都发出 Digest 质询，记下收到的请求
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let third = TcpListener::bind("127.0.0.1:0").unwrap();
+    let third_addr = third.local_addr().unwrap();
+    let requests = seen.clone();
+    std::thread::spawn(move || {
+      for stream in third.incoming() {
+        let Ok(mut stream) = stream else { break };
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = stream.read(&mut buf) {
+          if n == 0 {
+            break;
+          }
+          requests.lock().unwrap().push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+          let challenge = "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Digest realm=\"x\", nonce=\"abc\", qop=\"auth\"\r\nContent-Length: 0\r\n\r\n";
+          if stream.write_all(challenge.as_bytes()).is_err() {
+            break;
+          }
+        }
+      }
+    });
+    let origin = TcpListener::bind("127.0.0.1:0").unwrap();
+    let origin_addr = origin.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let (mut stream, _) = origin.accept().unwrap();
+      let mut buf = [0u8; 4096];
+      let _ = stream.read(&mut buf);
+      let redirect = format!(
+        "HTTP/1.1 302 Found\r\nLocation: http://{}/\r\nContent-Length: 0\r\n\r\n",
+        third_addr
+      );
+      let _ = stream.write_all(redirect.as_bytes());
+    });
+    let client = crate::Client::builder()
+      .timeout(std::time::Duration::from_secs(5))
+      .build()
+      .unwrap();
+    let response = client
+      .get(format!("http://{}/", origin_addr))
+      .digest_auth("alice", "secret")
+      .send()
+      .unwrap();
+    assert_eq!(response.status_code(), http::StatusCode::UNAUTHORIZED);
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert!(!seen[0].contains("authorization"));
+  }
+}
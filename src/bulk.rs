@@ -0,0 +1,271 @@
+use crate::errors::{new_io_error, Result};
+use crate::{Client, Response};
+use http::StatusCode;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// 检查点文件的第一行
+const HEADER: &str = "# slinger checkpoint v1";
+
+/// The recorded outcome of one target of a [`BulkScan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completed {
+  /// The target as given to [`Client::bulk`]
+  pub target: String,
+  /// The status code of the response, when one was received
+  pub status: Option<StatusCode>,
+  /// The error message, when the request failed
+  pub error: Option<String>,
+}
+
+impl Completed {
+  fn new(target: String, result: &Result<Response>) -> Completed {
+    match result {
+      Ok(response) => Completed {
+        target,
+        status: Some(response.status_code()),
+        error: None,
+      },
+      Err(err) => Completed {
+        target,
+        status: None,
+        error: Some(err.to_string()),
+      },
+    }
+  }
+}
+
+/// Sends one request per target and records the progress in a checkpoint
+/// file, so a scan of many hours survives a crash or a restart.
+///
+/// The checkpoint is a text file with one line per target: `done` with the
+/// status code, `failed` with the error message, or `pending`. It is
+/// rewritten every [`BulkScan::checkpoint`] interval and at the end, through a
+/// temporary file renamed over it, so a crash leaves the last complete
+/// checkpoint. [`BulkScan::resume_from`] loads one and skips the targets it
+/// records as completed.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// let client = slinger::Client::new();
+/// let targets = ["https://a.example/", "https://b.example/"];
+/// let completed = client
+///   .bulk(targets)
+///   .checkpoint("scan.checkpoint", 100)
+///   .resume_from("scan.checkpoint")?
+///   .run(|client, target| {
+///     let response = client.get(target).send();
+///     if let Ok(response) = &response {
+///       println!("{} {}", target, response.status_code());
+///     }
+///     response
+///   })?;
+/// println!("{} targets completed", completed.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BulkScan {
+  client: Client,
+  targets: Vec<String>,
+  checkpoint: Option<(PathBuf, usize)>,
+  retry_failed: bool,
+  resumed: HashMap<String, Completed>,
+}
+
+impl BulkScan {
+  pub(crate) fn new(client: Client, targets: Vec<String>) -> BulkScan {
+    BulkScan {
+      client,
+      targets,
+      checkpoint: None,
+      retry_failed: false,
+      resumed: HashMap::new(),
+    }
+  }
+  /// Write the checkpoint to `path` after every `interval` completed targets,
+  /// and once the scan ends. An `interval` of `0` only writes it at the end.
+  pub fn checkpoint<P: Into<PathBuf>>(mut self, path: P, interval: usize) -> BulkScan {
+    self.checkpoint = Some((path.into(), interval));
+    self
+  }
+  /// Send the targets recorded as `failed` by a resumed checkpoint again,
+  /// instead of keeping their error.
+  ///
+  /// Default is `false`.
+  pub fn retry_failed(mut self, enable: bool) -> BulkScan {
+    self.retry_failed = enable;
+    self
+  }
+  /// Load the completed targets of a checkpoint written by an earlier run,
+  /// they are not sent again and are returned by [`BulkScan::run`] as
+  /// recorded.
+  ///
+  /// A missing file is not an error, the scan then starts from scratch.
+  ///
+  /// # Errors
+  ///
+  /// Fails when the file can not be read or is not a checkpoint.
+  pub fn resume_from<P: AsRef<Path>>(mut self, path: P) -> Result<BulkScan> {
+    let file = match File::open(path) {
+      Ok(file) => file,
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(self),
+      Err(err) => return Err(err.into()),
+    };
+    for completed in read_checkpoint(BufReader::new(file))? {
+      self.resumed.insert(completed.target.clone(), completed);
+    }
+    Ok(self)
+  }
+  /// Send the targets in order with `send`, skipping the ones completed in a
+  /// resumed checkpoint, and return the outcome of every target.
+  ///
+  /// # Errors
+  ///
+  /// Fails when the checkpoint can not be written; the targets completed so
+  /// far are then in the last checkpoint written.
+  pub fn run<F>(self, mut send: F) -> Result<Vec<Completed>>
+    where
+      F: FnMut(&Client, &str) -> Result<Response>,
+  {
+    let mut outcomes: Vec<Option<Completed>> = self
+      .targets
+      .iter()
+      .map(|target| {
+        self
+          .resumed
+          .get(target)
+          .filter(|completed| !(self.retry_failed && completed.error.is_some()))
+          .cloned()
+      })
+      .collect();
+    let mut since_checkpoint = 0;
+    for (index, target) in self.targets.iter().enumerate() {
+      if outcomes[index].is_some() {
+        continue;
+      }
+      let result = send(&self.client, target);
+      outcomes[index] = Some(Completed::new(target.clone(), &result));
+      since_checkpoint += 1;
+      if let Some((path, interval)) = &self.checkpoint {
+        if *interval > 0 && since_checkpoint >= *interval {
+          write_checkpoint(path, &self.targets, &outcomes)?;
+          since_checkpoint = 0;
+        }
+      }
+    }
+    if let Some((path, _)) = &self.checkpoint {
+      write_checkpoint(path, &self.targets, &outcomes)?;
+    }
+    Ok(outcomes.into_iter().flatten().collect())
+  }
+}
+
+/// 先写临时文件再改名，崩溃时留下的总是完整的检查点
+fn write_checkpoint(path: &Path, targets: &[String], outcomes: &[Option<Completed>]) -> Result<()> {
+  let mut tmp = path.as_os_str().to_owned();
+  tmp.push(".tmp");
+  let tmp = PathBuf::from(tmp);
+  let mut file = std::io::BufWriter::new(File::create(&tmp)?);
+  writeln!(file, "{}", HEADER)?;
+  for (target, outcome) in targets.iter().zip(outcomes) {
+    let target = one_line(target);
+    match outcome {
+      Some(Completed { status: Some(status), .. }) => writeln!(file, "done\t{}\t{}", target, status.as_u16())?,
+      Some(Completed { error, .. }) => {
+        writeln!(file, "failed\t{}\t{}", target, one_line(error.as_deref().unwrap_or_default()))?
+      }
+      None => writeln!(file, "pending\t{}", target)?,
+    }
+  }
+  file.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+  std::fs::rename(&tmp, path)?;
+  Ok(())
+}
+
+/// 制表符和换行是文件的分隔符
+fn one_line(value: &str) -> String {
+  value.replace(['\t', '\r', '\n'], " ")
+}
+
+fn read_checkpoint<R: BufRead>(reader: R) -> Result<Vec<Completed>> {
+  let invalid = || new_io_error(ErrorKind::InvalidData, "invalid checkpoint file");
+  let mut lines = reader.lines();
+  if lines.next().transpose()?.as_deref() != Some(HEADER) {
+    return Err(invalid());
+  }
+  let mut completed = Vec::new();
+  for line in lines {
+    let line = line?;
+    let mut fields = line.splitn(3, '\t');
+    let (kind, target, value) = (fields.next(), fields.next(), fields.next());
+    match (kind, target) {
+      (Some("done"), Some(target)) => {
+        let status = value
+          .and_then(|value| StatusCode::from_bytes(value.as_bytes()).ok())
+          .ok_or_else(invalid)?;
+        completed.push(Completed {
+          target: target.to_string(),
+          status: Some(status),
+          error: None,
+        });
+      }
+      (Some("failed"), Some(target)) => completed.push(Completed {
+        target: target.to_string(),
+        status: None,
+        error: Some(value.unwrap_or_default().to_string()),
+      }),
+      (Some("pending"), Some(_)) => {}
+      (Some(""), None) => {}
+      _ => return Err(invalid()),
+    }
+  }
+  Ok(completed)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::errors::new_io_error;
+  use crate::{Client, Response};
+
+  #[test]
+  fn checkpoint_and_resume() {
+    let path = std::env::temp_dir().join(format!("slinger-checkpoint-{}", std::process::id()));
+    // 第三个目标时进程崩溃，前两个已经写入检查点
+    let crashed = std::panic::catch_unwind(|| {
+      Client::new()
+        .bulk(["a", "b\tc", "d"])
+        .checkpoint(&path, 1)
+        .run(|_, target| match target {
+          "a" => Ok(Response::from(http::Response::builder().status(204).body("").unwrap())),
+          "d" => panic!("crash"),
+          _ => Err(new_io_error(std::io::ErrorKind::TimedOut, "timed\nout")),
+        })
+    });
+    assert!(crashed.is_err());
+    let checkpoint = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+      checkpoint,
+      "# slinger checkpoint v1\ndone\ta\t204\nfailed\tb c\ttimed out\npending\td\n"
+    );
+    let mut sent = Vec::new();
+    let completed = Client::new()
+      .bulk(["a", "b c", "d"])
+      .resume_from(&path)
+      .unwrap()
+      .run(|_, target| {
+        sent.push(target.to_string());
+        Err(new_io_error(std::io::ErrorKind::TimedOut, "again"))
+      })
+      .unwrap();
+    assert_eq!(sent, ["d"]);
+    assert_eq!(completed.len(), 3);
+    assert_eq!(completed[0].status, Some(http::StatusCode::NO_CONTENT));
+    assert_eq!(completed[1].error.as_deref(), Some("timed out"));
+    let _ = std::fs::remove_file(&path);
+  }
+}
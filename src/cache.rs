@@ -0,0 +1,512 @@
+use crate::errors::Result;
+use crate::retry::{format_http_date, parse_http_date};
+use crate::sse::LiveBody;
+use crate::{Middleware, Next, Request, Response};
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// An entity tag from an `ETag` header, see [`Response::etag`](crate::Response::etag).
+///
+/// Pass it to [`RequestBuilder::if_none_match`](crate::RequestBuilder::if_none_match)
+/// to get a `304 Not Modified` while the resource is unchanged.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ETag {
+  raw: String,
+  weak: bool,
+}
+
+impl ETag {
+  /// Parse a quoted entity tag such as `"abc"` or `W/"abc"`.
+  pub fn parse(value: &str) -> Option<ETag> {
+    let value = value.trim();
+    let (weak, quoted) = match value.strip_prefix("W/") {
+      Some(quoted) => (true, quoted),
+      None => (false, value),
+    };
+    let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    if tag.contains('"') {
+      return None;
+    }
+    Some(ETag {
+      raw: value.to_string(),
+      weak,
+    })
+  }
+  /// The tag without the quotes and the weak prefix.
+  pub fn tag(&self) -> &str {
+    let quoted = self.raw.strip_prefix("W/").unwrap_or(&self.raw);
+    &quoted[1..quoted.len() - 1]
+  }
+  /// Whether the tag is weak, that is prefixed with `W/`.
+  pub fn is_weak(&self) -> bool {
+    self.weak
+  }
+}
+
+impl AsRef<str> for ETag {
+  /// The tag as sent in the header, quotes included.
+  fn as_ref(&self) -> &str {
+    &self.raw
+  }
+}
+
+impl fmt::Display for ETag {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.raw)
+  }
+}
+
+/// The directives of the `Cache-Control` headers of a response, see
+/// [`Response::cache_control`](crate::Response::cache_control).
+///
+/// Unknown directives are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+  /// `max-age`
+  pub max_age: Option<Duration>,
+  /// `s-maxage`
+  pub s_maxage: Option<Duration>,
+  /// `stale-while-revalidate`
+  pub stale_while_revalidate: Option<Duration>,
+  /// `no-cache`
+  pub no_cache: bool,
+  /// `no-store`
+  pub no_store: bool,
+  /// `no-transform`
+  pub no_transform: bool,
+  /// `must-revalidate`
+  pub must_revalidate: bool,
+  /// `private`
+  pub private: bool,
+  /// `public`
+  pub public: bool,
+  /// `immutable`
+  pub immutable: bool,
+}
+
+impl CacheControl {
+  /// Parse the directives of every `Cache-Control` header in `headers`,
+  /// `None` when there is none.
+  pub fn from_headers(headers: &HeaderMap) -> Option<CacheControl> {
+    let mut values = headers.get_all(http::header::CACHE_CONTROL).iter().peekable();
+    values.peek()?;
+    let mut cache_control = CacheControl::default();
+    for value in values {
+      let value = String::from_utf8_lossy(value.as_bytes());
+      for directive in split_directives(&value) {
+        let (name, argument) = match directive.split_once('=') {
+          Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+          None => (directive.trim(), None),
+        };
+        let seconds = argument
+          .and_then(|v| v.parse::<u64>().ok())
+          .map(Duration::from_secs);
+        match name.to_ascii_lowercase().as_str() {
+          "max-age" => cache_control.max_age = seconds,
+          "s-maxage" => cache_control.s_maxage = seconds,
+          "stale-while-revalidate" => cache_control.stale_while_revalidate = seconds,
+          "no-cache" => cache_control.no_cache = true,
+          "no-store" => cache_control.no_store = true,
+          "no-transform" => cache_control.no_transform = true,
+          "must-revalidate" => cache_control.must_revalidate = true,
+          "private" => cache_control.private = true,
+          "public" => cache_control.public = true,
+          "immutable" => cache_control.immutable = true,
+          _ => {}
+        }
+      }
+    }
+    Some(cache_control)
+  }
+}
+
+/// 按逗号拆分指令，引号里的逗号不算，例如 `private="a, b"`
+fn split_directives(value: &str) -> Vec<&str> {
+  let mut directives = Vec::new();
+  let mut quoted = false;
+  let mut start = 0;
+  for (i, c) in value.char_indices() {
+    match c {
+      '"' => quoted = !quoted,
+      ',' if !quoted => {
+        directives.push(&value[start..i]);
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+  directives.push(&value[start..]);
+  directives.retain(|d| !d.trim().is_empty());
+  directives
+}
+
+/// A response kept by the HTTP cache of a `Client`, see [`CacheStore`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheEntry {
+  /// The response, with the headers of the last revalidation merged in
+  pub response: Response,
+  /// When the response was received or last revalidated
+  pub stored: SystemTime,
+  /// The request headers named by the `Vary` header of the response
+  #[cfg_attr(feature = "serde", serde(with = "http_serde::header_map"))]
+  pub vary: HeaderMap,
+}
+
+/// Where the HTTP cache of a `Client` keeps responses, see
+/// [`ClientBuilder::cache`](crate::ClientBuilder::cache).
+///
+/// Entries are keyed by the URL of the request. [`MemoryCache`] keeps them in
+/// memory; implement this trait to keep them on disk or in a shared store,
+/// with the **serde** feature [`CacheEntry`] can be serialized.
+pub trait CacheStore: Send + Sync + 'static {
+  /// The entry stored for `key`.
+  fn get(&self, key: &str) -> Option<CacheEntry>;
+  /// Store `entry` for `key`, replacing the previous one.
+  fn put(&self, key: &str, entry: CacheEntry);
+  /// Remove the entry of `key`.
+  fn remove(&self, key: &str);
+}
+
+impl<S: CacheStore + ?Sized> CacheStore for Arc<S> {
+  fn get(&self, key: &str) -> Option<CacheEntry> {
+    (**self).get(key)
+  }
+  fn put(&self, key: &str, entry: CacheEntry) {
+    (**self).put(key, entry)
+  }
+  fn remove(&self, key: &str) {
+    (**self).remove(key)
+  }
+}
+
+/// A [`CacheStore`] in memory, shared by its clones.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::MemoryCache;
+///
+/// let cache = MemoryCache::new().capacity(1000);
+/// let client = slinger::Client::builder().cache(cache.clone()).build()?;
+/// client.get("https://example.com/app.js").send()?;
+/// // answered from the cache while fresh, or revalidated with a 304
+/// client.get("https://example.com/app.js").send()?;
+/// println!("{} responses cached", cache.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemoryCache {
+  entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+  capacity: Option<usize>,
+}
+
+impl MemoryCache {
+  /// An empty cache without a limit on the number of entries.
+  pub fn new() -> Self {
+    MemoryCache::default()
+  }
+  /// Keep at most `entries` responses, dropping the least recently stored
+  /// one when full.
+  pub fn capacity(mut self, entries: usize) -> Self {
+    self.capacity = Some(entries);
+    self
+  }
+  /// The number of responses in the cache.
+  pub fn len(&self) -> usize {
+    self.entries.lock().map_or(0, |entries| entries.len())
+  }
+  /// Returns true if the cache holds no response.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+  /// Remove every response.
+  pub fn clear(&self) {
+    if let Ok(mut entries) = self.entries.lock() {
+      entries.clear();
+    }
+  }
+}
+
+impl CacheStore for MemoryCache {
+  fn get(&self, key: &str) -> Option<CacheEntry> {
+    self.entries.lock().ok()?.get(key).cloned()
+  }
+  fn put(&self, key: &str, entry: CacheEntry) {
+    let Ok(mut entries) = self.entries.lock() else {
+      return;
+    };
+    if let Some(capacity) = self.capacity {
+      while entries.len() >= capacity && !entries.contains_key(key) {
+        let oldest = entries
+          .iter()
+          .min_by_key(|(_, entry)| entry.stored)
+          .map(|(key, _)| key.clone());
+        match oldest {
+          Some(oldest) => entries.remove(&oldest),
+          None => return,
+        };
+      }
+    }
+    entries.insert(key.to_string(), entry);
+  }
+  fn remove(&self, key: &str) {
+    if let Ok(mut entries) = self.entries.lock() {
+      entries.remove(key);
+    }
+  }
+}
+
+/// RFC 7234 的私有缓存，作为中间件挂在客户端上
+pub(crate) struct HttpCache<S> {
+  store: S,
+}
+
+impl<S: CacheStore> HttpCache<S> {
+  pub(crate) fn new(store: S) -> Self {
+    HttpCache { store }
+  }
+}
+
+impl<S: CacheStore> Middleware for HttpCache<S> {
+  fn handle(&self, mut request: Request, next: Next<'_>) -> Result<Response> {
+    let key = request.uri().to_string();
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+      let response = next.run(request)?;
+      // 修改资源的请求成功后，缓存里的旧响应作废
+      if response.status_code().is_success() || response.status_code().is_redirection() {
+        self.store.remove(&key);
+      }
+      return Ok(response);
+    }
+    let directives = CacheControl::from_headers(request.headers()).unwrap_or_default();
+    // 调用方自己带了条件请求头时，304 要交给调用方处理
+    let conditional = [
+      http::header::IF_NONE_MATCH,
+      http::header::IF_MODIFIED_SINCE,
+      http::header::RANGE,
+    ]
+    .iter()
+    .any(|name| request.headers().contains_key(name));
+    if directives.no_store || conditional || request.method() == Method::HEAD {
+      return next.run(request);
+    }
+    let cached = self
+      .store
+      .get(&key)
+      .filter(|entry| vary_matches(entry, request.headers()));
+    let now = SystemTime::now();
+    if let Some(entry) = &cached {
+      let age = current_age(entry, now);
+      if !directives.no_cache && is_fresh(&entry.response, age) {
+        return Ok(with_age(entry.response.clone(), age));
+      }
+      if let Some(etag) = entry.response.headers().get(http::header::ETAG) {
+        request.headers_mut().insert(http::header::IF_NONE_MATCH, etag.clone());
+      }
+      if let Some(time) = entry.response.last_modified() {
+        if let Ok(value) = HeaderValue::try_from(format_http_date(time)) {
+          request.headers_mut().insert(http::header::IF_MODIFIED_SINCE, value);
+        }
+      }
+    }
+    let sent = request.headers().clone();
+    let response = next.run(request)?;
+    if let (Some(mut entry), StatusCode::NOT_MODIFIED) = (cached, response.status_code()) {
+      for (name, value) in response.headers() {
+        if name != http::header::CONTENT_LENGTH && name != http::header::TRANSFER_ENCODING {
+          entry.response.headers_mut().insert(name, value.clone());
+        }
+      }
+      entry.stored = now;
+      let fresh = entry.response.clone();
+      self.store.put(&key, entry);
+      return Ok(fresh);
+    }
+    if is_storable(&response) && response.uri().to_string() == key {
+      if let Some(vary) = vary(&response, &sent) {
+        self.store.put(
+          &key,
+          CacheEntry {
+            response: response.clone(),
+            stored: now,
+            vary,
+          },
+        );
+      }
+    }
+    Ok(response)
+  }
+}
+
+/// 响应的 `Vary` 指定的请求头，`Vary: *` 时不能缓存
+fn vary(response: &Response, request: &HeaderMap) -> Option<HeaderMap> {
+  let mut vary = HeaderMap::new();
+  for value in response.headers().get_all(http::header::VARY) {
+    for name in value.to_str().ok()?.split(',').map(str::trim) {
+      if name == "*" {
+        return None;
+      }
+      let Ok(name) = http::HeaderName::try_from(name) else {
+        continue;
+      };
+      for value in request.get_all(&name) {
+        vary.append(name.clone(), value.clone());
+      }
+      vary.entry(name).or_insert_with(|| HeaderValue::from_static(""));
+    }
+  }
+  Some(vary)
+}
+
+fn vary_matches(entry: &CacheEntry, request: &HeaderMap) -> bool {
+  entry.vary.keys().all(|name| {
+    let stored: Vec<_> = entry.vary.get_all(name).iter().filter(|v| !v.is_empty()).collect();
+    let sent: Vec<_> = request.get_all(name).iter().collect();
+    stored == sent
+  })
+}
+
+/// 没有显式过期时间又没有校验值的响应存了也用不上
+fn is_storable(response: &Response) -> bool {
+  let cacheable_status = matches!(
+    response.status_code().as_u16(),
+    200 | 203 | 204 | 300 | 301 | 404 | 405 | 410 | 414 | 501
+  );
+  let directives = CacheControl::from_headers(response.headers()).unwrap_or_default();
+  let headers = response.headers();
+  // 响应体还在连接上时没有东西可以缓存
+  cacheable_status
+    && response.extensions().get::<LiveBody>().is_none()
+    && !directives.no_store
+    && (directives.max_age.is_some()
+    || headers.contains_key(http::header::EXPIRES)
+    || headers.contains_key(http::header::ETAG)
+    || headers.contains_key(http::header::LAST_MODIFIED))
+}
+
+/// 收到时的 `Age` 加上存储后经过的时间
+fn current_age(entry: &CacheEntry, now: SystemTime) -> Duration {
+  let age = entry
+    .response
+    .headers()
+    .get(http::header::AGE)
+    .and_then(|v| v.to_str().ok()?.trim().parse().ok())
+    .map_or(Duration::ZERO, Duration::from_secs);
+  age + now.duration_since(entry.stored).unwrap_or_default()
+}
+
+fn header_date(headers: &HeaderMap, name: http::header::HeaderName) -> Option<SystemTime> {
+  parse_http_date(headers.get(name)?.to_str().ok()?.trim())
+}
+
+/// `max-age`，其次 `Expires`，都没有时按 `Last-Modified` 估算为距 `Date` 的十分之一
+fn freshness_lifetime(response: &Response) -> Duration {
+  let headers = response.headers();
+  let directives = CacheControl::from_headers(headers).unwrap_or_default();
+  if directives.no_cache {
+    return Duration::ZERO;
+  }
+  if let Some(max_age) = directives.max_age {
+    return max_age;
+  }
+  let date = header_date(headers, http::header::DATE);
+  if let Some(expires) = headers.get(http::header::EXPIRES) {
+    // 无法解析的 Expires 表示已经过期
+    let expires = expires.to_str().ok().and_then(|v| parse_http_date(v.trim()));
+    return match (expires, date) {
+      (Some(expires), Some(date)) => expires.duration_since(date).unwrap_or_default(),
+      _ => Duration::ZERO,
+    };
+  }
+  match (header_date(headers, http::header::LAST_MODIFIED), date) {
+    (Some(modified), Some(date)) => date.duration_since(modified).unwrap_or_default() / 10,
+    _ => Duration::ZERO,
+  }
+}
+
+fn is_fresh(response: &Response, age: Duration) -> bool {
+  age < freshness_lifetime(response)
+}
+
+fn with_age(mut response: Response, age: Duration) -> Response {
+  response
+    .headers_mut()
+    .insert(http::header::AGE, HeaderValue::from(age.as_secs()));
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CacheControl, ETag, MemoryCache};
+  use crate::MockTransport;
+  use std::time::Duration;
+
+  #[test]
+  fn parse_etag_and_cache_control() {
+    let etag = ETag::parse("W/\"v1\"").unwrap();
+    assert!(etag.is_weak());
+    assert_eq!(etag.tag(), "v1");
+    assert_eq!(etag.to_string(), "W/\"v1\"");
+    assert_eq!(ETag::parse("\"\"").unwrap().tag(), "");
+    assert!(ETag::parse("v1").is_none());
+    let mut headers = http::HeaderMap::new();
+    assert!(CacheControl::from_headers(&headers).is_none());
+    headers.append("cache-control", "public, max-age=60".parse().unwrap());
+    headers.append("cache-control", "no-cache=\"Set-Cookie, Vary\", Immutable".parse().unwrap());
+    let cache_control = CacheControl::from_headers(&headers).unwrap();
+    assert_eq!(cache_control.max_age, Some(Duration::from_secs(60)));
+    assert!(cache_control.public && cache_control.no_cache && cache_control.immutable);
+    assert!(!cache_control.no_store);
+  }
+
+  #[test]
+  fn cache_and_revalidate() {
+    let cache = MemoryCache::new();
+    let mock = MockTransport::new()
+      .respond(concat!(
+        "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 1\r\n",
+        "Connection: close\r\n\r\na"
+      ))
+      .respond(concat!(
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nVary: Accept-Language\r\n",
+        "Content-Length: 1\r\nConnection: close\r\n\r\nb"
+      ))
+      .respond("HTTP/1.1 304 Not Modified\r\nX-Checked: 1\r\nConnection: close\r\n\r\n")
+      .respond("HTTP/1.1 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\nc");
+    let client = crate::Client::builder()
+      .transport(mock.clone())
+      .cache(cache.clone())
+      .build()
+      .unwrap();
+    // 新鲜的响应不再发请求
+    assert_eq!(client.get("http://example.com/a").send().unwrap().text().unwrap(), "a");
+    let cached = client.get("http://example.com/a").send().unwrap();
+    assert_eq!(cached.text().unwrap(), "a");
+    assert_eq!(cached.headers()["age"], "0");
+    // 只有校验值的响应每次都要用 If-None-Match 确认
+    let get = |language: &str| {
+      let request = client.get("http://example.com/b").header("accept-language", language);
+      request.send().unwrap()
+    };
+    assert_eq!(get("en").text().unwrap(), "b");
+    let revalidated = get("en");
+    assert_eq!(revalidated.status_code(), 200);
+    assert_eq!(revalidated.text().unwrap(), "b");
+    assert_eq!(revalidated.headers()["x-checked"], "1");
+    // Vary 的请求头不同，不能用缓存
+    assert_eq!(get("fr").text().unwrap(), "c");
+    assert_eq!(cache.len(), 2);
+    drop(client);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 4);
+    let revalidation = String::from_utf8_lossy(&requests[2]).to_lowercase();
+    assert!(revalidation.contains("\r\nif-none-match: \"v1\"\r\n"));
+    assert!(!String::from_utf8_lossy(&requests[3]).to_lowercase().contains("if-none-match"));
+  }
+}
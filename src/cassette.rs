@@ -0,0 +1,340 @@
+use crate::errors::{new_io_error, Result};
+use crate::response::ReasonPhrase;
+use crate::{Middleware, Next, Request, Response};
+use bytes::Bytes;
+use http::{HeaderName, HeaderValue, Method, StatusCode, Uri, Version};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Records the exchanges of a `Client` to a file and answers later requests
+/// from it, for deterministic tests of tools built on slinger.
+///
+/// Added to a client with [`ClientBuilder::with`](crate::ClientBuilder::with).
+/// In record mode every request goes to the network and the request with its
+/// final response, after redirects, is appended to the cassette. In replay
+/// mode nothing is sent: a request is answered with the next recorded
+/// response of the same method and URL, the body too with
+/// [`Cassette::match_body`], and fails when none is left.
+///
+/// The cassette is a text file with one block per exchange, bytes that are
+/// not printable are escaped so it can be reviewed and committed with the
+/// tests.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::Cassette;
+///
+/// // records on the first run, replays without network afterwards
+/// let cassette = Cassette::once("tests/cassettes/login.cassette")?;
+/// let client = slinger::Client::builder().with(cassette).build()?;
+/// let resp = client.get("http://httpbin.org/get").send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Cassette {
+  path: PathBuf,
+  mode: Mode,
+  match_body: bool,
+}
+
+#[derive(Debug)]
+enum Mode {
+  Record(Mutex<File>),
+  Replay(Mutex<VecDeque<Interaction>>),
+}
+
+/// 一次记录下来的交换
+#[derive(Debug)]
+struct Interaction {
+  method: Method,
+  uri: Uri,
+  body: Bytes,
+  response: Response,
+}
+
+const HEADER: &str = "slinger-cassette 1\n";
+
+impl Cassette {
+  /// Record to `path`, replacing the file when it exists.
+  pub fn record<P: AsRef<Path>>(path: P) -> Result<Cassette> {
+    let mut file = File::create(path.as_ref())?;
+    file.write_all(HEADER.as_bytes())?;
+    Ok(Cassette {
+      path: path.as_ref().to_path_buf(),
+      mode: Mode::Record(Mutex::new(file)),
+      match_body: false,
+    })
+  }
+  /// Replay the exchanges recorded in `path`.
+  ///
+  /// # Errors
+  ///
+  /// Fails when the file cannot be read or is not a cassette.
+  pub fn replay<P: AsRef<Path>>(path: P) -> Result<Cassette> {
+    let text = std::fs::read_to_string(path.as_ref())?;
+    Ok(Cassette {
+      path: path.as_ref().to_path_buf(),
+      mode: Mode::Replay(Mutex::new(parse(&text)?)),
+      match_body: false,
+    })
+  }
+  /// Replay `path` when it exists, otherwise record to it.
+  pub fn once<P: AsRef<Path>>(path: P) -> Result<Cassette> {
+    if path.as_ref().exists() {
+      Cassette::replay(path)
+    } else {
+      Cassette::record(path)
+    }
+  }
+  /// Also compare the request body when looking for a recorded response.
+  ///
+  /// Default is `false`, only the method and URL are compared.
+  pub fn match_body(mut self, enable: bool) -> Self {
+    self.match_body = enable;
+    self
+  }
+  /// Returns true if requests are answered from the cassette.
+  pub fn is_replaying(&self) -> bool {
+    matches!(self.mode, Mode::Replay(_))
+  }
+  /// The file of this cassette.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Middleware for Cassette {
+  fn handle(&self, request: Request, next: Next<'_>) -> Result<Response> {
+    let body = request
+      .body()
+      .as_ref()
+      .map(|b| Bytes::copy_from_slice(b.as_ref()))
+      .unwrap_or_default();
+    match &self.mode {
+      Mode::Record(file) => {
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+        let response = next.run(request)?;
+        let block = render(&method, &uri, &body, &response);
+        let mut file = file
+          .lock()
+          .map_err(|_| new_io_error(ErrorKind::Other, "cassette lock poisoned"))?;
+        file.write_all(block.as_bytes())?;
+        file.flush()?;
+        Ok(response)
+      }
+      Mode::Replay(interactions) => {
+        let mut interactions = interactions
+          .lock()
+          .map_err(|_| new_io_error(ErrorKind::Other, "cassette lock poisoned"))?;
+        let found = interactions.iter().position(|i| {
+          i.method == request.method()
+            && i.uri == *request.uri()
+            && (!self.match_body || i.body == body)
+        });
+        match found.and_then(|index| interactions.remove(index)) {
+          Some(interaction) => Ok(interaction.response),
+          None => Err(new_io_error(
+            ErrorKind::NotFound,
+            &format!(
+              "no recorded response for {} {} in {}",
+              request.method(),
+              request.uri(),
+              self.path.display()
+            ),
+          )),
+        }
+      }
+    }
+  }
+}
+
+/// 一次交换写成一段文本，字节内容先写长度再写转义后的单行
+fn render(method: &Method, uri: &Uri, body: &[u8], response: &Response) -> String {
+  let mut out = format!("\nrequest {} {}\n", method, uri);
+  push_bytes(&mut out, "body", body);
+  let reason = response
+    .reason_raw()
+    .map(|r| r.escape_ascii().to_string())
+    .unwrap_or_default();
+  out.push_str(&format!(
+    "response {:?} {} {}\n",
+    response.version(),
+    response.status_code().as_u16(),
+    reason
+  ));
+  out.push_str(&format!("uri {}\n", response.uri()));
+  for (name, value) in response.headers() {
+    out.push_str(&format!("header {}: {}\n", name, value.as_bytes().escape_ascii()));
+  }
+  let response_body = response.body().as_ref().map(|b| b.as_ref()).unwrap_or_default();
+  push_bytes(&mut out, "body", response_body);
+  out
+}
+
+fn push_bytes(out: &mut String, label: &str, bytes: &[u8]) {
+  out.push_str(&format!("{} {}\n{}\n", label, bytes.len(), bytes.escape_ascii()));
+}
+
+fn invalid(line: usize) -> crate::Error {
+  new_io_error(
+    ErrorKind::InvalidData,
+    &format!("invalid cassette at line {}", line + 1),
+  )
+}
+
+fn parse(text: &str) -> Result<VecDeque<Interaction>> {
+  let mut lines = text.lines().enumerate().peekable();
+  if lines.next().map(|(_, l)| l) != Some(HEADER.trim_end()) {
+    return Err(invalid(0));
+  }
+  let mut interactions = VecDeque::new();
+  while let Some((n, line)) = lines.next() {
+    if line.is_empty() {
+      continue;
+    }
+    let (method, uri) = line
+      .strip_prefix("request ")
+      .and_then(|rest| rest.split_once(' '))
+      .ok_or_else(|| invalid(n))?;
+    let method = Method::from_bytes(method.as_bytes()).map_err(|_| invalid(n))?;
+    let uri = Uri::try_from(uri).map_err(|_| invalid(n))?;
+    let body = read_bytes(&mut lines)?;
+    let (n, line) = lines.next().ok_or_else(|| invalid(n))?;
+    let mut parts = line
+      .strip_prefix("response ")
+      .ok_or_else(|| invalid(n))?
+      .splitn(3, ' ');
+    let version = match parts.next() {
+      Some("HTTP/0.9") => Version::HTTP_09,
+      Some("HTTP/1.0") => Version::HTTP_10,
+      Some("HTTP/1.1") => Version::HTTP_11,
+      Some("HTTP/2.0") => Version::HTTP_2,
+      Some("HTTP/3.0") => Version::HTTP_3,
+      _ => return Err(invalid(n)),
+    };
+    let status = parts
+      .next()
+      .and_then(|s| s.parse::<u16>().ok())
+      .and_then(|s| StatusCode::from_u16(s).ok())
+      .ok_or_else(|| invalid(n))?;
+    let reason = unescape(parts.next().unwrap_or_default());
+    let mut builder = http::Response::builder().version(version).status(status);
+    if !reason.is_empty() {
+      builder = builder.extension(ReasonPhrase(Bytes::from(reason)));
+    }
+    let mut response_uri = Uri::default();
+    while let Some((n, line)) = lines.next_if(|(_, l)| l.starts_with("header ") || l.starts_with("uri ")) {
+      if let Some(value) = line.strip_prefix("uri ") {
+        response_uri = Uri::try_from(value).map_err(|_| invalid(n))?;
+        continue;
+      }
+      let (name, value) = line["header ".len()..]
+        .split_once(": ")
+        .ok_or_else(|| invalid(n))?;
+      let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid(n))?;
+      let value = HeaderValue::from_bytes(&unescape(value)).map_err(|_| invalid(n))?;
+      builder = builder.header(name, value);
+    }
+    let response_body = read_bytes(&mut lines)?;
+    let mut response: Response = builder.body(response_body)?.into();
+    *response.url_mut() = response_uri;
+    interactions.push_back(Interaction {
+      method,
+      uri,
+      body: Bytes::from(body),
+      response,
+    });
+  }
+  Ok(interactions)
+}
+
+/// 读取 `body <长度>` 和下一行的转义内容
+fn read_bytes<'a, I>(lines: &mut I) -> Result<Vec<u8>>
+  where
+    I: Iterator<Item=(usize, &'a str)>,
+{
+  let (n, line) = lines.next().ok_or_else(|| invalid(0))?;
+  let length: usize = line
+    .strip_prefix("body ")
+    .and_then(|l| l.parse().ok())
+    .ok_or_else(|| invalid(n))?;
+  let (n, line) = lines.next().ok_or_else(|| invalid(n))?;
+  let bytes = unescape(line);
+  if bytes.len() != length {
+    return Err(invalid(n));
+  }
+  Ok(bytes)
+}
+
+/// 还原 `escape_ascii` 的转义
+fn unescape(text: &str) -> Vec<u8> {
+  let bytes = text.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] != b'\\' || i + 1 == bytes.len() {
+      out.push(bytes[i]);
+      i += 1;
+      continue;
+    }
+    match bytes[i + 1] {
+      b'n' => out.push(b'\n'),
+      b'r' => out.push(b'\r'),
+      b't' => out.push(b'\t'),
+      b'x' if i + 4 <= bytes.len() => {
+        let hex = std::str::from_utf8(&bytes[i + 2..i + 4]).unwrap_or_default();
+        out.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+        i += 4;
+        continue;
+      }
+      other => out.push(other),
+    }
+    i += 2;
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{parse, render, unescape, HEADER};
+  use crate::Response;
+
+  #[test]
+  fn cassette_round_trip() {
+    let raw = b"a\r\n\x00\xff'\"\\z";
+    assert_eq!(unescape(&raw.escape_ascii().to_string()), raw);
+    let response: Response = http::Response::builder()
+      .status(404)
+      .header("x-raw", http::HeaderValue::from_bytes(b"caf\xe9").unwrap())
+      .header("set-cookie", "a=1")
+      .header("set-cookie", "b=2")
+      .body(b"not\nfound\x01".to_vec())
+      .unwrap()
+      .into();
+    let uri: http::Uri = "http://example.com/x?y=1".parse().unwrap();
+    let text = format!(
+      "{}{}{}",
+      HEADER,
+      render(&http::Method::POST, &uri, b"k=v", &response),
+      render(&http::Method::GET, &uri, b"", &response)
+    );
+    let mut interactions = parse(&text).unwrap();
+    assert_eq!(interactions.len(), 2);
+    let first = interactions.pop_front().unwrap();
+    assert_eq!(first.method, http::Method::POST);
+    assert_eq!(first.uri, uri);
+    assert_eq!(first.body, "k=v");
+    assert_eq!(first.response.status_code(), 404);
+    assert_eq!(first.response.headers().get_all("set-cookie").iter().count(), 2);
+    assert_eq!(first.response.headers()["x-raw"].as_bytes(), b"caf\xe9");
+    assert_eq!(first.response.body().as_ref().unwrap().as_ref(), b"not\nfound\x01");
+    assert!(parse("not a cassette\n").is_err());
+  }
+}
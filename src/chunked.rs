@@ -0,0 +1,365 @@
+use crate::deadline::Deadline;
+#[cfg(feature = "gzip")]
+use crate::compress::{Encoder, Encoding};
+use crate::errors::{new_io_error, Result};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use std::fmt;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One item of a [`ChunkedBody`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Chunk {
+  /// Bytes sent as one chunk, an empty value is skipped since it would end the body.
+  Data(Bytes),
+  /// Send everything written so far to the server.
+  Flush,
+  /// Wait before going on with the next item, without flushing.
+  Pause(Duration),
+}
+
+impl Chunk {
+  /// A chunk with `data`.
+  pub fn data<B: Into<Bytes>>(data: B) -> Chunk {
+    Chunk::Data(data.into())
+  }
+}
+
+impl From<Bytes> for Chunk {
+  fn from(data: Bytes) -> Chunk {
+    Chunk::Data(data)
+  }
+}
+
+impl From<Vec<u8>> for Chunk {
+  fn from(data: Vec<u8>) -> Chunk {
+    Chunk::Data(data.into())
+  }
+}
+
+impl From<String> for Chunk {
+  fn from(data: String) -> Chunk {
+    Chunk::Data(data.into())
+  }
+}
+
+impl From<&'static str> for Chunk {
+  fn from(data: &'static str) -> Chunk {
+    Chunk::Data(Bytes::from_static(data.as_bytes()))
+  }
+}
+
+impl From<&'static [u8]> for Chunk {
+  fn from(data: &'static [u8]) -> Chunk {
+    Chunk::Data(Bytes::from_static(data))
+  }
+}
+
+type ChunkIter = Box<dyn Iterator<Item=Chunk> + Send>;
+
+/// A request body sent with `Transfer-Encoding: chunked` from an iterator,
+/// set with [`RequestBuilder::chunked_body`](crate::RequestBuilder::chunked_body).
+///
+/// The iterator is read while the request is being sent, so it can produce
+/// a long running stream such as log lines. Chunks are buffered until a
+/// [`Chunk::Flush`] or the end of the body, which makes it possible to
+/// observe how a server parses a body split across several writes.
+///
+/// The iterator can only be consumed once: a retry or a redirect that sends
+/// the body again fails. Chunked bodies are only sent over HTTP/1.1, or
+/// over HTTP/1.0 without chunking with
+/// [`BodyFraming::UntilClose`](crate::BodyFraming::UntilClose).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use std::time::Duration;
+/// use slinger::{Chunk, ChunkedBody};
+///
+/// let body = ChunkedBody::new(vec![
+///   Chunk::data("name=sli"),
+///   Chunk::Flush,
+///   Chunk::Pause(Duration::from_secs(2)),
+///   Chunk::data("nger"),
+/// ]);
+/// let resp = slinger::Client::new()
+///   .post("http://httpbin.org/post")
+///   .chunked_body(body)
+///   .send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ChunkedBody {
+  chunks: Arc<Mutex<Option<ChunkIter>>>,
+  delay: Option<Duration>,
+  flush_each: bool,
+  trailers: HeaderMap,
+  unframed: bool,
+  #[cfg(feature = "gzip")]
+  compress: Option<Encoding>,
+}
+
+impl fmt::Debug for ChunkedBody {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ChunkedBody")
+      .field("delay", &self.delay)
+      .field("flush_each", &self.flush_each)
+      .field("trailers", &self.trailers)
+      .finish()
+  }
+}
+
+impl ChunkedBody {
+  /// Create a `ChunkedBody` from the items of `chunks`, anything convertible
+  /// to bytes becomes a [`Chunk::Data`].
+  pub fn new<I>(chunks: I) -> ChunkedBody
+    where
+      I: IntoIterator,
+      I::Item: Into<Chunk> + 'static,
+      I::IntoIter: Send + 'static,
+  {
+    ChunkedBody {
+      chunks: Arc::new(Mutex::new(Some(Box::new(
+        chunks.into_iter().map(Into::into),
+      )))),
+      delay: None,
+      flush_each: false,
+      trailers: HeaderMap::new(),
+      unframed: false,
+      #[cfg(feature = "gzip")]
+      compress: None,
+    }
+  }
+  /// Flush every data chunk and wait `delay` after it.
+  ///
+  /// Default is no delay.
+  pub fn delay(mut self, delay: Duration) -> ChunkedBody {
+    self.delay = Some(delay);
+    self
+  }
+  /// Flush after every data chunk instead of only at [`Chunk::Flush`].
+  ///
+  /// Default is `false`.
+  pub fn flush_each(mut self, enable: bool) -> ChunkedBody {
+    self.flush_each = enable;
+    self
+  }
+  /// Send a trailer field after the last chunk, such as a signature computed
+  /// over the body. Adding the same name twice sends both values.
+  ///
+  /// The names are announced in the `Trailer` request header.
+  pub fn trailer(mut self, name: HeaderName, value: HeaderValue) -> ChunkedBody {
+    self.trailers.append(name, value);
+    self
+  }
+  /// 发送时用 `encoding` 压缩
+  #[cfg(feature = "gzip")]
+  pub(crate) fn compress(&mut self, encoding: Encoding) {
+    self.compress = Some(encoding);
+  }
+  /// 不写分块长度和结束块，数据原样写出，请求体由半关闭连接结束
+  pub(crate) fn unframed(&mut self) {
+    self.unframed = true;
+  }
+  /// `Trailer` 请求头的值
+  pub(crate) fn trailer_names(&self) -> Option<HeaderValue> {
+    let names: Vec<&str> = self.trailers.keys().map(|k| k.as_str()).collect();
+    if names.is_empty() {
+      return None;
+    }
+    HeaderValue::from_str(&names.join(", ")).ok()
+  }
+  /// 写入所有分块、结束块和 trailer，迭代器只能使用一次
+  pub(crate) fn write_to<W: Write>(&self, writer: W, deadline: &Deadline) -> Result<()> {
+    let chunks = self
+      .chunks
+      .lock()
+      .map_err(|_| new_io_error(std::io::ErrorKind::Other, "chunked body lock poisoned"))?
+      .take();
+    let chunks = chunks.ok_or_else(|| {
+      new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "chunked body has already been sent",
+      )
+    })?;
+    let mut writer = BufWriter::new(writer);
+    #[cfg(feature = "gzip")]
+    let mut encoder = self.compress.map(Encoder::new);
+    let flush_each = self.flush_each || self.delay.is_some();
+    for chunk in chunks {
+      deadline.check()?;
+      match chunk {
+        Chunk::Data(data) => {
+          if data.is_empty() {
+            continue;
+          }
+          // 压缩器攒够数据之前可能没有输出
+          #[cfg(feature = "gzip")]
+          let data = match &mut encoder {
+            Some(encoder) => encoder.encode(&data, flush_each)?,
+            None => data,
+          };
+          write_chunk(&mut writer, &data, self.unframed)?;
+          if flush_each {
+            writer.flush()?;
+          }
+          if let Some(delay) = self.delay {
+            std::thread::sleep(delay);
+          }
+        }
+        Chunk::Flush => {
+          #[cfg(feature = "gzip")]
+          if let Some(encoder) = &mut encoder {
+            write_chunk(&mut writer, &encoder.encode(&[], true)?, self.unframed)?;
+          }
+          writer.flush()?
+        }
+        Chunk::Pause(pause) => std::thread::sleep(pause),
+      }
+    }
+    #[cfg(feature = "gzip")]
+    if let Some(encoder) = encoder {
+      write_chunk(&mut writer, &encoder.finish()?, self.unframed)?;
+    }
+    if self.unframed {
+      writer.flush()?;
+      return Ok(());
+    }
+    writer.write_all(b"0\r\n")?;
+    for (name, value) in &self.trailers {
+      writer.write_all(name.as_str().as_bytes())?;
+      writer.write_all(b": ")?;
+      writer.write_all(value.as_bytes())?;
+      writer.write_all(b"\r\n")?;
+    }
+    writer.write_all(b"\r\n")?;
+    writer.flush()?;
+    Ok(())
+  }
+}
+
+/// 写一个分块，空的数据会结束请求体所以跳过
+fn write_chunk<W: Write>(writer: &mut W, data: &[u8], unframed: bool) -> std::io::Result<()> {
+  if data.is_empty() {
+    return Ok(());
+  }
+  if unframed {
+    return writer.write_all(data);
+  }
+  write!(writer, "{:x}\r\n", data.len())?;
+  writer.write_all(data)?;
+  writer.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Chunk, ChunkedBody};
+  use crate::Deadline;
+  use http::{HeaderName, HeaderValue};
+  use std::io::Write;
+  use std::sync::{Arc, Mutex};
+
+  /// 记录每次 flush 时写出的内容
+  struct Flushes(Arc<Mutex<Vec<Vec<u8>>>>, Vec<u8>);
+
+  impl Write for Flushes {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.1.extend_from_slice(buf);
+      Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+      if !self.1.is_empty() {
+        self.0.lock().unwrap().push(std::mem::take(&mut self.1));
+      }
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn flush_points() {
+    let body = ChunkedBody::new(vec![
+      Chunk::data("hello"),
+      Chunk::data(""),
+      Chunk::data(" world!"),
+      Chunk::Flush,
+      Chunk::data("end"),
+    ]);
+    let flushes = Arc::new(Mutex::new(Vec::new()));
+    let writer = Flushes(flushes.clone(), Vec::new());
+    body.write_to(writer, &Deadline::default()).unwrap();
+    assert_eq!(
+      *flushes.lock().unwrap(),
+      vec![
+        b"5\r\nhello\r\n7\r\n world!\r\n".to_vec(),
+        b"3\r\nend\r\n0\r\n\r\n".to_vec(),
+      ]
+    );
+    assert!(body.write_to(Vec::new(), &Deadline::default()).is_err());
+    let flushes = Arc::new(Mutex::new(Vec::new()));
+    let writer = Flushes(flushes.clone(), Vec::new());
+    ChunkedBody::new(["a", "b"])
+      .flush_each(true)
+      .write_to(writer, &Deadline::default())
+      .unwrap();
+    assert_eq!(flushes.lock().unwrap().len(), 3);
+  }
+
+  #[test]
+  fn trailers() {
+    let body = ChunkedBody::new(["abc"])
+      .trailer(HeaderName::from_static("x-sum"), HeaderValue::from_static("1"))
+      .trailer(HeaderName::from_static("x-sig"), HeaderValue::from_static("2"));
+    assert_eq!(body.trailer_names().unwrap(), "x-sum, x-sig");
+    let mut out = Vec::new();
+    body.write_to(&mut out, &Deadline::default()).unwrap();
+    assert_eq!(out, b"3\r\nabc\r\n0\r\nx-sum: 1\r\nx-sig: 2\r\n\r\n");
+  }
+
+  #[test]
+  fn until_close() {
+    use crate::BodyFraming;
+    use std::io::Read;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // 读到客户端半关闭为止，原样返回请求体，响应也没有长度
+    let server = std::thread::spawn(move || {
+      let mut requests = Vec::new();
+      for _ in 0..2 {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        stream.read_to_end(&mut request).unwrap();
+        let split = request.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n").unwrap();
+        stream.write_all(&request[split..]).unwrap();
+        requests.push(String::from_utf8(request[..split].to_vec()).unwrap().to_ascii_lowercase());
+      }
+      requests
+    });
+    let client = crate::Client::new();
+    let url = format!("http://{}/", addr);
+    let resp = client
+      .post(&url)
+      .body("\x00\x00\x00\x00\x03abc")
+      .header(http::header::CONTENT_LENGTH, "8")
+      .body_framing(BodyFraming::UntilClose)
+      .send()
+      .unwrap();
+    assert_eq!(resp.body().as_deref().unwrap().as_ref(), b"\x00\x00\x00\x00\x03abc");
+    let resp = client
+      .post(&url)
+      .version(http::Version::HTTP_10)
+      .chunked_body(ChunkedBody::new(vec![Chunk::data("hello "), Chunk::Flush, Chunk::data("world")]))
+      .body_framing(BodyFraming::UntilClose)
+      .send()
+      .unwrap();
+    assert_eq!(resp.text().unwrap(), "hello world");
+    for request in server.join().unwrap() {
+      assert!(!request.contains("content-length"));
+      assert!(!request.contains("transfer-encoding"));
+    }
+  }
+}
@@ -1,24 +1,76 @@
+use crate::activity::{Activity, ActivityLog};
+use crate::auth::{host_matches, Credentials, DigestAuth};
+use crate::bulk::BulkScan;
+use crate::cache::{CacheStore, HttpCache};
+use crate::chunked::ChunkedBody;
+use crate::connector::{default_port, ConnectorOverride};
 #[cfg(feature = "cookie")]
 use crate::cookies;
-use crate::errors::{new_io_error, Result};
+use crate::deadline::{Deadline, DeadlineReader, RequestDeadline};
+use crate::download::Download;
+use crate::early_hints::{self, EarlyHint, EarlyHintAction, EarlyHintPolicy, Preloads};
+use crate::errors::{new_io_error, Error, RedirectError, Result};
+use crate::fetch::{self, SmallFetch};
+use crate::hooks::Hooks;
+#[cfg(feature = "http2")]
+use crate::http2;
+use crate::impersonate::Browser;
+use crate::middleware::{Middleware, Next};
+#[cfg(feature = "ntlm")]
+use crate::ntlm::NtlmAuth;
+use crate::probe::{self, SchemeProbe};
+use crate::progress::{DownloadProgress, DownloadTo, Progress, ProgressWriter, UploadProgress};
+use crate::protocol::ProtocolPolicy;
+#[cfg(feature = "proxy")]
 use crate::proxy::Proxy;
-use crate::record::{HTTPRecord, LocalPeerRecord, RedirectRecord};
-use crate::redirect::{remove_sensitive_headers, Action, Policy};
-use crate::response::{ResponseBuilder, ResponseConfig};
-use crate::socket::Socket;
-use crate::{Connector, ConnectorBuilder, Request, RequestBuilder, Response};
-use bytes::Bytes;
-use http::{HeaderMap, HeaderValue, Method, StatusCode};
+use crate::proxy_protocol::ProxyProtocol;
+use crate::ratelimit::{ConnectionLimit, ConnectionPermit, Held, RateLimiter, Throttle, Throttled};
+#[cfg(feature = "tls")]
+use crate::record::AlpnRecord;
+#[cfg(feature = "record")]
+use crate::record::RecordLimit;
+use crate::record::{ConnectionClose, LocalPeerRecord, Recorder, RedirectRecord, Timing};
+use crate::redirect::{
+  is_cross_host, is_downgrade, is_loop, remove_sensitive_headers, Action, LocationPolicy, Policy,
+};
+#[cfg(feature = "http2")]
+use crate::request::H2cUpgrade;
+use crate::request::{
+  AutoHeaders, BodyFraming, ExplicitVersion, HeaderOrder, HostOverride, SocketControl,
+};
+use crate::response::{
+  is_closed_before_response, BodyTermination, PartialBody, ResponseBuilder, ResponseConfig,
+  ResponseLimits, StatusCodePolicy, UploadInterrupted,
+};
+use crate::retry::RetryPolicy;
+use crate::schedule::Scheduled;
+use crate::shard::{Shard, ShardLog, ShardStats};
+use crate::socket::{Socket, Tunnel};
+use crate::sse::{EventStream, PendingStream};
+use crate::streaming::{BodyReader, StreamBody};
+use crate::sweep::{self, HostProbe, NoRedirect};
+use crate::template::{Payloads, RawTemplate};
 #[cfg(feature = "tls")]
-use native_tls::{Certificate, Identity};
+use crate::tls::{Certificate, Identity};
+use crate::transport::{Transport, TransportHandle};
+use crate::waf;
+use crate::wire::{LoggedWriter, WireLog};
+#[cfg(feature = "sign")]
+use crate::Signer;
+use crate::{Connector, ConnectorBuilder, Request, RequestBuilder, RequestTarget, Response};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Version};
 #[cfg(feature = "tls")]
 use openssl::x509::X509;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{BufReader, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// A `Client` to make Requests with.
 ///
@@ -182,6 +234,35 @@ impl Client {
   {
     self.request(Method::OPTIONS, url)
   }
+  /// Convenience method to make a server-wide `OPTIONS *` request to the
+  /// server of a URL.
+  ///
+  /// # Errors
+  ///
+  /// This method fails whenever supplied `Uri` cannot be parsed.
+  pub fn options_asterisk<U>(&self, url: U) -> RequestBuilder
+    where
+      http::Uri: TryFrom<U>,
+      <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+  {
+    self.options(url).target(RequestTarget::Asterisk)
+  }
+  /// Convenience method to send `CONNECT authority` to the server of a URL,
+  /// for example to check whether it tunnels to other hosts.
+  ///
+  /// The server's response is returned as is; a `2xx` response has no body
+  /// and the tunnel is closed afterwards.
+  ///
+  /// # Errors
+  ///
+  /// This method fails whenever supplied `Uri` cannot be parsed.
+  pub fn connect_to<U>(&self, url: U, authority: http::uri::Authority) -> RequestBuilder
+    where
+      http::Uri: TryFrom<U>,
+      <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+  {
+    self.connect(url).target(RequestTarget::Authority(authority))
+  }
   /// Start building a `Request` with the `Method` and `Uri`.
   ///
   /// Returns a `RequestBuilder`, which will allow setting headers and
@@ -218,6 +299,36 @@ impl Client {
     builder = builder.raw(raw, unsafe_raw);
     builder
   }
+  /// Render `template` with every combination of `payloads`, each as a raw
+  /// request to `uri`, see [`RawTemplate`].
+  ///
+  /// Requests are generated lazily, in the order of
+  /// [`Payloads::combinations`], and `unsafe_raw` applies to all of them as
+  /// with [`Client::raw`].
+  ///
+  /// # Errors
+  ///
+  /// This method fails when `uri` cannot be parsed or a placeholder of
+  /// `template` has no payload list.
+  pub fn raw_template<'a, U>(
+    &'a self,
+    uri: U,
+    template: &'a RawTemplate,
+    payloads: &'a Payloads,
+    unsafe_raw: bool,
+  ) -> Result<impl Iterator<Item=RequestBuilder> + 'a>
+    where
+      http::Uri: TryFrom<U>,
+      <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+  {
+    let uri = http::Uri::try_from(uri).map_err(|err| Error::Http(err.into()))?;
+    template.check(payloads)?;
+    Ok(payloads.combinations().map(move |values| {
+      // 每个占位符都检查过有值，渲染不会失败
+      let raw = template.render(values).unwrap_or_default();
+      self.raw::<http::Uri, Bytes>(uri.clone(), raw, unsafe_raw)
+    }))
+  }
   /// Executes a `Request`.
   ///
   /// A `Request` can be built manually with `Request::new()` or obtained
@@ -231,26 +342,205 @@ impl Client {
   /// This method fails if there was an error while sending request,
   /// or redirect limit was exhausted.
   pub fn execute_request(&self, socket: &mut Socket, request: &Request) -> Result<Response> {
-    let raw: Bytes = request.to_raw();
+    self.execute_request_with_deadline(
+      &self.inner.connector,
+      socket,
+      request,
+      &Deadline::default(),
+      Timing::default(),
+    )
+  }
+  fn execute_request_with_deadline(
+    &self,
+    connector: &Connector,
+    socket: &mut Socket,
+    request: &Request,
+    deadline: &Deadline,
+    mut timing: Timing,
+  ) -> Result<Response> {
     #[cfg(feature = "tls")]
       let mut certificate: Option<X509> = None;
+    #[cfg(feature = "tls")]
+      let mut alpn: Option<AlpnRecord> = None;
     #[cfg(feature = "tls")]
     {
       if let Some(x509) = socket.peer_certificate() {
         certificate = Some(x509);
       }
+      if socket.ssl().is_some() {
+        alpn = Some(AlpnRecord {
+          offered: connector.alpn_protocols().to_vec(),
+          negotiated: socket.negotiated_alpn(),
+        });
+      }
+    }
+    if deadline.budget().is_some() {
+      socket.set_write_timeout(deadline.clamp(connector.write_timeout())?)?;
+    }
+    let start = Instant::now();
+    let first_byte = Cell::new(None);
+    let control = request.extensions().get::<SocketControl>().copied().unwrap_or_default();
+    let download_to = request.extensions().get::<DownloadTo>().map(|d| d.0.clone());
+    let config = ResponseConfig::new(request.is_unsafe(), None)
+      .allow_partial(
+        self.inner.resume_downloads > 0
+          && request.method() == Method::GET
+          && request.raw_request().is_none()
+          && download_to.is_none(),
+      )
+      .request_method(request.method())
+      .limits(self.inner.response_limits)
+      .status_code_policy(self.inner.status_code_policy)
+      .lenient(self.inner.lenient_parsing)
+      .event_stream(request.extensions().get::<EventStream>().is_some())
+      .stream_body(request.extensions().get::<StreamBody>().is_some())
+      .http09(request.version() == Version::HTTP_09 && request.raw_request().is_none())
+      .body_termination(control.body_termination)
+      .progress(request.extensions().get::<DownloadProgress>().map(|p| p.0.clone()))
+      .download_to(download_to);
+    let preloads = Preloads::default();
+    let config = match &self.inner.early_hints {
+      Some(policy) => config.early_hints(early_hints::hook(
+        self.clone(),
+        policy.clone(),
+        request.uri().clone(),
+        preloads.clone(),
+      )),
+      None => config,
+    };
+    // TLS 协商出 h2 时使用 HTTP/2，明文连接上请求了 HTTP/2 时直接发送或者用 h2c 升级
+    #[cfg(all(feature = "http2", feature = "tls"))]
+    let alpn_h2 = socket.negotiated_alpn().as_deref() == Some(b"h2".as_slice());
+    #[cfg(all(feature = "http2", not(feature = "tls")))]
+    let alpn_h2 = false;
+    #[cfg(feature = "http2")]
+    let response = if alpn_h2 {
+      Some(
+        http2::Connection::new(socket, *deadline, connector.read_timeout(), &first_byte)
+          .send(request, &config),
+      )
+    } else if request.version() == Version::HTTP_2
+      && request.extensions().get::<ExplicitVersion>().is_some()
+      && request.raw_request().is_none()
+      && matches!(socket, Socket::TCP(_))
+    {
+      if request.extensions().get::<H2cUpgrade>().is_some() {
+        Some(http2::upgrade(
+          socket,
+          request,
+          *deadline,
+          connector.read_timeout(),
+          &first_byte,
+          config.clone(),
+        ))
+      } else {
+        Some(
+          http2::Connection::new(socket, *deadline, connector.read_timeout(), &first_byte)
+            .send(request, &config),
+        )
+      }
+    } else {
+      None
+    };
+    #[cfg(not(feature = "http2"))]
+    let response: Option<Result<Response>> = None;
+    let mut irp = match response {
+      Some(response) => response?,
+      None => {
+        // 靠半关闭结束的请求体不用分块，HTTP/1.0 也能发送
+        if request.version() <= Version::HTTP_10
+          && request.extensions().get::<ChunkedBody>().is_some()
+          && request.extensions().get::<BodyFraming>() != Some(&BodyFraming::UntilClose)
+        {
+          return Err(new_io_error(
+            std::io::ErrorKind::Unsupported,
+            "chunked bodies need HTTP/1.1",
+          ));
+        }
+        let raw: Bytes = self.http1_request(request)?.to_raw();
+        let wire_log = self.inner.wire_log.as_ref();
+        if let Some(log) = wire_log {
+          log.sent(&raw);
+        }
+        let upload = request.extensions().get::<UploadProgress>();
+        let upload_throttle = self.inner.upload_throttle.as_deref();
+        let interrupted = if control.early_response {
+          write_until_response(socket, &raw, upload_throttle, deadline)?
+        } else if let Some(UploadProgress(progress)) = upload {
+          let mut writer = Throttled::new(&mut *socket, upload_throttle, *deadline);
+          write_with_progress(&mut writer, &raw, request, progress)?;
+          None
+        } else {
+          Throttled::new(&mut *socket, upload_throttle, *deadline).write_all(&raw)?;
+          None
+        };
+        if interrupted.is_none() {
+          if let Some(body) = request.extensions().get::<ChunkedBody>() {
+            let writer = LoggedWriter::new(
+              Throttled::new(&mut *socket, upload_throttle, *deadline),
+              wire_log,
+            );
+            match upload {
+              Some(UploadProgress(progress)) => {
+                body.write_to(ProgressWriter::new(writer, progress), deadline)?
+              }
+              None => body.write_to(writer, deadline)?,
+            }
+          }
+          socket.flush()?;
+        }
+        #[cfg(feature = "trace")]
+        tracing::debug!(
+          bytes = interrupted.unwrap_or(raw.len()),
+          interrupted = interrupted.is_some(),
+          chunked = request.extensions().get::<ChunkedBody>().is_some(),
+          "request written"
+        );
+        if control.half_close {
+          socket.shutdown(std::net::Shutdown::Write)?;
+        }
+        let idle = match control.body_termination {
+          BodyTermination::Idle(idle) => Some(idle),
+          _ => None,
+        };
+        let reader = BufReader::new(Throttled::new(
+          DeadlineReader::new(socket, *deadline, connector.read_timeout(), &first_byte)
+            .idle(idle)
+            .log(wire_log),
+          self.inner.download_throttle.as_deref(),
+          *deadline,
+        ));
+        let mut response = ResponseBuilder::new(reader, config).build()?;
+        if let Some(sent) = interrupted {
+          response.extensions_mut().insert(UploadInterrupted(sent));
+        }
+        response
+      }
+    };
+    let first_byte = first_byte.get().unwrap_or_else(Instant::now);
+    timing.first_byte = first_byte.duration_since(start);
+    timing.download = first_byte.elapsed();
+    #[cfg(feature = "trace")]
+    tracing::debug!(
+      status = irp.status_code().as_u16(),
+      version = ?irp.version(),
+      first_byte = ?timing.first_byte,
+      download = ?timing.download,
+      "response read"
+    );
+    irp.extensions_mut().insert(timing);
+    if preloads.0.lock().is_ok_and(|p| !p.is_empty()) {
+      irp.extensions_mut().insert(preloads);
     }
-    socket.write_all(&raw)?;
-    socket.flush()?;
-    let reader = BufReader::new(socket);
-    let mut irp =
-      ResponseBuilder::new(reader, ResponseConfig::new(request.is_unsafe(), None)).build()?;
     *irp.url_mut() = request.uri().clone();
     #[cfg(feature = "tls")]
     {
       if let Some(cert) = certificate {
         irp.extensions_mut().insert(cert);
       }
+      if let Some(alpn) = alpn {
+        irp.extensions_mut().insert(alpn);
+      }
     }
     Ok(irp)
   }
@@ -267,20 +557,630 @@ impl Client {
   /// This method fails if there was an error while sending request,
   /// or redirect limit was exhausted.
   pub fn execute<R: Into<Request>>(&self, request: R) -> Result<Response> {
-    let mut records = vec![];
+    Next::new(self, &self.inner.middlewares).run(request.into())
+  }
+  /// Execute a `Request` and return as soon as the headers of the final
+  /// response are read, leaving its body on the connection.
+  ///
+  /// The returned `Response` is the head of the response, without a body;
+  /// the [`BodyReader`] reads the body on demand, or closes the connection
+  /// without reading it. Redirects, authentication retries and middlewares
+  /// apply as with [`Client::execute`], only the final response is streamed.
+  /// HTTP/2 responses and responses written to a file with
+  /// [`RequestBuilder::download_to`] are read in full first.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::new();
+  /// let request = client.get("http://example.com/maybe-large").build()?;
+  /// let (head, body) = client.send_streaming(request)?;
+  /// let is_html = head
+  ///   .headers()
+  ///   .get("content-type")
+  ///   .is_some_and(|ct| ct.as_bytes().starts_with(b"text/html"));
+  /// if is_html {
+  ///   let html = body.bytes()?;
+  ///   println!("{} bytes of HTML", html.len());
+  /// } else {
+  ///   body.abort();
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn send_streaming<R: Into<Request>>(&self, request: R) -> Result<(Response, BodyReader)> {
     let mut request = request.into();
+    request.extensions_mut().insert(StreamBody);
+    let mut response = self.execute(request)?;
+    let body = BodyReader::take(&mut response);
+    Ok((response, body))
+  }
+  /// Execute `requests` on up to `concurrency` threads and return their
+  /// results in the order of the requests.
+  ///
+  /// Each request goes through [`Client::execute`], so middlewares, rate
+  /// limits, redirects and retries apply as usual and the state of the
+  /// `Client` is shared by all threads. Requests are taken from the iterator
+  /// as threads become free, a failed request does not stop the others.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::new();
+  /// let requests = ["admin", "login", "backup"]
+  ///   .into_iter()
+  ///   .map(|path| client.get(format!("http://example.com/{}", path)).build())
+  ///   .collect::<Result<Vec<_>, _>>()?;
+  /// for response in client.send_all(requests, 8) {
+  ///   match response {
+  ///     Ok(response) => println!("{} {}", response.uri(), response.status_code()),
+  ///     Err(err) => println!("{}", err),
+  ///   }
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn send_all<I, R>(&self, requests: I, concurrency: usize) -> Vec<Result<Response>>
+    where
+      I: IntoIterator<Item=R>,
+      I::IntoIter: Send,
+      R: Into<Request> + Send,
+  {
+    let queue = Mutex::new(requests.into_iter().enumerate());
+    let done: Mutex<Vec<(usize, Result<Response>)>> = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+      for _ in 0..concurrency.max(1) {
+        scope.spawn(|| loop {
+          // 取下一个请求时持有锁，发送时释放
+          let next = match queue.lock() {
+            Ok(mut queue) => queue.next(),
+            Err(_) => None,
+          };
+          let Some((index, request)) = next else {
+            break;
+          };
+          let result = self.execute(request);
+          if let Ok(mut done) = done.lock() {
+            done.push((index, result));
+          }
+        });
+      }
+    });
+    let mut done = done.into_inner().unwrap_or_default();
+    done.sort_by_key(|(index, _)| *index);
+    done.into_iter().map(|(_, result)| result).collect()
+  }
+  /// Send a `GET` request to every URL of `targets` once per value of
+  /// `hosts`, each time with that value as the `Host` header.
+  ///
+  /// Name resolution, the connection and TLS SNI follow the target URL, only
+  /// the `Host` header (or `:authority` over HTTP/2) changes, which is how
+  /// host header injection and virtual host routing are tested. Redirects are
+  /// not followed so their `Location` can be inspected. Requests are sent one
+  /// after the other, and every probe is identified by its
+  /// [`HostProbe::key`].
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// let client = slinger::Client::new();
+  /// let targets = vec!["https://example.com/".parse()?];
+  /// for probe in client.host_sweep(targets, ["example.com", "evil.example", "localhost"]) {
+  ///   let (target, host) = probe.key();
+  ///   if let Ok(resp) = &probe.result {
+  ///     println!("{} {} {}", target, host, resp.status_code());
+  ///   }
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn host_sweep<T, H, S>(&self, targets: T, hosts: H) -> Vec<HostProbe>
+    where
+      T: IntoIterator<Item=http::Uri>,
+      H: IntoIterator<Item=S>,
+      S: AsRef<str>,
+  {
+    sweep::run(self, targets, hosts)
+  }
+  /// Find the scheme a host answers on: send a `GET` request over HTTPS,
+  /// then over HTTP when that fails, and return the first that answered.
+  ///
+  /// `target` is a host name or address, optionally with a port, path and
+  /// query, such as `example.com`, `203.0.113.10:8443` or `[::1]:8080/admin`.
+  /// A port applies to both schemes, otherwise `443` and `80` are used. A
+  /// `target` with a scheme is only tried with that scheme. Certificates are
+  /// checked as the `Client` is configured, see
+  /// [`ClientBuilder::danger_accept_invalid_certs`]; HTTP is tried first with
+  /// [`ClientBuilder::probe_http_first`]. Without the `tls` feature only HTTP
+  /// is tried.
+  ///
+  /// # Errors
+  ///
+  /// Fails with the error of the last scheme tried when none answered, or
+  /// when `target` is not a valid URL.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::new();
+  /// for host in ["example.com", "203.0.113.10:8080"] {
+  ///   match client.probe(host) {
+  ///     Ok(probe) => println!("{} {}", probe.uri, probe.response.status_code()),
+  ///     Err(err) => println!("{}: {}", host, err),
+  ///   }
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn probe(&self, target: &str) -> Result<SchemeProbe> {
+    probe::run(self, target, self.inner.probe_http_first)
+  }
+  /// Download `url` only if its body is at most `max_bytes` long.
+  ///
+  /// The size is asked for with a `HEAD` request first. When that fails to
+  /// give a `Content-Length`, a `GET` for the first byte learns it from
+  /// `Content-Range` instead. A server that ignores the range sends the whole
+  /// body with that request, which is then returned as is. The advertised
+  /// size is reported in [`SmallFetch::Skipped`] when the body is too large
+  /// or its size can not be learned.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// use slinger::SmallFetch;
+  ///
+  /// let client = slinger::Client::new();
+  /// match client.fetch_if_small("https://example.com/backup.zip", 1024 * 1024)? {
+  ///   SmallFetch::Fetched(resp) => println!("{:?}", resp.body()),
+  ///   SmallFetch::Skipped { size, .. } => println!("skipped, size {:?}", size),
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn fetch_if_small<U>(&self, url: U, max_bytes: u64) -> Result<SmallFetch>
+    where
+      http::Uri: TryFrom<U>,
+      <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+  {
+    let uri = http::Uri::try_from(url).map_err(|err| Error::Http(err.into()))?;
+    fetch::run(self, uri, max_bytes)
+  }
+  /// Open a plain TCP connection to `host:port` through the proxy of the
+  /// client, or directly without one, and return it for any protocol.
+  ///
+  /// HTTP proxies are asked with `CONNECT`, SOCKS5 proxies with their
+  /// `CONNECT` command and SSH jump hosts with `ssh -W`; the connect, read and
+  /// write timeouts of the client apply to the returned [`Tunnel`].
+  ///
+  /// The tunnel waits for the rate limit and takes a slot under
+  /// [`ClientBuilder::max_connections_per_host`] like a request does, and
+  /// gives the slot back when it is dropped.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # #[cfg(feature = "proxy")]
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// use std::io::{Read, Write};
+  ///
+  /// let client = slinger::ClientBuilder::new()
+  ///   .proxy(slinger::Proxy::parse("socks5h://127.0.0.1:1080")?)
+  ///   .build()?;
+  /// let mut redis = client.tunnel("redis.internal", 6379)?;
+  /// redis.write_all(b"PING\r\n")?;
+  /// let mut reply = [0u8; 7];
+  /// redis.read_exact(&mut reply)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn tunnel(&self, host: &str, port: u16) -> Result<Tunnel> {
+    // IPv6 地址需要加上方括号才能放进 URL
+    let host = if host.contains(':') && !host.starts_with('[') {
+      format!("[{}]", host)
+    } else {
+      host.to_string()
+    };
+    let uri = http::Uri::try_from(format!("http://{}:{}/", host, port)).map_err(http::Error::from)?;
+    let deadline = Deadline::new(self.inner.deadline);
+    if let Some(limiter) = &self.inner.rate_limiter {
+      limiter.acquire(&uri, &deadline)?;
+    }
+    // 名额跟着隧道走，隧道关闭时才归还
+    let permit = self.connection_permit(&uri, &deadline)?;
+    let start = Instant::now();
+    let socket =
+      self
+        .inner
+        .connector
+        .connect_with_uri_deadline(&uri, &deadline, &mut Timing::default())?;
+    self.inner.hooks.on_connect(&uri, start.elapsed());
+    Ok(Tunnel {
+      socket,
+      _permit: permit,
+    })
+  }
+  /// Send all `requests` back to back on one HTTP/1.1 connection before
+  /// reading any response, then read the responses in order.
+  ///
+  /// Every request goes to the connection of the first one, so they must
+  /// share its scheme, host and port; raw requests are written as they are,
+  /// which makes this the building block of request smuggling and desync
+  /// probes. Middlewares, retries, redirects and cookies are not applied.
+  ///
+  /// There is one result per request. Once a response can not be read, the
+  /// connection is out of sync and the remaining requests get an error too.
+  ///
+  /// # Errors
+  ///
+  /// This method fails if the requests target different origins, or when the
+  /// connection can not be opened or the requests can not be written.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::new();
+  /// let requests = vec![
+  ///   client.get("http://example.com/a").build()?,
+  ///   client.get("http://example.com/b").build()?,
+  /// ];
+  /// for response in client.pipeline(requests)? {
+  ///   println!("{}", response?.status_code());
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn pipeline(&self, mut requests: Vec<Request>) -> Result<Vec<Result<Response>>> {
+    let uri = match requests.first() {
+      Some(request) => request.uri().clone(),
+      None => return Ok(Vec::new()),
+    };
+    let origin = |u: &http::Uri| {
+      (u.scheme().cloned(), u.host().map(str::to_ascii_lowercase), default_port(u))
+    };
+    if requests.iter().any(|request| origin(request.uri()) != origin(&uri)) {
+      return Err(new_io_error(
+        std::io::ErrorKind::InvalidInput,
+        "pipelined requests must share one origin",
+      ));
+    }
+    let deadline = Deadline::new(self.inner.deadline);
+    let _permit = self.connection_permit(&uri, &deadline)?;
+    let start = Instant::now();
+    let mut socket =
+      self
+        .inner
+        .connector
+        .connect_with_uri_deadline(&uri, &deadline, &mut Timing::default())?;
+    self.inner.hooks.on_connect(&uri, start.elapsed());
+    #[cfg(feature = "tls")]
+    if socket.negotiated_alpn().as_deref() == Some(b"h2".as_slice()) {
+      return Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "server negotiated HTTP/2, pipelining needs HTTP/1.1",
+      ));
+    }
+    for request in requests.iter_mut().filter(|r| r.raw_request().is_none()) {
+      self.add_default_headers(request);
+    }
+    // 先把所有请求写完再开始读响应
+    let wire_log = self.inner.wire_log.as_ref();
+    for request in &requests {
+      self.inner.hooks.on_request(request);
+      let raw = self.http1_request(request)?.to_raw();
+      if let Some(log) = wire_log {
+        log.sent(&raw);
+      }
+      let upload_throttle = self.inner.upload_throttle.as_deref();
+      Throttled::new(&mut socket, upload_throttle, deadline).write_all(&raw)?;
+      if let Some(body) = request.extensions().get::<ChunkedBody>() {
+        let writer = Throttled::new(&mut socket, upload_throttle, deadline);
+        body.write_to(LoggedWriter::new(writer, wire_log), &deadline)?;
+      }
+    }
+    socket.flush()?;
+    let first_byte = Cell::new(None);
+    let mut reader = Some(BufReader::new(Throttled::new(
+      DeadlineReader::new(
+        &mut socket,
+        deadline,
+        self.inner.connector.read_timeout(),
+        &first_byte,
+      )
+      .log(wire_log),
+      self.inner.download_throttle.as_deref(),
+      deadline,
+    )));
+    let mut responses = Vec::with_capacity(requests.len());
+    for request in &requests {
+      let start = Instant::now();
+      let result = match reader.take() {
+        Some(r) => {
+          let config = ResponseConfig::new(request.is_unsafe(), None)
+            .request_method(request.method())
+            .limits(self.inner.response_limits)
+            .status_code_policy(self.inner.status_code_policy)
+            .lenient(self.inner.lenient_parsing);
+          ResponseBuilder::new(r, config)
+            .build_with_reader()
+            .map(|(response, r)| {
+              reader = Some(r);
+              response
+            })
+        }
+        None => Err(new_io_error(
+          std::io::ErrorKind::ConnectionAborted,
+          "pipeline ended before this response",
+        )),
+      };
+      let result = result.and_then(|mut response| {
+        *response.url_mut() = request.uri().clone();
+        self.inner.hooks.on_response(request, &response, start.elapsed());
+        #[cfg(feature = "record")]
+        let mut recorder = Recorder::with_limit(self.inner.record_limit);
+        #[cfg(not(feature = "record"))]
+        let mut recorder = Recorder::default();
+        recorder.record_request(request);
+        recorder.record_response(&response);
+        recorder.finish()
+      });
+      if let Err(err) = &result {
+        self.inner.hooks.on_error(request, err, deadline.elapsed());
+      }
+      responses.push(result);
+    }
+    drop(reader);
+    let _ = socket.shutdown(std::net::Shutdown::Both);
+    Ok(responses)
+  }
+  /// Send `request` once `delay` has passed, without blocking.
+  ///
+  /// The request goes through the middlewares, redirects and retries of the
+  /// client as with [`Client::execute`]. Several calls make a timed sequence
+  /// of probes, for example to find when a token expires:
+  ///
+  /// ```rust
+  /// # use std::time::Duration;
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::new();
+  /// let probes: Vec<_> = [0, 60, 300]
+  ///   .iter()
+  ///   .map(|secs| {
+  ///     let request = client.get("https://example.com/me").bearer_auth("token").build()?;
+  ///     Ok(client.send_after(request, Duration::from_secs(*secs)))
+  ///   })
+  ///   .collect::<Result<_, slinger::Error>>()?;
+  /// for probe in probes {
+  ///   if let Some(response) = probe.wait() {
+  ///     println!("{}", response?.status_code());
+  ///   }
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn send_after<R: Into<Request>>(&self, request: R, delay: Duration) -> Scheduled {
+    self.send_at(request, Instant::now() + delay)
+  }
+  /// Send `request` at `at`, without blocking, see [`Client::send_after`].
+  pub fn send_at<R: Into<Request>>(&self, request: R, at: Instant) -> Scheduled {
+    Scheduled::spawn(self.clone(), request.into(), at)
+  }
+  /// The most recent request attempts of this client and its clones, oldest
+  /// first, including failed ones and retries.
+  ///
+  /// Empty unless enabled with
+  /// [`ClientBuilder::recent_activity`](ClientBuilder::recent_activity).
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// let client = slinger::Client::builder().recent_activity(16).build().unwrap();
+  /// let _ = client.get("http://127.0.0.1:1/").send();
+  /// for activity in client.recent_activity() {
+  ///   eprintln!("{}", activity);
+  /// }
+  /// ```
+  pub fn recent_activity(&self) -> Vec<Activity> {
+    self.inner.activity.snapshot()
+  }
+  /// Decide whether `target` belongs to shard `k` of `n`, so a fleet of
+  /// workers can split one target list without a central queue.
+  ///
+  /// Every worker runs the same list with its own `k`; the assignment is
+  /// [`Shard::of`] and does not depend on the client. Returns the shard to tag
+  /// the requests of the target with [`RequestBuilder::shard`], or `None`
+  /// when another shard owns it. Both outcomes are counted in
+  /// [`Client::shard_stats`] under shard `k`.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let (workers, me) = (4, 1);
+  /// let client = slinger::Client::new();
+  /// for target in ["https://a.example/", "https://b.example/", "https://c.example/"] {
+  ///   if let Some(shard) = client.assign_shard(target, workers, me) {
+  ///     let resp = client.get(target).shard(shard).send()?;
+  ///     println!("{} {:?} {}", shard, resp.shard(), resp.status_code());
+  ///   }
+  /// }
+  /// println!("{:?}", client.shard_stats());
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn assign_shard(&self, target: &str, n: u32, k: u32) -> Option<Shard> {
+    let shard = Shard::of(target, n);
+    let assigned = shard.index == k;
+    self.inner.shards.update(k, |stats| {
+      if assigned {
+        stats.assigned += 1;
+      } else {
+        stats.skipped += 1;
+      }
+    });
+    assigned.then_some(shard)
+  }
+  /// Start a scan sending one request per target, with checkpoints to
+  /// resume it after a crash, see [`BulkScan`](crate::bulk::BulkScan).
+  pub fn bulk<I, S>(&self, targets: I) -> BulkScan
+    where
+      I: IntoIterator<Item=S>,
+      S: Into<String>,
+  {
+    BulkScan::new(self.clone(), targets.into_iter().map(Into::into).collect())
+  }
+  /// Download `url` to the file at `path`, resuming a partial file and
+  /// broken transfers with `Range` requests, see [`Download`].
+  pub fn download<U, P>(&self, url: U, path: P) -> Download
+    where
+      http::Uri: TryFrom<U>,
+      <http::Uri as TryFrom<U>>::Error: Into<http::Error>,
+      P: Into<PathBuf>,
+  {
+    let uri = http::Uri::try_from(url).map_err(|err| Error::from(err.into()));
+    Download::new(self.clone(), uri, path.into())
+  }
+  /// Forget every address cached by [`ClientBuilder::dns_cache`], so the
+  /// next connections resolve their host names again.
+  pub fn clear_dns_cache(&self) {
+    if let Some(cache) = &self.inner.connector_builder.dns_cache {
+      cache.clear();
+    }
+  }
+  /// Shut down every idle connection the `Client` holds and return how many
+  /// were closed.
+  ///
+  /// A connection opened for a request is shut down before
+  /// [`RequestBuilder::send`] returns, unless an event stream
+  /// [`Response`] keeps it, see [`Response::close_connection`]. Only
+  /// connections opened ahead of time, such as for a `103 Early Hints`
+  /// preconnect, wait idle in the `Client` for the next request to their
+  /// origin; they are otherwise closed when the last clone of the `Client`
+  /// is dropped.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::new();
+  /// let resp = client.get("http://example.com/").send()?;
+  /// println!("{} idle connections closed", client.close_idle());
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn close_idle(&self) -> usize {
+    let Ok(mut preconnected) = self.inner.preconnected.lock() else {
+      return 0;
+    };
+    let closed = preconnected.len();
+    for (_key, socket) in preconnected.drain() {
+      let _ = socket.shutdown(std::net::Shutdown::Both);
+    }
+    closed
+  }
+  /// The counters of every shard seen by [`Client::assign_shard`] or on a
+  /// request tagged with [`RequestBuilder::shard`], by shard index.
+  pub fn shard_stats(&self) -> BTreeMap<u32, ShardStats> {
+    self.inner.shards.snapshot()
+  }
+  /// 中间件链的最后一环：发送请求，处理重试
+  pub(crate) fn send(&self, request: Request) -> Result<Response> {
+    let budget = match request.extensions().get::<RequestDeadline>() {
+      Some(RequestDeadline(budget)) => Some(*budget),
+      None => self.inner.deadline,
+    };
+    let deadline = Deadline::new(budget);
+    let mut attempt = 0;
+    loop {
+      attempt += 1;
+      let start = Instant::now();
+      let result = self
+        .execute_once(request.clone(), &deadline)
+        .map_err(|err| err.with_url(request.uri()));
+      self
+        .inner
+        .activity
+        .push(&request, &result, attempt, start.elapsed());
+      if let Err(err) = &result {
+        self.inner.hooks.on_error(&request, err, deadline.elapsed());
+      }
+      let delay = match &self.inner.retry_policy {
+        Some(policy) => policy.next_delay(&request, &result, attempt),
+        None => None,
+      };
+      match (delay, deadline.remaining()) {
+        (None, _) => return self.tag_shard(&request, result),
+        // 剩余时间不够等待下一次重试
+        (Some(delay), Some(remaining)) if delay >= remaining => {
+          return self.tag_shard(&request, result);
+        }
+        (Some(delay), _) => std::thread::sleep(delay),
+      }
+    }
+  }
+  /// 带分片标记的请求计入分片统计，响应也带上分片
+  fn tag_shard(&self, request: &Request, mut result: Result<Response>) -> Result<Response> {
+    let Some(shard) = request.extensions().get::<Shard>().copied() else {
+      return result;
+    };
+    self.inner.shards.update(shard.index, |stats| {
+      stats.requests += 1;
+      if result.is_err() {
+        stats.errors += 1;
+      }
+    });
+    if let Ok(response) = &mut result {
+      response.extensions_mut().insert(shard);
+    }
+    result
+  }
+  fn execute_once(&self, mut request: Request, deadline: &Deadline) -> Result<Response> {
+    #[cfg(feature = "record")]
+    let mut recorder = Recorder::with_limit(self.inner.record_limit);
+    #[cfg(not(feature = "record"))]
+    let mut recorder = Recorder::default();
     let mut cur_uri = request.uri().clone();
     let mut uris = vec![];
-    let mut conn: HashMap<String, Socket> = HashMap::new();
-    // 连接一次，同一个主机地址下复用socket连接
-    let uniq_key = |u: &http::Uri| -> String {
-      let scheme = u.scheme_str().unwrap_or_default();
-      let host = u.host().unwrap_or_default();
-      let port = u.port_u16().unwrap_or_default();
-      format!("{}{}{}", scheme, host, port)
+    let mut digest_retried = false;
+    // 是否添加了按主机配置的认证信息
+    let mut host_auth_applied = false;
+    #[cfg(feature = "ntlm")]
+    let mut ntlm_rounds = 0;
+    // 请求单独覆盖了连接设置时临时创建一个连接器
+    #[cfg_attr(not(feature = "http2"), allow(unused_mut))]
+    let mut overrides = request.extensions().get::<ConnectorOverride>().cloned();
+    // 请求指定了 HTTP/2 但连接器没有通过 ALPN 提供 h2
+    #[cfg(feature = "http2")]
+    if request.version() == Version::HTTP_2
+      && request.extensions().get::<ExplicitVersion>().is_some()
+      && !self.inner.connector.alpn_protocols().iter().any(|p| p == "h2")
+    {
+      overrides.get_or_insert_with(ConnectorOverride::default).alpn_protocols =
+        Some(vec!["h2".to_string(), "http/1.1".to_string()]);
+    }
+    // 预连接的 socket 是客户端自己的连接器建立的，临时连接器不能使用
+    let preconnected = overrides.is_none();
+    let connector = match overrides {
+      Some(overrides) => {
+        Arc::new(overrides.apply(self.inner.connector_builder.clone(), request.uri()).build()?)
+      }
+      None => self.inner.connector.clone(),
     };
+    let mut conn: HashMap<String, Socket> = HashMap::new();
+    // 每个打开的连接占用的名额
+    let mut permits: HashMap<String, ConnectionPermit> = HashMap::new();
     loop {
-      let mut record = HTTPRecord::default();
+      #[cfg(feature = "trace")]
+      let _span =
+        tracing::debug_span!("request", method = %request.method(), uri = %cur_uri).entered();
+      if request.raw_request().is_none() {
+        self.add_default_headers(&mut request);
+      }
       // 设置cookie到请求头
       #[cfg(feature = "cookie")]
       {
@@ -290,20 +1190,162 @@ impl Client {
           }
         }
       }
-      record.record_request(&request);
-      let socket = conn
-        .entry(uniq_key(&cur_uri))
-        .or_insert(self.inner.connector.connect_with_uri(&cur_uri)?);
-      let mut response = self.execute_request(socket, &request)?;
+      if !host_auth_applied && request.raw_request().is_none() {
+        if let Some((_, credentials)) =
+          self.inner.host_auth.iter().find(|(host, _)| host_matches(host, &cur_uri))
+        {
+          host_auth_applied = credentials.apply(&mut request)?;
+        }
+      }
+      if let (Some(policy), None) = (&self.inner.protocol_policy, request.raw_request()) {
+        if request.extensions().get::<ExplicitVersion>().is_none() {
+          *request.version_mut() = policy.request_version();
+        }
+      }
+      #[cfg(feature = "sign")]
+      if let (Some(signer), None) = (&self.inner.signer, request.raw_request()) {
+        signer.sign(&mut request)?;
+      }
+      request.extensions_mut().insert(connector.curl_flags());
+      recorder.record_request(&request);
+      if let Some(limiter) = &self.inner.rate_limiter {
+        limiter.acquire(&cur_uri, deadline)?;
+      }
+      let mut timing = Timing::default();
+      // 已经关闭的连接归还名额
+      permits.retain(|key, _| conn.contains_key(key));
+      // 有连接上限时先关掉这个请求自己的空闲连接，否则跳到别的主机会等自己占着的名额
+      if self.inner.connection_limit.is_some() && !conn.contains_key(&conn_key(&cur_uri)) {
+        for (_key, socket) in conn.drain() {
+          let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+        permits.clear();
+      }
+      let mut reused = conn.contains_key(&conn_key(&cur_uri));
+      // 之前打开的空闲连接，服务器可能已经把它关掉了
+      let mut idle = reused;
+      let socket = match conn.entry(conn_key(&cur_uri)) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+          let permit =
+            self.connection_permit(&cur_uri, deadline).map_err(|err| err.with_url(&cur_uri))?;
+          if let Some(permit) = permit {
+            permits.insert(conn_key(&cur_uri), permit);
+          }
+          match self.take_preconnected(&cur_uri, preconnected) {
+            Some(socket) => {
+              idle = true;
+              entry.insert(socket)
+            }
+            None => {
+              let start = Instant::now();
+              let socket = connector
+                .connect_with_uri_deadline(&cur_uri, deadline, &mut timing)
+                .map_err(|err| err.with_url(&cur_uri))?;
+              self.inner.hooks.on_connect(&cur_uri, start.elapsed());
+              if let Some(log) = &self.inner.wire_log {
+                let peer = socket.peer_addr().ok().and_then(|addr| addr.as_socket());
+                log.info(&format!(
+                  "Connected to {} ({})",
+                  cur_uri.host().unwrap_or_default(),
+                  peer.map(|addr| addr.to_string()).unwrap_or_default()
+                ));
+              }
+              entry.insert(socket)
+            }
+          }
+        }
+      };
+      if let (true, Some(log)) = (reused, &self.inner.wire_log) {
+        log.info(&format!("Re-using connection to {}", cur_uri.host().unwrap_or_default()));
+      }
+      self.inner.hooks.on_request(&request);
+      let start = Instant::now();
+      let mut response =
+        match self.execute_request_with_deadline(&connector, socket, &request, deadline, timing) {
+          // 空闲连接在响应开始前就断开时换一个新连接重发一次，分块请求体已经发出去了不能重发
+          Err(err)
+            if idle
+              && is_closed_before_response(&err)
+              && request.extensions().get::<ChunkedBody>().is_none() =>
+          {
+            if let Some(log) = &self.inner.wire_log {
+              log.info("Connection died, retrying a fresh connect");
+            }
+            let mut timing = Timing::default();
+            let start = Instant::now();
+            *socket = connector
+              .connect_with_uri_deadline(&cur_uri, deadline, &mut timing)
+              .map_err(|err| err.with_url(&cur_uri))?;
+            self.inner.hooks.on_connect(&cur_uri, start.elapsed());
+            reused = false;
+            self.execute_request_with_deadline(&connector, socket, &request, deadline, timing)
+          }
+          result => result,
+        }
+        .map_err(|err| err.with_url(&cur_uri))?;
+      self
+        .inner
+        .hooks
+        .on_response(&request, &response, start.elapsed());
       if let (Ok(remote_addr), Ok(local_addr)) = (socket.peer_addr(), socket.local_addr()) {
         response
           .extensions_mut()
-          .insert(LocalPeerRecord { remote_addr, local_addr });
+          .insert(LocalPeerRecord {
+            remote_addr,
+            local_addr,
+            reused,
+          });
       };
+      if self.inner.detect_waf {
+        if let Some(detection) = waf::detect(&response) {
+          response.extensions_mut().insert(detection);
+        }
+      }
+      // 记录连接在这次交换后是怎么结束的，已经关闭的连接不再复用
+      if response.extensions().get::<PendingStream>().is_none() {
+        let reusable = response.version() != Version::HTTP_2
+          && request.extensions().get::<SocketControl>().is_none()
+          && !wants_close(request.headers(), request.version());
+        let probe = self.inner.probe_connection_close;
+        let close = classify_close(socket, &response, reusable, probe);
+        if close != ConnectionClose::KeepAlive {
+          if let Some(socket) = conn.remove(&conn_key(&cur_uri)) {
+            let _ = socket.shutdown(std::net::Shutdown::Both);
+          }
+        }
+        if probe {
+          response.extensions_mut().insert(close);
+        }
+      }
+      // 事件流和流式响应体占用整个连接，交给响应读取，读超时作为两次读取之间的最长间隔
+      if let Some(stream) = response.extensions_mut().remove::<PendingStream>() {
+        if let Some(socket) = conn.remove(&conn_key(&cur_uri)) {
+          socket.set_read_timeout(connector.read_timeout())?;
+          let held = Held {
+            inner: socket,
+            _permit: permits.remove(&conn_key(&cur_uri)),
+          };
+          response.extensions_mut().insert(stream.into_body(held));
+        }
+      }
+      // HTTP/2 连接在响应结束时已经关闭，半关闭或者没发完请求的连接也不能复用
+      if response.version() == Version::HTTP_2 || request.extensions().get::<SocketControl>().is_some() {
+        if let Some(socket) = conn.remove(&conn_key(&cur_uri)) {
+          let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+      }
+      // 下载中断时重新连接，用 Range 请求续传剩余部分
+      if response.extensions().get::<PartialBody>().is_some() {
+        if let Some(socket) = conn.remove(&conn_key(&cur_uri)) {
+          let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+        permits.remove(&conn_key(&cur_uri));
+        self.resume_download(&connector, &request, &mut response, deadline)?;
+      }
       // 原始请求不跳转
       if request.raw_request().is_some() {
-        record.record_response(&response);
-        records.push(record);
+        recorder.record_response(&response);
         break;
       }
       // 保存请求头的cookie
@@ -316,6 +1358,37 @@ impl Client {
           }
         }
       }
+      // Digest 认证：收到质询后带上认证信息重新发送一次
+      if response.status_code() == StatusCode::UNAUTHORIZED && !digest_retried {
+        let authorization = match request.extensions().get::<DigestAuth>() {
+          Some(digest) => digest.respond(&request, response.headers())?,
+          None => None,
+        };
+        if let Some(authorization) = authorization {
+          digest_retried = true;
+          request
+            .headers_mut()
+            .insert(http::header::AUTHORIZATION, authorization);
+          recorder.record_response(&response);
+          continue;
+        }
+      }
+      // NTLM 认证：在同一个连接上完成 type1/type2/type3 交换
+      #[cfg(feature = "ntlm")]
+      if response.status_code() == StatusCode::UNAUTHORIZED && ntlm_rounds < 2 {
+        let authorization = match request.extensions().get::<NtlmAuth>() {
+          Some(ntlm) => ntlm.respond(response.headers(), &http::header::WWW_AUTHENTICATE)?,
+          None => None,
+        };
+        if let Some(authorization) = authorization {
+          ntlm_rounds += 1;
+          request
+            .headers_mut()
+            .insert(http::header::AUTHORIZATION, authorization);
+          recorder.record_response(&response);
+          continue;
+        }
+      }
       // 根据状态码判断是否应该跳转,并清除一些请求头信息
       // Determine whether to redirect on the status code and clear request header
       let should_redirect = match response.status_code() {
@@ -342,27 +1415,23 @@ impl Client {
         },
         _ => false,
       };
-      let mut redirect_info = RedirectRecord { should_redirect, next: None };
+      // Host 头探测只看第一跳的响应
+      let should_redirect = should_redirect && request.extensions().get::<NoRedirect>().is_none();
+      let location = response.headers().get(http::header::LOCATION).cloned();
+      let mut redirect_info = RedirectRecord {
+        should_redirect,
+        location: location.clone(),
+        next: None,
+      };
       // 如果要跳转，获取进入跳转策略流程
       if should_redirect {
         // 在请求头获取下一跳URL
-        let loc = response
-          .headers()
-          .get(http::header::LOCATION)
-          .and_then(|val| {
-            let val = val.to_str().ok()?;
-            if val.starts_with("https://") || val.starts_with("http://") {
-              http::Uri::from_str(val).ok()
-            } else {
-              let path = PathBuf::from(cur_uri.path()).join(val);
-              http::Uri::builder()
-                .scheme(cur_uri.scheme_str().unwrap_or_default())
-                .authority(cur_uri.authority()?.as_str())
-                .path_and_query(path.to_string_lossy().as_ref())
-                .build()
-                .ok()
-            }
-          });
+        let loc = location.and_then(|val| {
+          self
+            .inner
+            .location_policy
+            .resolve(val.as_bytes(), &cur_uri)
+        });
         redirect_info.next = loc.clone();
         response.extensions_mut().insert(redirect_info);
         // 清除来源
@@ -380,47 +1449,413 @@ impl Client {
             self
               .inner
               .redirect_policy
-              .check(response.status_code(), &loc, uris.as_slice());
+              .check(response.status_code(), &loc, uris.as_slice(), deadline);
           match action {
             Action::Follow => {
+              // 策略决定跟随后再检查循环和降级
+              let violation = if self.inner.detect_redirect_loops && is_loop(&loc, &uris) {
+                Some(RedirectError::Loop)
+              } else if self.inner.deny_redirect_downgrade && is_downgrade(&loc, &uris) {
+                Some(RedirectError::Downgrade)
+              } else {
+                None
+              };
+              if let Some(violation) = violation {
+                uris.push(loc.clone());
+                return Err(Error::Redirect(violation, uris).with_url(&loc));
+              }
+              #[cfg(feature = "trace")]
+              tracing::debug!(status = response.status_code().as_u16(), to = %loc, "redirect");
+              self.inner.hooks.on_redirect(&response, &loc);
               cur_uri = loc;
               *request.uri_mut() =
                 http::Uri::from_str(&cur_uri.to_string()).map_err(http::Error::from)?;
-              let mut headers = std::mem::replace(response.headers_mut(), HeaderMap::new());
-              remove_sensitive_headers(&mut headers, &cur_uri, uris.as_slice());
-              record.record_response(&response);
-              records.push(record);
+              // 原样发送的请求目标只属于原来的地址
+              if matches!(request.extensions().get::<RequestTarget>(), Some(RequestTarget::Raw(_))) {
+                request.extensions_mut().remove::<RequestTarget>();
+              }
+              // 指定的 Host 只对原来的主机有效
+              if request.extensions().get::<HostOverride>().is_some()
+                && uris.last().is_some_and(|prev| prev.authority() != cur_uri.authority())
+              {
+                request.headers_mut().remove(http::header::HOST);
+                request.extensions_mut().remove::<HostOverride>();
+              }
+              // 跨主机跳转时移除认证信息，避免凭证泄露到第三方
+              let cross_host = is_cross_host(&cur_uri, uris.as_slice());
+              if self.inner.strip_sensitive_headers {
+                remove_sensitive_headers(request.headers_mut(), &cur_uri, uris.as_slice());
+                // Digest 和 NTLM 凭证也不能回应第三方主机的质询
+                if cross_host {
+                  request.extensions_mut().remove::<DigestAuth>();
+                  #[cfg(feature = "ntlm")]
+                  request.extensions_mut().remove::<NtlmAuth>();
+                }
+              }
+              digest_retried = false;
+              // NTLM 交换绑定在一个连接上，换了主机重新开始
+              #[cfg(feature = "ntlm")]
+              if cross_host {
+                ntlm_rounds = 0;
+              }
+              // 按主机配置的认证信息只发给对应主机，跳转后重新匹配
+              if host_auth_applied {
+                Credentials::remove(&mut request);
+                host_auth_applied = false;
+              }
+              recorder.record_response(&response);
               continue;
             }
+            Action::TooManyRedirects => {
+              return Err(Error::TooManyRedirects(uris.len() - 1).with_url(&loc));
+            }
+            Action::Loop => {
+              uris.push(loc.clone());
+              return Err(Error::Redirect(RedirectError::Loop, uris).with_url(&loc));
+            }
             Action::Stop => {
-              record.record_response(&response);
-              records.push(record);
+              #[cfg(feature = "trace")]
+              tracing::debug!(to = %loc, "redirect stopped by policy");
+              recorder.record_response(&response);
               break;
             }
           }
         }
       }
-      record.record_response(&response);
-      records.push(record);
+      recorder.record_response(&response);
       break;
     }
     for (_key, socket) in conn {
       socket.shutdown(std::net::Shutdown::Both)?;
     }
-    let mut last_response = records
-      .last()
-      .ok_or(new_io_error(
-        std::io::ErrorKind::NotFound,
-        "not found record",
-      ))?
-      .response
-      .clone();
-    last_response.extensions_mut().insert(records);
-    Ok(last_response)
+    recorder.finish()
   }
 }
 
-#[cfg(feature = "cookie")]
+impl Client {
+  pub(crate) fn location_policy(&self) -> LocationPolicy {
+    self.inner.location_policy
+  }
+  pub(crate) fn auto_headers(&self) -> AutoHeaders {
+    self.inner.auto_headers
+  }
+  /// 后台连接到 `uri` 的源，连接好的 socket 留给下一个发往这个源的请求
+  pub(crate) fn preconnect(&self, uri: http::Uri) {
+    let client = self.clone();
+    std::thread::spawn(move || {
+      let start = Instant::now();
+      if let Ok(socket) = client.inner.connector.connect_with_uri(&uri) {
+        client.inner.hooks.on_connect(&uri, start.elapsed());
+        if let Ok(mut preconnected) = client.inner.preconnected.lock() {
+          preconnected.insert(conn_key(&uri), socket);
+        }
+      }
+    });
+  }
+  /// 打开新连接前占一个连接名额，没有限制时不用等
+  fn connection_permit(
+    &self,
+    uri: &http::Uri,
+    deadline: &Deadline,
+  ) -> Result<Option<ConnectionPermit>> {
+    match &self.inner.connection_limit {
+      Some(limit) => limit.acquire(uri, deadline).map(Some),
+      None => Ok(None),
+    }
+  }
+  fn take_preconnected(&self, uri: &http::Uri, allowed: bool) -> Option<Socket> {
+    if !allowed {
+      return None;
+    }
+    self.inner.preconnected.lock().ok()?.remove(&conn_key(uri))
+  }
+  /// 把客户端的默认请求头加到请求上，请求自己设置了的不覆盖
+  fn add_default_headers(&self, request: &mut Request) {
+    let auto = match request.extensions().get::<AutoHeaders>() {
+      Some(auto) => *auto,
+      None => {
+        request.extensions_mut().insert(self.inner.auto_headers);
+        self.inner.auto_headers
+      }
+    };
+    if let Some(order) = &self.inner.header_order {
+      if request.extensions().get::<HeaderOrder>().is_none() {
+        request.extensions_mut().insert(order.clone());
+      }
+    }
+    for name in self.inner.headers.keys() {
+      if !auto.allows(name) || request.headers().contains_key(name) {
+        continue;
+      }
+      for value in self.inner.headers.get_all(name) {
+        request.headers_mut().append(name.clone(), value.clone());
+      }
+    }
+  }
+  /// 协议策略选择了 HTTP/2 但服务器没有协商出 h2 时退回 HTTP/1.1
+  fn http1_request<'a>(&self, request: &'a Request) -> Result<Cow<'a, Request>> {
+    if request.version() != Version::HTTP_2 || request.raw_request().is_some() {
+      return Ok(Cow::Borrowed(request));
+    }
+    // 请求单独指定的 HTTP/2 不退回
+    let explicit = cfg!(feature = "http2") && request.extensions().get::<ExplicitVersion>().is_some();
+    match &self.inner.protocol_policy {
+      Some(policy) if !explicit => {
+        if !policy.allows_fallback() {
+          return Err(new_io_error(
+            std::io::ErrorKind::Unsupported,
+            "server did not negotiate HTTP/2",
+          ));
+        }
+        let mut request = request.clone();
+        *request.version_mut() = Version::HTTP_11;
+        Ok(Cow::Owned(request))
+      }
+      _ if explicit => Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "server did not negotiate HTTP/2",
+      )),
+      _ => Ok(Cow::Borrowed(request)),
+    }
+  }
+  fn resume_download(
+    &self,
+    connector: &Connector,
+    request: &Request,
+    response: &mut Response,
+    deadline: &Deadline,
+  ) -> Result<()> {
+    let mut resumes = 0;
+    while let Some(partial) = response.extensions_mut().remove::<PartialBody>() {
+      let received = response.body().as_ref().map_or(0, |b| b.len());
+      let accept_ranges = response
+        .headers()
+        .get(http::header::ACCEPT_RANGES)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"bytes"));
+      if resumes >= self.inner.resume_downloads || !accept_ranges {
+        return Err(partial.into());
+      }
+      resumes += 1;
+      let mut range_request = request.clone();
+      let headers = range_request.headers_mut();
+      headers.insert(
+        http::header::RANGE,
+        HeaderValue::try_from(format!("bytes={}-", received))?,
+      );
+      // 资源在两次请求之间变化时服务器会返回完整的 200 响应
+      if let Some(validator) = response
+        .headers()
+        .get(http::header::ETAG)
+        .or_else(|| response.headers().get(http::header::LAST_MODIFIED))
+      {
+        headers.insert(http::header::IF_RANGE, validator.clone());
+      }
+      let _permit = self.connection_permit(request.uri(), deadline)?;
+      let mut socket =
+        connector.connect_with_uri_deadline(request.uri(), deadline, &mut Timing::default())?;
+      let part = self.execute_request_with_deadline(
+        connector,
+        &mut socket,
+        &range_request,
+        deadline,
+        Timing::default(),
+      );
+      let _ = socket.shutdown(std::net::Shutdown::Both);
+      let mut part = match part {
+        Ok(part) => part,
+        Err(_) => {
+          response.extensions_mut().insert(partial);
+          continue;
+        }
+      };
+      let part_body = part.body().clone().unwrap_or_default();
+      match part.status_code() {
+        StatusCode::PARTIAL_CONTENT if content_range_start(part.headers()) == Some(received) => {
+          let mut body = response.body().clone().unwrap_or_default().to_vec();
+          body.extend_from_slice(&part_body);
+          *response.body_mut() = Some(body.into());
+        }
+        StatusCode::OK => {
+          *response.body_mut() = Some(part_body);
+        }
+        _ => return Err(partial.into()),
+      }
+      if let Some(partial) = part.extensions_mut().remove::<PartialBody>() {
+        response.extensions_mut().insert(partial);
+      }
+    }
+    Ok(())
+  }
+}
+
+/// 提前读取响应时每次写入的大小，写入之间检查服务器是否已经响应
+const EARLY_RESPONSE_CHUNK: usize = 16 * 1024;
+
+/// 分段写入请求，服务器开始响应或者关闭连接时停止，返回已经写入的字节数
+fn write_until_response(
+  socket: &mut Socket,
+  raw: &[u8],
+  throttle: Option<&Throttle>,
+  deadline: &Deadline,
+) -> Result<Option<usize>> {
+  let mut sent = 0;
+  for chunk in raw.chunks(EARLY_RESPONSE_CHUNK) {
+    if response_pending(socket)? {
+      return Ok(Some(sent));
+    }
+    match Throttled::new(&mut *socket, throttle, *deadline).write_all(chunk) {
+      Ok(()) => sent += chunk.len(),
+      // 服务器提前响应后可能已经关闭了连接，继续读取已经发来的响应
+      Err(err)
+        if matches!(
+          err.kind(),
+          std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset
+        ) =>
+      {
+        return Ok(Some(sent));
+      }
+      Err(err) => return Err(err.into()),
+    }
+  }
+  socket.flush()?;
+  Ok(None)
+}
+
+/// 分段写入请求，请求头写完之后按请求体的字节报告进度
+fn write_with_progress<W: Write>(
+  socket: &mut W,
+  raw: &[u8],
+  request: &Request,
+  progress: &Progress,
+) -> Result<()> {
+  let body_len = request.body().as_ref().map_or(0, |b| b.len());
+  let head_len = raw.len().saturating_sub(body_len);
+  socket.write_all(&raw[..head_len])?;
+  let mut sent = 0;
+  for chunk in raw[head_len..].chunks(UPLOAD_CHUNK) {
+    socket.write_all(chunk)?;
+    sent += chunk.len();
+    progress.report(sent as u64, Some(body_len as u64));
+  }
+  Ok(())
+}
+
+/// 报告进度时每次写入的字节数
+const UPLOAD_CHUNK: usize = 16 * 1024;
+
+/// 不阻塞地检查连接上是否已经有数据可读
+fn response_pending(socket: &mut Socket) -> Result<bool> {
+  match peek_nonblocking(socket) {
+    Ok(_) => Ok(true),
+    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+    Err(err) => Err(err.into()),
+  }
+}
+
+/// 不阻塞地查看连接上可读的字节数，`Ok(0)` 表示对端已经关闭
+///
+/// TLS 连接先解密再判断，握手之后的会话票据等记录不算数据，`close_notify` 算关闭
+fn peek_nonblocking(socket: &mut Socket) -> std::io::Result<usize> {
+  socket.set_nonblocking(true)?;
+  let result = match socket {
+    Socket::TCP(raw) | Socket::UDP(raw) => {
+      let mut buf = [std::mem::MaybeUninit::uninit(); 1];
+      raw.peek(&mut buf)
+    }
+    #[cfg(feature = "tls")]
+    Socket::TLS(stream) => tls_peek(stream),
+    #[cfg(feature = "tls")]
+    Socket::NestedTLS(stream) => tls_peek(stream),
+  };
+  socket.set_nonblocking(false)?;
+  result
+}
+
+/// 在 TLS 层查看解密后的数据，只处理记录不消耗应用数据
+#[cfg(feature = "tls")]
+fn tls_peek<S: std::io::Read + Write>(stream: &mut openssl::ssl::SslStream<S>) -> std::io::Result<usize> {
+  use openssl::ssl::ErrorCode;
+  let pending = stream.ssl().pending();
+  if pending > 0 {
+    return Ok(pending);
+  }
+  let mut buf = [0u8; 1];
+  match stream.ssl_peek(&mut buf) {
+    Ok(n) => Ok(n),
+    Err(err) if err.code() == ErrorCode::ZERO_RETURN => Ok(0),
+    Err(err) if err.code() == ErrorCode::WANT_READ || err.code() == ErrorCode::WANT_WRITE => {
+      Err(std::io::ErrorKind::WouldBlock.into())
+    }
+    // 没有 close_notify 直接断开
+    Err(err) if err.code() == ErrorCode::SYSCALL && err.io_error().is_none() => Ok(0),
+    Err(err) => Err(err.into_io_error().unwrap_or_else(std::io::Error::other)),
+  }
+}
+
+/// 同一个 scheme、主机和端口的请求复用一个连接
+fn conn_key(u: &http::Uri) -> String {
+  let scheme = u.scheme_str().unwrap_or_default();
+  let host = u.host().unwrap_or_default();
+  let port = u.port_u16().unwrap_or_default();
+  format!("{}{}{}", scheme, host, port)
+}
+
+/// 请求或响应的头部是否要求关闭连接
+fn wants_close(headers: &HeaderMap, version: Version) -> bool {
+  let has = |token: &str| {
+    headers
+      .get_all(http::header::CONNECTION)
+      .iter()
+      .filter_map(|v| v.to_str().ok())
+      .flat_map(|v| v.split(','))
+      .any(|v| v.trim().eq_ignore_ascii_case(token))
+  };
+  has("close") || (version <= Version::HTTP_10 && !has("keep-alive"))
+}
+
+/// 不读取数据，根据连接当前的状态判断这次交换后连接是怎么结束的
+///
+/// 不探测时只看头部，不碰连接
+fn classify_close(
+  socket: &mut Socket,
+  response: &Response,
+  reusable: bool,
+  probe: bool,
+) -> ConnectionClose {
+  use std::io::ErrorKind;
+  let reset = |kind: ErrorKind| matches!(kind, ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted);
+  if let Some(partial) = response.extensions().get::<PartialBody>() {
+    return match partial.kind {
+      ErrorKind::TimedOut | ErrorKind::WouldBlock => ConnectionClose::IdleTimeout,
+      kind if reset(kind) => ConnectionClose::Reset,
+      _ => ConnectionClose::ServerFin,
+    };
+  }
+  let keep = reusable && !wants_close(response.headers(), response.version());
+  if !probe {
+    return if keep {
+      ConnectionClose::KeepAlive
+    } else {
+      ConnectionClose::ClientClosed
+    };
+  }
+  match peek_nonblocking(socket) {
+    Ok(0) => ConnectionClose::ServerFin,
+    Err(err) if reset(err.kind()) => ConnectionClose::Reset,
+    Ok(_) if keep => ConnectionClose::KeepAlive,
+    Err(err) if keep && err.kind() == ErrorKind::WouldBlock => ConnectionClose::KeepAlive,
+    _ => ConnectionClose::ClientClosed,
+  }
+}
+
+/// 解析 `Content-Range: bytes 100-199/200` 的起始位置
+fn content_range_start(headers: &HeaderMap) -> Option<usize> {
+  let value = headers.get(http::header::CONTENT_RANGE)?.to_str().ok()?;
+  let range = value.trim().strip_prefix("bytes ")?;
+  range.split_once('-')?.0.trim().parse().ok()
+}
+
+#[cfg(feature = "cookie")]
 #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
 fn add_cookie_header(request: &mut Request, cookie_store: &dyn cookies::CookieStore) {
   if let Some(header) = cookie_store.cookies(request.uri()) {
@@ -483,10 +1918,18 @@ impl ClientBuilder {
   ///
   /// This is the same as `Client::builder()`.
   pub fn new() -> ClientBuilder {
-    let mut headers: HeaderMap<HeaderValue> = HeaderMap::with_capacity(2);
+    let mut headers: HeaderMap<HeaderValue> = HeaderMap::with_capacity(3);
     headers.insert(http::header::ACCEPT, HeaderValue::from_static("*/*"));
+    headers.insert(
+      http::header::USER_AGENT,
+      HeaderValue::from_static(concat!("slinger/", env!("CARGO_PKG_VERSION"))),
+    );
+    headers.insert(http::header::CONNECTION, HeaderValue::from_static("keep-alive"));
     ClientBuilder {
-      config: Config::default(),
+      config: Config {
+        headers,
+        ..Config::default()
+      },
     }
   }
   /// Returns a `Client` that uses this `ClientBuilder` configuration.
@@ -501,21 +1944,111 @@ impl ClientBuilder {
   /// See docs on
   /// [`slinger::client`][Client] for details.
   pub fn build(self) -> Result<Client> {
-    let config = self.config;
-    let connector = ConnectorBuilder::default()
-      .proxy(config.proxy)
+    let mut config = self.config;
+    if let Some(policy) = &config.protocol_policy {
+      policy.check()?;
+      if config.alpn_protocols.is_empty() {
+        config.alpn_protocols = policy.alpn_protocols();
+      }
+    }
+    let mut connector = ConnectorBuilder::default()
+      .hostname_verification(config.hostname_verification)
+      .certs_verification(config.certs_verification)
       .nodelay(config.nodelay)
       .read_timeout(config.timeout)
       .connect_timeout(config.connect_timeout)
       .write_timeout(config.timeout)
-      .build()?;
+      .alpn_protocols(config.alpn_protocols);
+    if let Some(time) = config.tcp_keepalive {
+      connector = connector.tcp_keepalive(time);
+    }
+    if let Some(header) = config.proxy_protocol {
+      connector = connector.proxy_protocol(header);
+    }
+    connector.transport = config.transport;
+    if let Some((ttl, max_entries)) = config.dns_cache {
+      connector = connector.dns_cache(ttl, max_entries);
+    }
+    #[cfg(feature = "tls")]
+    if config.tls_session_cache {
+      connector = connector.tls_session_cache(true);
+    }
+    #[cfg(feature = "tls")]
+    if let Some(path) = config.keylog {
+      connector = connector.keylog(path);
+    }
+    #[cfg(feature = "tls")]
+    for (host, hashes) in config.pins {
+      connector = connector.pin_sha256(host, hashes);
+    }
+    #[cfg(feature = "tls")]
+    if let Some(browser) = config.impersonate {
+      connector = connector.impersonate(browser);
+    }
+    #[cfg(feature = "tls")]
+    for cert in config.root_certs {
+      connector = connector.add_root_certificate(cert);
+    }
+    #[cfg(feature = "tls")]
+    if let Some(identity) = config.identity {
+      connector = connector.identity(identity);
+    }
+    connector = connector.tls_sni(config.tls_sni);
+    #[cfg(feature = "proxy")]
+    let connector = connector.proxy(config.proxy);
+    let connector_builder = connector;
+    let connector = connector_builder.build()?;
     Ok(Client {
       inner: ClientRef {
         #[cfg(feature = "cookie")]
         cookie_store: config.cookie_store,
         connector: Arc::new(connector),
+        connector_builder,
+        headers: config.headers,
+        auto_headers: config.auto_headers,
+        header_order: config.header_order,
+        early_hints: config.early_hints,
+        preconnected: Default::default(),
         redirect_policy: config.redirect_policy,
+        location_policy: config.location_policy,
         referer: config.referer,
+        strip_sensitive_headers: config.strip_sensitive_headers,
+        detect_redirect_loops: config.detect_redirect_loops,
+        deny_redirect_downgrade: config.deny_redirect_downgrade,
+        deadline: config.deadline,
+        #[cfg(feature = "record")]
+        record_limit: config.record_limit,
+        retry_policy: config.retry_policy,
+        rate_limiter: config
+          .rate_limit
+          .map(|(rate, burst)| Arc::new(RateLimiter::new(rate, burst, config.rate_limit_per_host))),
+        download_throttle: config.max_download_speed.map(|rate| Arc::new(Throttle::new(rate))),
+        upload_throttle: config.max_upload_speed.map(|rate| Arc::new(Throttle::new(rate))),
+        connection_limit: (config.max_connections_per_host.is_some()
+          || config.max_connections.is_some())
+        .then(|| {
+          Arc::new(ConnectionLimit::new(
+            config.max_connections_per_host,
+            config.max_connections,
+            config.connection_queue_timeout,
+          ))
+        }),
+        probe_http_first: config.probe_http_first,
+        activity: Arc::new(ActivityLog::new(config.recent_activity)),
+        shards: Arc::new(ShardLog::default()),
+        middlewares: config.middlewares,
+        #[cfg(feature = "sign")]
+        signer: config.signer,
+        host_auth: config.host_auth,
+        hooks: config.hooks,
+        resume_downloads: config.resume_downloads,
+        response_limits: config.response_limits,
+        status_code_policy: config.status_code_policy,
+        lenient_parsing: config.lenient_parsing,
+        detect_waf: config.detect_waf,
+        probe_connection_close: config.probe_connection_close,
+        protocol_policy: config.protocol_policy,
+        wire_log: config.wire_log,
       },
     })
   }
@@ -576,6 +2109,66 @@ impl ClientBuilder {
     }
     self
   }
+  /// Choose which of `Host`, `Content-Length`, `Connection`, `Accept` and
+  /// `User-Agent` are added to requests that do not set them, see
+  /// [`AutoHeaders`](crate::AutoHeaders).
+  ///
+  /// A request can override this with its own `AutoHeaders` extension.
+  ///
+  /// Default adds all of them.
+  pub fn auto_headers(mut self, auto: AutoHeaders) -> ClientBuilder {
+    self.config.auto_headers = auto;
+    self
+  }
+  /// Write request headers in a fixed order and casing, such as the one of a
+  /// browser, see [`HeaderOrder`](crate::HeaderOrder).
+  ///
+  /// Applies to the default headers too; a request can override it with
+  /// [`RequestBuilder::header_order`](crate::RequestBuilder::header_order).
+  ///
+  /// Default writes `Host` first and the other headers lowercase in the order
+  /// they were added.
+  pub fn header_order(mut self, order: HeaderOrder) -> ClientBuilder {
+    self.config.header_order = Some(order);
+    self
+  }
+  /// Send requests that look like the ones of `browser`, see [`Browser`].
+  ///
+  /// Replaces the default headers with the ones of the browser, writes them
+  /// in its order and casing, offers its ALPN protocols and, with the `tls`
+  /// feature, its TLS parameters. Headers, the header order and the ALPN list
+  /// set after this call take precedence.
+  ///
+  /// The `Accept-Encoding` header lists `br` like the browser does, but only
+  /// `gzip` and `deflate` are decoded with the `gzip` feature.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::builder()
+  ///   .impersonate(slinger::Browser::Chrome120)
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn impersonate(mut self, browser: Browser) -> ClientBuilder {
+    self.config.headers = browser.headers();
+    self.config.header_order = Some(browser.header_order());
+    self.config.alpn_protocols = browser.alpn_protocols();
+    #[cfg(feature = "tls")]
+    {
+      self.config.impersonate = Some(browser);
+    }
+    self
+  }
+  /// Try HTTP before HTTPS in [`Client::probe`].
+  ///
+  /// Default is `false`, HTTPS first.
+  pub fn probe_http_first(mut self, enable: bool) -> ClientBuilder {
+    self.config.probe_http_first = enable;
+    self
+  }
   // Redirect options
 
   /// Set a `redirect::Policy` for this client.
@@ -585,6 +2178,14 @@ impl ClientBuilder {
     self.config.redirect_policy = policy;
     self
   }
+  /// Set how the `Location` header of a redirect is normalized before it is
+  /// followed, see [`LocationPolicy`](crate::redirect::LocationPolicy).
+  ///
+  /// Default is `LocationPolicy::Lenient`.
+  pub fn location_policy(mut self, policy: LocationPolicy) -> ClientBuilder {
+    self.config.location_policy = policy;
+    self
+  }
   /// Enable or disable automatic setting of the `Referer` header.
   ///
   /// Default is `true`.
@@ -592,6 +2193,463 @@ impl ClientBuilder {
     self.config.referer = enable;
     self
   }
+  /// Enable or disable removal of credentials on cross-host redirects.
+  ///
+  /// When enabled, the `Authorization`, `Cookie` and `Proxy-Authorization`
+  /// headers are dropped before following a redirect whose host or port
+  /// differs from the previous request, and so are the credentials set with
+  /// [`RequestBuilder::digest_auth`](crate::RequestBuilder::digest_auth) and
+  /// `RequestBuilder::ntlm_auth`.
+  ///
+  /// Default is `true`.
+  pub fn strip_sensitive_headers(mut self, enable: bool) -> ClientBuilder {
+    self.config.strip_sensitive_headers = enable;
+    self
+  }
+  /// Enable or disable failing on redirect loops.
+  ///
+  /// When enabled, a redirect the policy decided to follow fails with
+  /// [`Error::Redirect`](crate::Error::Redirect) and
+  /// [`RedirectError::Loop`](crate::RedirectError::Loop), of kind
+  /// [`ErrorKind::RedirectLoop`](crate::ErrorKind::RedirectLoop), if it
+  /// repeats a redirect of the chain, see
+  /// [`Attempt::is_loop`](crate::redirect::Attempt::is_loop).
+  ///
+  /// A redirect is repeated when the same URL sends to the same next URL a
+  /// second time; visiting a URL again is not enough. `/a -> /login -> /a`
+  /// is followed, `/a -> /b -> /a -> /b` fails on the last hop. A server
+  /// bouncing between two URLs is caught on the second round instead of after
+  /// the whole hop budget, while a chain that only revisits URLs through
+  /// different redirects runs until the policy gives up.
+  ///
+  /// Default is `true`.
+  pub fn detect_redirect_loops(mut self, enable: bool) -> ClientBuilder {
+    self.config.detect_redirect_loops = enable;
+    self
+  }
+  /// Enable or disable refusing redirects from `https` to `http`.
+  ///
+  /// When enabled, such a redirect fails with
+  /// [`Error::Redirect`](crate::Error::Redirect) and
+  /// [`RedirectError::Downgrade`](crate::RedirectError::Downgrade) instead of
+  /// sending the request in clear text.
+  ///
+  /// Default is `false`.
+  pub fn deny_redirect_downgrade(mut self, enable: bool) -> ClientBuilder {
+    self.config.deny_redirect_downgrade = enable;
+    self
+  }
+  /// Cap the raw request and response bytes kept in each `HTTPRecord`.
+  ///
+  /// Default keeps everything.
+  #[cfg(feature = "record")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+  pub fn record_limit(mut self, limit: RecordLimit) -> ClientBuilder {
+    self.config.record_limit = limit;
+    self
+  }
+  /// Set a `RetryPolicy` for this client.
+  ///
+  /// Retries share the budget set with [`ClientBuilder::deadline`].
+  ///
+  /// Default is no retry.
+  pub fn retry(mut self, policy: RetryPolicy) -> ClientBuilder {
+    self.config.retry_policy = Some(policy);
+    self
+  }
+  /// Attach `credentials` to every request targeting `host`.
+  ///
+  /// `host` is matched case-insensitively against the request host, and when
+  /// it carries a port (`example.com:8443`) against the host and port. The
+  /// credentials are added only when the request has no authentication of its
+  /// own, and are removed again when a redirect leaves the host, so they never
+  /// reach another server. The first matching entry wins.
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// use slinger::auth::Credentials;
+  ///
+  /// let client = slinger::Client::builder()
+  ///   .auth_for_host("api.example.com", Credentials::bearer("token"))
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn auth_for_host<H: Into<String>>(
+    mut self,
+    host: H,
+    credentials: Credentials,
+  ) -> ClientBuilder {
+    self.config.host_auth.push((host.into(), credentials));
+    self
+  }
+  /// Call `hook` with every request right before it is written to the socket,
+  /// including the extra round-trips of redirects and authentication.
+  pub fn on_request<F>(mut self, hook: F) -> ClientBuilder
+    where
+      F: Fn(&Request) + Send + Sync + 'static,
+  {
+    self.config.hooks.add_request(Arc::new(hook));
+    self
+  }
+  /// Call `hook` with every response as soon as its head and body are read,
+  /// along with the request it answers and the time spent since the request
+  /// was written.
+  pub fn on_response<F>(mut self, hook: F) -> ClientBuilder
+    where
+      F: Fn(&Request, &Response, Duration) + Send + Sync + 'static,
+  {
+    self.config.hooks.add_response(Arc::new(hook));
+    self
+  }
+  /// Call `hook` with the redirect response and the next URI each time a
+  /// redirect is followed.
+  pub fn on_redirect<F>(mut self, hook: F) -> ClientBuilder
+    where
+      F: Fn(&Response, &http::Uri) + Send + Sync + 'static,
+  {
+    self.config.hooks.add_redirect(Arc::new(hook));
+    self
+  }
+  /// Call `hook` with the target URI and the time spent once a new connection
+  /// is established, DNS, proxy and TLS handshake included.
+  pub fn on_connect<F>(mut self, hook: F) -> ClientBuilder
+    where
+      F: Fn(&http::Uri, Duration) + Send + Sync + 'static,
+  {
+    self.config.hooks.add_connect(Arc::new(hook));
+    self
+  }
+  /// Decide what to do with each `Link` of a `103 Early Hints` response, the
+  /// way browsers preconnect to origins and preload resources before the
+  /// final response arrives.
+  ///
+  /// `policy` is called while the request is still waiting for its final
+  /// response. Preconnected sockets are picked up by the next request of the
+  /// client to the same origin, preloads are sent with
+  /// [`Client::send_at`] and their handles are returned by
+  /// [`Response::take_preloads`](crate::Response::take_preloads).
+  ///
+  /// Default ignores early hints, they are only kept in
+  /// [`Response::informational`](crate::Response::informational).
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// use slinger::{EarlyHint, EarlyHintAction};
+  ///
+  /// let client = slinger::Client::builder()
+  ///   .early_hints(|hint: &EarlyHint| match hint.rel.as_str() {
+  ///     "preconnect" => EarlyHintAction::Preconnect,
+  ///     "preload" if hint.destination.as_deref() == Some("style") => EarlyHintAction::Preload,
+  ///     _ => EarlyHintAction::Ignore,
+  ///   })
+  ///   .build()?;
+  /// let mut resp = client.get("https://example.com/").send()?;
+  /// for preload in resp.take_preloads() {
+  ///   println!("{:?}", preload.wait().map(|r| r.map(|r| r.status_code())));
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn early_hints<F>(mut self, policy: F) -> ClientBuilder
+    where
+      F: Fn(&EarlyHint) -> EarlyHintAction + Send + Sync + 'static,
+  {
+    self.config.early_hints = Some(EarlyHintPolicy(Arc::new(policy)));
+    self
+  }
+  /// Call `hook` with the request, the error and the time spent since the
+  /// request was executed whenever an attempt fails.
+  pub fn on_error<F>(mut self, hook: F) -> ClientBuilder
+    where
+      F: Fn(&Request, &Error, Duration) + Send + Sync + 'static,
+  {
+    self.config.hooks.add_error(Arc::new(hook));
+    self
+  }
+  /// Resume interrupted downloads with `Range` requests, up to `max_resumes` times.
+  ///
+  /// When the connection drops before a `GET` response with a `Content-Length`
+  /// has been fully read and the server sent `Accept-Ranges: bytes`, the client
+  /// reconnects and asks for the remaining bytes, guarded by `If-Range` with
+  /// the `ETag` or `Last-Modified` of the first response. Compressed bodies are
+  /// not resumed.
+  ///
+  /// Default is `0`, no resume.
+  pub fn resume_downloads(mut self, max_resumes: usize) -> ClientBuilder {
+    self.config.resume_downloads = max_resumes;
+    self
+  }
+  /// Fail with [`Error::ResponseLimit`] when a response body is larger than
+  /// `bytes`.
+  ///
+  /// The limit applies to the bytes read from the connection, it is checked
+  /// against `Content-Length` before reading and while reading chunked or
+  /// close-delimited bodies. Default is no limit.
+  pub fn max_response_size(mut self, bytes: u64) -> ClientBuilder {
+    self.config.response_limits.body_size = Some(bytes);
+    self
+  }
+  /// Keep response bodies larger than `threshold` bytes in a temporary file
+  /// instead of in memory.
+  ///
+  /// The file is mapped into memory, so [`Response::body`] and the readers
+  /// built from it work as usual while the operating system pages the data
+  /// in and out. The file is deleted once the last clone of the body is
+  /// dropped. Compressed bodies are decompressed into memory, still bounded
+  /// by the decompression limits. Default is to keep every body in memory.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::builder()
+  ///   .spill_to_disk(64 * 1024 * 1024)
+  ///   .build()?;
+  /// let resp = client.get("https://example.com/dump.tar").send()?;
+  /// println!("{} bytes", resp.body().as_ref().map_or(0, |b| b.len()));
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn spill_to_disk(mut self, threshold: u64) -> ClientBuilder {
+    self.config.response_limits.spill_threshold = Some(threshold);
+    self
+  }
+  /// Fail with [`Error::ResponseLimit`] when the status line and headers of a
+  /// response together are longer than `bytes`. Default is no limit.
+  pub fn max_header_size(mut self, bytes: usize) -> ClientBuilder {
+    self.config.response_limits.header_size = Some(bytes);
+    self
+  }
+  /// Fail with [`Error::ResponseLimit`] when a response has more than `count`
+  /// header lines. Default is no limit.
+  pub fn max_header_count(mut self, count: usize) -> ClientBuilder {
+    self.config.response_limits.header_count = Some(count);
+    self
+  }
+  /// Fail with [`Error::ResponseLimit`] when a gzip response body would
+  /// decompress to more than `bytes`; decompression stops at the limit.
+  ///
+  /// Default is 100 MiB, `u64::MAX` disables the limit.
+  #[cfg(feature = "gzip")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "gzip")))]
+  pub fn max_decompressed_size(mut self, bytes: u64) -> ClientBuilder {
+    self.config.response_limits.decompressed_size = bytes;
+    self
+  }
+  /// Fail with [`Error::ResponseLimit`] when a gzip response body expands to
+  /// more than `ratio` times its compressed size.
+  ///
+  /// Bodies that decompress to less than 1 MiB are not checked. Default is
+  /// `100`, `u64::MAX` disables the limit.
+  #[cfg(feature = "gzip")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "gzip")))]
+  pub fn max_compression_ratio(mut self, ratio: u64) -> ClientBuilder {
+    self.config.response_limits.compression_ratio = ratio;
+    self
+  }
+  /// Set how a response with a malformed status code such as `0200` is
+  /// handled, see [`StatusCodePolicy`].
+  ///
+  /// Default is [`StatusCodePolicy::Raw`].
+  pub fn status_code_policy(mut self, policy: StatusCodePolicy) -> ClientBuilder {
+    self.config.status_code_policy = policy;
+    self
+  }
+  /// Read responses of broken servers instead of failing or losing headers:
+  /// garbage and blank lines before the status line, malformed status lines
+  /// such as `http/1.1  200OK`, bare `\n` line endings, folded headers and
+  /// whitespace before the colon of a header name.
+  ///
+  /// What was repaired is recorded in [`Response::repairs`]. Malformed status
+  /// codes are still handled by the [`StatusCodePolicy`].
+  ///
+  /// Default is `false`.
+  pub fn lenient_parsing(mut self, enable: bool) -> ClientBuilder {
+    self.config.lenient_parsing = enable;
+    self
+  }
+  /// Log every byte written to and read from the connections to standard
+  /// error, like `curl -v`, see [`WireLog`].
+  ///
+  /// Default is `false`.
+  pub fn verbose(mut self, enable: bool) -> ClientBuilder {
+    self.config.wire_log = enable.then(WireLog::stderr);
+    self
+  }
+  /// Log every byte written to and read from the connections to `log`.
+  ///
+  /// HTTP/2 frames are not logged. Default is no log.
+  pub fn wire_log(mut self, log: WireLog) -> ClientBuilder {
+    self.config.wire_log = Some(log);
+    self
+  }
+  /// Tag blocked and rate limited responses with the WAF signature they match,
+  /// see [`waf::detect`](crate::waf::detect) and [`Response::waf`].
+  ///
+  /// A [`RetryPolicy`] then backs off on rate limits and does not retry block
+  /// pages. Default is `false`.
+  pub fn detect_waf(mut self, enable: bool) -> ClientBuilder {
+    self.config.detect_waf = enable;
+    self
+  }
+  /// Check the connection after each response to tell how it ended, see
+  /// [`Response::connection_close`].
+  ///
+  /// The check looks at the connection once without waiting, decrypting on
+  /// TLS connections. Default is `false`.
+  pub fn probe_connection_close(mut self, enable: bool) -> ClientBuilder {
+    self.config.probe_connection_close = enable;
+    self
+  }
+  /// Keep a summary of the last `capacity` request attempts in memory, for
+  /// [`Client::recent_activity`].
+  ///
+  /// Default is `0`, nothing is kept.
+  pub fn recent_activity(mut self, capacity: usize) -> ClientBuilder {
+    self.config.recent_activity = capacity;
+    self
+  }
+  /// Add a `Middleware` that every request of this client passes through.
+  ///
+  /// Middlewares run in the order they are added, the first one added sees
+  /// the request first and the response last.
+  pub fn with<M: Middleware>(mut self, middleware: M) -> ClientBuilder {
+    self.config.middlewares.push(Arc::new(middleware));
+    self
+  }
+  /// Sign every request with `signer` right before it is written, such as
+  /// [`SigV4`](crate::SigV4) for AWS APIs.
+  ///
+  /// Unlike a [`Middleware`], the signer runs after the default headers,
+  /// cookies and credentials of the client were added, and again for every
+  /// redirect hop and authentication round, so the signature covers the
+  /// request as it is sent.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `sign` feature to be enabled.
+  #[cfg(feature = "sign")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "sign")))]
+  pub fn sign<S: Signer>(mut self, signer: S) -> ClientBuilder {
+    self.config.signer = Some(Arc::new(signer));
+    self
+  }
+  /// Cache responses in `store` and answer from it, as a private HTTP cache
+  /// following RFC 7234.
+  ///
+  /// `GET` responses are kept when they have a freshness lifetime from
+  /// `Cache-Control: max-age` or `Expires`, or a validator. A fresh response
+  /// is returned without sending the request; a stale one is revalidated with
+  /// `If-None-Match` or `If-Modified-Since`, and a `304 Not Modified` returns
+  /// the cached response with the new headers. `no-store`, `no-cache` and
+  /// `Vary` are honored, requests carrying their own conditional headers are
+  /// sent as is, and successful unsafe requests such as `POST` drop the entry
+  /// of their URL. The cache is a middleware added at this point of the
+  /// chain, see [`ClientBuilder::with`].
+  pub fn cache<S: CacheStore>(self, store: S) -> ClientBuilder {
+    self.with(HttpCache::new(store))
+  }
+  /// Limit the rate at which this client sends requests.
+  ///
+  /// A token bucket holding up to `burst` tokens is refilled at
+  /// `requests_per_second`; each request, redirects included, and each
+  /// [`Client::tunnel`] takes one token and blocks until one is available. The bucket is shared by every clone of
+  /// the `Client`, and waiting counts against [`ClientBuilder::deadline`].
+  ///
+  /// Default is no limit.
+  pub fn rate_limit(mut self, requests_per_second: f64, burst: u32) -> ClientBuilder {
+    self.config.rate_limit = Some((requests_per_second, burst));
+    self
+  }
+  /// Keep a separate rate limit bucket for each `scheme://host:port`.
+  ///
+  /// Default is `false`, one bucket for all hosts.
+  pub fn rate_limit_per_host(mut self, enable: bool) -> ClientBuilder {
+    self.config.rate_limit_per_host = enable;
+    self
+  }
+  /// Cap the speed at which responses are read, in bytes per second.
+  ///
+  /// The cap is shared by every connection and every clone of the `Client`
+  /// and applies to HTTP/1 responses, headers included. Reads are cut into
+  /// pieces of about a tenth of a second, which also makes the client look
+  /// like a slow reader to the server. Waiting counts against
+  /// [`ClientBuilder::deadline`].
+  ///
+  /// Default is no limit.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::builder()
+  ///   .max_download_speed(64 * 1024)
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn max_download_speed(mut self, bytes_per_second: u64) -> ClientBuilder {
+    self.config.max_download_speed = Some(bytes_per_second);
+    self
+  }
+  /// Cap the speed at which HTTP/1 requests are written, in bytes per second,
+  /// see [`ClientBuilder::max_download_speed`].
+  ///
+  /// Default is no limit.
+  pub fn max_upload_speed(mut self, bytes_per_second: u64) -> ClientBuilder {
+    self.config.max_upload_speed = Some(bytes_per_second);
+    self
+  }
+  /// Keep at most `max` connections open at once to each
+  /// `scheme://host:port`, at least one.
+  ///
+  /// A request that needs a new connection beyond the cap waits until
+  /// another one is closed, see [`ClientBuilder::connection_queue_timeout`].
+  /// The cap is shared by every clone of the `Client` and counts the
+  /// connections of requests, pipelines, event streams and tunnels opened
+  /// with [`Client::tunnel`]; early hint preconnects are not counted.
+  ///
+  /// Default is no limit.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let client = slinger::Client::builder()
+  ///   .max_connections_per_host(4)
+  ///   .max_connections(256)
+  ///   .connection_queue_timeout(std::time::Duration::from_secs(30))
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn max_connections_per_host(mut self, max: usize) -> ClientBuilder {
+    self.config.max_connections_per_host = Some(max);
+    self
+  }
+  /// Keep at most `max` connections open at once over all hosts, at least
+  /// one, see [`ClientBuilder::max_connections_per_host`].
+  ///
+  /// Default is no limit.
+  pub fn max_connections(mut self, max: usize) -> ClientBuilder {
+    self.config.max_connections = Some(max);
+    self
+  }
+  /// Fail a request with a `TimedOut` error when it waited `timeout` for a
+  /// free connection under [`ClientBuilder::max_connections_per_host`] or
+  /// [`ClientBuilder::max_connections`].
+  ///
+  /// Waiting also counts against [`ClientBuilder::deadline`]. Default is to
+  /// wait until the deadline, or forever without one.
+  pub fn connection_queue_timeout(mut self, timeout: Duration) -> ClientBuilder {
+    self.config.connection_queue_timeout = Some(timeout);
+    self
+  }
   // Proxy options
 
   /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -599,6 +2657,8 @@ impl ClientBuilder {
   /// # Note
   ///
   /// Adding a proxy will disable the automatic usage of the "system" proxy.
+  #[cfg(feature = "proxy")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "proxy")))]
   pub fn proxy(mut self, proxy: Proxy) -> ClientBuilder {
     self.config.proxy = Some(proxy);
     self
@@ -607,15 +2667,36 @@ impl ClientBuilder {
 
   /// Set a timeout for connect, read and write operations of a `Client`.
   ///
-  /// Default is 30 seconds.
+  /// Each timeout applies to a single operation and resets after it succeeds,
+  /// so a server trickling bytes can keep a request open; use
+  /// [`ClientBuilder::deadline`] or [`RequestBuilder::timeout`] for a hard
+  /// limit on the whole request.
   ///
-  /// Pass `None` to disable timeout.
+  /// Default is `None`.
   pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
     self.config.timeout = Some(timeout);
     self
   }
+  /// Set a total time budget for each request of a `Client`.
+  ///
+  /// The budget is measured with a monotonic clock from the moment the request
+  /// is executed and is shared by DNS resolution, connect, TLS handshake, writes,
+  /// reads and every additional round-trip caused by redirects or authentication.
+  /// Each socket timeout is clamped to the time left, and a `TimedOut` error is
+  /// returned once the budget is used up.
+  ///
+  /// Default is `None`.
+  pub fn deadline(mut self, budget: Duration) -> ClientBuilder {
+    self.config.deadline = Some(budget);
+    self
+  }
   /// Set a timeout for only the connect phase of a `Client`.
   ///
+  /// When the host resolves to several addresses they are tried in turn and
+  /// the timeout is split between them, so one dead address does not fail
+  /// the request. The address connected to is in
+  /// [`Response::local_peer_record`](crate::Response::local_peer_record).
+  ///
   /// Default is `None`.
   pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
     self.config.connect_timeout = Some(timeout);
@@ -630,6 +2711,32 @@ impl ClientBuilder {
     self.config.nodelay = enabled;
     self
   }
+  /// Enable TCP keepalive on every connection, see
+  /// [`ConnectorBuilder::tcp_keepalive`].
+  ///
+  /// Default is no keepalive.
+  pub fn tcp_keepalive(mut self, time: Duration) -> ClientBuilder {
+    self.config.tcp_keepalive = Some(time);
+    self
+  }
+  /// Send a PROXY protocol header on every new connection, see
+  /// [`ConnectorBuilder::proxy_protocol`].
+  ///
+  /// Default is no header.
+  pub fn proxy_protocol(mut self, header: ProxyProtocol) -> ClientBuilder {
+    self.config.proxy_protocol = Some(header);
+    self
+  }
+  /// Open every connection with a custom [`Transport`] instead of the
+  /// network, for example a [`MockTransport`](crate::MockTransport) in
+  /// tests.
+  ///
+  /// Proxy, PROXY protocol and TLS settings don't apply to those
+  /// connections. Default is the [`Connector`] built from this builder.
+  pub fn transport<T: Transport>(mut self, transport: T) -> ClientBuilder {
+    self.config.transport = Some(TransportHandle(Arc::new(transport)));
+    self
+  }
   #[cfg(feature = "tls")]
   // TLS options
   /// Add a custom root certificate.
@@ -648,7 +2755,7 @@ impl ClientBuilder {
   /// let der = std::fs::read("my-cert.der")?;
   ///
   /// // create a certificate
-  /// let cert = slinger::native_tls::Certificate::from_der(&der)?;
+  /// let cert = slinger::Certificate::from_der(&der)?;
   ///
   /// // get a client builder
   /// let client = slinger::Client::builder()
@@ -670,6 +2777,20 @@ impl ClientBuilder {
   #[cfg(feature = "tls")]
   /// Sets the identity to be used for client certificate authentication.
   ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn build_client() -> Result<(), Box<dyn std::error::Error>> {
+  /// // the certificate and its private key in one PEM file
+  /// let pem = std::fs::read("client.pem")?;
+  /// let client = slinger::Client::builder()
+  ///     .identity(slinger::Identity::from_pem(&pem)?)
+  ///     .build()?;
+  /// # drop(client);
+  /// # Ok(())
+  /// # }
+  /// ```
+  ///
   /// # Optional
   ///
   /// This requires the optional `tls`
@@ -717,6 +2838,95 @@ impl ClientBuilder {
     self.config.tls_sni = tls_sni;
     self
   }
+  /// Set the exact list of protocols offered with ALPN during the TLS handshake.
+  ///
+  /// The order is kept as given, which allows forcing `http/1.1`, offering `h2`
+  /// or advertising arbitrary protocol names. The negotiated value is available
+  /// with [`Response::alpn_record`](crate::Response::alpn_record).
+  ///
+  /// Default is an empty list, ALPN is not used.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `tls` feature to be enabled.
+  pub fn alpn_protocols<I, S>(mut self, protocols: I) -> ClientBuilder
+    where
+      I: IntoIterator<Item=S>,
+      S: AsRef<str>,
+  {
+    self.config.alpn_protocols = protocols
+      .into_iter()
+      .map(|p| p.as_ref().to_string())
+      .collect();
+    self
+  }
+  /// Cache resolved addresses per host name for `ttl`, keeping at most
+  /// `max_entries` host names, see [`ConnectorBuilder::dns_cache`].
+  ///
+  /// The cache is shared by every clone of the `Client`, and emptied with
+  /// [`Client::clear_dns_cache`]. Default is no cache, every new connection
+  /// resolves the host name.
+  pub fn dns_cache(mut self, ttl: Duration, max_entries: usize) -> ClientBuilder {
+    self.config.dns_cache = Some((ttl, max_entries));
+    self
+  }
+  /// Resume TLS sessions when reconnecting to a host, see
+  /// [`ConnectorBuilder::tls_session_cache`].
+  ///
+  /// Default is `false`.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `tls` feature to be enabled.
+  #[cfg(feature = "tls")]
+  pub fn tls_session_cache(mut self, enable: bool) -> ClientBuilder {
+    self.config.tls_session_cache = enable;
+    self
+  }
+  /// Write the TLS secrets of every connection to `path` for decrypting
+  /// captured traffic, see [`ConnectorBuilder::keylog`].
+  ///
+  /// Default uses the `SSLKEYLOGFILE` environment variable when it is set.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `tls` feature to be enabled.
+  #[cfg(feature = "tls")]
+  pub fn keylog<P: Into<PathBuf>>(mut self, path: P) -> ClientBuilder {
+    self.config.keylog = Some(path.into());
+    self
+  }
+  /// Pin the public keys trusted for `host`, see [`ConnectorBuilder::pin_sha256`].
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `tls` feature to be enabled.
+  #[cfg(feature = "tls")]
+  pub fn pin_sha256<H, I, S>(mut self, host: H, hashes: I) -> ClientBuilder
+    where
+      H: Into<String>,
+      I: IntoIterator<Item=S>,
+      S: AsRef<str>,
+  {
+    let hashes = hashes.into_iter().map(|h| h.as_ref().to_string()).collect();
+    self.config.pins.push((host.into(), hashes));
+    self
+  }
+  /// Set the `ProtocolPolicy` deciding the HTTP version of this client.
+  ///
+  /// Requests are sent with the version the policy resolves to, and unless
+  /// [`ClientBuilder::alpn_protocols`] is set the ALPN list is derived from it.
+  ///
+  /// Default is `None`, requests keep their own version and ALPN is not used.
+  ///
+  /// # Errors
+  ///
+  /// [`ClientBuilder::build`] fails if the policy forces a version this crate
+  /// cannot speak.
+  pub fn protocol_policy(mut self, policy: ProtocolPolicy) -> ClientBuilder {
+    self.config.protocol_policy = Some(policy);
+    self
+  }
   /// Enable a persistent cookie store for the client.
   ///
   /// Cookies received in responses will be preserved and included in
@@ -760,10 +2970,45 @@ impl ClientBuilder {
 struct Config {
   connect_timeout: Option<Duration>,
   headers: HeaderMap,
+  auto_headers: AutoHeaders,
+  header_order: Option<HeaderOrder>,
+  early_hints: Option<EarlyHintPolicy>,
   referer: bool,
+  strip_sensitive_headers: bool,
+  detect_redirect_loops: bool,
+  deny_redirect_downgrade: bool,
+  #[cfg(feature = "proxy")]
   proxy: Option<Proxy>,
   timeout: Option<Duration>,
+  deadline: Option<Duration>,
+  #[cfg(feature = "record")]
+  record_limit: RecordLimit,
+  retry_policy: Option<RetryPolicy>,
+  rate_limit: Option<(f64, u32)>,
+  rate_limit_per_host: bool,
+  max_download_speed: Option<u64>,
+  max_upload_speed: Option<u64>,
+  max_connections_per_host: Option<usize>,
+  max_connections: Option<usize>,
+  connection_queue_timeout: Option<Duration>,
+  probe_http_first: bool,
+  middlewares: Vec<Arc<dyn Middleware>>,
+  #[cfg(feature = "sign")]
+  signer: Option<Arc<dyn Signer>>,
+  host_auth: Vec<(String, Credentials)>,
+  hooks: Hooks,
+  resume_downloads: usize,
+  response_limits: ResponseLimits,
+  status_code_policy: StatusCodePolicy,
+  lenient_parsing: bool,
+  recent_activity: usize,
+  wire_log: Option<WireLog>,
+  detect_waf: bool,
+  probe_connection_close: bool,
   nodelay: bool,
+  tcp_keepalive: Option<Duration>,
+  proxy_protocol: Option<ProxyProtocol>,
+  transport: Option<TransportHandle>,
   #[cfg(feature = "tls")]
   root_certs: Vec<Certificate>,
   #[cfg(feature = "tls")]
@@ -771,7 +3016,19 @@ struct Config {
   hostname_verification: bool,
   certs_verification: bool,
   tls_sni: bool,
+  alpn_protocols: Vec<String>,
+  #[cfg(feature = "tls")]
+  tls_session_cache: bool,
+  dns_cache: Option<(Duration, usize)>,
+  #[cfg(feature = "tls")]
+  keylog: Option<PathBuf>,
+  #[cfg(feature = "tls")]
+  pins: Vec<(String, Vec<String>)>,
+  #[cfg(feature = "tls")]
+  impersonate: Option<Browser>,
+  protocol_policy: Option<ProtocolPolicy>,
   redirect_policy: Policy,
+  location_policy: LocationPolicy,
   #[cfg(feature = "cookie")]
   cookie_store: Option<Arc<dyn cookies::CookieStore>>,
 }
@@ -781,18 +3038,65 @@ impl Default for Config {
     Self {
       connect_timeout: None,
       headers: Default::default(),
+      auto_headers: AutoHeaders::default(),
+      header_order: None,
+      early_hints: None,
       referer: false,
+      strip_sensitive_headers: true,
+      detect_redirect_loops: true,
+      deny_redirect_downgrade: false,
+      #[cfg(feature = "proxy")]
       proxy: None,
       timeout: None,
+      deadline: None,
+      #[cfg(feature = "record")]
+      record_limit: RecordLimit::default(),
+      retry_policy: None,
+      rate_limit: None,
+      rate_limit_per_host: false,
+      max_download_speed: None,
+      max_upload_speed: None,
+      max_connections_per_host: None,
+      max_connections: None,
+      connection_queue_timeout: None,
+      probe_http_first: false,
+      middlewares: vec![],
+      #[cfg(feature = "sign")]
+      signer: None,
+      host_auth: vec![],
+      hooks: Hooks::default(),
+      resume_downloads: 0,
+      response_limits: ResponseLimits::default(),
+      status_code_policy: StatusCodePolicy::default(),
+      lenient_parsing: false,
+      recent_activity: 0,
+      wire_log: None,
+      detect_waf: false,
+      probe_connection_close: false,
       nodelay: false,
+      tcp_keepalive: None,
+      proxy_protocol: None,
+      transport: None,
       #[cfg(feature = "tls")]
       root_certs: vec![],
       #[cfg(feature = "tls")]
       identity: None,
       hostname_verification: false,
       certs_verification: false,
-      tls_sni: false,
+      tls_sni: true,
+      alpn_protocols: vec![],
+      #[cfg(feature = "tls")]
+      tls_session_cache: false,
+      dns_cache: None,
+      #[cfg(feature = "tls")]
+      keylog: None,
+      #[cfg(feature = "tls")]
+      pins: Vec::new(),
+      #[cfg(feature = "tls")]
+      impersonate: None,
+      protocol_policy: None,
       redirect_policy: Policy::Limit(10),
+      location_policy: LocationPolicy::default(),
       #[cfg(feature = "cookie")]
       cookie_store: None,
     }
@@ -804,6 +3108,480 @@ struct ClientRef {
   #[cfg(feature = "cookie")]
   cookie_store: Option<Arc<dyn cookies::CookieStore>>,
   connector: Arc<Connector>,
+  connector_builder: ConnectorBuilder,
+  headers: HeaderMap,
+  auto_headers: AutoHeaders,
+  header_order: Option<HeaderOrder>,
+  early_hints: Option<EarlyHintPolicy>,
+  preconnected: Arc<Mutex<HashMap<String, Socket>>>,
   redirect_policy: Policy,
+  location_policy: LocationPolicy,
   referer: bool,
+  strip_sensitive_headers: bool,
+  detect_redirect_loops: bool,
+  deny_redirect_downgrade: bool,
+  deadline: Option<Duration>,
+  #[cfg(feature = "record")]
+  record_limit: RecordLimit,
+  retry_policy: Option<RetryPolicy>,
+  rate_limiter: Option<Arc<RateLimiter>>,
+  download_throttle: Option<Arc<Throttle>>,
+  upload_throttle: Option<Arc<Throttle>>,
+  connection_limit: Option<Arc<ConnectionLimit>>,
+  probe_http_first: bool,
+  activity: Arc<ActivityLog>,
+  shards: Arc<ShardLog>,
+  middlewares: Vec<Arc<dyn Middleware>>,
+  #[cfg(feature = "sign")]
+  signer: Option<Arc<dyn Signer>>,
+  host_auth: Vec<(String, Credentials)>,
+  hooks: Hooks,
+  resume_downloads: usize,
+  response_limits: ResponseLimits,
+  status_code_policy: StatusCodePolicy,
+  lenient_parsing: bool,
+  detect_waf: bool,
+  probe_connection_close: bool,
+  protocol_policy: Option<ProtocolPolicy>,
+  wire_log: Option<WireLog>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Client;
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+  use std::time::Duration;
+
+  #[test]
+  fn pipeline() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      // 四个请求都到了再一起响应，第三个响应体不完整就关闭连接，第四个不再发送
+      let mut received = Vec::new();
+      let mut buf = [0u8; 4096];
+      while received.windows(4).filter(|w| w == b"\r\n\r\n").count() < 4 {
+        let n = stream.read(&mut buf).unwrap();
+        assert_ne!(n, 0);
+        received.extend_from_slice(&buf[..n]);
+      }
+      stream
+        .write_all(concat!(
+          "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na",
+          "HTTP/1.1 404 Not Found\r\nContent-Length: 1\r\n\r\nb",
+          "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nc",
+        ).as_bytes())
+        .unwrap();
+      String::from_utf8(received).unwrap()
+    });
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let requests = ["/a", "/b", "/c", "/d"]
+      .iter()
+      .map(|path| client.get(format!("http://{}{}", addr, path)).build().unwrap())
+      .collect();
+    let responses = client.pipeline(requests).unwrap();
+    assert_eq!(responses.len(), 4);
+    let first = responses[0].as_ref().unwrap();
+    assert_eq!(first.status_code(), 200);
+    assert_eq!(first.uri().path(), "/a");
+    assert_eq!(first.text().unwrap(), "a");
+    let second = responses[1].as_ref().unwrap();
+    assert_eq!(second.status_code(), 404);
+    assert_eq!(second.uri().path(), "/b");
+    assert_eq!(second.text().unwrap(), "b");
+    // 读不出第三个响应之后连接已经错位，后面的请求也得到错误
+    assert!(responses[2].is_err(), "{:?}", responses[2]);
+    let err = responses[3].as_ref().unwrap_err();
+    assert!(err.to_string().contains("pipeline ended"), "{}", err);
+    let received = server.join().unwrap();
+    let lines: Vec<_> = received.lines().filter(|l| l.starts_with("GET ")).collect();
+    assert_eq!(
+      lines,
+      ["GET /a HTTP/1.1", "GET /b HTTP/1.1", "GET /c HTTP/1.1", "GET /d HTTP/1.1"]
+    );
+    let mixed = vec![
+      client.get(format!("http://{}/a", addr)).build().unwrap(),
+      client.get("http://other.example/").build().unwrap(),
+    ];
+    assert!(client.pipeline(mixed).is_err());
+  }
+
+  #[test]
+  fn tunnel_takes_connection_slot() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    // 回显收到的数据，直到对端关闭
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { break };
+        std::thread::spawn(move || {
+          let mut buf = [0u8; 64];
+          while let Ok(n @ 1..) = stream.read(&mut buf) {
+            let _ = stream.write_all(&buf[..n]);
+          }
+        });
+      }
+    });
+    let client = Client::builder()
+      .timeout(Duration::from_secs(5))
+      .max_connections_per_host(1)
+      .connection_queue_timeout(Duration::from_millis(200))
+      .build()
+      .unwrap();
+    let mut tunnel = client.tunnel("127.0.0.1", port).unwrap();
+    tunnel.write_all(b"PING").unwrap();
+    let mut reply = [0u8; 4];
+    tunnel.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"PING");
+    // 隧道占着唯一的名额，请求和新隧道都要排队直到超时
+    let err = client.tunnel("127.0.0.1", port).unwrap_err();
+    assert!(err.is_timeout(), "{}", err);
+    let err = client.get(format!("http://127.0.0.1:{}/", port)).send().unwrap_err();
+    assert!(err.is_timeout(), "{}", err);
+    drop(tunnel);
+    let mut tunnel = client.tunnel("127.0.0.1", port).unwrap();
+    tunnel.write_all(b"PONG").unwrap();
+    tunnel.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"PONG");
+  }
+
+  #[test]
+  fn resume_downloads() {
+    use crate::MockTransport;
+    // 第一个连接只发了 4 个字节就断开，续传请求拿到剩下的 6 个
+    let partial = MockTransport::new()
+      .respond(concat!(
+        "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n",
+        "Accept-Ranges: bytes\r\nETag: \"v1\"\r\n\r\n0123",
+      ))
+      .respond(concat!(
+        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 4-9/10\r\n",
+        "Content-Length: 6\r\n\r\n456789",
+      ));
+    let client = Client::builder()
+      .transport(partial.clone())
+      .resume_downloads(1)
+      .build()
+      .unwrap();
+    let response = client.get("http://example.com/file").send().unwrap();
+    assert_eq!(response.text().unwrap(), "0123456789");
+    let requests = partial.requests();
+    assert_eq!(requests.len(), 2);
+    let resumed = String::from_utf8_lossy(&requests[1]).to_lowercase();
+    assert!(resumed.contains("\r\nrange: bytes=4-\r\n"), "{}", resumed);
+    assert!(resumed.contains("\r\nif-range: \"v1\"\r\n"), "{}", resumed);
+    // 默认不续传
+    let partial = MockTransport::new().respond(
+      "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nAccept-Ranges: bytes\r\n\r\n0123",
+    );
+    let client = Client::builder().transport(partial.clone()).build().unwrap();
+    assert!(client.get("http://example.com/file").send().is_err());
+    assert_eq!(partial.requests().len(), 1);
+  }
+
+  /// 记下 span 的名字和事件的消息，用来检查埋点
+  #[cfg(feature = "trace")]
+  #[derive(Clone, Default)]
+  struct Collector(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+  #[cfg(feature = "trace")]
+  impl tracing::Subscriber for Collector {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+      true
+    }
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+      let mut seen = self.0.lock().unwrap();
+      seen.push(format!("span {}", span.metadata().name()));
+      tracing::span::Id::from_u64(seen.len() as u64)
+    }
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+    fn event(&self, event: &tracing::Event<'_>) {
+      struct Message(String);
+      impl tracing::field::Visit for Message {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+          if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+          }
+        }
+      }
+      let mut message = Message(String::new());
+      event.record(&mut message);
+      self.0.lock().unwrap().push(format!("event {}", message.0));
+    }
+    fn enter(&self, _: &tracing::span::Id) {}
+    fn exit(&self, _: &tracing::span::Id) {}
+  }
+
+  #[cfg(feature = "trace")]
+  #[test]
+  fn trace_spans_and_events() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      read_head(&mut stream);
+      let _ = stream.write_all(b"HTTP/1.1 302 Found\r\nLocation: /b\r\nContent-Length: 0\r\n\r\n");
+      read_head(&mut stream);
+      let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    });
+    let collector = Collector::default();
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let response = tracing::subscriber::with_default(collector.clone(), || {
+      client.get(format!("http://{}/a", addr)).send().unwrap()
+    });
+    assert_eq!(response.text().unwrap(), "ok");
+    let seen = collector.0.lock().unwrap().clone();
+    // 跳转复用同一个连接，只连接一次
+    assert_eq!(
+      seen,
+      [
+        "span request",
+        "span connect",
+        "event resolved",
+        "event connected",
+        "event request written",
+        "event response read",
+        "event redirect",
+        "span request",
+        "event request written",
+        "event response read",
+      ]
+    );
+  }
+
+  /// 逐字节读到请求头结束，不多读请求之后的数据
+  fn read_head(stream: &mut dyn Read) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+      if stream.read(&mut byte).unwrap_or(0) == 0 {
+        break;
+      }
+      head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+  }
+
+  #[test]
+  fn connection_close() {
+    use crate::record::ConnectionClose;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // /fin 的响应体读到关闭为止，其余响应声明 Connection: close 但等客户端先关
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { break };
+        if read_head(&mut stream).starts_with("GET /fin ") {
+          let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\nbye");
+          continue;
+        }
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+        let _ = stream.read(&mut [0u8; 1]);
+      }
+    });
+    let client = Client::builder()
+      .timeout(Duration::from_secs(5))
+      .probe_connection_close(true)
+      .build()
+      .unwrap();
+    let response = client.get(format!("http://{}/fin", addr)).send().unwrap();
+    assert_eq!(response.connection_close(), Some(ConnectionClose::ServerFin));
+    let response = client.get(format!("http://{}/close", addr)).send().unwrap();
+    assert_eq!(response.connection_close(), Some(ConnectionClose::ClientClosed));
+    // 默认不探测
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let response = client.get(format!("http://{}/fin", addr)).send().unwrap();
+    assert_eq!(response.connection_close(), None);
+  }
+
+  #[cfg(feature = "tls")]
+  #[test]
+  fn connection_close_tls() {
+    use crate::record::ConnectionClose;
+    let acceptor = crate::tls::test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // TCP 连接都留着等客户端先关，/fin 只发 close_notify
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else { break };
+        let Ok(mut stream) = acceptor.accept(stream) else {
+          continue;
+        };
+        if read_head(&mut stream).starts_with("GET /fin ") {
+          let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\nbye");
+          let _ = stream.shutdown();
+        } else {
+          let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+        }
+        let _ = stream.get_mut().read(&mut [0u8; 1]);
+      }
+    });
+    let client = Client::builder()
+      .timeout(Duration::from_secs(5))
+      .probe_connection_close(true)
+      .build()
+      .unwrap();
+    let response = client.get(format!("https://{}/fin", addr)).send().unwrap();
+    assert_eq!(response.text().unwrap(), "bye");
+    assert_eq!(response.connection_close(), Some(ConnectionClose::ServerFin));
+    let response = client.get(format!("https://{}/close", addr)).send().unwrap();
+    assert_eq!(response.connection_close(), Some(ConnectionClose::ClientClosed));
+  }
+
+  #[cfg(feature = "tls")]
+  #[test]
+  fn alpn_offer_and_record() {
+    let acceptor = crate::tls::test_acceptor(b"\x05odd/1\x08http/1.1");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else { break };
+        let Ok(mut stream) = acceptor.accept(stream) else {
+          continue;
+        };
+        read_head(&mut stream);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+      }
+    });
+    // 服务器优先选 odd/1，只有客户端提供了才会选中
+    for (offered, negotiated) in [
+      (vec!["http/1.1", "odd/1"], &b"odd/1"[..]),
+      (vec!["http/1.1"], &b"http/1.1"[..]),
+    ] {
+      let client = Client::builder()
+        .timeout(Duration::from_secs(5))
+        .alpn_protocols(&offered)
+        .build()
+        .unwrap();
+      let response = client.get(format!("https://{}/", addr)).send().unwrap();
+      assert_eq!(response.text().unwrap(), "ok");
+      let alpn = response.alpn_record().unwrap();
+      assert_eq!(alpn.offered, offered);
+      assert_eq!(alpn.negotiated.as_deref(), Some(negotiated));
+    }
+  }
+
+  /// 用 `issuer` 签发一张证书，没有签发者时自签成 CA
+  #[cfg(feature = "tls")]
+  fn issue(
+    cn: &str,
+    issuer: Option<&(openssl::x509::X509, openssl::pkey::PKey<openssl::pkey::Private>)>,
+  ) -> (openssl::x509::X509, openssl::pkey::PKey<openssl::pkey::Private>) {
+    use openssl::asn1::{Asn1Integer, Asn1Time};
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::{X509Builder, X509NameBuilder};
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", cn).unwrap();
+    let name = name.build();
+    let mut cert = X509Builder::new().unwrap();
+    cert.set_version(2).unwrap();
+    let serial = Asn1Integer::from_bn(&BigNum::from_u32(cn.len() as u32).unwrap()).unwrap();
+    cert.set_serial_number(&serial).unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    match issuer {
+      Some((ca, ca_key)) => {
+        cert.set_issuer_name(ca.subject_name()).unwrap();
+        let san = SubjectAlternativeName::new()
+          .dns(cn)
+          .build(&cert.x509v3_context(Some(ca), None))
+          .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.sign(ca_key, MessageDigest::sha256()).unwrap();
+      }
+      None => {
+        cert.set_issuer_name(&name).unwrap();
+        cert.append_extension(BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+      }
+    }
+    (cert.build(), key)
+  }
+
+  #[cfg(feature = "tls")]
+  #[test]
+  fn private_ca_and_client_certificate() {
+    use crate::{Certificate, Identity};
+    use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode};
+    use std::sync::mpsc;
+
+    let ca = issue("slinger test ca", None);
+    let (server_cert, server_key) = issue("localhost", Some(&ca));
+    let (client_cert, client_key) = issue("client", Some(&ca));
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&server_cert).unwrap();
+    acceptor.set_private_key(&server_key).unwrap();
+    acceptor.cert_store_mut().add_cert(ca.0.clone()).unwrap();
+    acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    let acceptor = acceptor.build();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    // 每个连接报告 SNI 和客户端证书的 CN
+    let (seen, handshakes) = mpsc::channel();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else { break };
+        let Ok(mut stream) = acceptor.accept(stream) else {
+          continue;
+        };
+        let sni = stream.ssl().servername(openssl::ssl::NameType::HOST_NAME).map(str::to_string);
+        let cn = stream.ssl().peer_certificate().and_then(|cert| {
+          let entry = cert.subject_name().entries().next()?;
+          Some(String::from_utf8_lossy(entry.data().as_slice()).into_owned())
+        });
+        let _ = seen.send((sni, cn));
+        read_head(&mut stream);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+      }
+    });
+    let pem = [client_cert.to_pem().unwrap(), client_key.private_key_to_pem_pkcs8().unwrap()].concat();
+    let builder = || {
+      Client::builder()
+        .timeout(Duration::from_secs(5))
+        .danger_accept_invalid_certs(false)
+        .danger_accept_invalid_hostnames(false)
+    };
+    let url = format!("https://localhost:{}/", port);
+    let client = builder()
+      .add_root_certificate(Certificate::from_pem(&ca.0.to_pem().unwrap()).unwrap())
+      .identity(Identity::from_pem(&pem).unwrap())
+      .build()
+      .unwrap();
+    assert_eq!(client.get(&url).send().unwrap().text().unwrap(), "ok");
+    let (sni, cn) = handshakes.recv().unwrap();
+    assert_eq!(sni.as_deref(), Some("localhost"));
+    assert_eq!(cn.as_deref(), Some("client"));
+    // 不发 SNI
+    let client = builder()
+      .add_root_certificate(Certificate::from_der(&ca.0.to_der().unwrap()).unwrap())
+      .identity(Identity::from_pkcs8_pem(&client_cert.to_pem().unwrap(), &pem).unwrap())
+      .tls_sni(false)
+      .build()
+      .unwrap();
+    assert_eq!(client.get(&url).send().unwrap().text().unwrap(), "ok");
+    assert_eq!(handshakes.recv().unwrap(), (None, Some("client".to_string())));
+    // 没有私有 CA 时服务器证书不可信，没有客户端证书时服务器拒绝握手
+    let client = builder().identity(Identity::from_pem(&pem).unwrap()).build().unwrap();
+    assert!(client.get(&url).send().is_err());
+    let client = builder()
+      .add_root_certificate(Certificate::from_pem(&ca.0.to_pem().unwrap()).unwrap())
+      .build()
+      .unwrap();
+    assert!(client.get(&url).send().is_err());
+  }
 }
@@ -0,0 +1,151 @@
+use crate::errors::Result;
+use crate::{ChunkedBody, Request};
+use bytes::Bytes;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use http::HeaderValue;
+use std::io::Write;
+
+/// A `Content-Encoding` to compress a request body with, see
+/// [`RequestBuilder::compress_body`](crate::RequestBuilder::compress_body).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+  /// `gzip`
+  Gzip,
+  /// `deflate`, the zlib format
+  Deflate,
+}
+
+impl Encoding {
+  /// The value of the `Content-Encoding` header.
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Encoding::Gzip => "gzip",
+      Encoding::Deflate => "deflate",
+    }
+  }
+}
+
+/// 请求体在构建请求时压缩
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CompressBody(pub(crate) Encoding);
+
+/// 边写边压缩，每次取出已经压缩好的部分
+pub(crate) enum Encoder {
+  Gzip(GzEncoder<Vec<u8>>),
+  Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+  pub(crate) fn new(encoding: Encoding) -> Encoder {
+    match encoding {
+      Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+      Encoding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::default())),
+    }
+  }
+  /// 压缩 `data`，`flush` 时把缓冲在压缩器里的数据也输出
+  pub(crate) fn encode(&mut self, data: &[u8], flush: bool) -> std::io::Result<Bytes> {
+    let output = match self {
+      Encoder::Gzip(encoder) => {
+        encoder.write_all(data)?;
+        if flush {
+          encoder.flush()?;
+        }
+        encoder.get_mut()
+      }
+      Encoder::Deflate(encoder) => {
+        encoder.write_all(data)?;
+        if flush {
+          encoder.flush()?;
+        }
+        encoder.get_mut()
+      }
+    };
+    Ok(std::mem::take(output).into())
+  }
+  pub(crate) fn finish(self) -> std::io::Result<Bytes> {
+    let output = match self {
+      Encoder::Gzip(encoder) => encoder.finish()?,
+      Encoder::Deflate(encoder) => encoder.finish()?,
+    };
+    Ok(output.into())
+  }
+}
+
+/// 压缩请求体并设置 `Content-Encoding`，分块请求体在发送时压缩
+pub(crate) fn compress_request(request: &mut Request) -> Result<()> {
+  let Some(CompressBody(encoding)) = request.extensions().get::<CompressBody>().copied() else {
+    return Ok(());
+  };
+  if let Some(body) = request.extensions_mut().get_mut::<ChunkedBody>() {
+    body.compress(encoding);
+  } else {
+    // 没有请求体时不压缩
+    let Some(body) = request.body().filter(|b| !b.is_empty()) else {
+      return Ok(());
+    };
+    let mut encoder = Encoder::new(encoding);
+    let mut compressed = encoder.encode(body, false)?.to_vec();
+    compressed.extend(encoder.finish()?);
+    *request.body_mut() = Some(compressed.into());
+    request.headers_mut().remove(http::header::CONTENT_LENGTH);
+  }
+  request.headers_mut().insert(
+    http::header::CONTENT_ENCODING,
+    HeaderValue::from_static(encoding.as_str()),
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Encoding;
+  use crate::{Chunk, ChunkedBody, Client, Deadline};
+  use flate2::read::{MultiGzDecoder, ZlibDecoder};
+  use std::io::Read;
+
+  #[test]
+  fn compress_bodies() {
+    let client = Client::new();
+    let request = client
+      .post("http://example.com/")
+      .body("hello hello hello")
+      .header(http::header::CONTENT_LENGTH, "17")
+      .compress_body(Encoding::Deflate)
+      .build()
+      .unwrap();
+    assert_eq!(request.headers()["content-encoding"], "deflate");
+    assert!(request.headers().get("content-length").is_none());
+    let mut body = String::new();
+    ZlibDecoder::new(&request.body().unwrap()[..]).read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello hello hello");
+    // 没有请求体时不压缩
+    let request = client.get("http://example.com/").compress_body(Encoding::Gzip).build().unwrap();
+    assert!(request.headers().get("content-encoding").is_none());
+    // 分块请求体在发送时压缩，Flush 时压缩器也输出
+    let request = client
+      .post("http://example.com/")
+      .compress_body(Encoding::Gzip)
+      .chunked_body(ChunkedBody::new(vec![Chunk::data("abc"), Chunk::Flush, Chunk::data("def")]))
+      .build()
+      .unwrap();
+    assert_eq!(request.headers()["content-encoding"], "gzip");
+    let mut sent = Vec::new();
+    let chunked = request.extensions().get::<ChunkedBody>().unwrap();
+    chunked.write_to(&mut sent, &Deadline::default()).unwrap();
+    let mut compressed = Vec::new();
+    let mut rest = &sent[..];
+    loop {
+      let line_end = rest.windows(2).position(|w| w == b"\r\n").unwrap();
+      let size = usize::from_str_radix(std::str::from_utf8(&rest[..line_end]).unwrap(), 16).unwrap();
+      if size == 0 {
+        break;
+      }
+      compressed.extend(&rest[line_end + 2..line_end + 2 + size]);
+      rest = &rest[line_end + 4 + size..];
+    }
+    let mut body = String::new();
+    MultiGzDecoder::new(&compressed[..]).read_to_string(&mut body).unwrap();
+    assert_eq!(body, "abcdef");
+  }
+}
@@ -1,14 +1,31 @@
-#[cfg(feature = "tls")]
-use crate::errors::new_io_error;
-use crate::errors::Result;
-use crate::proxy::{Proxy, ProxySocket};
+use crate::curl::{self, CurlFlags};
+use crate::deadline::Deadline;
+use crate::errors::{new_io_error, Error, Result};
+#[cfg(feature = "proxy")]
+use crate::proxy::Proxy;
+use crate::proxy_protocol::ProxyProtocol;
+use crate::record::Timing;
 use crate::socket::Socket;
+use crate::dns::DnsCache;
+use crate::transport::{Transport, TransportHandle};
+#[cfg(feature = "tls")]
+use crate::impersonate::Browser;
+#[cfg(feature = "tls")]
+use crate::tls::{self, Certificate, Identity, Pins, SessionCache};
 #[cfg(feature = "tls")]
-use native_tls::{HandshakeError, TlsConnector};
+use openssl::ssl::{
+  HandshakeError, SslConnector, SslMethod, SslSessionCacheMode, SslStream, SslVerifyMode,
+};
 use socket2::Socket as RawSocket;
-use socket2::{Domain, Protocol, Type};
-use std::net::SocketAddr;
-use std::time::Duration;
+use socket2::{Domain, Protocol, TcpKeepalive, Type};
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::sync::Arc;
+#[cfg(feature = "tls")]
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 /// ConnectorBuilder
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -19,7 +36,32 @@ pub struct ConnectorBuilder {
   write_timeout: Option<Duration>,
   connect_timeout: Option<Duration>,
   nodelay: bool,
+  keepalive: Option<Duration>,
+  keepalive_interval: Option<Duration>,
+  keepalive_retries: Option<u32>,
+  send_buffer_size: Option<usize>,
+  recv_buffer_size: Option<usize>,
+  ttl: Option<u32>,
+  proxy_protocol: Option<ProxyProtocol>,
+  #[cfg(feature = "proxy")]
   proxy: Option<Proxy>,
+  alpn_protocols: Vec<String>,
+  #[cfg(feature = "tls")]
+  tls_sessions: Option<SessionCache>,
+  #[cfg(feature = "tls")]
+  keylog: Option<PathBuf>,
+  #[cfg(feature = "tls")]
+  pins: Vec<(String, Vec<String>)>,
+  #[cfg(feature = "tls")]
+  impersonate: Option<Browser>,
+  #[cfg(feature = "tls")]
+  root_certs: Vec<Certificate>,
+  #[cfg(feature = "tls")]
+  identity: Option<Identity>,
+  no_sni: bool,
+  pub(crate) transport: Option<TransportHandle>,
+  pub(crate) dns_cache: Option<DnsCache>,
+  connect_to: Option<ConnectTo>,
 }
 
 impl ConnectorBuilder {
@@ -56,6 +98,86 @@ impl ConnectorBuilder {
     self.nodelay = value;
     self
   }
+  /// Enable TCP keepalive and send the first probe after the connection has
+  /// been idle for `time`.
+  ///
+  /// Default is no keepalive.
+  pub fn tcp_keepalive(mut self, time: Duration) -> ConnectorBuilder {
+    self.keepalive = Some(time);
+    self
+  }
+  /// Enable TCP keepalive and set the time between two probes.
+  ///
+  /// Ignored on platforms without a per-socket setting, such as OpenBSD.
+  pub fn keepalive_interval(mut self, interval: Duration) -> ConnectorBuilder {
+    self.keepalive_interval = Some(interval);
+    self
+  }
+  /// Enable TCP keepalive and set the number of unanswered probes after
+  /// which the connection is dropped.
+  ///
+  /// Ignored on platforms without a per-socket setting, such as Windows.
+  pub fn keepalive_retries(mut self, retries: u32) -> ConnectorBuilder {
+    self.keepalive_retries = Some(retries);
+    self
+  }
+  /// Set the size of the `SO_SNDBUF` send buffer.
+  ///
+  /// Default is the size chosen by the operating system.
+  pub fn send_buffer_size(mut self, size: usize) -> ConnectorBuilder {
+    self.send_buffer_size = Some(size);
+    self
+  }
+  /// Set the size of the `SO_RCVBUF` receive buffer.
+  ///
+  /// Default is the size chosen by the operating system.
+  pub fn recv_buffer_size(mut self, size: usize) -> ConnectorBuilder {
+    self.recv_buffer_size = Some(size);
+    self
+  }
+  /// Set the time-to-live of outgoing packets, the hop limit on IPv6.
+  ///
+  /// Default is the value chosen by the operating system.
+  pub fn ttl(mut self, ttl: u32) -> ConnectorBuilder {
+    self.ttl = Some(ttl);
+    self
+  }
+  /// Send a PROXY protocol header on every new connection.
+  ///
+  /// The header is written right after the connection to the target is
+  /// established, through a proxy included, and before the TLS handshake.
+  ///
+  /// Default is no header.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// use slinger::ProxyProtocol;
+  ///
+  /// let connector = slinger::ConnectorBuilder::default()
+  ///   .proxy_protocol(ProxyProtocol::V2 {
+  ///     src: "203.0.113.7:51234".parse()?,
+  ///     dst: "10.0.0.1:443".parse()?,
+  ///     tlvs: Vec::new(),
+  ///   })
+  ///   .build()?;
+  /// let socket = connector.connect_with_uri(&"https://10.0.0.1".parse()?)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn proxy_protocol(mut self, header: ProxyProtocol) -> ConnectorBuilder {
+    self.proxy_protocol = Some(header);
+    self
+  }
+  /// Open every connection with `transport` instead of connecting over the
+  /// network, see [`Transport`].
+  ///
+  /// The other settings of the builder don't apply to those connections.
+  pub fn transport<T: Transport>(mut self, transport: T) -> ConnectorBuilder {
+    self.transport = Some(TransportHandle(Arc::new(transport)));
+    self
+  }
   /// Enables a read timeout.
   ///
   /// The timeout applies to each read operation, and resets after a
@@ -90,6 +212,170 @@ impl ConnectorBuilder {
     self.connect_timeout = timeout;
     self
   }
+  /// Set the exact list of protocols offered with ALPN during the TLS handshake.
+  ///
+  /// The protocols are sent in the given order and are not validated, so
+  /// uncommon values can be offered as well. An empty list disables ALPN.
+  ///
+  /// Default is an empty list.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// let connector = slinger::ConnectorBuilder::default()
+  ///   .alpn_protocols(&["h2", "http/1.1"])
+  ///   .build()?;
+  /// let socket = connector.connect_with_uri(&"https://example.com".parse()?)?;
+  /// # #[cfg(feature = "tls")]
+  /// println!("{:?}", socket.negotiated_alpn());
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn alpn_protocols<I, S>(mut self, protocols: I) -> ConnectorBuilder
+    where
+      I: IntoIterator<Item=S>,
+      S: AsRef<str>,
+  {
+    self.alpn_protocols = protocols
+      .into_iter()
+      .map(|p| p.as_ref().to_string())
+      .collect();
+    self
+  }
+  /// Cache TLS sessions per host and resume them when connecting to the same
+  /// host again.
+  ///
+  /// A resumed session skips the certificate exchange and key agreement of a
+  /// full handshake, which dominates the cost of many short connections to
+  /// one host. Sessions from session tickets and session IDs are both kept,
+  /// the cache is shared by every `Connector` built from this builder and its
+  /// clones. At most 1024 hosts are kept, the oldest one is dropped first.
+  ///
+  /// Default is `false`.
+  #[cfg(feature = "tls")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+  pub fn tls_session_cache(mut self, enable: bool) -> ConnectorBuilder {
+    self.tls_sessions = enable.then(SessionCache::default);
+    self
+  }
+  /// Cache the addresses a host name resolves to for `ttl`, keeping at most
+  /// `max_entries` host names, instead of resolving it for every connection.
+  ///
+  /// The system resolver does not report the TTL of its answers, so every
+  /// entry lives for `ttl`. The cache is shared by every `Connector` built
+  /// from this builder and its clones. Default is no cache.
+  pub fn dns_cache(mut self, ttl: Duration, max_entries: usize) -> ConnectorBuilder {
+    self.dns_cache = Some(DnsCache::new(ttl, max_entries));
+    self
+  }
+  /// Append the TLS secrets of every connection to `path` in the NSS key log
+  /// format, so captured traffic can be decrypted with Wireshark.
+  ///
+  /// Without this the file named by the `SSLKEYLOGFILE` environment variable
+  /// is used when it is set.
+  ///
+  /// # Errors
+  ///
+  /// [`ConnectorBuilder::build`] fails when `path` can not be opened for
+  /// appending.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let connector = slinger::ConnectorBuilder::default()
+  ///   .keylog("/tmp/sslkeys.log")
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "tls")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+  pub fn keylog<P: Into<PathBuf>>(mut self, path: P) -> ConnectorBuilder {
+    self.keylog = Some(path.into());
+    self
+  }
+  /// Pin the public keys trusted for `host`, like the certificate pinning of
+  /// mobile apps.
+  ///
+  /// Each hash is the base64 SHA-256 of a DER encoded SubjectPublicKeyInfo,
+  /// optionally prefixed with `sha256/`. After the handshake one certificate
+  /// of the chain presented by `host` must match one of the hashes, otherwise
+  /// the connection fails with [`Error::CertificatePin`](crate::Error::CertificatePin).
+  /// Pins of the same host are added up, hosts without pins are not checked.
+  /// With the `aws-lc` and `aws-lc-fips` features only the certificates sent
+  /// by the server are checked, a pinned root it doesn't send never matches.
+  ///
+  /// # Errors
+  ///
+  /// [`ConnectorBuilder::build`] fails when a hash is not valid base64 of 32
+  /// bytes.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let connector = slinger::ConnectorBuilder::default()
+  ///   .pin_sha256("example.com", ["sha256/AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="])
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "tls")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+  pub fn pin_sha256<H, I, S>(mut self, host: H, hashes: I) -> ConnectorBuilder
+    where
+      H: Into<String>,
+      I: IntoIterator<Item=S>,
+      S: AsRef<str>,
+  {
+    let hashes = hashes.into_iter().map(|h| h.as_ref().to_string()).collect();
+    self.pins.push((host.into(), hashes));
+    self
+  }
+  /// Offer the cipher suites, key exchange groups and signature algorithms of
+  /// `browser` in its order, see [`Browser`].
+  ///
+  /// Connections only use TLS 1.2 and newer, like the browsers do.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let connector = slinger::ConnectorBuilder::default()
+  ///   .impersonate(slinger::Browser::Firefox120)
+  ///   .build()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "tls")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+  pub fn impersonate(mut self, browser: Browser) -> ConnectorBuilder {
+    self.impersonate = Some(browser);
+    self
+  }
+  /// Trust `cert` in addition to the system root certificates.
+  #[cfg(feature = "tls")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+  pub fn add_root_certificate(mut self, cert: Certificate) -> ConnectorBuilder {
+    self.root_certs.push(cert);
+    self
+  }
+  /// Send `identity` when the server asks for a client certificate.
+  #[cfg(feature = "tls")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+  pub fn identity(mut self, identity: Identity) -> ConnectorBuilder {
+    self.identity = Some(identity);
+    self
+  }
+  /// Controls the use of TLS server name indication.
+  ///
+  /// Defaults to `true`.
+  pub fn tls_sni(mut self, value: bool) -> ConnectorBuilder {
+    self.no_sni = !value;
+    self
+  }
   // Proxy options
 
   /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -97,6 +383,8 @@ impl ConnectorBuilder {
   /// # Note
   ///
   /// Adding a proxy will disable the automatic usage of the "system" proxy.
+  #[cfg(feature = "proxy")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "proxy")))]
   pub fn proxy(mut self, addr: Option<Proxy>) -> ConnectorBuilder {
     self.proxy = addr;
     self
@@ -107,18 +395,75 @@ impl ConnectorBuilder {
   /// Combine the configuration of this builder with a connector to create a `Connector`.
   pub fn build(&self) -> Result<Connector> {
     #[cfg(feature = "tls")]
-      let tls = TlsConnector::builder()
-      .danger_accept_invalid_hostnames(!self.hostname_verification)
-      .danger_accept_invalid_certs(!self.certs_verification)
-      .build()?;
+    let tls_sessions = self.tls_sessions.clone();
+    #[cfg(feature = "tls")]
+    let pins = Pins::new(&self.pins)?;
+    #[cfg(feature = "tls")]
+      let tls = {
+      let mut builder = SslConnector::builder(SslMethod::tls())?;
+      tls::load_system_roots(&mut builder);
+      for cert in &self.root_certs {
+        builder.cert_store_mut().add_cert(cert.x509().clone())?;
+      }
+      if let Some(identity) = &self.identity {
+        identity.configure(&mut builder)?;
+      }
+      if let Some(browser) = self.impersonate {
+        browser.configure_tls(&mut builder)?;
+      }
+      if !self.alpn_protocols.is_empty() {
+        builder.set_alpn_protos(&tls::alpn_wire_format(&self.alpn_protocols)?)?;
+      }
+      if let Some(path) = tls::keylog_path(self.keylog.as_deref()) {
+        match tls::keylog_callback(&path) {
+          Ok(callback) => builder.set_keylog_callback(callback),
+          // 环境变量指向的文件打不开时不影响连接
+          Err(err) if self.keylog.is_some() => return Err(err),
+          Err(_) => {}
+        }
+      }
+      if let Some(sessions) = &tls_sessions {
+        // 客户端会话只放在自己的缓存里，OpenSSL 内部的缓存不会被客户端查找
+        builder.set_session_cache_mode(SslSessionCacheMode::CLIENT | SslSessionCacheMode::NO_INTERNAL);
+        let sessions = sessions.clone();
+        builder.set_new_session_callback(move |ssl, session| {
+          if let Some(key) = ssl.ex_data(tls::session_key_index()) {
+            sessions.insert(key.clone(), session);
+          }
+        });
+      }
+      builder.build()
+    };
     let conn = Connector {
       connect_timeout: self.connect_timeout,
       nodelay: self.nodelay,
+      keepalive: self.keepalive,
+      keepalive_interval: self.keepalive_interval,
+      keepalive_retries: self.keepalive_retries,
+      send_buffer_size: self.send_buffer_size,
+      recv_buffer_size: self.recv_buffer_size,
+      ttl: self.ttl,
+      proxy_protocol: self.proxy_protocol.clone(),
       read_timeout: self.read_timeout,
       write_timeout: self.write_timeout,
+      #[cfg(feature = "proxy")]
       proxy: self.proxy.clone(),
+      alpn_protocols: self.alpn_protocols.clone(),
+      #[cfg(feature = "tls")]
+      hostname_verification: self.hostname_verification,
+      #[cfg(feature = "tls")]
+      certs_verification: self.certs_verification,
       #[cfg(feature = "tls")]
       tls,
+      #[cfg(feature = "tls")]
+      tls_sessions,
+      #[cfg(feature = "tls")]
+      pins,
+      #[cfg(feature = "tls")]
+      sni: !self.no_sni,
+      transport: self.transport.clone(),
+      dns_cache: self.dns_cache.clone(),
+      connect_to: self.connect_to.clone(),
     };
     Ok(conn)
   }
@@ -129,11 +474,33 @@ impl ConnectorBuilder {
 pub struct Connector {
   connect_timeout: Option<Duration>,
   nodelay: bool,
+  keepalive: Option<Duration>,
+  keepalive_interval: Option<Duration>,
+  keepalive_retries: Option<u32>,
+  send_buffer_size: Option<usize>,
+  recv_buffer_size: Option<usize>,
+  ttl: Option<u32>,
+  proxy_protocol: Option<ProxyProtocol>,
   read_timeout: Option<Duration>,
   write_timeout: Option<Duration>,
+  #[cfg(feature = "proxy")]
   proxy: Option<Proxy>,
+  alpn_protocols: Vec<String>,
+  #[cfg(feature = "tls")]
+  hostname_verification: bool,
+  #[cfg(feature = "tls")]
+  certs_verification: bool,
+  #[cfg(feature = "tls")]
+  tls: SslConnector,
+  #[cfg(feature = "tls")]
+  tls_sessions: Option<SessionCache>,
   #[cfg(feature = "tls")]
-  tls: TlsConnector,
+  pins: Pins,
+  #[cfg(feature = "tls")]
+  sni: bool,
+  transport: Option<TransportHandle>,
+  dns_cache: Option<DnsCache>,
+  connect_to: Option<ConnectTo>,
 }
 
 impl PartialEq for Connector {
@@ -145,14 +512,65 @@ impl PartialEq for Connector {
 impl Connector {
   /// Connect to a remote endpoint with addr
   pub fn connect_with_addr<S: Into<SocketAddr>>(&self, addr: S) -> Result<Socket> {
-    let addr = addr.into();
+    self.connect_with_addr_deadline(addr, &Deadline::default())
+  }
+  pub(crate) fn connect_with_addr_deadline<S: Into<SocketAddr>>(
+    &self,
+    addr: S,
+    deadline: &Deadline,
+  ) -> Result<Socket> {
+    self.connect_addr(addr.into(), deadline, self.connect_timeout)
+  }
+  /// Connect to the first reachable address of `addrs`.
+  ///
+  /// Addresses are tried one after the other, alternating between IPv6 and
+  /// IPv4 like Happy Eyeballs (RFC 8305) starting with the family the
+  /// resolver put first. The connect timeout is split between the addresses
+  /// left, so a dead address does not use it up; the error of the last
+  /// address is returned when none is reachable.
+  pub(crate) fn connect_with_addrs_deadline(
+    &self,
+    addrs: &[SocketAddr],
+    deadline: &Deadline,
+  ) -> Result<Socket> {
+    let addrs = interleave(addrs);
+    let start = Instant::now();
+    let mut last_err = None;
+    for (i, addr) in addrs.iter().enumerate() {
+      deadline.check()?;
+      let left = (addrs.len() - i) as u32;
+      let timeout = self.connect_timeout.map(|t| t.saturating_sub(start.elapsed()) / left);
+      if timeout.is_some_and(|t| t.is_zero()) {
+        break;
+      }
+      match self.connect_addr(*addr, deadline, timeout) {
+        Ok(socket) => return Ok(socket),
+        Err(err) => {
+          #[cfg(feature = "trace")]
+          tracing::debug!(%addr, error = %err, "connect failed");
+          last_err = Some(err);
+        }
+      }
+    }
+    Err(last_err.unwrap_or(new_io_error(
+      std::io::ErrorKind::TimedOut,
+      "connect timeout",
+    )))
+  }
+  fn connect_addr(
+    &self,
+    addr: SocketAddr,
+    deadline: &Deadline,
+    connect_timeout: Option<Duration>,
+  ) -> Result<Socket> {
     let socket = RawSocket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
     if self.nodelay {
       socket.set_nodelay(self.nodelay)?;
     }
-    socket.set_read_timeout(self.read_timeout)?;
-    socket.set_write_timeout(self.write_timeout)?;
-    match self.connect_timeout {
+    self.set_socket_options(&socket, addr)?;
+    socket.set_read_timeout(deadline.clamp(self.read_timeout)?)?;
+    socket.set_write_timeout(deadline.clamp(self.write_timeout)?)?;
+    match deadline.clamp(connect_timeout)? {
       None => {
         socket.connect(&addr.into())?;
       }
@@ -162,34 +580,566 @@ impl Connector {
     }
     Ok(Socket::TCP(socket))
   }
+  /// 连接之前设置 keepalive、缓冲区大小和 TTL，缓冲区大小会影响握手时的窗口
+  fn set_socket_options(&self, socket: &RawSocket, addr: SocketAddr) -> Result<()> {
+    if self.keepalive.is_some() || self.keepalive_interval.is_some() || self.keepalive_retries.is_some() {
+      let mut keepalive = TcpKeepalive::new();
+      if let Some(time) = self.keepalive {
+        keepalive = keepalive.with_time(time);
+      }
+      #[cfg(any(
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "netbsd",
+        target_os = "windows",
+      ))]
+      if let Some(interval) = self.keepalive_interval {
+        keepalive = keepalive.with_interval(interval);
+      }
+      #[cfg(any(
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "netbsd",
+      ))]
+      if let Some(retries) = self.keepalive_retries {
+        keepalive = keepalive.with_retries(retries);
+      }
+      socket.set_tcp_keepalive(&keepalive)?;
+    }
+    if let Some(size) = self.send_buffer_size {
+      socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = self.recv_buffer_size {
+      socket.set_recv_buffer_size(size)?;
+    }
+    if let Some(ttl) = self.ttl {
+      if addr.is_ipv4() {
+        socket.set_ttl(ttl)?;
+      } else {
+        socket.set_unicast_hops_v6(ttl)?;
+      }
+    }
+    Ok(())
+  }
+  /// Open a UDP socket connected to `addr`.
+  ///
+  /// The read and write timeouts of the connector become the receive and send
+  /// timeouts of the socket, so a lost datagram ends a read with
+  /// `WouldBlock` or `TimedOut` instead of blocking forever.
+  ///
+  /// # Errors
+  ///
+  /// Fails with `Unsupported` when a proxy is configured, datagrams are
+  /// never relayed through a proxy.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// use std::io::{Read, Write};
+  /// use std::time::Duration;
+  ///
+  /// let connector = slinger::ConnectorBuilder::default()
+  ///   .read_timeout(Some(Duration::from_secs(2)))
+  ///   .build()?;
+  /// let mut socket = connector.connect_udp(([127, 0, 0, 1], 53))?;
+  /// socket.write_all(b"ping")?;
+  /// let mut buf = [0; 512];
+  /// let n = socket.read(&mut buf)?;
+  /// println!("{:?}", &buf[..n]);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn connect_udp<S: Into<SocketAddr>>(&self, addr: S) -> Result<Socket> {
+    #[cfg(feature = "proxy")]
+    if self.proxy.is_some() {
+      return Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "udp through a proxy is not supported",
+      ));
+    }
+    let addr = addr.into();
+    let socket = RawSocket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_read_timeout(self.read_timeout)?;
+    socket.set_write_timeout(self.write_timeout)?;
+    // UDP 的 connect 只记录对端地址，之后只收这个地址发来的数据报
+    socket.connect(&addr.into())?;
+    Ok(Socket::UDP(socket))
+  }
   /// Connect to a remote endpoint with url
   pub fn connect_with_uri(&self, target: &http::Uri) -> Result<Socket> {
-    ProxySocket::new(target, &self.proxy).conn_with_connector(self)
+    self.connect_with_uri_deadline(target, &Deadline::default(), &mut Timing::default())
+  }
+  pub(crate) fn connect_with_uri_deadline(
+    &self,
+    target: &http::Uri,
+    deadline: &Deadline,
+    timing: &mut Timing,
+  ) -> Result<Socket> {
+    #[cfg(feature = "trace")]
+    let _span = tracing::debug_span!("connect", uri = %target).entered();
+    let socket = self.connect_timed(target, deadline, timing);
+    #[cfg(feature = "trace")]
+    match &socket {
+      Ok(_) => tracing::debug!(
+        dns = ?timing.dns,
+        connect = ?timing.connect,
+        tls = ?timing.tls,
+        "connected"
+      ),
+      Err(err) => tracing::debug!(error = %err, "connect failed"),
+    }
+    socket
+  }
+  fn connect_timed(
+    &self,
+    target: &http::Uri,
+    deadline: &Deadline,
+    timing: &mut Timing,
+  ) -> Result<Socket> {
+    deadline.check()?;
+    if let Some(TransportHandle(transport)) = &self.transport {
+      let start = Instant::now();
+      let socket = transport.connect(target);
+      timing.connect = start.elapsed();
+      return socket;
+    }
+    #[cfg(all(feature = "ssh", unix))]
+    if let Some(Proxy::Ssh(ssh)) = &self.proxy {
+      // 目标由跳板机解析和连接，本地没有 DNS 阶段
+      let start = Instant::now();
+      let socket = self.conn_with_ssh(ssh, target, deadline, timing);
+      timing.connect = start.elapsed().saturating_sub(timing.tls);
+      return socket;
+    }
+    let start = Instant::now();
+    let addrs = self.conn_addrs(target)?;
+    timing.dns = start.elapsed();
+    #[cfg(feature = "trace")]
+    tracing::trace!(?addrs, elapsed = ?timing.dns, "resolved");
+    // DNS 解析可能阻塞很久，解析完再检查一次
+    deadline.check()?;
+    let start = Instant::now();
+    let socket = self.conn_with_target(target, deadline, &addrs, timing);
+    // TLS 握手单独计时，剩下的都算连接时间
+    timing.connect = start.elapsed().saturating_sub(timing.tls);
+    socket
+  }
+  fn conn_with_target(
+    &self,
+    target: &http::Uri,
+    deadline: &Deadline,
+    addrs: &[SocketAddr],
+    timing: &mut Timing,
+  ) -> Result<Socket> {
+    #[cfg(feature = "tls")]
+    let upgrade_to_tls = |socket: Socket, domain: &str, proxy: bool, timing: &mut Timing| {
+      let start = Instant::now();
+      let socket = self.tls_handshake(socket, domain, proxy);
+      timing.tls += start.elapsed();
+      #[cfg(feature = "trace")]
+      match &socket {
+        Ok(socket) => tracing::debug!(
+          domain,
+          elapsed = ?start.elapsed(),
+          alpn = ?socket.negotiated_alpn().map(|p| String::from_utf8_lossy(&p).into_owned()),
+          resumed = socket.session_reused(),
+          "tls handshake"
+        ),
+        Err(err) => tracing::debug!(domain, error = %err, "tls handshake failed"),
+      }
+      socket
+    };
+    #[cfg(not(feature = "tls"))]
+    let _ = timing;
+    #[allow(unused_mut)]
+    let mut socket = self.connect_with_addrs_deadline(addrs, deadline)?;
+    let target_host = target.host().ok_or(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "no host in url",
+    ))?;
+    #[cfg(feature = "proxy")]
+    if let Some(proxy) = &self.proxy {
+      let port = default_port(target).ok_or(new_io_error(
+        std::io::ErrorKind::InvalidData,
+        "no port in url",
+      ))?;
+      match proxy {
+        Proxy::HTTP(h) => {
+          #[cfg(feature = "tls")]
+          if h.https {
+            socket = upgrade_to_tls(socket, tls_domain(proxy.domain()?), true, timing)?;
+          }
+          h.connect(&mut socket, &format!("{}:{}", target_host, port))?;
+        }
+        Proxy::Socket(s) => {
+          s.conn(&mut socket, target)?;
+          self.write_proxy_protocol(&mut socket)?;
+          return Ok(socket);
+        }
+        #[cfg(all(feature = "ssh", unix))]
+        Proxy::Ssh(_) => {}
+      }
+    }
+    self.write_proxy_protocol(&mut socket)?;
+    #[cfg(feature = "tls")]
+    if target.scheme() == Some(&http::uri::Scheme::HTTPS) {
+      socket = upgrade_to_tls(socket, tls_domain(target_host), false, timing)?;
+    }
+    #[cfg(not(feature = "tls"))]
+    let _ = target_host;
+    Ok(socket)
+  }
+  #[cfg(all(feature = "ssh", unix))]
+  fn conn_with_ssh(
+    &self,
+    ssh: &crate::proxy::SshProxy,
+    target: &http::Uri,
+    deadline: &Deadline,
+    timing: &mut Timing,
+  ) -> Result<Socket> {
+    let socket = ssh.connect(target, deadline.clamp(self.connect_timeout)?)?;
+    socket.set_read_timeout(deadline.clamp(self.read_timeout)?)?;
+    socket.set_write_timeout(deadline.clamp(self.write_timeout)?)?;
+    #[allow(unused_mut)]
+    let mut socket = Socket::TCP(socket);
+    self.write_proxy_protocol(&mut socket)?;
+    #[cfg(feature = "tls")]
+    if target.scheme() == Some(&http::uri::Scheme::HTTPS) {
+      let domain = target.host().ok_or(new_io_error(
+        std::io::ErrorKind::InvalidData,
+        "no host in url",
+      ))?;
+      let start = Instant::now();
+      socket = self.upgrade_to_tls(socket, tls_domain(domain))?;
+      timing.tls += start.elapsed();
+    }
+    #[cfg(not(feature = "tls"))]
+    let _ = timing;
+    Ok(socket)
+  }
+  /// 连接建立后、TLS 握手前发送 PROXY protocol 头部
+  fn write_proxy_protocol(&self, socket: &mut Socket) -> Result<()> {
+    if let Some(header) = &self.proxy_protocol {
+      socket.write_all(&header.encode()?)?;
+    }
+    Ok(())
+  }
+  fn conn_addrs(&self, target: &http::Uri) -> Result<Vec<SocketAddr>> {
+    // 获取连接地址，如果有代理先返回代理地址
+    #[cfg(feature = "proxy")]
+    if let Some(proxy) = &self.proxy {
+      return proxy.to_addr().map(|addr| vec![addr]);
+    }
+    let host = target.host().ok_or(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "no host in url",
+    ))?;
+    let port = default_port(target).ok_or(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "no port in url",
+    ))?;
+    // 请求指定的地址只替换原来主机的解析结果，跳转到别的主机照常解析
+    if let Some(connect_to) = self.connect_to.as_ref().filter(|c| c.matches(host, port)) {
+      return Ok(vec![connect_to.addr]);
+    }
+    let addrs = match (ip_literal(host, port), &self.dns_cache) {
+      (Some(addr), _) => addr.map(|addr| vec![addr]),
+      (None, Some(cache)) => cache.lookup(host, port),
+      (None, None) => (host, port).to_socket_addrs().map(Iterator::collect),
+    };
+    let addrs = addrs.map_err(|err| Error::Dns(host.to_string(), err))?;
+    if addrs.is_empty() {
+      return Err(new_io_error(
+        std::io::ErrorKind::InvalidData,
+        "no addr in url",
+      ));
+    }
+    Ok(addrs)
+  }
+  pub(crate) fn read_timeout(&self) -> Option<Duration> {
+    self.read_timeout
+  }
+  /// 连接设置对应的 curl 参数
+  pub(crate) fn curl_flags(&self) -> CurlFlags {
+    let mut flags = Vec::new();
+    #[cfg(feature = "proxy")]
+    if let Some(proxy) = &self.proxy {
+      flags.extend(proxy.curl_args());
+    }
+    #[cfg(feature = "tls")]
+    if !self.certs_verification || !self.hostname_verification {
+      flags.push("--insecure".to_string());
+    }
+    if let Some(timeout) = self.connect_timeout {
+      flags.extend(["--connect-timeout".to_string(), curl::seconds(timeout)]);
+    }
+    if let Some(connect_to) = &self.connect_to {
+      flags.extend([
+        "--connect-to".to_string(),
+        format!("{}:{}:{}", connect_to.host, connect_to.port, connect_to.addr),
+      ]);
+    }
+    CurlFlags(flags)
+  }
+  pub(crate) fn write_timeout(&self) -> Option<Duration> {
+    self.write_timeout
+  }
+  /// The protocols offered with ALPN, in order.
+  pub fn alpn_protocols(&self) -> &[String] {
+    &self.alpn_protocols
   }
   #[cfg(feature = "tls")]
   /// A `Connector` will use transport layer security (TLS) by default to connect to destinations.
+  ///
+  /// A socket that is already TLS gets a second handshake inside the first
+  /// one, as needed for a `CONNECT` tunnel through an `https://` proxy.
   pub fn upgrade_to_tls(&self, stream: Socket, domain: &str) -> Result<Socket> {
-    // 上面是原始socket
-    let i = match stream {
-      Socket::TCP(s) => {
-        let mut stream = self.tls.connect(domain, s);
-        while let Err(HandshakeError::WouldBlock(mid_handshake)) = stream {
-          stream = mid_handshake.handshake();
-        }
-        Socket::TLS(stream?)
-      }
-      // 本来就是tls了
-      Socket::TLS(_t) => {
-        return Err(new_io_error(
-          std::io::ErrorKind::ConnectionAborted,
-          "it's already tls",
-        ));
+    self.tls_handshake(stream, domain, false)
+  }
+  /// 和代理握手时只协商 HTTP/1.1，隧道要用 HTTP/1.1 的 CONNECT 建立
+  #[cfg(feature = "tls")]
+  fn tls_handshake(&self, stream: Socket, domain: &str, proxy: bool) -> Result<Socket> {
+    if let Socket::UDP(_) = stream {
+      return Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "tls over udp is not supported",
+      ));
+    }
+    let mut config = self.tls.configure()?;
+    config.set_verify_hostname(self.hostname_verification);
+    config.set_use_server_name_indication(self.sni);
+    if !self.certs_verification {
+      config.set_verify(SslVerifyMode::NONE);
+    }
+    if proxy && !self.alpn_protocols.is_empty() {
+      config.set_alpn_protos(b"\x08http/1.1")?;
+    }
+    if let Some(sessions) = &self.tls_sessions {
+      let key = tls::session_key(domain, self.certs_verification, self.hostname_verification);
+      if let Some(session) = sessions.get(&key) {
+        // SAFETY: 缓存里的会话是从 DER 重新解析的副本，不属于任何 SslContext
+        unsafe { config.set_session(&session)? };
       }
+      config.set_ex_data(tls::session_key_index(), key);
+    }
+    let socket = match stream {
+      Socket::TCP(s) => Socket::TLS(handshake(config.connect(domain, s))?),
+      // 已经是 TLS 的连接上再握手一次
+      stream => Socket::NestedTLS(
+        handshake(config.connect(domain, Box::new(stream))).map_err(nested_handshake_error)?,
+      ),
     };
-    Ok(i)
+    if let (false, Some(ssl)) = (self.pins.is_empty(), socket.ssl()) {
+      self.pins.check(domain, ssl)?;
+    }
+    Ok(socket)
+  }
+}
+
+/// 非阻塞的 socket 上握手会返回 WouldBlock，继续握手直到完成
+#[cfg(feature = "tls")]
+fn handshake<S: std::io::Read + std::io::Write>(
+  mut stream: std::result::Result<SslStream<S>, HandshakeError<S>>,
+) -> std::result::Result<SslStream<S>, HandshakeError<S>> {
+  while let Err(HandshakeError::WouldBlock(mid_handshake)) = stream {
+    stream = mid_handshake.handshake();
+  }
+  stream
+}
+
+/// 嵌套握手的错误转换成 OpenSSL 或者 IO 错误
+#[cfg(feature = "tls")]
+fn nested_handshake_error(err: HandshakeError<Box<Socket>>) -> Error {
+  let err = match err {
+    HandshakeError::SetupFailure(stack) => return Error::OpenSSl(stack),
+    HandshakeError::Failure(mid) | HandshakeError::WouldBlock(mid) => mid.into_error(),
+  };
+  match err.into_io_error() {
+    Ok(err) => Error::IO(err),
+    Err(err) => match err.ssl_error() {
+      Some(stack) => Error::OpenSSl(stack.clone()),
+      None => Error::IO(std::io::Error::other(err)),
+    },
+  }
+}
+
+/// 单个请求覆盖的连接设置，放在请求的 extensions 中
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct ConnectorOverride {
+  #[cfg(feature = "proxy")]
+  pub(crate) proxy: Option<Option<Proxy>>,
+  pub(crate) hostname_verification: Option<bool>,
+  pub(crate) certs_verification: Option<bool>,
+  pub(crate) nodelay: Option<bool>,
+  pub(crate) read_timeout: Option<Duration>,
+  pub(crate) write_timeout: Option<Duration>,
+  pub(crate) connect_timeout: Option<Duration>,
+  pub(crate) alpn_protocols: Option<Vec<String>>,
+  pub(crate) connect_to: Option<SocketAddr>,
+}
+
+impl ConnectorOverride {
+  /// `origin` 是请求原来的地址，指定的连接地址只用于它的主机和端口
+  pub(crate) fn apply(&self, mut builder: ConnectorBuilder, origin: &http::Uri) -> ConnectorBuilder {
+    #[cfg(feature = "proxy")]
+    if let Some(proxy) = &self.proxy {
+      builder.proxy = proxy.clone();
+    }
+    if let Some(value) = self.hostname_verification {
+      builder.hostname_verification = value;
+    }
+    if let Some(value) = self.certs_verification {
+      builder.certs_verification = value;
+    }
+    if let Some(value) = self.nodelay {
+      builder.nodelay = value;
+    }
+    if let Some(timeout) = self.read_timeout {
+      builder.read_timeout = Some(timeout);
+    }
+    if let Some(timeout) = self.write_timeout {
+      builder.write_timeout = Some(timeout);
+    }
+    if let Some(timeout) = self.connect_timeout {
+      builder.connect_timeout = Some(timeout);
+    }
+    if let Some(protocols) = &self.alpn_protocols {
+      builder.alpn_protocols = protocols.clone();
+    }
+    if let (Some(addr), Some(host), Some(port)) =
+      (self.connect_to, origin.host(), default_port(origin))
+    {
+      builder.connect_to = Some(ConnectTo {
+        host: host.to_string(),
+        port,
+        addr,
+      });
+    }
+    builder
+  }
+}
+
+/// 连接到指定地址而不是解析出来的地址，URI、Host 和 SNI 不变
+#[derive(Debug, Clone, PartialEq)]
+struct ConnectTo {
+  host: String,
+  port: u16,
+  addr: SocketAddr,
+}
+
+impl ConnectTo {
+  fn matches(&self, host: &str, port: u16) -> bool {
+    self.port == port && self.host.eq_ignore_ascii_case(host)
   }
 }
 
+pub(crate) fn default_port(uri: &http::Uri) -> Option<u16> {
+  match uri.port_u16() {
+    Some(p) => Some(p),
+    None => match uri.scheme_str() {
+      Some("https") => Some(443u16),
+      Some("http") => Some(80u16),
+      Some("socks5") | Some("socks5h") => Some(1080u16),
+      _ => None,
+    },
+  }
+}
+
+/// IPv6 和 IPv4 地址交替排列，从第一个地址的地址族开始
+fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+  let Some(first) = addrs.first() else {
+    return Vec::new();
+  };
+  let (same, other): (Vec<SocketAddr>, Vec<SocketAddr>) =
+    addrs.iter().partition(|addr| addr.is_ipv6() == first.is_ipv6());
+  let (mut same, mut other) = (same.into_iter(), other.into_iter());
+  let mut ordered = Vec::with_capacity(addrs.len());
+  loop {
+    match (same.next(), other.next()) {
+      (None, None) => return ordered,
+      (a, b) => ordered.extend(a.into_iter().chain(b)),
+    }
+  }
+}
+
+/// 主机名是 IP 地址时直接得到连接地址，不是时返回 `None`
+///
+/// IPv6 地址带方括号，可以带 RFC 6874 的区域 ID，比如 `[fe80::1%25eth0]`，
+/// 区域是编号时直接用作 scope id，是网卡名时交给系统换成编号。
+pub(crate) fn ip_literal(host: &str, port: u16) -> Option<std::io::Result<SocketAddr>> {
+  if let Ok(ip) = host.parse::<Ipv4Addr>() {
+    return Some(Ok(SocketAddr::new(ip.into(), port)));
+  }
+  let host = host.strip_prefix('[')?.strip_suffix(']')?;
+  let (ip, zone) = match host.split_once('%') {
+    Some((ip, zone)) => (ip.parse::<Ipv6Addr>().ok()?, Some(decode_zone(zone))),
+    None => (host.parse::<Ipv6Addr>().ok()?, None),
+  };
+  let scope_id = match zone.as_deref().map(str::parse::<u32>) {
+    None => 0,
+    Some(Ok(id)) => id,
+    Some(Err(_)) => {
+      let zone = zone.unwrap_or_default();
+      return Some(
+        (format!("{}%{}", ip, zone), port)
+          .to_socket_addrs()
+          .and_then(|mut addrs| {
+            addrs.next().ok_or(std::io::Error::new(
+              std::io::ErrorKind::NotFound,
+              format!("unknown zone {}", zone),
+            ))
+          }),
+      );
+    }
+  };
+  Some(Ok(SocketAddrV6::new(ip, port, 0, scope_id).into()))
+}
+
+/// 去掉 IPv6 地址的方括号，区域 ID 写成系统认识的 `fe80::1%eth0`
+#[cfg(all(feature = "ssh", unix))]
+pub(crate) fn bare_host(host: &str) -> Cow<'_, str> {
+  let Some(inner) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) else {
+    return Cow::Borrowed(host);
+  };
+  match inner.split_once('%') {
+    Some((ip, zone)) => Cow::Owned(format!("{}%{}", ip, decode_zone(zone))),
+    None => Cow::Borrowed(inner),
+  }
+}
+
+/// 去掉主机名或者 `host:port` 里的区域 ID，区域只在本机有意义，不发给服务器
+pub(crate) fn without_zone(authority: &str) -> Cow<'_, str> {
+  let start = authority.find('[').and_then(|open| authority[open..].find('%').map(|i| open + i));
+  match (start, authority.find(']')) {
+    (Some(start), Some(end)) if start < end => {
+      Cow::Owned(format!("{}{}", &authority[..start], &authority[end..]))
+    }
+    _ => Cow::Borrowed(authority),
+  }
+}
+
+/// TLS 握手用的主机名，IP 地址不带方括号和区域 ID，证书按 IP 地址校验
+#[cfg(feature = "tls")]
+fn tls_domain(host: &str) -> &str {
+  match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+    Some(ip) => ip.split('%').next().unwrap_or(ip),
+    None => host,
+  }
+}
+
+/// `%25eth0` 按 RFC 6874 解码，也接受没有编码的 `%eth0`
+fn decode_zone(zone: &str) -> String {
+  let zone = zone.strip_prefix("25").filter(|z| !z.is_empty()).unwrap_or(zone);
+  percent_decode_str(zone).decode_utf8_lossy().into_owned()
+}
+
 //
 impl Default for Connector {
   fn default() -> Self {
@@ -198,3 +1148,356 @@ impl Default for Connector {
       .expect("new default connector failure")
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{interleave, ip_literal, without_zone, ConnectTo, ConnectorBuilder};
+  use crate::deadline::Deadline;
+  use std::io::{Read, Write};
+  use std::net::{SocketAddr, TcpListener};
+  use std::time::Duration;
+
+  #[test]
+  fn connect_failover() {
+    let addrs: Vec<SocketAddr> = ["10.0.0.1:80", "10.0.0.2:80", "[::1]:80", "[::2]:80", "[::3]:80"]
+      .iter()
+      .map(|a| a.parse().unwrap())
+      .collect();
+    let ordered: Vec<String> = interleave(&addrs).iter().map(|a| a.to_string()).collect();
+    assert_eq!(ordered, ["10.0.0.1:80", "[::1]:80", "10.0.0.2:80", "[::2]:80", "[::3]:80"]);
+    // 第一个地址拒绝连接时换下一个地址
+    let dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let live = TcpListener::bind("127.0.0.1:0").unwrap();
+    let connector = ConnectorBuilder::default()
+      .connect_timeout(Some(Duration::from_secs(5)))
+      .build()
+      .unwrap();
+    let addrs = [dead, live.local_addr().unwrap()];
+    let socket = connector.connect_with_addrs_deadline(&addrs, &Deadline::default()).unwrap();
+    assert_eq!(socket.peer_addr().unwrap().as_socket(), Some(addrs[1]));
+    let err = connector.connect_with_addrs_deadline(&[dead], &Deadline::default()).unwrap_err();
+    assert!(err.is_connect());
+  }
+
+  #[test]
+  fn ipv6_zone() {
+    let addr = |host: &str| ip_literal(host, 80).map(|addr| addr.unwrap());
+    assert_eq!(addr("127.0.0.1"), Some("127.0.0.1:80".parse().unwrap()));
+    assert_eq!(addr("[::1]"), Some("[::1]:80".parse().unwrap()));
+    assert_eq!(addr("[fe80::1%252]"), Some("[fe80::1%2]:80".parse().unwrap()));
+    assert_eq!(addr("[fe80::1%2]"), Some("[fe80::1%2]:80".parse().unwrap()));
+    assert_eq!(addr("example.com"), None);
+    assert_eq!(addr("::1"), None);
+    #[cfg(target_os = "linux")]
+    assert_eq!(addr("[fe80::1%25lo]"), Some("[fe80::1%1]:80".parse().unwrap()));
+    assert!(ip_literal("[fe80::1%25nosuchif0]", 80).unwrap().is_err());
+    assert_eq!(without_zone("[fe80::1%25eth0]:8080"), "[fe80::1]:8080");
+    assert_eq!(without_zone("example.com:80"), "example.com:80");
+  }
+
+  #[cfg(feature = "proxy")]
+  #[test]
+  fn ipv6_zone_proxy() {
+    use std::io::{BufRead, BufReader};
+    // 代理本身是带区域 ID 的 IPv6 地址，目标的区域 ID 原样放在 CONNECT 里，不出现在 Host 头里
+    let Ok(listener) = TcpListener::bind("[::1]:0") else {
+      return;
+    };
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut reader = BufReader::new(stream.try_clone().unwrap());
+      let mut head = Vec::new();
+      for _ in 0..2 {
+        let mut lines = Vec::new();
+        loop {
+          let mut line = String::new();
+          reader.read_line(&mut line).unwrap();
+          if line == "\r\n" {
+            break;
+          }
+          lines.push(line.trim_end().to_string());
+        }
+        (&stream)
+          .write_all(match head.is_empty() {
+            true => b"HTTP/1.1 200 Connection established\r\n\r\n".as_slice(),
+            false => b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".as_slice(),
+          })
+          .unwrap();
+        head.extend(lines);
+      }
+      head
+    });
+    let proxy = crate::Proxy::parse(format!("http://[::1%251]:{}", port)).unwrap();
+    let expected: SocketAddr = format!("[::1%1]:{}", port).parse().unwrap();
+    assert_eq!(proxy.to_addr().unwrap(), expected);
+    let client = crate::Client::builder().proxy(proxy).build().unwrap();
+    let response = client.get("http://[fe80::1%25eth0]:8080/admin").send().unwrap();
+    assert_eq!(response.status_code(), 200);
+    let head = server.join().unwrap();
+    assert_eq!(head[0], "CONNECT [fe80::1%25eth0]:8080 HTTP/1.1");
+    assert!(head.contains(&"GET /admin HTTP/1.1".to_string()));
+    assert!(head.contains(&"host: [fe80::1]:8080".to_string()), "{:?}", head);
+  }
+
+  #[cfg(all(feature = "tls", feature = "proxy"))]
+  #[test]
+  fn tls_in_tls_proxy() {
+    let acceptor = crate::tls::test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    // 代理和目标是同一个线程：外层握手、CONNECT、内层握手、请求
+    let server = std::thread::spawn(move || {
+      let read_head = |stream: &mut dyn Read| {
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+          stream.read_exact(&mut byte).unwrap();
+          head.push(byte[0]);
+        }
+        String::from_utf8(head).unwrap()
+      };
+      let (stream, _) = listener.accept().unwrap();
+      let mut proxy = acceptor.accept(stream).unwrap();
+      let connect = read_head(&mut proxy);
+      proxy.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap();
+      let mut origin = acceptor.accept(proxy).unwrap();
+      let request = read_head(&mut origin);
+      origin.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+      (connect, request)
+    });
+    let proxy = crate::Proxy::parse(format!("https://127.0.0.1:{}", port)).unwrap();
+    let client = crate::Client::builder().proxy(proxy).build().unwrap();
+    let response = client.get("https://example.com/inner").send().unwrap();
+    assert_eq!(response.text().unwrap(), "ok");
+    let (connect, request) = server.join().unwrap();
+    assert!(connect.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+    assert!(request.starts_with("GET /inner HTTP/1.1\r\n"));
+  }
+
+  #[cfg(all(feature = "ssh", unix))]
+  #[test]
+  fn ssh_jump_host() {
+    use std::os::unix::fs::PermissionsExt;
+    // 假的 ssh：记下参数，按脚本输出日志，转发成功后自己当目标服务器
+    let fake_ssh = |name: &str, script: &str| {
+      let path = std::env::temp_dir().join(format!("slinger-ssh-{}-{}", std::process::id(), name));
+      std::fs::write(&path, format!("#!/bin/sh\necho \"$@\" > \"$0.args\"\n{}", script)).unwrap();
+      std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+      path
+    };
+    let ok = fake_ssh(
+      "ok",
+      "echo 'debug1: Authenticated to bastion.invalid' >&2\n\
+       echo 'debug2: channel 0: open confirm rwindow 0 rmax 32768' >&2\n\
+       read line\n\
+       printf 'HTTP/1.1 200 OK\\r\\nContent-Length: 2\\r\\nConnection: close\\r\\n\\r\\nok'\n",
+    );
+    let refused = fake_ssh(
+      "refused",
+      "echo 'debug1: Authenticated to bastion.invalid' >&2\n\
+       echo 'channel 0: open failed: connect failed: Connection refused' >&2\n\
+       echo 'stdio forwarding failed' >&2\n\
+       exit 255\n",
+    );
+    let client = |program: &std::path::Path| {
+      let proxy = crate::Proxy::parse("ssh://scanner@bastion.invalid:2222")
+        .unwrap()
+        .ssh_program(program);
+      crate::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .proxy(proxy)
+        .build()
+        .unwrap()
+    };
+    let response = client(&ok).get("http://target.invalid/").send().unwrap();
+    assert_eq!(response.text().unwrap(), "ok");
+    let args = std::fs::read_to_string(ok.with_extension("args")).unwrap();
+    assert!(args.starts_with("-W target.invalid:80 "), "{}", args);
+    assert!(args.ends_with(" -p 2222 -l scanner -- bastion.invalid\n"), "{}", args);
+    // ssh 先退出时是连接错误，带上 ssh 的最后一条消息
+    let err = client(&refused).get("http://target.invalid/").send().unwrap_err();
+    assert!(err.is_connect(), "{:?}", err);
+    assert!(err.to_string().contains("stdio forwarding failed"), "{}", err);
+    let missing = std::env::temp_dir().join("slinger-ssh-missing");
+    let err = client(&missing).get("http://target.invalid/").send().unwrap_err();
+    assert!(err.is_connect(), "{:?}", err);
+    assert!(err.to_string().contains("failed to start"), "{}", err);
+    for path in [ok, refused] {
+      let _ = std::fs::remove_file(path.with_extension("args"));
+      let _ = std::fs::remove_file(path);
+    }
+  }
+
+  #[test]
+  fn connect_to() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut head = Vec::new();
+      let mut buf = [0u8; 1024];
+      while !head.ends_with(b"\r\n\r\n") {
+        let n = stream.read(&mut buf).unwrap();
+        head.extend_from_slice(&buf[..n]);
+      }
+      stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+      String::from_utf8(head).unwrap().to_ascii_lowercase()
+    });
+    let resp = crate::Client::new()
+      .get("http://backend.invalid:8080/path")
+      .connect_to(addr)
+      .send()
+      .unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+    assert_eq!(resp.remote_addr(), Some(addr));
+    let head = server.join().unwrap();
+    assert!(head.starts_with("get /path http/1.1\r\n"));
+    assert!(head.contains("\r\nhost: backend.invalid:8080\r\n"));
+    // 只替换原来的主机和端口
+    let connect_to = ConnectTo {
+      host: "backend.invalid".to_string(),
+      port: 8080,
+      addr,
+    };
+    assert!(connect_to.matches("Backend.Invalid", 8080));
+    assert!(!connect_to.matches("backend.invalid", 443));
+    assert!(!connect_to.matches("other.invalid", 8080));
+  }
+  #[cfg(feature = "tls")]
+  #[test]
+  fn tls_session_resumption() {
+    let acceptor = crate::tls::test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else { break };
+        if let Ok(mut stream) = acceptor.accept(stream) {
+          let _ = stream.write_all(b"ok");
+        }
+      }
+    });
+    let uri: http::Uri = format!("https://{}/", addr).parse().unwrap();
+    // 读到数据时 TLS 1.3 的会话票据也一起处理了
+    let connect = |connector: &super::Connector| {
+      let mut socket = connector.connect_with_uri(&uri).unwrap();
+      let mut buf = [0u8; 2];
+      socket.read_exact(&mut buf).unwrap();
+      socket.session_reused()
+    };
+    let builder = ConnectorBuilder::default()
+      .connect_timeout(Some(Duration::from_secs(5)))
+      .read_timeout(Some(Duration::from_secs(5)))
+      .tls_session_cache(true);
+    let connector = builder.build().unwrap();
+    assert!(!connect(&connector));
+    assert!(connect(&connector));
+    // 同一个构建器建出的连接器共享缓存
+    assert!(connect(&builder.clone().nodelay(true).build().unwrap()));
+    // 不校验证书时的会话不能让校验证书的连接跳过校验
+    let strict = builder.clone().certs_verification(true).build().unwrap();
+    assert!(strict.connect_with_uri(&uri).is_err());
+    assert!(!connect(&ConnectorBuilder::default().build().unwrap()));
+  }
+
+  #[test]
+  fn connect_udp() {
+    use std::net::UdpSocket;
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let connector = ConnectorBuilder::default()
+      .read_timeout(Some(Duration::from_millis(300)))
+      .build()
+      .unwrap();
+    let mut socket = connector.connect_udp(addr).unwrap();
+    socket.write_all(b"ping").unwrap();
+    let mut buf = [0u8; 64];
+    let (n, peer) = server.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"ping");
+    // 别的地址发来的数据报被丢掉，只收到对端的回复
+    let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+    stranger.send_to(b"noise", peer).unwrap();
+    server.send_to(b"pong", peer).unwrap();
+    let n = socket.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"pong");
+    // 数据报丢失时读超时返回，而不是一直阻塞
+    let err = socket.read(&mut buf).unwrap_err();
+    assert!(
+      matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut),
+      "{:?}",
+      err
+    );
+    #[cfg(feature = "tls")]
+    {
+      let err = connector.upgrade_to_tls(socket, "localhost").unwrap_err();
+      assert!(err.to_string().contains("tls over udp"), "{}", err);
+    }
+    #[cfg(feature = "proxy")]
+    {
+      let proxied = ConnectorBuilder::default()
+        .proxy(Some(crate::Proxy::parse("socks5://127.0.0.1:1080").unwrap()))
+        .build()
+        .unwrap();
+      assert!(proxied.connect_udp(addr).is_err());
+    }
+  }
+
+  #[cfg(feature = "tls")]
+  #[test]
+  fn alpn_protocols_on_socket() {
+    let acceptor = crate::tls::test_acceptor(b"\x08http/1.1");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else { break };
+        if let Ok(mut stream) = acceptor.accept(stream) {
+          let _ = stream.read(&mut [0u8; 1]);
+        }
+      }
+    });
+    let uri: http::Uri = format!("https://{}/", addr).parse().unwrap();
+    let offered: &[&str] = &["h2", "http/1.1"];
+    let connector = ConnectorBuilder::default()
+      .connect_timeout(Some(Duration::from_secs(5)))
+      .alpn_protocols(offered)
+      .build()
+      .unwrap();
+    let socket = connector.connect_with_uri(&uri).unwrap();
+    assert_eq!(socket.negotiated_alpn().as_deref(), Some(&b"http/1.1"[..]));
+    drop(socket);
+    // 不提供 ALPN 时服务器不选协议
+    let socket = ConnectorBuilder::default().build().unwrap().connect_with_uri(&uri).unwrap();
+    assert_eq!(socket.negotiated_alpn(), None);
+  }
+
+  #[test]
+  fn socket_options() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri: http::Uri = format!("http://{}/", listener.local_addr().unwrap()).parse().unwrap();
+    let connector = ConnectorBuilder::default()
+      .tcp_keepalive(Duration::from_secs(60))
+      .keepalive_interval(Duration::from_secs(10))
+      .keepalive_retries(4)
+      .send_buffer_size(64 * 1024)
+      .recv_buffer_size(64 * 1024)
+      .ttl(42)
+      .build()
+      .unwrap();
+    let socket = connector.connect_with_uri(&uri).unwrap();
+    assert!(socket.keepalive().unwrap());
+    #[cfg(target_os = "linux")]
+    {
+      assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(60));
+      assert_eq!(socket.keepalive_interval().unwrap(), Duration::from_secs(10));
+      assert_eq!(socket.keepalive_retries().unwrap(), 4);
+    }
+    // 系统可能把缓冲区放大，但不会比要求的小
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+    assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    assert_eq!(socket.ttl().unwrap(), 42);
+    let socket = ConnectorBuilder::default().build().unwrap().connect_with_uri(&uri).unwrap();
+    assert!(!socket.keepalive().unwrap());
+  }
+}
@@ -102,67 +102,120 @@ fn is_secure(url: &http::Uri) -> bool {
   }
 }
 
-pub trait CookieStore: Debug {
+pub trait CookieStore: Debug + Send + Sync {
   fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item=&HeaderValue>, url: &http::Uri);
   fn cookies(&self, url: &http::Uri) -> Option<HeaderValue>;
 }
 
+/// A cookie parsed from a `Set-Cookie` header.
 #[derive(Debug, Clone)]
 pub struct Cookie<'a>(cookie::Cookie<'a>);
 
+/// Parse the value of a `Set-Cookie` header.
+///
+/// Parsing is lenient, the way browsers handle it: invalid UTF-8 is replaced,
+/// a pair without `=` is read as a value with an empty name, and malformed or
+/// unknown attributes are skipped instead of rejecting the whole cookie.
+/// Returns `None` only when the header carries neither a name nor a value.
+///
+/// # Example
+///
+/// ```rust
+/// let cookie = slinger::cookie::parse_set_cookie(b"sid=abc; Path=/; Max-Age=oops; HttpOnly").unwrap();
+/// assert_eq!(cookie.name(), "sid");
+/// assert!(cookie.http_only());
+/// assert!(cookie.max_age().is_none());
+/// ```
+pub fn parse_set_cookie(value: &[u8]) -> Option<Cookie<'static>> {
+  let value = String::from_utf8_lossy(value);
+  let (pair, attributes) = match value.split_once(';') {
+    Some((pair, attributes)) => (pair, attributes),
+    None => (value.as_ref(), ""),
+  };
+  let (name, value) = match pair.split_once('=') {
+    Some((name, value)) => (name.trim(), value.trim()),
+    None => ("", pair.trim()),
+  };
+  if name.is_empty() && value.is_empty() {
+    return None;
+  }
+  // 名字和值自己处理，属性交给cookie库解析，它会跳过无法识别的属性
+  let mut cookie = cookie::Cookie::parse(format!("_=;{}", attributes))
+    .map(|c| c.into_owned())
+    .unwrap_or_else(|_| cookie::Cookie::new("_", ""));
+  cookie.set_name(name.to_string());
+  cookie.set_value(value.to_string());
+  Some(Cookie(cookie))
+}
+
 impl<'a> Cookie<'a> {
   fn parse(value: &'a HeaderValue) -> Result<Cookie<'a>, cookie::ParseError> {
     std::str::from_utf8(value.as_bytes())
       .map_err(cookie::ParseError::from)
       .and_then(cookie::Cookie::parse)
-      .map_err(cookie::ParseError::from)
       .map(Cookie)
   }
 
+  /// The name of the cookie.
   pub fn name(&self) -> &str {
     self.0.name()
   }
 
+  /// The value of the cookie.
   pub fn value(&self) -> &str {
     self.0.value()
   }
 
+  /// Whether the `HttpOnly` attribute is set.
   pub fn http_only(&self) -> bool {
     self.0.http_only().unwrap_or(false)
   }
 
+  /// Whether the `Secure` attribute is set.
   pub fn secure(&self) -> bool {
     self.0.secure().unwrap_or(false)
   }
 
+  /// Whether `SameSite=Lax` is set.
   pub fn same_site_lax(&self) -> bool {
     self.0.same_site() == Some(cookie::SameSite::Lax)
   }
 
+  /// Whether `SameSite=Strict` is set.
   pub fn same_site_strict(&self) -> bool {
     self.0.same_site() == Some(cookie::SameSite::Strict)
   }
 
+  /// Whether `SameSite=None` is set.
+  pub fn same_site_none(&self) -> bool {
+    self.0.same_site() == Some(cookie::SameSite::None)
+  }
+
+  /// The `Path` attribute, if any.
   pub fn path(&self) -> Option<&str> {
     self.0.path()
   }
 
+  /// The `Domain` attribute, if any.
   pub fn domain(&self) -> Option<&str> {
     self.0.domain()
   }
 
+  /// The `Max-Age` attribute, if any.
   pub fn max_age(&self) -> Option<std::time::Duration> {
     self.0.max_age().map(|d| {
       d.try_into()
         .expect("time::Duration into std::time::Duration")
     })
   }
+  /// The `Expires` attribute, if any.
   pub fn expires(&self) -> Option<SystemTime> {
     match self.0.expires() {
       Some(cookie::Expiration::DateTime(offset)) => Some(SystemTime::from(offset)),
       None | Some(cookie::Expiration::Session) => None,
     }
   }
+  /// Whether the cookie may be sent to `request_url` given its `Secure` and `HttpOnly` flags.
   pub fn matches(&self, request_url: &http::Uri) -> bool {
     (!self.0.secure().unwrap_or(false) || is_secure(request_url))
       && (!self.0.http_only().unwrap_or(false) || is_http_scheme(request_url))
@@ -200,6 +253,25 @@ pub(crate) fn extract_response_cookie_headers(
 
 pub(crate) fn extract_response_cookies(
   headers: &http::HeaderMap,
-) -> impl Iterator<Item=Result<Cookie, cookie::ParseError>> {
+) -> impl Iterator<Item=Result<Cookie<'_>, cookie::ParseError>> {
   headers.get_all(SET_COOKIE).iter().map(Cookie::parse)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::parse_set_cookie;
+
+  #[test]
+  fn lenient_set_cookie() {
+    let cookie = parse_set_cookie(b"token; Secure; SameSite=None; Domain=").unwrap();
+    assert_eq!(cookie.name(), "");
+    assert_eq!(cookie.value(), "token");
+    assert!(cookie.secure());
+    assert!(cookie.same_site_none());
+    assert!(cookie.domain().is_none());
+    let cookie = parse_set_cookie(b"a=\xff; Expires=garbage; Path=/x").unwrap();
+    assert_eq!(cookie.path(), Some("/x"));
+    assert!(cookie.expires().is_none());
+    assert!(parse_set_cookie(b" ; HttpOnly").is_none());
+  }
+}
@@ -0,0 +1,201 @@
+use crate::connector::ConnectorOverride;
+use crate::deadline::RequestDeadline;
+use crate::request::{HeaderOrder, RawHeaders, RequestTarget};
+use crate::Request;
+use http::{Method, Version};
+use std::time::Duration;
+
+/// 请求实际使用的连接设置对应的 curl 参数，发送时由客户端放入请求
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CurlFlags(pub(crate) Vec<String>);
+
+/// 单个请求覆盖的连接设置对应的 curl 参数，请求还没有发送时使用
+fn override_flags(overrides: &ConnectorOverride) -> Vec<String> {
+  let mut flags = Vec::new();
+  #[cfg(feature = "proxy")]
+  match &overrides.proxy {
+    Some(Some(proxy)) => flags.extend(proxy.curl_args()),
+    Some(None) => flags.extend(["--noproxy".to_string(), "*".to_string()]),
+    None => {}
+  }
+  if overrides.certs_verification == Some(false) || overrides.hostname_verification == Some(false)
+  {
+    flags.push("--insecure".to_string());
+  }
+  if let Some(timeout) = overrides.connect_timeout {
+    flags.extend(["--connect-timeout".to_string(), seconds(timeout)]);
+  }
+  flags
+}
+
+pub(crate) fn seconds(duration: Duration) -> String {
+  format!("{}", duration.as_secs_f64())
+}
+
+pub(crate) fn render(request: &Request) -> String {
+  // 原始请求先解析成结构化的请求，curl 无法发送的字节会丢失
+  let parsed = request
+    .raw_request()
+    .as_ref()
+    .and_then(|raw| Request::parse_with_target(&raw.raw, request.uri()).ok());
+  let message = parsed.as_ref().unwrap_or(request);
+  let body = message
+    .body()
+    .as_ref()
+    .map(|b| b.as_ref())
+    .unwrap_or_default();
+  let mut args: Vec<String> = Vec::new();
+  let method = message.method();
+  if method == Method::HEAD {
+    args.push("--head".to_string());
+  } else if method != (if body.is_empty() { Method::GET } else { Method::POST }) {
+    args.extend(["--request".to_string(), method.to_string()]);
+  }
+  let https = message.uri().scheme() == Some(&http::uri::Scheme::HTTPS);
+  match message.version() {
+    Version::HTTP_10 => args.push("--http1.0".to_string()),
+    Version::HTTP_11 if https => args.push("--http1.1".to_string()),
+    Version::HTTP_2 if https => args.push("--http2".to_string()),
+    Version::HTTP_2 => args.push("--http2-prior-knowledge".to_string()),
+    _ => {}
+  }
+  let path = message.uri().path();
+  if path.split('/').any(|segment| segment == "." || segment == "..") {
+    args.push("--path-as-is".to_string());
+  }
+  match request.extensions().get::<RequestTarget>() {
+    Some(RequestTarget::Asterisk) => {
+      args.extend(["--request-target".to_string(), "*".to_string()])
+    }
+    Some(RequestTarget::Authority(authority)) => {
+      args.extend(["--request-target".to_string(), authority.to_string()])
+    }
+    Some(RequestTarget::Raw(raw)) => {
+      args.extend(["--request-target".to_string(), String::from_utf8_lossy(raw).to_string()])
+    }
+    _ => {}
+  }
+  let order = message.extensions().get::<HeaderOrder>();
+  let mut headers: Vec<_> = message.headers().iter().collect();
+  if let Some(order) = order {
+    headers.sort_by_key(|(name, _)| order.position(name));
+  }
+  for (name, value) in headers {
+    // curl 会自己算出一样的长度，只保留和请求体不一致的 Content-Length
+    if name == http::header::CONTENT_LENGTH && value.as_bytes() == body.len().to_string().as_bytes()
+    {
+      continue;
+    }
+    let name = match order {
+      Some(order) => order.spelling(name),
+      None => name.to_string(),
+    };
+    args.push("--header".to_string());
+    args.push(header_arg(&name, value.as_bytes()));
+  }
+  if let Some(RawHeaders(raw)) = request.extensions().get::<RawHeaders>() {
+    for (name, value) in raw {
+      args.push("--header".to_string());
+      args.push(header_arg(&String::from_utf8_lossy(name), value));
+    }
+  }
+  let mut line = String::from("curl");
+  for arg in &args {
+    line.push(' ');
+    line.push_str(&quote(arg.as_bytes()));
+  }
+  if !body.is_empty() {
+    line.push_str(" --data-binary ");
+    line.push_str(&quote(body));
+  }
+  let flags = match (
+    request.extensions().get::<CurlFlags>(),
+    request.extensions().get::<ConnectorOverride>(),
+  ) {
+    (Some(CurlFlags(flags)), _) => flags.clone(),
+    (None, Some(overrides)) => override_flags(overrides),
+    (None, None) => Vec::new(),
+  };
+  let deadline = request
+    .extensions()
+    .get::<RequestDeadline>()
+    .map(|RequestDeadline(timeout)| ["--max-time".to_string(), seconds(*timeout)]);
+  for flag in flags.iter().chain(deadline.iter().flatten()) {
+    line.push(' ');
+    line.push_str(&quote(flag.as_bytes()));
+  }
+  line.push(' ');
+  line.push_str(&quote(message.uri().to_string().as_bytes()));
+  line
+}
+
+/// 值为空时 curl 需要写成 `name;` 才会发送这个请求头
+fn header_arg(name: &str, value: &[u8]) -> String {
+  if value.is_empty() {
+    return format!("{};", name);
+  }
+  format!("{}: {}", name, String::from_utf8_lossy(value))
+}
+
+/// 按 POSIX shell 的规则引用参数，不可打印的字节用 `$'...'` 转义
+fn quote(arg: &[u8]) -> String {
+  let safe = |b: &u8| b.is_ascii_alphanumeric() || b"-_./:=@,+%".contains(b);
+  if !arg.is_empty() && arg.iter().all(safe) {
+    return String::from_utf8_lossy(arg).into_owned();
+  }
+  match std::str::from_utf8(arg) {
+    Ok(text) if !text.chars().any(|c| c.is_control() && c != '\n' && c != '\t') => {
+      format!("'{}'", text.replace('\'', "'\\''"))
+    }
+    _ => {
+      let mut out = String::from("$'");
+      for &b in arg {
+        match b {
+          b'\'' | b'\\' => {
+            out.push('\\');
+            out.push(b as char);
+          }
+          b'\n' => out.push_str("\\n"),
+          b'\r' => out.push_str("\\r"),
+          b'\t' => out.push_str("\\t"),
+          0x20..=0x7e => out.push(b as char),
+          _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+      }
+      out.push('\'');
+      out
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::request::RawRequest;
+  use std::time::Duration;
+
+  #[test]
+  fn curl_command() {
+    let request = crate::Client::new()
+      .post("https://example.com/a/../admin?x=1")
+      .header("X-Note", "it's")
+      .header("X-Empty", "")
+      .body(b"a\r\n\x00".to_vec())
+      .timeout(Duration::from_millis(1500))
+      .build()
+      .unwrap();
+    assert_eq!(
+      request.to_curl(),
+      "curl --http1.1 --path-as-is --header 'x-note: it'\\''s' --header 'x-empty;' \
+       --data-binary $'a\\r\\n\\x00' --max-time 1.5 'https://example.com/a/../admin?x=1'"
+    );
+    let mut raw: crate::Request = http::Request::get("http://127.0.0.1:8080/")
+      .body(Vec::new())
+      .unwrap()
+      .into();
+    *raw.raw_request_mut() = Some(RawRequest {
+      unsafe_raw: false,
+      raw: "GET /x HTTP/1.1\r\nHost: a.example\r\n\r\n".into(),
+    });
+    assert_eq!(raw.to_curl(), "curl --header 'Host: a.example' http://127.0.0.1:8080/x");
+  }
+}
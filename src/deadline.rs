@@ -0,0 +1,164 @@
+use crate::errors::{new_io_error, Result};
+use crate::socket::Socket;
+use crate::wire::WireLog;
+use std::cell::Cell;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// A monotonic time budget shared by every phase of a request.
+///
+/// A `Deadline` is started when `Client::execute` begins and is consulted
+/// before DNS resolution, connect, every write and every read, including the
+/// extra round-trips caused by redirects and authentication challenges. Each
+/// socket operation timeout is clamped to the remaining budget, so the whole
+/// exchange never outlives it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+  start: Instant,
+  budget: Option<Duration>,
+}
+
+impl Default for Deadline {
+  fn default() -> Self {
+    Deadline::new(None)
+  }
+}
+
+impl Deadline {
+  /// Start a new deadline, `None` means no limit.
+  pub fn new(budget: Option<Duration>) -> Self {
+    Deadline {
+      start: Instant::now(),
+      budget,
+    }
+  }
+  /// The total budget of this deadline.
+  pub fn budget(&self) -> Option<Duration> {
+    self.budget
+  }
+  /// Time elapsed since the deadline was started.
+  pub fn elapsed(&self) -> Duration {
+    self.start.elapsed()
+  }
+  /// Time left before the deadline expires, `None` if there is no limit.
+  pub fn remaining(&self) -> Option<Duration> {
+    self
+      .budget
+      .map(|budget| budget.saturating_sub(self.start.elapsed()))
+  }
+  /// Returns true once the budget is used up.
+  pub fn is_expired(&self) -> bool {
+    self.remaining() == Some(Duration::ZERO)
+  }
+  /// Returns a `TimedOut` error once the budget is used up.
+  pub(crate) fn check(&self) -> Result<()> {
+    if self.is_expired() {
+      return Err(new_io_error(
+        std::io::ErrorKind::TimedOut,
+        "deadline exceeded",
+      ));
+    }
+    Ok(())
+  }
+  /// Clamp an operation timeout to the remaining budget.
+  pub(crate) fn clamp(&self, timeout: Option<Duration>) -> Result<Option<Duration>> {
+    self.check()?;
+    // 超时为0会被当作不超时，至少保留1毫秒
+    let remaining = self
+      .remaining()
+      .map(|r| std::cmp::max(r, Duration::from_millis(1)));
+    Ok(match (timeout, remaining) {
+      (Some(t), Some(r)) => Some(std::cmp::min(t, r)),
+      (None, r) => r,
+      (t, None) => t,
+    })
+  }
+}
+
+/// 单个请求的总超时，覆盖 `ClientBuilder::deadline`，放在请求的 extensions 中
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequestDeadline(pub(crate) Duration);
+
+/// 每次读取前按剩余时间重新设置读超时，并记录收到第一个字节的时间
+pub(crate) struct DeadlineReader<'a> {
+  socket: &'a mut Socket,
+  deadline: Deadline,
+  read_timeout: Option<Duration>,
+  first_byte: &'a Cell<Option<Instant>>,
+  idle: Option<Duration>,
+  log: Option<&'a WireLog>,
+}
+
+impl<'a> DeadlineReader<'a> {
+  pub(crate) fn new(
+    socket: &'a mut Socket,
+    deadline: Deadline,
+    read_timeout: Option<Duration>,
+    first_byte: &'a Cell<Option<Instant>>,
+  ) -> Self {
+    DeadlineReader {
+      socket,
+      deadline,
+      read_timeout,
+      first_byte,
+      idle: None,
+      log: None,
+    }
+  }
+  /// 收到第一个字节之后，每次读取最多等待 `idle`
+  pub(crate) fn idle(mut self, idle: Option<Duration>) -> Self {
+    self.idle = idle;
+    self
+  }
+  /// 读到的字节记到日志里
+  pub(crate) fn log(mut self, log: Option<&'a WireLog>) -> Self {
+    self.log = log;
+    self
+  }
+}
+
+impl Read for DeadlineReader<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let idle = self.idle.filter(|_| self.first_byte.get().is_some());
+    if self.deadline.budget().is_some() || idle.is_some() {
+      let read_timeout = match (self.read_timeout, idle) {
+        (Some(timeout), Some(idle)) => Some(timeout.min(idle)),
+        (timeout, idle) => timeout.or(idle),
+      };
+      let timeout = self
+        .deadline
+        .clamp(read_timeout)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "deadline exceeded"))?;
+      self.socket.set_read_timeout(timeout)?;
+    }
+    let n = self.socket.read(buf)?;
+    if let (Some(log), true) = (self.log, n > 0) {
+      log.received(&buf[..n]);
+    }
+    if n > 0 && self.first_byte.get().is_none() {
+      self.first_byte.set(Some(Instant::now()));
+    }
+    Ok(n)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Deadline;
+  use std::time::Duration;
+
+  #[test]
+  fn clamp_to_remaining() {
+    let deadline = Deadline::new(Some(Duration::from_secs(5)));
+    let timeout = deadline.clamp(Some(Duration::from_secs(30))).unwrap();
+    assert!(timeout.unwrap() <= Duration::from_secs(5));
+    let unlimited = Deadline::new(None);
+    assert_eq!(
+      unlimited.clamp(Some(Duration::from_secs(30))).unwrap(),
+      Some(Duration::from_secs(30))
+    );
+    let expired = Deadline::new(Some(Duration::ZERO));
+    assert!(expired.is_expired());
+    assert!(expired.clamp(None).is_err());
+  }
+}
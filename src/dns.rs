@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 过期时间和解析出的地址
+type Entry = (Instant, Vec<IpAddr>);
+
+/// 按主机名缓存解析出的地址，过期前不再查询 DNS；克隆共享同一份缓存
+#[derive(Clone)]
+pub(crate) struct DnsCache {
+  entries: Arc<Mutex<HashMap<String, Entry>>>,
+  ttl: Duration,
+  max_entries: usize,
+}
+
+impl std::fmt::Debug for DnsCache {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("DnsCache")
+      .field("hosts", &self.len())
+      .field("ttl", &self.ttl)
+      .field("max_entries", &self.max_entries)
+      .finish()
+  }
+}
+
+impl PartialEq for DnsCache {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.entries, &other.entries)
+  }
+}
+
+impl DnsCache {
+  pub(crate) fn new(ttl: Duration, max_entries: usize) -> Self {
+    DnsCache {
+      entries: Arc::new(Mutex::new(HashMap::new())),
+      ttl,
+      max_entries,
+    }
+  }
+  /// 解析 `host`，命中且没过期时直接用缓存的地址
+  pub(crate) fn lookup(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    // IP 地址不用查询
+    if let Ok(ip) = host.parse::<IpAddr>() {
+      return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let key = host.to_ascii_lowercase();
+    let now = Instant::now();
+    if let Ok(entries) = self.entries.lock() {
+      if let Some((expires, ips)) = entries.get(&key) {
+        if *expires > now {
+          return Ok(ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect());
+        }
+      }
+    }
+    let addrs: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+    if self.max_entries > 0 && !addrs.is_empty() {
+      if let Ok(mut entries) = self.entries.lock() {
+        entries.retain(|_, (expires, _)| *expires > now);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+          let oldest = entries
+            .iter()
+            .min_by_key(|(_, (expires, _))| *expires)
+            .map(|(host, _)| host.clone());
+          if let Some(oldest) = oldest {
+            entries.remove(&oldest);
+          }
+        }
+        let ips = addrs.iter().map(SocketAddr::ip).collect();
+        entries.insert(key, (now + self.ttl, ips));
+      }
+    }
+    Ok(addrs)
+  }
+  pub(crate) fn clear(&self) {
+    if let Ok(mut entries) = self.entries.lock() {
+      entries.clear();
+    }
+  }
+  pub(crate) fn len(&self) -> usize {
+    self.entries.lock().map_or(0, |entries| entries.len())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::DnsCache;
+  use std::time::Duration;
+
+  #[test]
+  fn cache_and_expire() {
+    let cache = DnsCache::new(Duration::from_secs(60), 1);
+    let addrs = cache.lookup("LocalHost", 80).unwrap();
+    assert!(addrs.iter().all(|addr| addr.ip().is_loopback() && addr.port() == 80));
+    assert_eq!(cache.len(), 1);
+    // 命中缓存时端口按这次的请求
+    let cached = cache.lookup("localhost", 8080).unwrap();
+    assert_eq!(cached.len(), addrs.len());
+    assert!(cached.iter().all(|addr| addr.port() == 8080));
+    cache.lookup("127.0.0.1", 80).unwrap();
+    assert_eq!(cache.len(), 1);
+    cache.clear();
+    assert_eq!(cache.len(), 0);
+    // 有效期为 0 时每次都重新解析
+    let cache = DnsCache::new(Duration::ZERO, 8);
+    cache.lookup("localhost", 80).unwrap();
+    let entries = cache.entries.lock().unwrap();
+    assert!(entries.values().all(|(expires, _)| *expires <= std::time::Instant::now()));
+  }
+}
@@ -0,0 +1,337 @@
+use crate::errors::{new_io_error, Result};
+use crate::Client;
+use http::header::{HeaderMap, HeaderValue};
+use http::StatusCode;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Downloads a URL to a file over flaky links, resuming with `Range`
+/// requests where the transfer stopped.
+///
+/// An existing file at the path is taken as the start of the content and
+/// only the rest is requested with `Range: bytes=N-`. A connection that
+/// breaks in the middle of the body keeps what was received, and the next
+/// attempt continues from there. When the server answers a range request
+/// with the whole content, because it does not support ranges or the
+/// content changed, the file is written again from the start. The length of
+/// the file is checked against `Content-Range` or `Content-Length` at the
+/// end.
+///
+/// With [`Download::segments`] a new file is fetched in parallel ranges when
+/// the server announces `Accept-Ranges: bytes` and the length of the content.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// let client = slinger::Client::new();
+/// let length = client
+///   .download("https://example.com/big.iso", "big.iso")
+///   .segments(4)
+///   .attempts(10)
+///   .run()?;
+/// println!("{} bytes on disk", length);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use = "Download does nothing until you 'run' it"]
+pub struct Download {
+  client: Client,
+  uri: Result<http::Uri>,
+  path: PathBuf,
+  segments: usize,
+  attempts: usize,
+}
+
+impl Download {
+  pub(crate) fn new(client: Client, uri: Result<http::Uri>, path: PathBuf) -> Download {
+    Download {
+      client,
+      uri,
+      path,
+      segments: 1,
+      attempts: 3,
+    }
+  }
+  /// Fetch a new file in up to `count` ranges at the same time, each on its
+  /// own connection.
+  ///
+  /// Only used when a `HEAD` request shows the server supports ranges and
+  /// the length of the content, and the file does not exist yet. Default is
+  /// `1`.
+  pub fn segments(mut self, count: usize) -> Download {
+    self.segments = count.max(1);
+    self
+  }
+  /// Give up after `count` failed requests for the file, or for each segment.
+  ///
+  /// Default is `3`.
+  pub fn attempts(mut self, count: usize) -> Download {
+    self.attempts = count.max(1);
+    self
+  }
+  /// Download the file and return its length.
+  ///
+  /// # Errors
+  ///
+  /// Fails when the URL is invalid, when the server answers with a status
+  /// that is not `200`, `206` or `416`, when every attempt failed, or when
+  /// the length of the file does not match the one announced by the server.
+  pub fn run(self) -> Result<u64> {
+    let uri = match &self.uri {
+      Ok(uri) => uri.clone(),
+      Err(_) => return self.uri.map(|_| 0),
+    };
+    let have = std::fs::metadata(&self.path).map_or(0, |m| m.len());
+    let segmented = match self.segments > 1 && have == 0 {
+      true => self.probe(&uri)?,
+      false => None,
+    };
+    let total = match segmented {
+      Some((total, validator)) => self.run_segments(&uri, total, validator)?,
+      None => self.fetch(&uri, 0, have, None, None)?,
+    };
+    let length = std::fs::metadata(&self.path)?.len();
+    match total {
+      Some(total) if total != length => Err(new_io_error(
+        ErrorKind::InvalidData,
+        &format!("downloaded {} bytes, expected {}", length, total),
+      )),
+      _ => Ok(length),
+    }
+  }
+  /// 用 HEAD 请求确认服务器支持 Range 并取得内容长度和校验值
+  fn probe(&self, uri: &http::Uri) -> Result<Option<(u64, Option<HeaderValue>)>> {
+    let response = self.client.head(uri.clone()).send()?;
+    let headers = response.headers();
+    let accept_ranges = headers
+      .get(http::header::ACCEPT_RANGES)
+      .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"bytes"));
+    let total = headers
+      .get(http::header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.trim().parse::<u64>().ok());
+    match (response.status_code(), accept_ranges, total) {
+      (StatusCode::OK, true, Some(total)) if total > 0 => Ok(Some((total, validator(headers)))),
+      _ => Ok(None),
+    }
+  }
+  fn run_segments(
+    &self,
+    uri: &http::Uri,
+    total: u64,
+    validator: Option<HeaderValue>,
+  ) -> Result<Option<u64>> {
+    File::create(&self.path)?.set_len(total)?;
+    let count = (self.segments as u64).min(total);
+    let size = total.div_ceil(count);
+    let results: Vec<Result<Option<u64>>> = std::thread::scope(|scope| {
+      let handles: Vec<_> = (0..count)
+        .map(|index| {
+          let start = index * size;
+          let end = (start + size).min(total);
+          let validator = validator.clone();
+          scope.spawn(move || self.fetch(uri, index, start, Some(end), validator))
+        })
+        .collect();
+      handles
+        .into_iter()
+        .map(|handle| {
+          handle
+            .join()
+            .unwrap_or_else(|_| Err(new_io_error(ErrorKind::Other, "download segment panicked")))
+        })
+        .collect()
+    });
+    for result in results {
+      result?;
+    }
+    Ok(Some(total))
+  }
+  /// 下载 `[start, end)` 写到文件的 `start` 处，`end` 为空时一直到内容结束；返回服务器给出的总长度
+  fn fetch(
+    &self,
+    uri: &http::Uri,
+    index: u64,
+    start: u64,
+    end: Option<u64>,
+    validator: Option<HeaderValue>,
+  ) -> Result<Option<u64>> {
+    let mut part = self.path.clone().into_os_string();
+    part.push(format!(".part{}", index));
+    let part = PathBuf::from(part);
+    let result = self.fetch_to(uri, &part, start, end, validator);
+    let _ = std::fs::remove_file(&part);
+    result
+  }
+  fn fetch_to(
+    &self,
+    uri: &http::Uri,
+    part: &Path,
+    mut pos: u64,
+    end: Option<u64>,
+    mut validator: Option<HeaderValue>,
+  ) -> Result<Option<u64>> {
+    let mut total = None;
+    let mut failures = 0;
+    // 确认服务器支持 Range 之前，中断的范围请求收到的可能是从头开始的内容
+    let mut ranged = end.is_some();
+    loop {
+      let range = pos > 0 || end.is_some();
+      let mut request = self.client.get(uri.clone()).download_to(part);
+      if range {
+        let last = end.map(|end| (end - 1).to_string()).unwrap_or_default();
+        request = request.header(http::header::RANGE, format!("bytes={}-{}", pos, last));
+        if let Some(validator) = &validator {
+          request = request.header(http::header::IF_RANGE, validator.clone());
+        }
+      }
+      let response = match request.send() {
+        Ok(response) => response,
+        Err(err) => {
+          // 连接断开前收到的部分已经写进了临时文件
+          if !range || ranged {
+            pos += copy_part(part, &self.path, pos)?;
+          } else {
+            let _ = std::fs::remove_file(part);
+          }
+          failures += 1;
+          if failures >= self.attempts {
+            return Err(err);
+          }
+          continue;
+        }
+      };
+      let headers = response.headers();
+      if validator.is_none() {
+        validator = self::validator(headers);
+      }
+      match response.status_code() {
+        StatusCode::PARTIAL_CONTENT => {
+          let (start, length) = content_range(headers).ok_or_else(|| {
+            new_io_error(ErrorKind::InvalidData, "invalid Content-Range in partial response")
+          })?;
+          if start != Some(pos) {
+            return Err(new_io_error(
+              ErrorKind::InvalidData,
+              &format!("Content-Range does not start at byte {}", pos),
+            ));
+          }
+          ranged = true;
+          total = length.or(total);
+          let received = copy_part(part, &self.path, pos)?;
+          pos += received;
+          if end.or(total).is_none_or(|end| pos >= end) {
+            return Ok(total);
+          }
+          // 服务器给的范围比请求的短，继续请求剩下的
+          if received == 0 {
+            failures += 1;
+          }
+        }
+        StatusCode::OK if end.is_none() => {
+          // 服务器不支持 Range 或内容已经变了，整个文件重新写
+          let length = copy_part(part, &self.path, 0)?;
+          OpenOptions::new().write(true).open(&self.path)?.set_len(length)?;
+          return Ok(Some(length));
+        }
+        StatusCode::OK => {
+          return Err(new_io_error(
+            ErrorKind::InvalidData,
+            "server ignored the Range of a download segment",
+          ));
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE if end.is_none() => {
+          let length = content_range(headers).and_then(|(_, length)| length);
+          if length == Some(pos) {
+            return Ok(length);
+          }
+          // 本地文件比服务器上的长，从头下载
+          OpenOptions::new().write(true).open(&self.path)?.set_len(0)?;
+          pos = 0;
+          ranged = false;
+          failures += 1;
+        }
+        status => {
+          return Err(new_io_error(
+            ErrorKind::Other,
+            &format!("download of {} failed with status {}", uri, status),
+          ));
+        }
+      }
+      if failures >= self.attempts {
+        return Err(new_io_error(
+          ErrorKind::Other,
+          &format!("download of {} did not complete", uri),
+        ));
+      }
+    }
+  }
+}
+
+/// 重新请求时用于 If-Range 的校验值
+fn validator(headers: &HeaderMap) -> Option<HeaderValue> {
+  headers
+    .get(http::header::ETAG)
+    .or_else(|| headers.get(http::header::LAST_MODIFIED))
+    .cloned()
+}
+
+/// 解析 `Content-Range: bytes 0-99/1000` 或 `bytes */1000`，返回起始位置和总长度
+fn content_range(headers: &HeaderMap) -> Option<(Option<u64>, Option<u64>)> {
+  let value = headers.get(http::header::CONTENT_RANGE)?.to_str().ok()?;
+  let (range, length) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+  let start = range.split_once('-').and_then(|(start, _)| start.trim().parse().ok());
+  Some((start, length.trim().parse().ok()))
+}
+
+/// 把临时文件的内容写到目标文件的 `offset` 处并删除临时文件，返回写入的字节数
+fn copy_part(part: &Path, path: &Path, offset: u64) -> Result<u64> {
+  let mut source = match File::open(part) {
+    Ok(source) => source,
+    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+    Err(err) => return Err(err.into()),
+  };
+  let mut target = OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+  target.seek(SeekFrom::Start(offset))?;
+  let copied = std::io::copy(&mut source, &mut target)?;
+  drop(source);
+  std::fs::remove_file(part)?;
+  Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::MockTransport;
+
+  #[test]
+  fn resume_download() {
+    let path = std::env::temp_dir().join(format!("slinger-download-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    // 第一次连接在 5 个字节后断开，第二次从断开的地方继续
+    let mock = MockTransport::new()
+      .respond("HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello")
+      .respond(concat!(
+        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-10/11\r\n",
+        "Content-Length: 6\r\nConnection: close\r\n\r\n world"
+      ));
+    let client = crate::Client::builder().transport(mock.clone()).build().unwrap();
+    let length = client.download("http://example.com/f", &path).run().unwrap();
+    assert_eq!(length, 11);
+    assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+    drop(client);
+    let requests = mock.requests();
+    let resumed = String::from_utf8_lossy(&requests[1]).to_lowercase();
+    assert!(resumed.contains("\r\nrange: bytes=5-\r\n"));
+    // 文件已经完整时服务器返回 416
+    let mock = MockTransport::new().respond(concat!(
+      "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */11\r\n",
+      "Content-Length: 0\r\n\r\n"
+    ));
+    let client = crate::Client::builder().transport(mock).build().unwrap();
+    assert_eq!(client.download("http://example.com/f", &path).run().unwrap(), 11);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,161 @@
+use crate::redirect::LocationPolicy;
+use crate::schedule::Scheduled;
+use crate::Client;
+use http::{HeaderMap, Uri};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A resource announced with a `Link` header of `103 Early Hints`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EarlyHint {
+  /// The link target, resolved against the request URL
+  pub uri: Uri,
+  /// The `rel` parameter, lowercased, such as `preconnect` or `preload`
+  pub rel: String,
+  /// The `as` parameter of a preload, such as `style` or `script`
+  pub destination: Option<String>,
+}
+
+/// What the client does with an [`EarlyHint`], decided by the policy set with
+/// [`ClientBuilder::early_hints`](crate::ClientBuilder::early_hints).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EarlyHintAction {
+  /// Nothing, the hint is only kept in [`Response::informational`](crate::Response::informational).
+  #[default]
+  Ignore,
+  /// Open a connection to the origin of the link in the background. The
+  /// next request of the client to that origin uses it instead of connecting.
+  Preconnect,
+  /// Send a `GET` for the link in the background, its handle is returned by
+  /// [`Response::take_preloads`](crate::Response::take_preloads).
+  Preload,
+}
+
+/// 客户端上配置的处理策略
+#[derive(Clone)]
+pub(crate) struct EarlyHintPolicy(pub(crate) Arc<dyn Fn(&EarlyHint) -> EarlyHintAction + Send + Sync>);
+
+impl std::fmt::Debug for EarlyHintPolicy {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("EarlyHintPolicy")
+  }
+}
+
+/// 收到 103 时在读取最终响应之前调用，由 `ResponseConfig` 携带
+#[derive(Clone)]
+pub(crate) struct EarlyHintHook(pub(crate) Arc<dyn Fn(&HeaderMap) + Send + Sync>);
+
+impl std::fmt::Debug for EarlyHintHook {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("EarlyHintHook")
+  }
+}
+
+/// 预加载请求的句柄，放在响应的 extensions 中
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Preloads(pub(crate) Arc<Mutex<Vec<Scheduled>>>);
+
+/// 按策略处理一个 103 响应里的所有链接
+pub(crate) fn hook(client: Client, policy: EarlyHintPolicy, base: Uri, preloads: Preloads) -> EarlyHintHook {
+  EarlyHintHook(Arc::new(move |headers: &HeaderMap| {
+    for hint in parse(headers, &base, client.location_policy()) {
+      match (policy.0)(&hint) {
+        EarlyHintAction::Ignore => {}
+        EarlyHintAction::Preconnect => client.preconnect(hint.uri),
+        EarlyHintAction::Preload => {
+          let request = client.get(hint.uri).build();
+          if let (Ok(request), Ok(mut preloads)) = (request, preloads.0.lock()) {
+            preloads.push(client.send_at(request, Instant::now()));
+          }
+        }
+      }
+    }
+  }))
+}
+
+/// 解析所有 `Link` 请求头，无法解析的链接直接跳过
+fn parse(headers: &HeaderMap, base: &Uri, policy: LocationPolicy) -> Vec<EarlyHint> {
+  let mut hints = Vec::new();
+  for value in headers.get_all(http::header::LINK) {
+    let Ok(value) = value.to_str() else {
+      continue;
+    };
+    for (target, params) in split_links(value) {
+      let Some(uri) = policy.resolve(target.as_bytes(), base) else {
+        continue;
+      };
+      let param = |name: &str| {
+        params
+          .iter()
+          .find(|(key, _)| key.eq_ignore_ascii_case(name))
+          .map(|(_, value)| value.clone())
+      };
+      hints.push(EarlyHint {
+        uri,
+        rel: param("rel").unwrap_or_default().to_ascii_lowercase(),
+        destination: param("as"),
+      });
+    }
+  }
+  hints
+}
+
+/// 拆分 `<uri>; rel=preload; as=style, <uri>; rel=preconnect`，参数值可以带引号
+fn split_links(value: &str) -> Vec<(&str, Vec<(String, String)>)> {
+  let mut links = Vec::new();
+  let mut rest = value;
+  while let Some(start) = rest.find('<') {
+    let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+      break;
+    };
+    let target = rest[start + 1..end].trim();
+    rest = &rest[end + 1..];
+    // 参数一直到引号之外的下一个逗号
+    let mut quoted = false;
+    let stop = rest
+      .char_indices()
+      .find(|(_, c)| {
+        if *c == '"' {
+          quoted = !quoted;
+        }
+        *c == ',' && !quoted
+      })
+      .map_or(rest.len(), |(i, _)| i);
+    let params = rest[..stop]
+      .split(';')
+      .filter_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        Some((key.trim().to_string(), value.trim().trim_matches('"').to_string()))
+      })
+      .collect();
+    links.push((target, params));
+    rest = &rest[stop..];
+  }
+  links
+}
+
+#[cfg(test)]
+mod tests {
+  use super::parse;
+  use crate::redirect::LocationPolicy;
+  use http::{HeaderMap, HeaderValue};
+
+  #[test]
+  fn parse_link_headers() {
+    let mut headers = HeaderMap::new();
+    headers.append(
+      http::header::LINK,
+      HeaderValue::from_static("</style.css>; rel=preload; as=style, <https://cdn.example>; rel=\"PreConnect\""),
+    );
+    headers.append(http::header::LINK, HeaderValue::from_static("<app.js>;rel=preload;as=script"));
+    let base = "https://example.com/dir/page".parse().unwrap();
+    let hints = parse(&headers, &base, LocationPolicy::default());
+    assert_eq!(hints.len(), 3);
+    assert_eq!(hints[0].uri, "https://example.com/style.css");
+    assert_eq!(hints[0].destination.as_deref(), Some("style"));
+    assert_eq!(hints[1].uri, "https://cdn.example/");
+    assert_eq!(hints[1].rel, "preconnect");
+    assert_eq!(hints[2].uri, "https://example.com/dir/app.js");
+    assert_eq!(hints[2].rel, "preload");
+  }
+}
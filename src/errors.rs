@@ -2,7 +2,6 @@
 
 #[cfg(feature = "tls")]
 use socket2::Socket;
-use std::io::ErrorKind;
 use std::num::ParseIntError;
 use thiserror::Error as ThisError;
 
@@ -15,15 +14,16 @@ pub enum Error {
   #[cfg(feature = "tls")]
   /// HandshakeError
   #[error(transparent)]
-  TlsHandshake(#[from] native_tls::HandshakeError<Socket>),
-  #[error(transparent)]
-  #[cfg(feature = "tls")]
-  /// native_tls Error
-  NativeTls(#[from] native_tls::Error),
+  TlsHandshake(#[from] openssl::ssl::HandshakeError<Socket>),
   #[cfg(feature = "tls")]
   /// openssl ErrorStack
   #[error(transparent)]
   OpenSSl(#[from] openssl::error::ErrorStack),
+  #[cfg(feature = "tls")]
+  /// No certificate of the chain matched the pins of the host set with
+  /// `ConnectorBuilder::pin_sha256`, with the host
+  #[error("certificate pin mismatch for {0}")]
+  CertificatePin(String),
   /// Error
   #[error(transparent)]
   IO(#[from] std::io::Error),
@@ -36,11 +36,202 @@ pub enum Error {
   /// Proxy ReplyError
   #[error(transparent)]
   ReplyError(#[from] ReplyError),
+  /// A response exceeded a limit set on the `ClientBuilder`, with the limit
+  #[error("response {0} limit of {1} exceeded")]
+  ResponseLimit(ResponseLimit, u64),
+  /// The host name could not be resolved, with the host
+  #[error("failed to resolve {0}: {1}")]
+  Dns(String, #[source] std::io::Error),
+  /// The redirect policy gave up with
+  /// [`Attempt::too_many_redirects`](crate::redirect::Attempt::too_many_redirects),
+  /// with the number of redirects followed
+  #[error("too many redirects ({0})")]
+  TooManyRedirects(usize),
+  /// A redirect chain was given up because it loops or leaves `https`, with
+  /// the reason and every URL of the chain, the one not followed last
+  #[error("{0}: {}", chain(.1))]
+  Redirect(RedirectError, Vec<http::Uri>),
+  /// An error of a request, with the URL it was sent to
+  #[error("{url}: {source}")]
+  WithUrl {
+    /// The URL of the request, the hop that failed in a redirect chain
+    url: http::Uri,
+    /// The error itself
+    source: Box<Error>,
+  },
   /// Unknown Error
   #[error("other")]
   Other(String),
 }
 
+/// The category of an [`Error`], see [`Error::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+  /// The host name could not be resolved
+  Dns,
+  /// The connection could not be established or was lost
+  Connect,
+  /// The TLS handshake failed, certificate and pin checks included
+  TlsHandshake,
+  /// A timeout or the deadline of the request expired
+  Timeout,
+  /// The redirect policy gave up on a redirect chain
+  TooManyRedirects,
+  /// The redirect chain repeats a redirect, see
+  /// [`ClientBuilder::detect_redirect_loops`](crate::ClientBuilder::detect_redirect_loops)
+  RedirectLoop,
+  /// A redirect from `https` to `http` was refused
+  InsecureRedirect,
+  /// The response body exceeded a size or decompression limit
+  BodyTooLarge,
+  /// The response headers exceeded a size or count limit
+  HeadersTooLarge,
+  /// The proxy refused or failed to reach the target
+  Proxy,
+  /// Invalid data, a malformed URL, header or response
+  Parse,
+  /// Any other I/O error
+  Io,
+  /// Anything else
+  Other,
+}
+
+impl Error {
+  /// The category of this error, so callers can tell a refused connection
+  /// from a TLS failure without matching messages.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// use slinger::ErrorKind;
+  ///
+  /// match slinger::get("https://example.com") {
+  ///   Err(err) if err.kind() == ErrorKind::Connect => println!("down: {:?}", err.url()),
+  ///   Err(err) if err.kind() == ErrorKind::TlsHandshake => println!("bad tls: {}", err),
+  ///   other => println!("{:?}", other.map(|r| r.status_code())),
+  /// }
+  /// ```
+  pub fn kind(&self) -> ErrorKind {
+    use std::io::ErrorKind as Io;
+    match self.root() {
+      #[cfg(feature = "tls")]
+      Error::TlsHandshake(_) | Error::OpenSSl(_) | Error::CertificatePin(_) => {
+        ErrorKind::TlsHandshake
+      }
+      Error::IO(err) => match err.kind() {
+        Io::TimedOut | Io::WouldBlock => ErrorKind::Timeout,
+        Io::ConnectionRefused
+        | Io::ConnectionReset
+        | Io::ConnectionAborted
+        | Io::NotConnected
+        | Io::AddrInUse
+        | Io::AddrNotAvailable
+        | Io::HostUnreachable
+        | Io::NetworkUnreachable
+        | Io::BrokenPipe => ErrorKind::Connect,
+        Io::InvalidData | Io::InvalidInput => ErrorKind::Parse,
+        _ => ErrorKind::Io,
+      },
+      Error::Http(_) | Error::IntError(_) => ErrorKind::Parse,
+      Error::ReplyError(_) => ErrorKind::Proxy,
+      Error::ResponseLimit(ResponseLimit::HeaderSize | ResponseLimit::HeaderCount, _) => {
+        ErrorKind::HeadersTooLarge
+      }
+      Error::ResponseLimit(..) => ErrorKind::BodyTooLarge,
+      Error::Dns(..) => ErrorKind::Dns,
+      Error::TooManyRedirects(_) => ErrorKind::TooManyRedirects,
+      Error::Redirect(RedirectError::Loop, _) => ErrorKind::RedirectLoop,
+      Error::Redirect(RedirectError::Downgrade, _) => ErrorKind::InsecureRedirect,
+      Error::WithUrl { .. } | Error::Other(_) => ErrorKind::Other,
+    }
+  }
+  /// The URL of the request that failed, when known.
+  pub fn url(&self) -> Option<&http::Uri> {
+    match self {
+      Error::WithUrl { url, .. } => Some(url),
+      _ => None,
+    }
+  }
+  /// Returns true if the request timed out.
+  pub fn is_timeout(&self) -> bool {
+    self.kind() == ErrorKind::Timeout
+  }
+  /// Returns true if the connection could not be established or was lost.
+  pub fn is_connect(&self) -> bool {
+    self.kind() == ErrorKind::Connect
+  }
+  /// 去掉 URL 上下文后的错误
+  pub(crate) fn root(&self) -> &Error {
+    match self {
+      Error::WithUrl { source, .. } => source.root(),
+      err => err,
+    }
+  }
+  /// 加上请求的 URL，已经有 URL 的错误保持不变
+  pub(crate) fn with_url(self, url: &http::Uri) -> Error {
+    match self {
+      Error::WithUrl { .. } => self,
+      err => Error::WithUrl {
+        url: url.clone(),
+        source: Box::new(err),
+      },
+    }
+  }
+}
+
+/// Which response limit was exceeded, see [`Error::ResponseLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseLimit {
+  /// `ClientBuilder::max_response_size`
+  BodySize,
+  /// `ClientBuilder::max_header_size`
+  HeaderSize,
+  /// `ClientBuilder::max_header_count`
+  HeaderCount,
+  /// `ClientBuilder::max_decompressed_size`
+  DecompressedSize,
+  /// `ClientBuilder::max_compression_ratio`
+  CompressionRatio,
+}
+
+impl std::fmt::Display for ResponseLimit {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      ResponseLimit::BodySize => "body size",
+      ResponseLimit::HeaderSize => "header size",
+      ResponseLimit::HeaderCount => "header count",
+      ResponseLimit::DecompressedSize => "decompressed size",
+      ResponseLimit::CompressionRatio => "compression ratio",
+    })
+  }
+}
+
+/// Why a redirect chain was given up, see [`Error::Redirect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectError {
+  /// The chain repeats a redirect, see
+  /// [`ClientBuilder::detect_redirect_loops`](crate::ClientBuilder::detect_redirect_loops)
+  Loop,
+  /// The chain goes from `https` to `http`, see
+  /// [`ClientBuilder::deny_redirect_downgrade`](crate::ClientBuilder::deny_redirect_downgrade)
+  Downgrade,
+}
+
+impl std::fmt::Display for RedirectError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      RedirectError::Loop => "redirect loop",
+      RedirectError::Downgrade => "redirect from https to http",
+    })
+  }
+}
+
+/// 跳转链写成 `a -> b -> c`
+fn chain(uris: &[http::Uri]) -> String {
+  uris.iter().map(|uri| uri.to_string()).collect::<Vec<_>>().join(" -> ")
+}
+
 #[derive(ThisError, Debug)]
 pub enum ReplyError {
   #[error("Succeeded")]
@@ -76,6 +267,39 @@ impl From<http::header::InvalidHeaderValue> for Error {
   }
 }
 
-pub(crate) fn new_io_error(error_kind: ErrorKind, msg: &str) -> Error {
+pub(crate) fn new_io_error(error_kind: std::io::ErrorKind, msg: &str) -> Error {
   Error::IO(std::io::Error::new(error_kind, msg))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{new_io_error, Error, ErrorKind, RedirectError, ResponseLimit};
+
+  #[test]
+  fn error_kinds() {
+    let uri: http::Uri = "http://example.com/a".parse().unwrap();
+    let err = new_io_error(std::io::ErrorKind::ConnectionRefused, "refused").with_url(&uri);
+    assert_eq!(err.kind(), ErrorKind::Connect);
+    assert!(err.is_connect());
+    assert_eq!(err.url(), Some(&uri));
+    assert_eq!(err.to_string(), "http://example.com/a: refused");
+    assert!(std::error::Error::source(&err).is_some());
+    let other: http::Uri = "http://example.com/b".parse().unwrap();
+    assert_eq!(err.with_url(&other).url(), Some(&uri));
+    let err = new_io_error(std::io::ErrorKind::TimedOut, "deadline exceeded");
+    assert!(err.is_timeout());
+    assert_eq!(err.url(), None);
+    let err = Error::Dns("nope.invalid".into(), std::io::ErrorKind::Other.into());
+    assert_eq!(err.kind(), ErrorKind::Dns);
+    assert_eq!(Error::ResponseLimit(ResponseLimit::BodySize, 1).kind(), ErrorKind::BodyTooLarge);
+    assert_eq!(Error::TooManyRedirects(10).kind(), ErrorKind::TooManyRedirects);
+    assert_eq!(Error::Redirect(RedirectError::Loop, Vec::new()).kind(), ErrorKind::RedirectLoop);
+    let chain = vec!["https://a.example/".parse().unwrap(), "http://a.example/".parse().unwrap()];
+    let err = Error::Redirect(RedirectError::Downgrade, chain);
+    assert_eq!(err.kind(), ErrorKind::InsecureRedirect);
+    assert_eq!(
+      err.to_string(),
+      "redirect from https to http: https://a.example/ -> http://a.example/"
+    );
+  }
+}
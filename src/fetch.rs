@@ -0,0 +1,83 @@
+use crate::errors::Result;
+use crate::{Client, Response};
+use http::{HeaderMap, StatusCode, Uri};
+
+/// The outcome of [`Client::fetch_if_small`].
+#[derive(Debug)]
+pub enum SmallFetch {
+  /// The body was within the limit and has been downloaded
+  Fetched(Response),
+  /// The body was not downloaded
+  Skipped {
+    /// The size advertised by the server, `None` when it could not be learned
+    size: Option<u64>,
+    /// The `HEAD` or ranged `GET` response the size was read from
+    probe: Response,
+  },
+}
+
+impl SmallFetch {
+  /// The downloaded response, if the body was within the limit.
+  pub fn fetched(self) -> Option<Response> {
+    match self {
+      SmallFetch::Fetched(response) => Some(response),
+      SmallFetch::Skipped { .. } => None,
+    }
+  }
+}
+
+pub(crate) fn run(client: &Client, uri: Uri, max_bytes: u64) -> Result<SmallFetch> {
+  let head = client.head(uri.clone()).send()?;
+  let (size, probe) = match head.content_length() {
+    Some(size) if head.status_code().is_success() => (Some(size), head),
+    // HEAD 不被支持或者没有给出长度时，用只要一个字节的范围请求问出总大小
+    _ => {
+      let ranged = client
+        .get(uri.clone())
+        .header(http::header::RANGE, "bytes=0-0")
+        .send()?;
+      match ranged.status_code() {
+        StatusCode::PARTIAL_CONTENT => (content_range_total(ranged.headers()), ranged),
+        // 服务器忽略了 Range，完整的响应已经下载下来了
+        status if status.is_success() => {
+          let size = ranged
+            .content_length()
+            .or_else(|| ranged.body().as_ref().map(|b| b.len() as u64));
+          return Ok(match size {
+            Some(size) if size <= max_bytes => SmallFetch::Fetched(ranged),
+            _ => SmallFetch::Skipped { size, probe: ranged },
+          });
+        }
+        _ => (None, ranged),
+      }
+    }
+  };
+  match size {
+    Some(size) if size <= max_bytes => Ok(SmallFetch::Fetched(client.get(uri).send()?)),
+    _ => Ok(SmallFetch::Skipped { size, probe }),
+  }
+}
+
+/// 解析 `Content-Range: bytes 0-0/1234` 中的总大小，`*` 表示未知
+fn content_range_total(headers: &HeaderMap) -> Option<u64> {
+  let value = headers.get(http::header::CONTENT_RANGE)?.to_str().ok()?;
+  let (_, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+  total.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::content_range_total;
+  use http::{HeaderMap, HeaderValue};
+
+  #[test]
+  fn parse_content_range_total() {
+    let mut headers = HeaderMap::new();
+    headers.insert(http::header::CONTENT_RANGE, HeaderValue::from_static("bytes 0-0/1234"));
+    assert_eq!(content_range_total(&headers), Some(1234));
+    headers.insert(http::header::CONTENT_RANGE, HeaderValue::from_static("bytes 0-0/*"));
+    assert_eq!(content_range_total(&headers), None);
+    headers.clear();
+    assert_eq!(content_range_total(&headers), None);
+  }
+}
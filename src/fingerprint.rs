@@ -0,0 +1,157 @@
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+/// 找到 HTML `<title>` 的内容，返回原始字节
+pub(crate) fn title(body: &[u8]) -> Option<&[u8]> {
+  let start = find_ignore_case(body, b"<title")?;
+  let rest = &body[start + b"<title".len()..];
+  // `<titles>` 之类的不是标题标签
+  if !matches!(rest.first(), Some(b'>' | b' ' | b'\t' | b'\r' | b'\n' | b'/')) {
+    return None;
+  }
+  let open = rest.iter().position(|&b| b == b'>')?;
+  let rest = &rest[open + 1..];
+  let end = find_ignore_case(rest, b"</title").unwrap_or(rest.len());
+  Some(&rest[..end])
+}
+
+/// 合并空白并解码常见的 HTML 实体
+pub(crate) fn clean_title(raw: &str) -> String {
+  let text = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text.as_str();
+  while let Some(amp) = rest.find('&') {
+    out.push_str(&rest[..amp]);
+    rest = &rest[amp..];
+    let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+      let c = match &rest[1..end + 1] {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        entity => {
+          let code = match entity.strip_prefix('#')? {
+            hex if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok()?,
+            dec => dec.parse().ok()?,
+          };
+          char::from_u32(code)?
+        }
+      };
+      Some((c, end + 2))
+    });
+    match decoded {
+      Some((c, len)) => {
+        out.push(c);
+        rest = &rest[len..];
+      }
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out.trim().to_string()
+}
+
+/// Shodan 的 favicon 哈希：按 76 列换行的 base64 的 MurmurHash3
+pub(crate) fn favicon_hash(body: &[u8]) -> i32 {
+  let encoded = BASE64_STANDARD.encode(body);
+  let mut lines = Vec::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+  for line in encoded.as_bytes().chunks(76) {
+    lines.extend_from_slice(line);
+    lines.push(b'\n');
+  }
+  mmh3(&lines, 0) as i32
+}
+
+/// 小写十六进制的 SHA-256
+pub(crate) fn body_hash(body: &[u8]) -> String {
+  Sha256::digest(body)
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+/// 空白分隔的单词数
+pub(crate) fn word_count(body: &[u8]) -> usize {
+  body
+    .split(|b| b.is_ascii_whitespace())
+    .filter(|word| !word.is_empty())
+    .count()
+}
+
+/// 行数，最后一行没有换行也算一行
+pub(crate) fn line_count(body: &[u8]) -> usize {
+  let newlines = body.iter().filter(|&&b| b == b'\n').count();
+  match body.last() {
+    None | Some(b'\n') => newlines,
+    Some(_) => newlines + 1,
+  }
+}
+
+/// MurmurHash3 x86 32 位
+fn mmh3(data: &[u8], seed: u32) -> u32 {
+  const C1: u32 = 0xcc9e_2d51;
+  const C2: u32 = 0x1b87_3593;
+  let mut h = seed;
+  let mut blocks = data.chunks_exact(4);
+  for block in blocks.by_ref() {
+    let mut k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+    k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    h ^= k;
+    h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+  }
+  let tail = blocks.remainder();
+  if !tail.is_empty() {
+    let mut k = 0u32;
+    for (i, &b) in tail.iter().enumerate() {
+      k |= (b as u32) << (8 * i);
+    }
+    h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+  }
+  h ^= data.len() as u32;
+  h ^= h >> 16;
+  h = h.wrapping_mul(0x85eb_ca6b);
+  h ^= h >> 13;
+  h = h.wrapping_mul(0xc2b2_ae35);
+  h ^ (h >> 16)
+}
+
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{clean_title, mmh3};
+  use crate::Response;
+
+  #[test]
+  fn fingerprints() {
+    assert_eq!(mmh3(b"", 0), 0);
+    assert_eq!(mmh3(b"hello", 0), 0x248b_fa47);
+    assert_eq!(mmh3(b"hello world", 0), 0x5e92_8f0f);
+    let resp = Response::from_bytes(
+      b"HTTP/1.1 200 OK\r\nContent-Length: 61\r\n\r\n<html><TITLE id=t>\n  Tom &amp; Jerry&#39;s </title>\nhi</html>",
+    )
+    .unwrap();
+    assert_eq!(resp.title().as_deref(), Some("Tom & Jerry's"));
+    assert_eq!(resp.word_count(), 7);
+    assert_eq!(resp.line_count(), 3);
+    assert_eq!(
+      resp.body_hash(),
+      "69676363f92048fe9d98fae2c90f17aef704d1251d4502c89803a828b53ed662"
+    );
+    assert_eq!(clean_title("a &bogus; &#x41;"), "a &bogus; A");
+    let empty = Response::from_bytes(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+    assert_eq!(empty.title(), None);
+    assert_eq!(empty.line_count(), 0);
+    assert_eq!(empty.favicon_hash(), 0);
+  }
+}
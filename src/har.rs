@@ -0,0 +1,347 @@
+use crate::errors::Result;
+use crate::record::HTTPRecord;
+use crate::{Middleware, Next, Request, Response};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Collects every exchange of a `Client` as HTTP Archive (HAR 1.2) entries.
+///
+/// Added to a client with [`ClientBuilder::with`](crate::ClientBuilder::with),
+/// it keeps one entry per hop of every request, redirects included, with the
+/// headers as sent and received, bodies and timings. Clones share the same
+/// entries, so keep one to read the archive with [`HarRecorder::to_har`] and
+/// open it in browser devtools or any HAR viewer. Requests that fail leave no
+/// entry.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// use slinger::HarRecorder;
+///
+/// let har = HarRecorder::new();
+/// let client = slinger::Client::builder().with(har.clone()).build()?;
+/// client.get("http://httpbin.org/redirect/2").send()?;
+/// std::fs::write("scan.har", har.to_har())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HarRecorder {
+  entries: Arc<Mutex<Vec<String>>>,
+}
+
+impl HarRecorder {
+  /// An empty recorder.
+  pub fn new() -> Self {
+    HarRecorder::default()
+  }
+  /// The number of entries kept so far.
+  pub fn len(&self) -> usize {
+    self.entries.lock().map(|e| e.len()).unwrap_or_default()
+  }
+  /// Returns true if no entry was kept yet.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+  /// Drop every entry kept so far.
+  pub fn clear(&self) {
+    if let Ok(mut entries) = self.entries.lock() {
+      entries.clear();
+    }
+  }
+  /// The HAR 1.2 JSON document of every entry kept so far.
+  pub fn to_har(&self) -> String {
+    match self.entries.lock() {
+      Ok(entries) => log(&entries),
+      Err(_) => log(&[]),
+    }
+  }
+}
+
+impl Middleware for HarRecorder {
+  fn handle(&self, request: Request, next: Next<'_>) -> Result<Response> {
+    let response = next.run(request)?;
+    if let (Some(records), Ok(mut entries)) = (response.http_record(), self.entries.lock()) {
+      entries.extend(records.iter().map(entry));
+    }
+    Ok(response)
+  }
+}
+
+/// 由记录生成完整的 HAR 文档
+pub(crate) fn from_records(records: &[HTTPRecord]) -> String {
+  let entries: Vec<String> = records.iter().map(entry).collect();
+  log(&entries)
+}
+
+fn log(entries: &[String]) -> String {
+  format!(
+    r#"{{"log":{{"version":"1.2","creator":{{"name":"slinger","version":{}}},"entries":[{}]}}}}"#,
+    string(env!("CARGO_PKG_VERSION")),
+    entries.join(",")
+  )
+}
+
+fn entry(record: &HTTPRecord) -> String {
+  let response = &record.response;
+  let timing = response.timing().cloned().unwrap_or_default();
+  let mut fields = vec![
+    field(
+      "startedDateTime",
+      string(&date_time(record.started.unwrap_or(UNIX_EPOCH))),
+    ),
+    field("time", millis(timing.total())),
+    field("request", request(record)),
+    field("response", response_object(response)),
+    field("cache", "{}".to_string()),
+    field(
+      "timings",
+      object(&[
+        field("blocked", "-1".to_string()),
+        field("dns", millis(timing.dns)),
+        // HAR 的 connect 包含 TLS 握手
+        field("connect", millis(timing.connect + timing.tls)),
+        field("ssl", millis(timing.tls)),
+        field("send", "0".to_string()),
+        field("wait", millis(timing.first_byte)),
+        field("receive", millis(timing.download)),
+      ]),
+    ),
+  ];
+  if let Some(addr) = response.remote_addr() {
+    fields.push(field("serverIPAddress", string(&addr.ip().to_string())));
+  }
+  object(&fields)
+}
+
+fn request(record: &HTTPRecord) -> String {
+  let request = &record.request;
+  let body = request
+    .body()
+    .as_ref()
+    .map(|b| b.as_ref())
+    .unwrap_or_default();
+  let mut fields = vec![
+    field("method", string(request.method().as_str())),
+    field("url", string(&request.uri().to_string())),
+    field("httpVersion", string(&format!("{:?}", request.version()))),
+    field("cookies", "[]".to_string()),
+    field("headers", sent_headers(record)),
+    field("queryString", query_string(request.uri().query().unwrap_or_default())),
+    field("headersSize", "-1".to_string()),
+    field("bodySize", body.len().to_string()),
+  ];
+  if !body.is_empty() {
+    let mime = header_str(request.headers(), http::header::CONTENT_TYPE);
+    fields.push(field(
+      "postData",
+      object(&[
+        field("mimeType", string(mime)),
+        field("text", string(&String::from_utf8_lossy(body))),
+      ]),
+    ));
+  }
+  object(&fields)
+}
+
+fn response_object(response: &Response) -> String {
+  let body = response
+    .body()
+    .as_ref()
+    .map(|b| b.as_ref())
+    .unwrap_or_default();
+  let status = response.status_code();
+  let status_text = match response.reason_raw() {
+    Some(reason) => String::from_utf8_lossy(reason).into_owned(),
+    None => status.canonical_reason().unwrap_or_default().to_string(),
+  };
+  let mut content = vec![
+    field("size", body.len().to_string()),
+    field(
+      "mimeType",
+      string(header_str(response.headers(), http::header::CONTENT_TYPE)),
+    ),
+  ];
+  // 不是 UTF-8 的响应体按 HAR 的约定用 base64 保存
+  match std::str::from_utf8(body) {
+    Ok(text) => content.push(field("text", string(text))),
+    Err(_) => {
+      content.push(field("text", string(&BASE64_STANDARD.encode(body))));
+      content.push(field("encoding", string("base64")));
+    }
+  }
+  let headers: Vec<(&str, &[u8])> = response
+    .headers()
+    .iter()
+    .map(|(k, v)| (k.as_str(), v.as_bytes()))
+    .collect();
+  object(&[
+    field("status", status.as_u16().to_string()),
+    field("statusText", string(&status_text)),
+    field("httpVersion", string(&format!("{:?}", response.version()))),
+    field("cookies", "[]".to_string()),
+    field("headers", headers_array(&headers)),
+    field("content", object(&content)),
+    field(
+      "redirectURL",
+      string(header_str(response.headers(), http::header::LOCATION)),
+    ),
+    field("headersSize", "-1".to_string()),
+    field("bodySize", body.len().to_string()),
+  ])
+}
+
+/// 优先用原始请求里实际发送的请求头，包括自动添加的 Host 和 Content-Length
+fn sent_headers(record: &HTTPRecord) -> String {
+  let raw = &record.raw_request;
+  if !raw.is_empty() {
+    let headers: Vec<(&str, &[u8])> = raw
+      .split(|b| *b == b'\n')
+      .skip(1)
+      .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+      .take_while(|line| !line.is_empty())
+      .filter_map(|line| {
+        let colon = line.iter().position(|b| *b == b':')?;
+        let name = std::str::from_utf8(&line[..colon]).ok()?;
+        Some((name, line[colon + 1..].trim_ascii()))
+      })
+      .collect();
+    return headers_array(&headers);
+  }
+  let headers: Vec<(&str, &[u8])> = record
+    .request
+    .headers()
+    .iter()
+    .map(|(k, v)| (k.as_str(), v.as_bytes()))
+    .collect();
+  headers_array(&headers)
+}
+
+fn headers_array(headers: &[(&str, &[u8])]) -> String {
+  let items: Vec<String> = headers
+    .iter()
+    .map(|(name, value)| {
+      object(&[
+        field("name", string(name)),
+        field("value", string(&String::from_utf8_lossy(value))),
+      ])
+    })
+    .collect();
+  format!("[{}]", items.join(","))
+}
+
+fn query_string(query: &str) -> String {
+  let items: Vec<String> = query
+    .split('&')
+    .filter(|pair| !pair.is_empty())
+    .map(|pair| {
+      let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+      object(&[field("name", string(name)), field("value", string(value))])
+    })
+    .collect();
+  format!("[{}]", items.join(","))
+}
+
+fn header_str(headers: &http::HeaderMap, name: http::HeaderName) -> &str {
+  headers
+    .get(name)
+    .and_then(|v| v.to_str().ok())
+    .unwrap_or_default()
+}
+
+fn field(name: &str, value: String) -> String {
+  format!("{}:{}", string(name), value)
+}
+
+fn object(fields: &[String]) -> String {
+  format!("{{{}}}", fields.join(","))
+}
+
+fn millis(duration: Duration) -> String {
+  format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// JSON 字符串，转义引号、反斜杠和控制字符
+fn string(value: &str) -> String {
+  let mut out = String::with_capacity(value.len() + 2);
+  out.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+/// UTC 的 ISO 8601 时间，精确到毫秒
+fn date_time(time: SystemTime) -> String {
+  let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+  let secs = since.as_secs();
+  let days = (secs / 86400) as i64;
+  let rest = secs % 86400;
+  // 按公历从 0000-03-01 起算的纪元换算年月日
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+    year,
+    month,
+    day,
+    rest / 3600,
+    rest % 3600 / 60,
+    rest % 60,
+    since.subsec_millis()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{date_time, from_records, string};
+  use crate::record::HTTPRecord;
+  use std::time::{Duration, UNIX_EPOCH};
+
+  #[test]
+  fn har_document() {
+    assert_eq!(
+      date_time(UNIX_EPOCH + Duration::from_millis(1_709_251_199_123)),
+      "2024-02-29T23:59:59.123Z"
+    );
+    assert_eq!(string("a\"\\\u{1}"), r#""a\"\\\u0001""#);
+    let mut record = HTTPRecord::default();
+    record.request = http::Request::post("http://example.com/login?next=%2F&x")
+      .body("user=me")
+      .unwrap()
+      .into();
+    record.raw_request = "POST /login HTTP/1.1\r\nhost: example.com\r\ncontent-length: 7\r\n\r\nuser=me".into();
+    record.response = http::Response::builder()
+      .status(302)
+      .header("location", "/home")
+      .body(vec![0xff])
+      .unwrap()
+      .into();
+    let har = from_records(&[record]);
+    assert!(har.starts_with(r#"{"log":{"version":"1.2","creator":{"name":"slinger""#));
+    assert!(har.contains(r#""headers":[{"name":"host","value":"example.com"},{"name":"content-length","value":"7"}]"#));
+    assert!(har.contains(r#""queryString":[{"name":"next","value":"%2F"},{"name":"x","value":""}]"#));
+    assert!(har.contains(r#""postData":{"mimeType":"","text":"user=me"}"#));
+    assert!(har.contains(r#""content":{"size":1,"mimeType":"","text":"/w==","encoding":"base64"}"#));
+    assert!(har.contains(r#""redirectURL":"/home""#));
+  }
+}
@@ -0,0 +1,124 @@
+use crate::errors::Error;
+use crate::{Request, Response};
+use std::sync::Arc;
+use std::time::Duration;
+
+type RequestHook = Arc<dyn Fn(&Request) + Send + Sync>;
+type ResponseHook = Arc<dyn Fn(&Request, &Response, Duration) + Send + Sync>;
+type RedirectHook = Arc<dyn Fn(&Response, &http::Uri) + Send + Sync>;
+type ConnectHook = Arc<dyn Fn(&http::Uri, Duration) + Send + Sync>;
+type ErrorHook = Arc<dyn Fn(&Request, &Error, Duration) + Send + Sync>;
+
+/// 请求生命周期回调，由 `ClientBuilder::on_*` 注册
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+  request: Vec<RequestHook>,
+  response: Vec<ResponseHook>,
+  redirect: Vec<RedirectHook>,
+  connect: Vec<ConnectHook>,
+  error: Vec<ErrorHook>,
+}
+
+impl std::fmt::Debug for Hooks {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Hooks")
+      .field("request", &self.request.len())
+      .field("response", &self.response.len())
+      .field("redirect", &self.redirect.len())
+      .field("connect", &self.connect.len())
+      .field("error", &self.error.len())
+      .finish()
+  }
+}
+
+impl Hooks {
+  pub(crate) fn add_request(&mut self, hook: RequestHook) {
+    self.request.push(hook);
+  }
+  pub(crate) fn add_response(&mut self, hook: ResponseHook) {
+    self.response.push(hook);
+  }
+  pub(crate) fn add_redirect(&mut self, hook: RedirectHook) {
+    self.redirect.push(hook);
+  }
+  pub(crate) fn add_connect(&mut self, hook: ConnectHook) {
+    self.connect.push(hook);
+  }
+  pub(crate) fn add_error(&mut self, hook: ErrorHook) {
+    self.error.push(hook);
+  }
+  pub(crate) fn on_request(&self, request: &Request) {
+    self.request.iter().for_each(|hook| hook(request));
+  }
+  pub(crate) fn on_response(&self, request: &Request, response: &Response, elapsed: Duration) {
+    self
+      .response
+      .iter()
+      .for_each(|hook| hook(request, response, elapsed));
+  }
+  pub(crate) fn on_redirect(&self, response: &Response, next: &http::Uri) {
+    self.redirect.iter().for_each(|hook| hook(response, next));
+  }
+  pub(crate) fn on_connect(&self, uri: &http::Uri, elapsed: Duration) {
+    self.connect.iter().for_each(|hook| hook(uri, elapsed));
+  }
+  pub(crate) fn on_error(&self, request: &Request, error: &Error, elapsed: Duration) {
+    self
+      .error
+      .iter()
+      .for_each(|hook| hook(request, error, elapsed));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::MockTransport;
+  use std::sync::{Arc, Mutex};
+
+  #[test]
+  fn lifecycle_hooks() {
+    let mock = MockTransport::new()
+      .respond(concat!(
+        "HTTP/1.1 302 Found\r\nLocation: /b\r\n",
+        "Content-Length: 0\r\nConnection: close\r\n\r\n",
+      ))
+      .respond("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = |seen: &Arc<Mutex<Vec<String>>>| {
+      let seen = seen.clone();
+      move |line: String| seen.lock().unwrap().push(line)
+    };
+    let (request, response, redirect, connect, error) =
+      (log(&seen), log(&seen), log(&seen), log(&seen), log(&seen));
+    let client = crate::Client::builder()
+      .transport(mock)
+      .on_request(move |req| request(format!("request {}", req.uri().path())))
+      .on_response(move |req, resp, _| {
+        response(format!("response {} {}", req.uri().path(), resp.status_code().as_u16()))
+      })
+      .on_redirect(move |resp, to| {
+        redirect(format!("redirect {} {}", resp.status_code().as_u16(), to.path()))
+      })
+      .on_connect(move |uri, _| connect(format!("connect {}", uri.host().unwrap_or_default())))
+      .on_error(move |req, _, _| error(format!("error {}", req.uri().path())))
+      .build()
+      .unwrap();
+    let resp = client.get("http://example.com/a").send().unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+    // 没有剩余的模拟响应，连接失败，请求没有写出去
+    assert!(client.get("http://example.com/c").send().is_err());
+    assert_eq!(
+      *seen.lock().unwrap(),
+      [
+        "connect example.com",
+        "request /a",
+        "response /a 302",
+        "redirect 302 /b",
+        "connect example.com",
+        "request /b",
+        "response /b 200",
+        "error /c",
+      ]
+    );
+  }
+}
@@ -0,0 +1,334 @@
+//! HPACK header compression for HTTP/2 (RFC 7541)
+use crate::errors::{new_io_error, Result};
+use std::collections::VecDeque;
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+  (":authority", ""),
+  (":method", "GET"),
+  (":method", "POST"),
+  (":path", "/"),
+  (":path", "/index.html"),
+  (":scheme", "http"),
+  (":scheme", "https"),
+  (":status", "200"),
+  (":status", "204"),
+  (":status", "206"),
+  (":status", "304"),
+  (":status", "400"),
+  (":status", "404"),
+  (":status", "500"),
+  ("accept-charset", ""),
+  ("accept-encoding", "gzip, deflate"),
+  ("accept-language", ""),
+  ("accept-ranges", ""),
+  ("accept", ""),
+  ("access-control-allow-origin", ""),
+  ("age", ""),
+  ("allow", ""),
+  ("authorization", ""),
+  ("cache-control", ""),
+  ("content-disposition", ""),
+  ("content-encoding", ""),
+  ("content-language", ""),
+  ("content-length", ""),
+  ("content-location", ""),
+  ("content-range", ""),
+  ("content-type", ""),
+  ("cookie", ""),
+  ("date", ""),
+  ("etag", ""),
+  ("expect", ""),
+  ("expires", ""),
+  ("from", ""),
+  ("host", ""),
+  ("if-match", ""),
+  ("if-modified-since", ""),
+  ("if-none-match", ""),
+  ("if-range", ""),
+  ("if-unmodified-since", ""),
+  ("last-modified", ""),
+  ("link", ""),
+  ("location", ""),
+  ("max-forwards", ""),
+  ("proxy-authenticate", ""),
+  ("proxy-authorization", ""),
+  ("range", ""),
+  ("referer", ""),
+  ("refresh", ""),
+  ("retry-after", ""),
+  ("server", ""),
+  ("set-cookie", ""),
+  ("strict-transport-security", ""),
+  ("transfer-encoding", ""),
+  ("user-agent", ""),
+  ("vary", ""),
+  ("via", ""),
+  ("www-authenticate", ""),
+];
+
+/// 霍夫曼编码每个符号的位数，码字按规范霍夫曼编码依次分配，256 是 EOS
+const HUFFMAN_LENGTHS: [u8; 257] = [
+  13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28,
+  28, 28, 28, 28, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+  6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6,
+  5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10,
+  13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+  7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6,
+  15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6, 6, 5,
+  6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28,
+  20, 22, 20, 20, 22, 22, 22, 23, 22, 23, 23, 23, 23, 23, 24, 23,
+  24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24,
+  22, 21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23,
+  21, 21, 22, 21, 23, 22, 23, 23, 20, 22, 22, 22, 23, 22, 22, 23,
+  26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25,
+  19, 21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27,
+  20, 24, 20, 21, 22, 21, 21, 23, 22, 22, 25, 25, 24, 24, 26, 23,
+  26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26,
+  30,
+];
+
+/// 默认动态表大小
+pub(crate) const DEFAULT_TABLE_SIZE: usize = 4096;
+
+fn compression_error(msg: &str) -> crate::Error {
+  new_io_error(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// 编码请求头，只使用不索引的字面量，不改变对端的动态表
+pub(crate) fn encode<'a, I>(headers: I) -> Vec<u8>
+  where
+    I: IntoIterator<Item = (&'a [u8], &'a [u8])>,
+{
+  let mut out = Vec::new();
+  for (name, value) in headers {
+    out.push(0);
+    encode_string(&mut out, name);
+    encode_string(&mut out, value);
+  }
+  out
+}
+
+fn encode_string(out: &mut Vec<u8>, s: &[u8]) {
+  encode_int(out, s.len(), 7, 0);
+  out.extend_from_slice(s);
+}
+
+fn encode_int(out: &mut Vec<u8>, mut value: usize, prefix: u8, flags: u8) {
+  let max = (1usize << prefix) - 1;
+  if value < max {
+    out.push(flags | value as u8);
+    return;
+  }
+  out.push(flags | max as u8);
+  value -= max;
+  while value >= 128 {
+    out.push((value % 128) as u8 | 0x80);
+    value /= 128;
+  }
+  out.push(value as u8);
+}
+
+/// 响应头解码器，一个连接一个，保存对端的动态表
+#[derive(Debug)]
+pub(crate) struct Decoder {
+  table: VecDeque<(Vec<u8>, Vec<u8>)>,
+  size: usize,
+  max_size: usize,
+}
+
+impl Default for Decoder {
+  fn default() -> Self {
+    Decoder {
+      table: VecDeque::new(),
+      size: 0,
+      max_size: DEFAULT_TABLE_SIZE,
+    }
+  }
+}
+
+impl Decoder {
+  /// 解码一个完整的头部块
+  pub(crate) fn decode(&mut self, mut block: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut headers = Vec::new();
+    while let Some(&first) = block.first() {
+      if first & 0x80 != 0 {
+        // 索引
+        let index = decode_int(&mut block, 7)?;
+        headers.push(self.get(index)?);
+      } else if first & 0x40 != 0 {
+        // 字面量，加入动态表
+        let (name, value) = self.literal(&mut block, 6)?;
+        self.insert(name.clone(), value.clone());
+        headers.push((name, value));
+      } else if first & 0x20 != 0 {
+        // 动态表大小更新
+        let size = decode_int(&mut block, 5)?;
+        if size > DEFAULT_TABLE_SIZE {
+          return Err(compression_error("hpack table size update too large"));
+        }
+        self.max_size = size;
+        self.evict(0);
+      } else {
+        // 字面量，不加入动态表
+        headers.push(self.literal(&mut block, 4)?);
+      }
+    }
+    Ok(headers)
+  }
+  fn literal(&self, block: &mut &[u8], prefix: u8) -> Result<(Vec<u8>, Vec<u8>)> {
+    let index = decode_int(block, prefix)?;
+    let name = if index == 0 {
+      decode_string(block)?
+    } else {
+      self.get(index)?.0
+    };
+    Ok((name, decode_string(block)?))
+  }
+  fn get(&self, index: usize) -> Result<(Vec<u8>, Vec<u8>)> {
+    if index == 0 {
+      return Err(compression_error("hpack index 0"));
+    }
+    if let Some((name, value)) = STATIC_TABLE.get(index - 1) {
+      return Ok((name.as_bytes().to_vec(), value.as_bytes().to_vec()));
+    }
+    self
+      .table
+      .get(index - STATIC_TABLE.len() - 1)
+      .cloned()
+      .ok_or_else(|| compression_error("hpack index out of range"))
+  }
+  fn insert(&mut self, name: Vec<u8>, value: Vec<u8>) {
+    let size = name.len() + value.len() + 32;
+    self.evict(size);
+    // 比整个表还大的条目只会清空表
+    if size <= self.max_size {
+      self.size += size;
+      self.table.push_front((name, value));
+    }
+  }
+  fn evict(&mut self, incoming: usize) {
+    while self.size + incoming > self.max_size {
+      match self.table.pop_back() {
+        Some((name, value)) => self.size -= name.len() + value.len() + 32,
+        None => break,
+      }
+    }
+  }
+}
+
+fn decode_int(block: &mut &[u8], prefix: u8) -> Result<usize> {
+  let (&first, rest) = block
+    .split_first()
+    .ok_or_else(|| compression_error("hpack integer truncated"))?;
+  *block = rest;
+  let max = (1usize << prefix) - 1;
+  let mut value = (first as usize) & max;
+  if value < max {
+    return Ok(value);
+  }
+  let mut shift = 0;
+  loop {
+    let (&byte, rest) = block
+      .split_first()
+      .ok_or_else(|| compression_error("hpack integer truncated"))?;
+    *block = rest;
+    if shift > 28 {
+      return Err(compression_error("hpack integer overflow"));
+    }
+    value += ((byte & 0x7f) as usize) << shift;
+    shift += 7;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+}
+
+fn decode_string(block: &mut &[u8]) -> Result<Vec<u8>> {
+  let huffman = block.first().is_some_and(|b| b & 0x80 != 0);
+  let length = decode_int(block, 7)?;
+  if length > block.len() {
+    return Err(compression_error("hpack string truncated"));
+  }
+  let (s, rest) = block.split_at(length);
+  *block = rest;
+  if huffman {
+    huffman_decode(s)
+  } else {
+    Ok(s.to_vec())
+  }
+}
+
+/// 按规范霍夫曼编码逐位解码：同样长度的码字是连续的
+fn huffman_decode(input: &[u8]) -> Result<Vec<u8>> {
+  // 每个长度的符号数量，以及按（长度，符号）排序的符号
+  let mut count = [0u32; 31];
+  for &length in HUFFMAN_LENGTHS.iter() {
+    count[length as usize] += 1;
+  }
+  let mut symbols: Vec<u16> = (0..257).collect();
+  symbols.sort_by_key(|&s| HUFFMAN_LENGTHS[s as usize]);
+  let mut out = Vec::new();
+  let mut code: u32 = 0;
+  let mut length = 0;
+  // 当前长度的第一个码字，以及它在 symbols 中的位置
+  let mut first: u32 = 0;
+  let mut offset: u32 = 0;
+  for byte in input {
+    for bit in (0..8).rev() {
+      code = (code << 1) | u32::from((byte >> bit) & 1);
+      length += 1;
+      if length > 30 {
+        return Err(compression_error("hpack invalid huffman code"));
+      }
+      let n = count[length];
+      if code.wrapping_sub(first) < n {
+        let symbol = symbols[(offset + code - first) as usize];
+        if symbol == 256 {
+          return Err(compression_error("hpack huffman EOS in string"));
+        }
+        out.push(symbol as u8);
+        code = 0;
+        length = 0;
+        first = 0;
+        offset = 0;
+      } else {
+        offset += n;
+        first = (first + n) << 1;
+      }
+    }
+  }
+  // 结尾只能是不超过 7 位的 1 作为填充
+  if length > 7 || code != (1 << length) - 1 {
+    return Err(compression_error("hpack invalid huffman padding"));
+  }
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{encode, Decoder};
+
+  #[test]
+  fn decode_rfc_examples() {
+    // RFC 7541 C.4.1 / C.4.2：霍夫曼编码的请求，第二个请求引用动态表
+    let hex = |s: &str| -> Vec<u8> {
+      (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+    };
+    let mut decoder = Decoder::default();
+    let headers = decoder
+      .decode(&hex("828684418cf1e3c2e5f23a6ba0ab90f4ff"))
+      .unwrap();
+    assert_eq!(headers[3], (b":authority".to_vec(), b"www.example.com".to_vec()));
+    let headers = decoder
+      .decode(&hex("828684be5886a8eb10649cbf"))
+      .unwrap();
+    assert_eq!(headers[3], (b":authority".to_vec(), b"www.example.com".to_vec()));
+    assert_eq!(headers[4], (b"cache-control".to_vec(), b"no-cache".to_vec()));
+    let block = encode([(b"x-long".as_slice(), "v".repeat(300).as_bytes())]);
+    let headers = Decoder::default().decode(&block).unwrap();
+    assert_eq!(headers[0].1.len(), 300);
+  }
+}
@@ -0,0 +1,642 @@
+//! 单个流的 HTTP/2 请求：TLS 上通过 ALPN 协商，明文连接上直接发送或用 h2c 升级
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crate::chunked::ChunkedBody;
+use crate::deadline::{Deadline, DeadlineReader};
+use crate::errors::{new_io_error, Result};
+use crate::hpack;
+use crate::request::{AutoHeaders, RawHeaders, RequestTarget};
+use crate::response::{push_informational, ResponseBuilder, ResponseConfig, Trailers};
+use crate::socket::Socket;
+use crate::{Request, Response};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{BufReader, Cursor, Read, Write};
+use std::time::{Duration, Instant};
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// 帧类型
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+// 帧标志
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+// SETTINGS 参数
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+/// 每个连接只使用一个流
+const STREAM_ID: u32 = 1;
+const DEFAULT_WINDOW: i64 = 65_535;
+const MAX_WINDOW: u32 = (1 << 31) - 1;
+/// 没有修改 SETTINGS_MAX_FRAME_SIZE 时双方的最大帧
+const DEFAULT_MAX_FRAME_SIZE: usize = 16_384;
+/// h2c 升级时响应头的最大长度
+const MAX_UPGRADE_HEAD: usize = 64 * 1024;
+
+/// 连接级的请求头，HTTP/2 中不允许出现
+const CONNECTION_HEADERS: [HeaderName; 5] = [
+  http::header::CONNECTION,
+  http::header::HOST,
+  http::header::TRANSFER_ENCODING,
+  http::header::UPGRADE,
+  HeaderName::from_static("keep-alive"),
+];
+
+struct Frame {
+  kind: u8,
+  flags: u8,
+  stream: u32,
+  payload: Vec<u8>,
+}
+
+fn protocol_error(msg: &str) -> crate::Error {
+  new_io_error(std::io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) struct Connection<'a> {
+  socket: &'a mut Socket,
+  deadline: Deadline,
+  read_timeout: Option<Duration>,
+  first_byte: &'a Cell<Option<Instant>>,
+  decoder: hpack::Decoder,
+  // 对端允许的最大帧和发送窗口
+  max_frame_size: usize,
+  connection_window: i64,
+  stream_window: i64,
+  initial_window: i64,
+  // 发送请求体时收到的流帧，留给读取响应时处理
+  pending: VecDeque<Frame>,
+}
+
+impl<'a> Connection<'a> {
+  pub(crate) fn new(
+    socket: &'a mut Socket,
+    deadline: Deadline,
+    read_timeout: Option<Duration>,
+    first_byte: &'a Cell<Option<Instant>>,
+  ) -> Self {
+    Connection {
+      socket,
+      deadline,
+      read_timeout,
+      first_byte,
+      decoder: hpack::Decoder::default(),
+      max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+      connection_window: DEFAULT_WINDOW,
+      stream_window: DEFAULT_WINDOW,
+      initial_window: DEFAULT_WINDOW,
+      pending: VecDeque::new(),
+    }
+  }
+  /// 发送请求并读取完整的响应，然后用 GOAWAY 结束连接
+  pub(crate) fn send(mut self, request: &Request, config: &ResponseConfig) -> Result<Response> {
+    if request.raw_request().is_some() {
+      return Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "raw requests can not be sent over HTTP/2",
+      ));
+    }
+    check_chunked(request)?;
+    self.write_preface()?;
+    let body = request.body().map(|b| b.as_ref()).unwrap_or_default();
+    self.write_headers(request, body.is_empty())?;
+    if !body.is_empty() {
+      self.write_body(body)?;
+    }
+    self.socket.flush()?;
+    self.finish(config)
+  }
+  /// 收到 101 之后，升级前的请求就是流 1，只需要发送连接前言然后读取响应
+  fn send_upgraded(mut self, config: &ResponseConfig) -> Result<Response> {
+    self.write_preface()?;
+    self.socket.flush()?;
+    self.finish(config)
+  }
+  fn write_preface(&mut self) -> Result<()> {
+    let mut start = PREFACE.to_vec();
+    encode_frame(&mut start, SETTINGS, 0, 0, &settings());
+    encode_frame(
+      &mut start,
+      WINDOW_UPDATE,
+      0,
+      0,
+      &(MAX_WINDOW - DEFAULT_WINDOW as u32).to_be_bytes(),
+    );
+    self.socket.write_all(&start)?;
+    Ok(())
+  }
+  fn finish(mut self, config: &ResponseConfig) -> Result<Response> {
+    let response = self.read_response(config);
+    let mut goaway = Vec::new();
+    encode_frame(&mut goaway, GOAWAY, 0, 0, &[0; 8]);
+    let _ = self.socket.write_all(&goaway);
+    response
+  }
+  fn write_headers(&mut self, request: &Request, end_stream: bool) -> Result<()> {
+    let uri = request.uri();
+    let target = request.extensions().get::<RequestTarget>();
+    let authority = match (target, request.headers().get(http::header::HOST)) {
+      (Some(RequestTarget::Authority(authority)), _) => authority.as_str().as_bytes().to_vec(),
+      (_, Some(host)) => host.as_bytes().to_vec(),
+      _ => uri.authority().map(|a| a.as_str()).unwrap_or_default().as_bytes().to_vec(),
+    };
+    let mut fields: Vec<(&[u8], Vec<u8>)> = vec![(b":method", request.method().as_str().into())];
+    if request.method() != Method::CONNECT {
+      let path: Vec<u8> = match target {
+        Some(RequestTarget::Asterisk) => b"*".to_vec(),
+        Some(RequestTarget::Raw(raw)) => raw.to_vec(),
+        _ => uri.path_and_query().map(|p| p.as_str()).unwrap_or("/").into(),
+      };
+      fields.push((b":scheme", uri.scheme_str().unwrap_or("https").into()));
+      fields.push((b":path", path));
+    }
+    fields.push((b":authority", authority));
+    for (name, value) in request.headers() {
+      if CONNECTION_HEADERS.contains(name) || (name == http::header::TE && value != "trailers") {
+        continue;
+      }
+      fields.push((name.as_str().as_bytes(), value.as_bytes().to_vec()));
+    }
+    let body_len = request.body().map_or(0, |b| b.len());
+    let auto = request.extensions().get::<AutoHeaders>().copied().unwrap_or_default();
+    if auto.allows(&http::header::CONTENT_LENGTH)
+      && body_len > 0
+      && !request.headers().contains_key(http::header::CONTENT_LENGTH)
+    {
+      fields.push((b"content-length", body_len.to_string().into_bytes()));
+    }
+    if let Some(RawHeaders(raw)) = request.extensions().get::<RawHeaders>() {
+      fields.extend(raw.iter().map(|(name, value)| (name.as_ref(), value.to_vec())));
+    }
+    let block = hpack::encode(fields.iter().map(|(n, v)| (*n, v.as_slice())));
+    // 头部块超过最大帧时拆分到 CONTINUATION 帧
+    let mut chunks = block.chunks(self.max_frame_size).peekable();
+    let mut out = Vec::new();
+    let mut kind = HEADERS;
+    let mut flags = if end_stream { END_STREAM } else { 0 };
+    while let Some(chunk) = chunks.next() {
+      if chunks.peek().is_none() {
+        flags |= END_HEADERS;
+      }
+      encode_frame(&mut out, kind, flags, STREAM_ID, chunk);
+      kind = CONTINUATION;
+      flags = 0;
+    }
+    if block.is_empty() {
+      encode_frame(&mut out, HEADERS, flags | END_HEADERS, STREAM_ID, &[]);
+    }
+    self.socket.write_all(&out)?;
+    Ok(())
+  }
+  fn write_body(&mut self, mut body: &[u8]) -> Result<()> {
+    while !body.is_empty() {
+      let window = self.connection_window.min(self.stream_window);
+      if window <= 0 {
+        // 等待对端的 WINDOW_UPDATE
+        self.socket.flush()?;
+        let frame = self.read_frame()?;
+        if !self.control(&frame)? && frame.stream == STREAM_ID {
+          // 服务器提前响应，不再发送剩余的请求体
+          self.pending.push_back(frame);
+          return Ok(());
+        }
+        continue;
+      }
+      let n = body.len().min(window as usize).min(self.max_frame_size);
+      let (chunk, rest) = body.split_at(n);
+      let flags = if rest.is_empty() { END_STREAM } else { 0 };
+      let mut out = Vec::with_capacity(n + 9);
+      encode_frame(&mut out, DATA, flags, STREAM_ID, chunk);
+      self.socket.write_all(&out)?;
+      self.connection_window -= n as i64;
+      self.stream_window -= n as i64;
+      body = rest;
+    }
+    Ok(())
+  }
+  fn read_response(&mut self, config: &ResponseConfig) -> Result<Response> {
+    let mut status = None;
+    let mut headers = HeaderMap::new();
+    let mut body = Vec::new();
+    let mut informational = Vec::new();
+    let mut trailers = HeaderMap::new();
+    loop {
+      let frame = match self.pending.pop_front() {
+        Some(frame) => frame,
+        None => self.read_frame()?,
+      };
+      if self.control(&frame)? || frame.stream != STREAM_ID {
+        continue;
+      }
+      match frame.kind {
+        HEADERS => {
+          let mut block = strip_padding(&frame)?.to_vec();
+          let mut flags = frame.flags;
+          while flags & END_HEADERS == 0 {
+            let next = self.read_frame()?;
+            if next.kind != CONTINUATION || next.stream != STREAM_ID {
+              return Err(protocol_error("expected CONTINUATION frame"));
+            }
+            block.extend(next.payload);
+            flags |= next.flags & END_HEADERS;
+          }
+          let fields = self.decoder.decode(&block)?;
+          if status.is_none() {
+            let code = fields
+              .iter()
+              .find(|(name, _)| name == b":status")
+              .and_then(|(_, value)| StatusCode::from_bytes(value).ok())
+              .ok_or_else(|| protocol_error("response without :status"))?;
+            // 1xx 是临时响应，单独保存
+            if code.is_informational() {
+              let fields: HeaderMap = regular_headers(fields).collect();
+              config.on_early_hints(code, &fields);
+              push_informational(
+                &mut informational,
+                http::Version::HTTP_2,
+                code,
+                fields,
+              )?;
+              continue;
+            }
+            status = Some(code);
+            headers.extend(regular_headers(fields));
+          } else {
+            // 最终响应之后的 HEADERS 是 trailer
+            trailers.extend(regular_headers(fields));
+          }
+        }
+        DATA => {
+          body.extend_from_slice(strip_padding(&frame)?);
+          config.check_body_size(body.len() as u64)?;
+        }
+        RST_STREAM => {
+          let code = frame.payload.get(..4).map_or(0, |c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]));
+          return Err(new_io_error(
+            std::io::ErrorKind::ConnectionReset,
+            &format!("stream reset by server with error code {}", code),
+          ));
+        }
+        _ => {}
+      }
+      if frame.flags & END_STREAM != 0 && matches!(frame.kind, HEADERS | DATA) {
+        break;
+      }
+    }
+    let status = status.ok_or_else(|| protocol_error("stream ended without a response"))?;
+    let body = config.decode_body(&headers, body.into())?;
+    let mut builder = http::Response::builder()
+      .version(http::Version::HTTP_2)
+      .status(status);
+    if let Some(h) = builder.headers_mut() {
+      *h = headers;
+    }
+    let mut response: Response = builder.body(body)?.into();
+    if !informational.is_empty() {
+      response.extensions_mut().insert(informational);
+    }
+    if !trailers.is_empty() {
+      response.extensions_mut().insert(Trailers(trailers));
+    }
+    Ok(response)
+  }
+  /// 处理连接级的帧，返回是否已经处理
+  fn control(&mut self, frame: &Frame) -> Result<bool> {
+    match frame.kind {
+      SETTINGS if frame.flags & ACK == 0 => {
+        for setting in frame.payload.chunks_exact(6) {
+          let id = u16::from_be_bytes([setting[0], setting[1]]);
+          let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+          match id {
+            SETTINGS_INITIAL_WINDOW_SIZE => {
+              self.stream_window += i64::from(value) - self.initial_window;
+              self.initial_window = i64::from(value);
+            }
+            SETTINGS_MAX_FRAME_SIZE => self.max_frame_size = value as usize,
+            _ => {}
+          }
+        }
+        let mut ack = Vec::new();
+        encode_frame(&mut ack, SETTINGS, ACK, 0, &[]);
+        self.socket.write_all(&ack)?;
+      }
+      SETTINGS => {}
+      PING if frame.flags & ACK == 0 => {
+        let mut pong = Vec::new();
+        encode_frame(&mut pong, PING, ACK, 0, &frame.payload);
+        self.socket.write_all(&pong)?;
+      }
+      PING => {}
+      WINDOW_UPDATE => {
+        let increment = frame
+          .payload
+          .get(..4)
+          .map_or(0, |c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]) & MAX_WINDOW);
+        match frame.stream {
+          0 => self.connection_window += i64::from(increment),
+          STREAM_ID => self.stream_window += i64::from(increment),
+          _ => {}
+        }
+      }
+      GOAWAY => {
+        let last_stream = frame
+          .payload
+          .get(..4)
+          .map_or(0, |c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]) & MAX_WINDOW);
+        if last_stream < STREAM_ID {
+          return Err(new_io_error(
+            std::io::ErrorKind::ConnectionAborted,
+            "server sent GOAWAY before processing the request",
+          ));
+        }
+      }
+      PUSH_PROMISE => return Err(protocol_error("server push was disabled")),
+      DATA | HEADERS | CONTINUATION | RST_STREAM => return Ok(false),
+      // PRIORITY 以及未知的帧直接忽略
+      _ => {}
+    }
+    Ok(true)
+  }
+  fn read_frame(&mut self) -> Result<Frame> {
+    let mut reader = DeadlineReader::new(
+      &mut *self.socket,
+      self.deadline,
+      self.read_timeout,
+      self.first_byte,
+    );
+    let mut head = [0u8; 9];
+    reader.read_exact(&mut head)?;
+    let length = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+    if length > DEFAULT_MAX_FRAME_SIZE {
+      return Err(protocol_error("frame larger than SETTINGS_MAX_FRAME_SIZE"));
+    }
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload)?;
+    Ok(Frame {
+      kind: head[3],
+      flags: head[4],
+      stream: u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & MAX_WINDOW,
+      payload,
+    })
+  }
+}
+
+/// 去掉伪头部，忽略非法的头部
+fn regular_headers(fields: Vec<(Vec<u8>, Vec<u8>)>) -> impl Iterator<Item=(HeaderName, HeaderValue)> {
+  fields.into_iter().filter_map(|(name, value)| {
+    if name.starts_with(b":") {
+      return None;
+    }
+    Some((HeaderName::from_bytes(&name).ok()?, HeaderValue::from_bytes(&value).ok()?))
+  })
+}
+
+/// 关闭服务器推送，接收窗口开到最大，不再需要发送 WINDOW_UPDATE
+fn settings() -> Vec<u8> {
+  let mut settings = Vec::new();
+  settings.extend(SETTINGS_ENABLE_PUSH.to_be_bytes());
+  settings.extend(0u32.to_be_bytes());
+  settings.extend(SETTINGS_INITIAL_WINDOW_SIZE.to_be_bytes());
+  settings.extend(MAX_WINDOW.to_be_bytes());
+  settings
+}
+
+/// 分块请求体只能用 HTTP/1.1 发送
+fn check_chunked(request: &Request) -> Result<()> {
+  if request.extensions().get::<ChunkedBody>().is_some() {
+    return Err(new_io_error(
+      std::io::ErrorKind::Unsupported,
+      "chunked bodies can not be sent over HTTP/2",
+    ));
+  }
+  Ok(())
+}
+
+/// 用 `Upgrade: h2c` 发送 HTTP/1.1 请求，服务器同意升级后按 HTTP/2 读取响应，
+/// 否则按 HTTP/1.1 读取
+pub(crate) fn upgrade(
+  socket: &mut Socket,
+  request: &Request,
+  deadline: Deadline,
+  read_timeout: Option<Duration>,
+  first_byte: &Cell<Option<Instant>>,
+  config: ResponseConfig,
+) -> Result<Response> {
+  check_chunked(request)?;
+  let mut upgrade = request.clone();
+  *upgrade.version_mut() = http::Version::HTTP_11;
+  let headers = upgrade.headers_mut();
+  headers.insert(
+    http::header::CONNECTION,
+    HeaderValue::from_static("Upgrade, HTTP2-Settings"),
+  );
+  headers.insert(http::header::UPGRADE, HeaderValue::from_static("h2c"));
+  headers.insert(
+    HeaderName::from_static("http2-settings"),
+    HeaderValue::try_from(URL_SAFE_NO_PAD.encode(settings()))?,
+  );
+  socket.write_all(&upgrade.to_raw())?;
+  socket.flush()?;
+  // 逐字节读取响应头，避免把升级后的 HTTP/2 帧读进缓冲区
+  let mut head = Vec::new();
+  {
+    let mut reader = DeadlineReader::new(&mut *socket, deadline, read_timeout, first_byte);
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+      if head.len() > MAX_UPGRADE_HEAD {
+        return Err(protocol_error("upgrade response head too large"));
+      }
+      reader.read_exact(&mut byte)?;
+      head.push(byte[0]);
+    }
+  }
+  if head.starts_with(b"HTTP/1.1 101") {
+    return Connection::new(socket, deadline, read_timeout, first_byte).send_upgraded(&config);
+  }
+  let reader = DeadlineReader::new(socket, deadline, read_timeout, first_byte);
+  ResponseBuilder::new(BufReader::new(Cursor::new(head).chain(reader)), config).build()
+}
+
+fn encode_frame(out: &mut Vec<u8>, kind: u8, flags: u8, stream: u32, payload: &[u8]) {
+  out.extend(&(payload.len() as u32).to_be_bytes()[1..]);
+  out.push(kind);
+  out.push(flags);
+  out.extend(stream.to_be_bytes());
+  out.extend(payload);
+}
+
+/// 去掉 DATA 和 HEADERS 帧的填充以及优先级字段
+fn strip_padding(frame: &Frame) -> Result<&[u8]> {
+  let mut payload = frame.payload.as_slice();
+  let mut padding = 0;
+  if frame.flags & PADDED != 0 {
+    let (&length, rest) = payload
+      .split_first()
+      .ok_or_else(|| protocol_error("invalid padding"))?;
+    padding = length as usize;
+    payload = rest;
+  }
+  if frame.kind == HEADERS && frame.flags & PRIORITY != 0 {
+    payload = payload
+      .get(5..)
+      .ok_or_else(|| protocol_error("invalid priority"))?;
+  }
+  if padding > payload.len() {
+    return Err(protocol_error("invalid padding"));
+  }
+  Ok(&payload[..payload.len() - padding])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Client;
+  use std::net::{TcpListener, TcpStream};
+
+  fn read_frame(stream: &mut TcpStream) -> Frame {
+    let mut head = [0u8; 9];
+    stream.read_exact(&mut head).unwrap();
+    let mut payload = vec![0; u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize];
+    stream.read_exact(&mut payload).unwrap();
+    Frame {
+      kind: head[3],
+      flags: head[4],
+      stream: u32::from_be_bytes([head[5], head[6], head[7], head[8]]),
+      payload,
+    }
+  }
+
+  #[test]
+  fn single_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut preface = [0u8; PREFACE.len()];
+      stream.read_exact(&mut preface).unwrap();
+      assert_eq!(preface, PREFACE);
+      let settings = read_frame(&mut stream);
+      assert_eq!((settings.kind, settings.stream), (SETTINGS, 0));
+      assert_eq!(settings.payload, super::settings());
+      assert_eq!(read_frame(&mut stream).kind, WINDOW_UPDATE);
+      let headers = read_frame(&mut stream);
+      assert_eq!((headers.kind, headers.flags, headers.stream), (HEADERS, END_HEADERS, STREAM_ID));
+      let fields = hpack::Decoder::default().decode(&headers.payload).unwrap();
+      let data = read_frame(&mut stream);
+      assert_eq!((data.kind, data.flags, data.stream), (DATA, END_STREAM, STREAM_ID));
+      // 服务器的 SETTINGS 要先于响应，客户端应回 ACK
+      let mut out = Vec::new();
+      encode_frame(&mut out, SETTINGS, 0, 0, &[]);
+      let block = hpack::encode([(b":status".as_slice(), b"201".as_slice()), (b"x-reply", b"1")]);
+      encode_frame(&mut out, HEADERS, END_HEADERS, STREAM_ID, &block);
+      encode_frame(&mut out, DATA, 0, STREAM_ID, b"hel");
+      encode_frame(&mut out, DATA, END_STREAM, STREAM_ID, b"lo");
+      stream.write_all(&out).unwrap();
+      let ack = read_frame(&mut stream);
+      assert_eq!((ack.kind, ack.flags), (SETTINGS, ACK));
+      assert_eq!(read_frame(&mut stream).kind, GOAWAY);
+      (fields, data.payload)
+    });
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let response = client
+      .post(format!("http://{}/upload?x=1", addr))
+      .version(http::Version::HTTP_2)
+      .header("x-test", "yes")
+      .body("ping")
+      .send()
+      .unwrap();
+    assert_eq!(response.version(), http::Version::HTTP_2);
+    assert_eq!(response.status_code(), StatusCode::CREATED);
+    assert_eq!(response.headers()["x-reply"], "1");
+    assert_eq!(response.text().unwrap(), "hello");
+    let (fields, body) = server.join().unwrap();
+    let field = |name: &[u8]| {
+      fields
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| String::from_utf8_lossy(v).into_owned())
+    };
+    assert_eq!(field(b":method").as_deref(), Some("POST"));
+    assert_eq!(field(b":scheme").as_deref(), Some("http"));
+    assert_eq!(field(b":path").as_deref(), Some("/upload?x=1"));
+    assert_eq!(field(b":authority"), Some(addr.to_string()));
+    assert_eq!(field(b"x-test").as_deref(), Some("yes"));
+    assert_eq!(field(b"content-length").as_deref(), Some("4"));
+    assert_eq!(field(b"host"), None);
+    assert_eq!(body, b"ping");
+  }
+
+  /// 读到空行为止的 HTTP/1.1 请求头
+  fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+      stream.read_exact(&mut byte).unwrap();
+      head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap().to_ascii_lowercase()
+  }
+
+  #[test]
+  fn h2c_upgrade() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let mut heads = Vec::new();
+      // 第一个连接同意升级，第二个连接不理会升级直接用 HTTP/1.1 响应
+      let (mut stream, _) = listener.accept().unwrap();
+      heads.push(read_head(&mut stream));
+      stream
+        .write_all(b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n")
+        .unwrap();
+      let mut preface = [0u8; PREFACE.len()];
+      stream.read_exact(&mut preface).unwrap();
+      assert_eq!(preface, PREFACE);
+      assert_eq!(read_frame(&mut stream).kind, SETTINGS);
+      assert_eq!(read_frame(&mut stream).kind, WINDOW_UPDATE);
+      let mut out = Vec::new();
+      let block = hpack::encode([(b":status".as_slice(), b"200".as_slice())]);
+      encode_frame(&mut out, HEADERS, END_HEADERS, STREAM_ID, &block);
+      encode_frame(&mut out, DATA, END_STREAM, STREAM_ID, b"upgraded");
+      stream.write_all(&out).unwrap();
+      assert_eq!(read_frame(&mut stream).kind, GOAWAY);
+      let (mut stream, _) = listener.accept().unwrap();
+      heads.push(read_head(&mut stream));
+      stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nplain")
+        .unwrap();
+      heads
+    });
+    let client = Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let url = format!("http://{}/", addr);
+    let upgraded = client.get(&url).h2c_upgrade().send().unwrap();
+    assert_eq!(upgraded.version(), http::Version::HTTP_2);
+    assert_eq!(upgraded.text().unwrap(), "upgraded");
+    let plain = client.get(&url).h2c_upgrade().send().unwrap();
+    assert_eq!(plain.version(), http::Version::HTTP_11);
+    assert_eq!(plain.text().unwrap(), "plain");
+    let settings = format!("http2-settings: {}\r\n", URL_SAFE_NO_PAD.encode(super::settings()));
+    for head in server.join().unwrap() {
+      assert!(head.starts_with("get / http/1.1\r\n"), "{}", head);
+      assert!(head.contains("connection: upgrade, http2-settings\r\n"), "{}", head);
+      assert!(head.contains("upgrade: h2c\r\n"), "{}", head);
+      assert!(head.contains(&settings.to_ascii_lowercase()), "{}", head);
+    }
+  }
+}
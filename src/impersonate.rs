@@ -0,0 +1,309 @@
+use crate::request::HeaderOrder;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(feature = "tls")]
+use openssl::ssl::{SslConnectorBuilder, SslVersion};
+
+/// A browser whose requests are imitated with
+/// [`ClientBuilder::impersonate`](crate::ClientBuilder::impersonate).
+///
+/// A profile holds the `User-Agent`, `Accept-*`, client hint and fetch
+/// metadata headers of a top level navigation, the order and casing the
+/// browser writes them in over HTTP/1.1, and with the `tls` feature its
+/// cipher suites, key exchange groups and signature algorithms in the
+/// browser's order. OpenSSL decides the order of the TLS extensions and does
+/// not send GREASE values, so the JA3 hash is close to the browser's but not
+/// equal. With the `aws-lc` and `aws-lc-fips` features the TLS 1.3 cipher
+/// suites and the signature algorithms keep the order of AWS-LC.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::Browser;
+///
+/// let client = slinger::Client::builder()
+///   .impersonate(Browser::Chrome120)
+///   .build()?;
+/// let resp = client.get("https://example.com/").send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Browser {
+  /// Chrome 120 on Windows
+  Chrome120,
+  /// Firefox 120 on Windows
+  Firefox120,
+  /// Safari 17 on macOS
+  Safari17,
+}
+
+/// 浏览器发送的 TLS 参数，按 OpenSSL 的写法
+#[cfg(feature = "tls")]
+struct TlsProfile {
+  /// TLS 1.3 的密码套件
+  #[cfg_attr(any(feature = "aws-lc", feature = "aws-lc-fips"), allow(dead_code))]
+  ciphersuites: &'static str,
+  /// TLS 1.2 及以下的密码套件
+  ciphers: &'static str,
+  groups: &'static str,
+  #[cfg_attr(any(feature = "aws-lc", feature = "aws-lc-fips"), allow(dead_code))]
+  sigalgs: &'static str,
+}
+
+impl Browser {
+  /// The headers the browser sends on a top level navigation.
+  pub fn headers(&self) -> HeaderMap {
+    let pairs: &[(&str, &str)] = match self {
+      Browser::Chrome120 => &[
+        ("sec-ch-ua", r#""Not_A Brand";v="8", "Chromium";v="120", "Google Chrome";v="120""#),
+        ("sec-ch-ua-mobile", "?0"),
+        ("sec-ch-ua-platform", r#""Windows""#),
+        ("upgrade-insecure-requests", "1"),
+        (
+          "user-agent",
+          concat!(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 ",
+            "(KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"
+          ),
+        ),
+        (
+          "accept",
+          concat!(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,",
+            "image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7"
+          ),
+        ),
+        ("sec-fetch-site", "none"),
+        ("sec-fetch-mode", "navigate"),
+        ("sec-fetch-user", "?1"),
+        ("sec-fetch-dest", "document"),
+        ("accept-encoding", "gzip, deflate, br"),
+        ("accept-language", "en-US,en;q=0.9"),
+      ],
+      Browser::Firefox120 => &[
+        (
+          "user-agent",
+          "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:120.0) Gecko/20100101 Firefox/120.0",
+        ),
+        (
+          "accept",
+          "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+        ),
+        ("accept-language", "en-US,en;q=0.5"),
+        ("accept-encoding", "gzip, deflate, br"),
+        ("upgrade-insecure-requests", "1"),
+        ("sec-fetch-dest", "document"),
+        ("sec-fetch-mode", "navigate"),
+        ("sec-fetch-site", "none"),
+        ("sec-fetch-user", "?1"),
+      ],
+      Browser::Safari17 => &[
+        ("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+        ("sec-fetch-site", "none"),
+        ("sec-fetch-dest", "document"),
+        ("accept-language", "en-US,en;q=0.9"),
+        ("sec-fetch-mode", "navigate"),
+        (
+          "user-agent",
+          concat!(
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 ",
+            "(KHTML, like Gecko) Version/17.2 Safari/605.1.15"
+          ),
+        ),
+        ("accept-encoding", "gzip, deflate, br"),
+      ],
+    };
+    let mut headers = HeaderMap::with_capacity(pairs.len() + 1);
+    headers.insert(http::header::CONNECTION, HeaderValue::from_static("keep-alive"));
+    for (name, value) in pairs {
+      headers.insert(HeaderName::from_static(name), HeaderValue::from_static(value));
+    }
+    headers
+  }
+  /// The order and casing the browser writes its headers in over HTTP/1.1.
+  pub fn header_order(&self) -> HeaderOrder {
+    let names: &[&str] = match self {
+      Browser::Chrome120 => &[
+        "Host",
+        "Connection",
+        "Content-Length",
+        "Cache-Control",
+        "sec-ch-ua",
+        "sec-ch-ua-mobile",
+        "sec-ch-ua-platform",
+        "Upgrade-Insecure-Requests",
+        "Origin",
+        "Content-Type",
+        "User-Agent",
+        "Accept",
+        "Sec-Fetch-Site",
+        "Sec-Fetch-Mode",
+        "Sec-Fetch-User",
+        "Sec-Fetch-Dest",
+        "Referer",
+        "Accept-Encoding",
+        "Accept-Language",
+        "Cookie",
+      ],
+      Browser::Firefox120 => &[
+        "Host",
+        "User-Agent",
+        "Accept",
+        "Accept-Language",
+        "Accept-Encoding",
+        "Content-Type",
+        "Content-Length",
+        "Origin",
+        "Connection",
+        "Referer",
+        "Cookie",
+        "Upgrade-Insecure-Requests",
+        "Sec-Fetch-Dest",
+        "Sec-Fetch-Mode",
+        "Sec-Fetch-Site",
+        "Sec-Fetch-User",
+      ],
+      Browser::Safari17 => &[
+        "Host",
+        "Content-Type",
+        "Origin",
+        "Accept",
+        "Sec-Fetch-Site",
+        "Cookie",
+        "Sec-Fetch-Dest",
+        "Content-Length",
+        "Accept-Language",
+        "Sec-Fetch-Mode",
+        "User-Agent",
+        "Referer",
+        "Accept-Encoding",
+        "Connection",
+      ],
+    };
+    HeaderOrder::new(names.iter().copied()).title_case(true)
+  }
+  /// The protocols the browser offers with ALPN, without `h2` when the
+  /// `http2` feature is disabled.
+  pub fn alpn_protocols(&self) -> Vec<String> {
+    let mut protocols = vec!["http/1.1".to_string()];
+    if cfg!(feature = "http2") {
+      protocols.insert(0, "h2".to_string());
+    }
+    protocols
+  }
+  #[cfg(feature = "tls")]
+  fn tls_profile(&self) -> TlsProfile {
+    match self {
+      Browser::Chrome120 => TlsProfile {
+        ciphersuites: "TLS_AES_128_GCM_SHA256:TLS_AES_256_GCM_SHA384:TLS_CHACHA20_POLY1305_SHA256",
+        ciphers: concat!(
+          "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:",
+          "ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:",
+          "ECDHE-ECDSA-CHACHA20-POLY1305:ECDHE-RSA-CHACHA20-POLY1305:",
+          "ECDHE-RSA-AES128-SHA:ECDHE-RSA-AES256-SHA:",
+          "AES128-GCM-SHA256:AES256-GCM-SHA384:AES128-SHA:AES256-SHA"
+        ),
+        groups: "X25519:P-256:P-384",
+        sigalgs: concat!(
+          "ECDSA+SHA256:rsa_pss_rsae_sha256:RSA+SHA256:ECDSA+SHA384:",
+          "rsa_pss_rsae_sha384:RSA+SHA384:rsa_pss_rsae_sha512:RSA+SHA512"
+        ),
+      },
+      Browser::Firefox120 => TlsProfile {
+        ciphersuites: "TLS_AES_128_GCM_SHA256:TLS_CHACHA20_POLY1305_SHA256:TLS_AES_256_GCM_SHA384",
+        ciphers: concat!(
+          "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:",
+          "ECDHE-ECDSA-CHACHA20-POLY1305:ECDHE-RSA-CHACHA20-POLY1305:",
+          "ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:",
+          "ECDHE-ECDSA-AES256-SHA:ECDHE-ECDSA-AES128-SHA:",
+          "ECDHE-RSA-AES128-SHA:ECDHE-RSA-AES256-SHA:",
+          "AES128-GCM-SHA256:AES256-GCM-SHA384:AES128-SHA:AES256-SHA"
+        ),
+        groups: "X25519:P-256:P-384:P-521:ffdhe2048:ffdhe3072",
+        sigalgs: concat!(
+          "ECDSA+SHA256:ECDSA+SHA384:ECDSA+SHA512:rsa_pss_rsae_sha256:",
+          "rsa_pss_rsae_sha384:rsa_pss_rsae_sha512:RSA+SHA256:RSA+SHA384:",
+          "RSA+SHA512:ECDSA+SHA1:RSA+SHA1"
+        ),
+      },
+      Browser::Safari17 => TlsProfile {
+        ciphersuites: "TLS_AES_128_GCM_SHA256:TLS_AES_256_GCM_SHA384:TLS_CHACHA20_POLY1305_SHA256",
+        ciphers: concat!(
+          "ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-ECDSA-AES128-GCM-SHA256:",
+          "ECDHE-ECDSA-CHACHA20-POLY1305:ECDHE-RSA-AES256-GCM-SHA384:",
+          "ECDHE-RSA-AES128-GCM-SHA256:ECDHE-RSA-CHACHA20-POLY1305:",
+          "ECDHE-ECDSA-AES256-SHA:ECDHE-ECDSA-AES128-SHA:",
+          "ECDHE-RSA-AES256-SHA:ECDHE-RSA-AES128-SHA:",
+          "AES256-GCM-SHA384:AES128-GCM-SHA256:AES256-SHA:AES128-SHA"
+        ),
+        groups: "X25519:P-256:P-384:P-521",
+        sigalgs: concat!(
+          "ECDSA+SHA256:rsa_pss_rsae_sha256:RSA+SHA256:ECDSA+SHA384:ECDSA+SHA1:",
+          "rsa_pss_rsae_sha384:RSA+SHA384:rsa_pss_rsae_sha512:RSA+SHA512:RSA+SHA1"
+        ),
+      },
+    }
+  }
+  /// 按浏览器的顺序设置密码套件、密钥交换组和签名算法
+  #[cfg(feature = "tls")]
+  pub(crate) fn configure_tls(&self, builder: &mut SslConnectorBuilder) -> crate::Result<()> {
+    let profile = self.tls_profile();
+    builder.set_min_proto_version(Some(SslVersion::TLS1_2))?;
+    // AWS-LC 不能设置 TLS 1.3 的密码套件和签名算法，保留它自己的顺序
+    #[cfg(not(any(feature = "aws-lc", feature = "aws-lc-fips")))]
+    builder.set_ciphersuites(profile.ciphersuites)?;
+    builder.set_cipher_list(profile.ciphers)?;
+    builder.set_groups_list(profile.groups)?;
+    #[cfg(not(any(feature = "aws-lc", feature = "aws-lc-fips")))]
+    builder.set_sigalgs_list(profile.sigalgs)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Browser;
+  use crate::MockTransport;
+
+  #[test]
+  fn impersonate_headers() {
+    let mock = MockTransport::new().respond("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    let client = crate::Client::builder()
+      .impersonate(Browser::Firefox120)
+      .transport(mock.clone())
+      .build()
+      .unwrap();
+    client.get("http://example.com/").send().unwrap();
+    drop(client);
+    let sent = String::from_utf8(mock.requests().remove(0)).unwrap();
+    let names: Vec<&str> = sent
+      .lines()
+      .skip(1)
+      .take_while(|line| !line.is_empty())
+      .map(|line| line.split(':').next().unwrap())
+      .collect();
+    assert_eq!(
+      names,
+      [
+        "Host",
+        "User-Agent",
+        "Accept",
+        "Accept-Language",
+        "Accept-Encoding",
+        "Connection",
+        "Upgrade-Insecure-Requests",
+        "Sec-Fetch-Dest",
+        "Sec-Fetch-Mode",
+        "Sec-Fetch-Site",
+        "Sec-Fetch-User",
+      ]
+    );
+    assert!(sent.contains("\r\nUser-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:120.0)"));
+    #[cfg(feature = "tls")]
+    for browser in [Browser::Chrome120, Browser::Firefox120, Browser::Safari17] {
+      crate::ConnectorBuilder::default().impersonate(browser).build().unwrap();
+    }
+  }
+}
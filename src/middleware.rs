@@ -0,0 +1,148 @@
+use crate::errors::Result;
+use crate::{Client, Request, Response};
+use std::sync::Arc;
+
+/// A layer that every request of a `Client` passes through.
+///
+/// Middlewares run in the order they were added with
+/// [`ClientBuilder::with`](crate::ClientBuilder::with). Each one receives the
+/// request and a [`Next`] handle; calling [`Next::run`] hands the request to the
+/// following middleware and finally to the client, which sends it with its
+/// redirect, authentication and retry handling. A middleware may change the
+/// request before passing it on, inspect or replace the response, call `next`
+/// several times, or answer without sending anything.
+///
+/// Closures with the signature `Fn(Request, Next<'_>) -> Result<Response>` are
+/// middlewares too.
+///
+/// # Example
+///
+/// ```rust
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::{Next, Request};
+///
+/// let client = slinger::Client::builder()
+///   .with(|mut request: Request, next: Next<'_>| {
+///     request
+///       .headers_mut()
+///       .insert("x-trace-id", http::HeaderValue::from_static("42"));
+///     let response = next.run(request)?;
+///     println!("{}", response.status_code());
+///     Ok(response)
+///   })
+///   .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+  /// Handle `request`, usually by passing it on with `next.run(request)`.
+  fn handle(&self, request: Request, next: Next<'_>) -> Result<Response>;
+}
+
+impl<F> Middleware for F
+  where
+    F: Fn(Request, Next<'_>) -> Result<Response> + Send + Sync + 'static,
+{
+  fn handle(&self, request: Request, next: Next<'_>) -> Result<Response> {
+    self(request, next)
+  }
+}
+
+impl std::fmt::Debug for dyn Middleware {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Middleware")
+  }
+}
+
+/// The rest of the middleware chain, see [`Middleware`].
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+  client: &'a Client,
+  middlewares: &'a [Arc<dyn Middleware>],
+}
+
+impl std::fmt::Debug for Next<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Next")
+      .field("remaining", &self.middlewares.len())
+      .finish()
+  }
+}
+
+impl<'a> Next<'a> {
+  pub(crate) fn new(client: &'a Client, middlewares: &'a [Arc<dyn Middleware>]) -> Self {
+    Next {
+      client,
+      middlewares,
+    }
+  }
+  /// Pass `request` to the next middleware, or send it when none is left.
+  pub fn run(self, request: Request) -> Result<Response> {
+    match self.middlewares.split_first() {
+      Some((middleware, rest)) => middleware.handle(
+        request,
+        Next {
+          client: self.client,
+          middlewares: rest,
+        },
+      ),
+      None => self.client.send(request),
+    }
+  }
+  /// The `Client` executing the chain.
+  pub fn client(&self) -> &'a Client {
+    self.client
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Client, Next, Request, Response};
+
+  #[test]
+  fn chain_order_and_short_circuit() {
+    let client = Client::builder()
+      .with(|mut request: Request, next: Next<'_>| {
+        request
+          .headers_mut()
+          .insert("x-step", http::HeaderValue::from_static("outer"));
+        next.run(request)
+      })
+      .with(|request: Request, _next: Next<'_>| {
+        let step = request.headers().get("x-step").cloned().unwrap();
+        Ok(Response::builder().header("x-step", step).body("").unwrap().into())
+      })
+      .build()
+      .unwrap();
+    let response = client.get("http://127.0.0.1:1/").send().unwrap();
+    assert_eq!(response.headers().get("x-step").unwrap(), "outer");
+  }
+
+  #[test]
+  fn send_all_keeps_order() {
+    let client = Client::builder()
+      .with(|request: Request, _next: Next<'_>| {
+        let path = request.uri().path().to_string();
+        if path == "/3" {
+          return Err(crate::errors::new_io_error(std::io::ErrorKind::Other, "failed"));
+        }
+        // 前面的请求更慢，完成顺序和请求顺序相反
+        let n: u64 = path[1..].parse().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(40 - n * 4));
+        Ok(Response::builder().body(path).unwrap().into())
+      })
+      .build()
+      .unwrap();
+    let requests: Vec<_> = (0..10)
+      .map(|n| client.get(format!("http://127.0.0.1:1/{}", n)).build().unwrap())
+      .collect();
+    let results = client.send_all(requests, 4);
+    assert_eq!(results.len(), 10);
+    for (n, result) in results.iter().enumerate() {
+      match result {
+        Ok(response) => assert_eq!(response.text().unwrap(), format!("/{}", n)),
+        Err(_) => assert_eq!(n, 3),
+      }
+    }
+  }
+}
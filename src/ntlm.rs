@@ -0,0 +1,396 @@
+//! NTLM (NTLMv2) message exchange
+use crate::errors::{new_io_error, Result};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use md4::{Digest, Md4};
+use md5::Md5;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static CLIENT_CHALLENGE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+const NEGOTIATE_FLAGS: u32 = NEGOTIATE_UNICODE
+  | NEGOTIATE_OEM
+  | REQUEST_TARGET
+  | NEGOTIATE_NTLM
+  | NEGOTIATE_ALWAYS_SIGN
+  | NEGOTIATE_EXTENDED_SESSIONSECURITY
+  | NEGOTIATE_128
+  | NEGOTIATE_56;
+const MSV_AV_EOL: u16 = 0;
+const MSV_AV_TIMESTAMP: u16 = 7;
+// 1601-01-01 到 1970-01-01 的秒数
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+/// NTLM credentials.
+///
+/// Attached to a request with [`RequestBuilder::ntlm_auth`](crate::RequestBuilder::ntlm_auth)
+/// or to an HTTP proxy with [`Proxy::ntlm_auth`](crate::Proxy::ntlm_auth), the client runs the
+/// negotiate/challenge/authenticate (type 1/2/3) exchange over the same kept-alive connection
+/// whenever the server answers `401` (or the proxy `407`) offering `NTLM` or `Negotiate`.
+///
+/// The username may carry the domain as `DOMAIN\user`.
+///
+/// # Optional
+///
+/// This requires the optional `ntlm` feature to be enabled.
+#[derive(Clone, PartialEq)]
+pub struct NtlmAuth {
+  domain: String,
+  username: String,
+  password: String,
+  workstation: String,
+}
+
+impl std::fmt::Debug for NtlmAuth {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("NtlmAuth")
+      .field("domain", &self.domain)
+      .field("username", &self.username)
+      .field("workstation", &self.workstation)
+      .finish()
+  }
+}
+
+impl NtlmAuth {
+  /// new NTLM credentials
+  pub fn new<U: Into<String>, P: Into<String>>(username: U, password: P) -> Self {
+    let username = username.into();
+    let (domain, username) = match username.split_once('\\') {
+      Some((domain, username)) => (domain.to_string(), username.to_string()),
+      None => (String::new(), username),
+    };
+    NtlmAuth {
+      domain,
+      username,
+      password: password.into(),
+      workstation: String::new(),
+    }
+  }
+  /// Set the workstation name sent in the authenticate message.
+  pub fn workstation<W: Into<String>>(mut self, workstation: W) -> Self {
+    self.workstation = workstation.into();
+    self
+  }
+  /// Answer the `NTLM`/`Negotiate` challenge found in `headers` under `challenge_header`
+  /// (`WWW-Authenticate` or `Proxy-Authenticate`).
+  ///
+  /// A bare scheme is answered with the negotiate (type 1) message and a scheme carrying a
+  /// challenge (type 2) is answered with the authenticate (type 3) message. Returns `Ok(None)`
+  /// when neither scheme is offered.
+  pub fn respond(
+    &self,
+    headers: &HeaderMap,
+    challenge_header: &HeaderName,
+  ) -> Result<Option<HeaderValue>> {
+    let offer = headers
+      .get_all(challenge_header)
+      .iter()
+      .filter_map(|v| v.to_str().ok())
+      .filter_map(|v| {
+        let v = v.trim();
+        let (scheme, token) = v.split_once(' ').unwrap_or((v, ""));
+        if scheme.eq_ignore_ascii_case("ntlm") || scheme.eq_ignore_ascii_case("negotiate") {
+          Some((scheme.to_string(), token.trim().to_string()))
+        } else {
+          None
+        }
+      })
+      // 同时提供两种方案时优先使用NTLM
+      .min_by_key(|(scheme, _)| !scheme.eq_ignore_ascii_case("ntlm"));
+    let (scheme, token) = match offer {
+      Some(offer) => offer,
+      None => return Ok(None),
+    };
+    let message = if token.is_empty() {
+      negotiate_message()
+    } else {
+      let challenge = BASE64_STANDARD
+        .decode(token.as_bytes())
+        .map_err(|_| new_io_error(std::io::ErrorKind::InvalidData, "invalid ntlm challenge"))?;
+      let challenge = ChallengeMessage::parse(&challenge)?;
+      self.authenticate_message(&challenge, client_challenge(), None)
+    };
+    let mut header =
+      HeaderValue::try_from(format!("{} {}", scheme, BASE64_STANDARD.encode(message)))?;
+    header.set_sensitive(true);
+    Ok(Some(header))
+  }
+  fn nt_owf_v2(&self) -> Vec<u8> {
+    let nt_hash = Md4::digest(utf16le(&self.password));
+    let mut identity = utf16le(&self.username.to_uppercase());
+    identity.extend(utf16le(&self.domain));
+    hmac_md5(&nt_hash, &[&identity])
+  }
+  fn authenticate_message(
+    &self,
+    challenge: &ChallengeMessage,
+    client_challenge: [u8; 8],
+    timestamp: Option<u64>,
+  ) -> Vec<u8> {
+    let key = self.nt_owf_v2();
+    let timestamp = timestamp
+      .or(challenge.timestamp)
+      .unwrap_or_else(filetime_now);
+    let mut temp = vec![0x01, 0x01, 0, 0, 0, 0, 0, 0];
+    temp.extend(timestamp.to_le_bytes());
+    temp.extend(client_challenge);
+    temp.extend([0u8; 4]);
+    temp.extend(&challenge.target_info);
+    temp.extend([0u8; 4]);
+    let nt_proof = hmac_md5(&key, &[&challenge.server_challenge, &temp]);
+    let mut nt_response = nt_proof;
+    nt_response.extend(&temp);
+    let mut lm_response = hmac_md5(&key, &[&challenge.server_challenge, &client_challenge]);
+    lm_response.extend(client_challenge);
+
+    let unicode = challenge.flags & NEGOTIATE_UNICODE != 0;
+    let encode = |s: &str| -> Vec<u8> {
+      if unicode {
+        utf16le(s)
+      } else {
+        s.as_bytes().to_vec()
+      }
+    };
+    let payloads = [
+      lm_response,
+      nt_response,
+      encode(&self.domain),
+      encode(&self.username),
+      encode(&self.workstation),
+      Vec::new(),
+    ];
+    // 固定头部 64 字节，之后依次是各个字段的内容
+    let mut message = Vec::with_capacity(64);
+    message.extend(SIGNATURE);
+    message.extend(3u32.to_le_bytes());
+    let mut offset = 64u32;
+    for payload in payloads.iter() {
+      message.extend((payload.len() as u16).to_le_bytes());
+      message.extend((payload.len() as u16).to_le_bytes());
+      message.extend(offset.to_le_bytes());
+      offset += payload.len() as u32;
+    }
+    let flags = challenge.flags & NEGOTIATE_FLAGS | NEGOTIATE_NTLM;
+    message.extend(flags.to_le_bytes());
+    for payload in payloads {
+      message.extend(payload);
+    }
+    message
+  }
+}
+
+/// type 2 message
+#[derive(Debug, Clone, PartialEq)]
+struct ChallengeMessage {
+  flags: u32,
+  server_challenge: [u8; 8],
+  target_info: Vec<u8>,
+  timestamp: Option<u64>,
+}
+
+impl ChallengeMessage {
+  fn parse(message: &[u8]) -> Result<Self> {
+    let invalid = || new_io_error(std::io::ErrorKind::InvalidData, "invalid ntlm challenge");
+    if message.len() < 32 || &message[..8] != SIGNATURE || read_u32(message, 8) != Some(2) {
+      return Err(invalid());
+    }
+    let flags = read_u32(message, 20).ok_or_else(invalid)?;
+    let mut server_challenge = [0u8; 8];
+    server_challenge.copy_from_slice(&message[24..32]);
+    let target_info = if message.len() >= 48 {
+      let len = read_u16(message, 40).ok_or_else(invalid)? as usize;
+      let offset = read_u32(message, 44).ok_or_else(invalid)? as usize;
+      message
+        .get(offset..offset + len)
+        .ok_or_else(invalid)?
+        .to_vec()
+    } else {
+      Vec::new()
+    };
+    let timestamp = av_pairs(&target_info)
+      .into_iter()
+      .find(|(id, value)| *id == MSV_AV_TIMESTAMP && value.len() == 8)
+      .map(|(_, value)| u64::from_le_bytes(value.try_into().unwrap_or_default()));
+    Ok(ChallengeMessage {
+      flags,
+      server_challenge,
+      target_info,
+      timestamp,
+    })
+  }
+}
+
+fn av_pairs(target_info: &[u8]) -> Vec<(u16, &[u8])> {
+  let mut pairs = Vec::new();
+  let mut pos = 0;
+  while let (Some(id), Some(len)) = (read_u16(target_info, pos), read_u16(target_info, pos + 2)) {
+    if id == MSV_AV_EOL {
+      break;
+    }
+    let start = pos + 4;
+    match target_info.get(start..start + len as usize) {
+      Some(value) => pairs.push((id, value)),
+      None => break,
+    }
+    pos = start + len as usize;
+  }
+  pairs
+}
+
+/// type 1 message
+fn negotiate_message() -> Vec<u8> {
+  let mut message = Vec::with_capacity(32);
+  message.extend(SIGNATURE);
+  message.extend(1u32.to_le_bytes());
+  message.extend(NEGOTIATE_FLAGS.to_le_bytes());
+  // 不携带域名和工作站
+  message.extend([0u8; 16]);
+  message
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(buf.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(buf.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+  s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+fn hmac_md5(key: &[u8], data: &[&[u8]]) -> Vec<u8> {
+  let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC can take key of any size");
+  for d in data {
+    mac.update(d);
+  }
+  mac.finalize().into_bytes().to_vec()
+}
+
+fn filetime_now() -> u64 {
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default();
+  (now.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000 + u64::from(now.subsec_nanos() / 100)
+}
+
+fn client_challenge() -> [u8; 8] {
+  let counter = CLIENT_CHALLENGE_COUNTER.fetch_add(1, Ordering::Relaxed);
+  let seed = format!("{}:{}:{}", filetime_now(), counter, std::process::id());
+  let mut challenge = [0u8; 8];
+  challenge.copy_from_slice(&Md5::digest(seed.as_bytes())[..8]);
+  challenge
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // MS-NLMP 4.2.4 NTLMv2 Authentication
+  #[test]
+  fn ms_nlmp_ntlmv2_proof() {
+    let auth = NtlmAuth::new("Domain\\User", "Password");
+    assert_eq!(
+      auth.nt_owf_v2(),
+      [
+        0x0c, 0x86, 0x8a, 0x40, 0x3b, 0xfd, 0x7a, 0x93, 0xa3, 0x00, 0x1e, 0xf2, 0x2e, 0xf0, 0x2e,
+        0x3f
+      ]
+    );
+    let target_info = vec![
+      0x02, 0x00, 0x0c, 0x00, 0x44, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x61, 0x00, 0x69, 0x00, 0x6e,
+      0x00, 0x01, 0x00, 0x0c, 0x00, 0x53, 0x00, 0x65, 0x00, 0x72, 0x00, 0x76, 0x00, 0x65, 0x00,
+      0x72, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let challenge = ChallengeMessage {
+      flags: 0xe28a8233,
+      server_challenge: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+      target_info,
+      timestamp: None,
+    };
+    let message = auth.authenticate_message(&challenge, [0xaa; 8], Some(0));
+    let nt_offset = read_u32(&message, 24).unwrap() as usize;
+    assert_eq!(
+      &message[nt_offset..nt_offset + 16],
+      [
+        0x68, 0xcd, 0x0a, 0xb8, 0x51, 0xe5, 0x1c, 0x96, 0xaa, 0xbc, 0x92, 0x7b, 0xeb, 0xef, 0x6a,
+        0x1c
+      ]
+    );
+    let lm_offset = read_u32(&message, 16).unwrap() as usize;
+    assert_eq!(
+      &message[lm_offset..lm_offset + 16],
+      [
+        0x86, 0xc3, 0x50, 0x97, 0xac, 0x9c, 0xec, 0x10, 0x25, 0x54, 0x76, 0x4a, 0x57, 0xcc, 0xcc,
+        0x19
+      ]
+    );
+  }
+
+  #[test]
+  fn ntlm_not_sent_after_cross_host_redirect() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    // 第三方主机对每个请求都要求 NTLM 认证，记下收到的请求
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let third = TcpListener::bind("127.0.0.1:0").unwrap();
+    let third_addr = third.local_addr().unwrap();
+    let requests = seen.clone();
+    std::thread::spawn(move || {
+      for stream in third.incoming() {
+        let Ok(mut stream) = stream else { break };
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = stream.read(&mut buf) {
+          if n == 0 {
+            break;
+          }
+          requests.lock().unwrap().push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+          let challenge =
+            "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: NTLM\r\nContent-Length: 0\r\n\r\n";
+          if stream.write_all(challenge.as_bytes()).is_err() {
+            break;
+          }
+        }
+      }
+    });
+    let origin = TcpListener::bind("127.0.0.1:0").unwrap();
+    let origin_addr = origin.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let (mut stream, _) = origin.accept().unwrap();
+      let mut buf = [0u8; 4096];
+      let _ = stream.read(&mut buf);
+      let redirect = format!(
+        "HTTP/1.1 302 Found\r\nLocation: http://{}/\r\nContent-Length: 0\r\n\r\n",
+        third_addr
+      );
+      let _ = stream.write_all(redirect.as_bytes());
+    });
+    let client = crate::Client::builder()
+      .timeout(std::time::Duration::from_secs(5))
+      .build()
+      .unwrap();
+    let response = client
+      .get(format!("http://{}/", origin_addr))
+      .ntlm_auth("DOMAIN\\alice", "secret")
+      .send()
+      .unwrap();
+    assert_eq!(response.status_code(), http::StatusCode::UNAUTHORIZED);
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert!(!seen[0].contains("authorization"));
+  }
+}
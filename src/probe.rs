@@ -0,0 +1,146 @@
+use crate::errors::{new_io_error, Result};
+use crate::{Client, Response};
+use http::Uri;
+
+/// The outcome of [`Client::probe`].
+#[derive(Debug)]
+pub struct SchemeProbe {
+  /// The URL that answered, with the scheme and port that were tried
+  pub uri: Uri,
+  /// The response to the `GET` request sent to `uri`, redirects followed
+  pub response: Response,
+}
+
+impl SchemeProbe {
+  /// The scheme that answered, `http` or `https`.
+  pub fn scheme(&self) -> &str {
+    self.uri.scheme_str().unwrap_or_default()
+  }
+  /// The port that answered.
+  pub fn port(&self) -> u16 {
+    crate::connector::default_port(&self.uri).unwrap_or_default()
+  }
+}
+
+pub(crate) fn run(client: &Client, target: &str, http_first: bool) -> Result<SchemeProbe> {
+  let candidates = candidates(target, http_first)?;
+  let mut last_err = None;
+  for uri in candidates {
+    match client.get(uri.clone()).send() {
+      Ok(response) => return Ok(SchemeProbe { uri, response }),
+      Err(err) => {
+        #[cfg(feature = "trace")]
+        tracing::debug!(uri = %uri, error = %err, "probe failed");
+        last_err = Some(err);
+      }
+    }
+  }
+  Err(last_err.unwrap_or_else(|| {
+    new_io_error(std::io::ErrorKind::InvalidInput, "no scheme to probe")
+  }))
+}
+
+/// 要尝试的地址：写了协议时只试这一个，否则按顺序试 https 和 http
+fn candidates(target: &str, http_first: bool) -> Result<Vec<Uri>> {
+  let (schemes, rest) = match target.split_once("://") {
+    Some((scheme, rest)) => (vec![scheme.to_ascii_lowercase()], rest),
+    // 没有 TLS 时 https 会以明文连接，不能当作 https 应答
+    None if cfg!(feature = "tls") && !http_first => {
+      (vec!["https".to_string(), "http".to_string()], target)
+    }
+    None if cfg!(feature = "tls") => (vec!["http".to_string(), "https".to_string()], target),
+    None => (vec!["http".to_string()], target),
+  };
+  let (authority, path) = match rest.find(['/', '?']) {
+    Some(index) => rest.split_at(index),
+    None => (rest, "/"),
+  };
+  let path = if path.starts_with('?') {
+    format!("/{}", path)
+  } else {
+    path.to_string()
+  };
+  schemes
+    .into_iter()
+    .map(|scheme| {
+      Uri::builder()
+        .scheme(scheme.as_str())
+        .authority(authority)
+        .path_and_query(path.as_str())
+        .build()
+        .map_err(Into::into)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::candidates;
+  use crate::Client;
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+
+  #[test]
+  fn probe_schemes() {
+    let uris = |target: &str, http_first: bool| -> Vec<String> {
+      candidates(target, http_first).unwrap().iter().map(|u| u.to_string()).collect()
+    };
+    assert_eq!(uris("HTTP://example.com:8080", false), ["http://example.com:8080/"]);
+    assert_eq!(uris("https://example.com/a?b", true), ["https://example.com/a?b"]);
+    #[cfg(feature = "tls")]
+    {
+      assert_eq!(uris("example.com", false), ["https://example.com/", "http://example.com/"]);
+      assert_eq!(uris("[::1]:8443?x", true), ["http://[::1]:8443/?x", "https://[::1]:8443/?x"]);
+    }
+    #[cfg(not(feature = "tls"))]
+    assert_eq!(uris("example.com", false), ["http://example.com/"]);
+    // 明文服务器拒绝 TLS 握手，退回 http
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { break };
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).unwrap_or_default();
+        if buf[..n].starts_with(b"GET ") {
+          let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+        }
+      }
+    });
+    let client = Client::builder().timeout(std::time::Duration::from_secs(5)).build().unwrap();
+    let probe = client.probe(&addr.to_string()).unwrap();
+    assert_eq!(probe.scheme(), "http");
+    assert_eq!(probe.port(), addr.port());
+    assert_eq!(probe.response.text().unwrap(), "ok");
+  }
+
+  #[cfg(feature = "tls")]
+  #[test]
+  fn probe_certificates() {
+    let acceptor = crate::tls::test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else { break };
+        let Ok(mut stream) = acceptor.accept(stream) else {
+          continue;
+        };
+        let mut buf = [0u8; 1024];
+        let _ = stream.read(&mut buf);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+      }
+    });
+    let timeout = std::time::Duration::from_secs(5);
+    // 默认不校验证书，自签名的 https 也算应答
+    let client = Client::builder().timeout(timeout).build().unwrap();
+    assert_eq!(client.probe(&addr.to_string()).unwrap().scheme(), "https");
+    let strict = Client::builder()
+      .timeout(timeout)
+      .danger_accept_invalid_certs(false)
+      .build()
+      .unwrap();
+    let err = strict.probe(&format!("https://{}", addr)).unwrap_err();
+    assert_eq!(err.kind(), crate::ErrorKind::TlsHandshake, "{:?}", err);
+  }
+}
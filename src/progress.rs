@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// 进度回调，参数是已经传输的字节数和已知的总字节数
+#[derive(Clone)]
+pub(crate) struct Progress(pub(crate) Arc<dyn Fn(u64, Option<u64>) + Send + Sync>);
+
+impl std::fmt::Debug for Progress {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Progress")
+  }
+}
+
+impl Progress {
+  pub(crate) fn report(&self, done: u64, total: Option<u64>) {
+    (self.0)(done, total)
+  }
+}
+
+/// 请求上设置的下载进度回调，放在请求的 extensions 中
+#[derive(Clone, Debug)]
+pub(crate) struct DownloadProgress(pub(crate) Progress);
+
+/// 请求上设置的上传进度回调，放在请求的 extensions 中
+#[derive(Clone, Debug)]
+pub(crate) struct UploadProgress(pub(crate) Progress);
+
+/// 成功响应的响应体直接写到这个文件，不留在内存里
+#[derive(Clone, Debug)]
+pub(crate) struct DownloadTo(pub(crate) PathBuf);
+
+/// 写入时报告已经写出的字节数，用于总长度未知的分块请求体
+pub(crate) struct ProgressWriter<'a, W: Write> {
+  inner: W,
+  progress: &'a Progress,
+  written: u64,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+  pub(crate) fn new(inner: W, progress: &'a Progress) -> Self {
+    ProgressWriter {
+      inner,
+      progress,
+      written: 0,
+    }
+  }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    self.written += n as u64;
+    self.progress.report(self.written, None);
+    Ok(n)
+  }
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  #[test]
+  fn download_and_upload_progress() {
+    let body = "x".repeat(100 * 1024);
+    let mock = crate::MockTransport::new()
+      .respond("HTTP/1.1 302 Found\r\nLocation: /file\r\nContent-Length: 3\r\n\r\nbye")
+      .respond(format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+    let client = crate::Client::builder().transport(mock).build().unwrap();
+    let path = std::env::temp_dir().join(format!("slinger-download-{}", std::process::id()));
+    let downloaded = Arc::new(Mutex::new(Vec::new()));
+    let uploaded = Arc::new(Mutex::new(Vec::new()));
+    let (d, u) = (downloaded.clone(), uploaded.clone());
+    let resp = client
+      .post("http://example.com/")
+      .body(vec![0u8; 40 * 1024])
+      .download_progress(move |done, total| d.lock().unwrap().push((done, total)))
+      .upload_progress(move |sent, total| u.lock().unwrap().push((sent, total)))
+      .download_to(&path)
+      .send()
+      .unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert!(resp.body().as_ref().is_none_or(|b| b.is_empty()));
+    assert_eq!(std::fs::read(&path).unwrap(), body.as_bytes());
+    let downloaded = downloaded.lock().unwrap();
+    assert_eq!(downloaded.first(), Some(&(3, Some(3))));
+    assert_eq!(downloaded.last(), Some(&(body.len() as u64, Some(body.len() as u64))));
+    let total = Some(40 * 1024);
+    assert_eq!(
+      uploaded.lock().unwrap()[..3],
+      [(16 * 1024, total), (32 * 1024, total), (40 * 1024, total)]
+    );
+    assert_eq!(resp.copy_to_file(&path).unwrap(), 0);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
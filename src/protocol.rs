@@ -0,0 +1,97 @@
+use crate::errors::{new_io_error, Result};
+use http::Version;
+
+/// Decides which HTTP version a `Client` speaks and what it offers with ALPN.
+///
+/// The policy is the single place where version decisions are made: the
+/// connector derives its ALPN list from it and every request is sent with the
+/// version it resolves to. HTTP/2 is spoken over TLS with the `http2` feature
+/// when the server picks `h2`; otherwise, and for plain `http://` URLs, the
+/// preferring policies fall back to HTTP/1.1. HTTP/3 is not implemented, and
+/// forcing a version this build can not speak is rejected when the client is
+/// built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ProtocolPolicy {
+  /// Only HTTP/1.1, offered as `http/1.1` with ALPN.
+  #[default]
+  Http1Only,
+  /// Use HTTP/2 when the server supports it, otherwise HTTP/1.1.
+  PreferHttp2,
+  /// Try HTTP/3 first, then HTTP/2, then HTTP/1.1.
+  Http3First,
+  /// Always send this exact version, without fallback.
+  Force(Version),
+}
+
+impl ProtocolPolicy {
+  /// The versions this policy accepts, most preferred first, limited to the
+  /// ones this crate can speak.
+  pub fn versions(&self) -> Vec<Version> {
+    let wanted = match self {
+      ProtocolPolicy::Http1Only => vec![Version::HTTP_11],
+      ProtocolPolicy::PreferHttp2 => vec![Version::HTTP_2, Version::HTTP_11],
+      ProtocolPolicy::Http3First => vec![Version::HTTP_3, Version::HTTP_2, Version::HTTP_11],
+      ProtocolPolicy::Force(version) => vec![*version],
+    };
+    wanted.into_iter().filter(is_supported).collect()
+  }
+  /// The protocol identifiers to offer with ALPN, in order.
+  pub fn alpn_protocols(&self) -> Vec<String> {
+    self
+      .versions()
+      .iter()
+      .filter_map(|version| match *version {
+        Version::HTTP_2 => Some("h2".to_string()),
+        Version::HTTP_11 => Some("http/1.1".to_string()),
+        Version::HTTP_10 => Some("http/1.0".to_string()),
+        Version::HTTP_09 => Some("http/0.9".to_string()),
+        _ => None,
+      })
+      .collect()
+  }
+  /// The version requests are sent with.
+  pub(crate) fn request_version(&self) -> Version {
+    self.versions().first().copied().unwrap_or(Version::HTTP_11)
+  }
+  /// 服务器没有协商出请求的版本时能否退回 HTTP/1.1
+  pub(crate) fn allows_fallback(&self) -> bool {
+    !matches!(self, ProtocolPolicy::Force(_))
+  }
+  /// 强制的版本无法使用时返回错误
+  pub(crate) fn check(&self) -> Result<()> {
+    if self.versions().is_empty() {
+      return Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "protocol policy forces an unsupported HTTP version",
+      ));
+    }
+    Ok(())
+  }
+}
+
+fn is_supported(version: &Version) -> bool {
+  matches!(*version, Version::HTTP_09 | Version::HTTP_10 | Version::HTTP_11)
+    || (cfg!(feature = "http2") && *version == Version::HTTP_2)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ProtocolPolicy;
+  use http::Version;
+
+  #[test]
+  fn fallback_to_http1() {
+    #[cfg(not(feature = "http2"))]
+    {
+      assert_eq!(ProtocolPolicy::PreferHttp2.versions(), vec![Version::HTTP_11]);
+      assert!(ProtocolPolicy::Force(Version::HTTP_2).check().is_err());
+    }
+    #[cfg(feature = "http2")]
+    assert_eq!(ProtocolPolicy::Http3First.alpn_protocols(), vec!["h2", "http/1.1"]);
+    assert!(ProtocolPolicy::Force(Version::HTTP_3).check().is_err());
+    assert_eq!(
+      ProtocolPolicy::Force(Version::HTTP_10).request_version(),
+      Version::HTTP_10
+    );
+  }
+}
@@ -1,21 +1,29 @@
-use crate::connector::Connector;
+use crate::auth::encode_basic_auth;
+#[cfg(all(feature = "ssh", unix))]
+use crate::connector::bare_host;
+use crate::connector::{default_port, ip_literal, without_zone};
 use crate::errors::{new_io_error, Error, ReplyError, Result};
+#[cfg(feature = "ntlm")]
+use crate::ntlm::NtlmAuth;
 use crate::response::ResponseBuilder;
 use crate::socket::Socket;
-use crate::{Request, Response};
+use crate::{Request, RequestTarget, Response};
 use bytes::Bytes;
 use http::uri::Authority;
 use http::HeaderValue;
 use percent_encoding::percent_decode;
 use std::io::{BufReader, Read, Write};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
-use std::str::FromStr;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
+#[cfg(all(feature = "ssh", unix))]
+use std::time::Duration;
 
 impl Proxy {
   fn http(host: &str, addr: SocketAddr) -> Result<Self> {
     Ok(Proxy::HTTP(HttpProxy {
       https: false,
       auth: None,
+      #[cfg(feature = "ntlm")]
+      ntlm: None,
       addr,
       host: host.to_string(),
     }))
@@ -25,6 +33,8 @@ impl Proxy {
     Ok(Proxy::HTTP(HttpProxy {
       https: true,
       auth: None,
+      #[cfg(feature = "ntlm")]
+      ntlm: None,
       addr,
       host: host.to_string(),
     }))
@@ -57,11 +67,69 @@ impl Proxy {
       Proxy::Socket(ref mut s) => {
         s.set_auth(username.into(), password.into());
       }
+      // 用户名作为 ssh 的登录名，带密码的 ssh 代理在解析时已经拒绝
+      #[cfg(all(feature = "ssh", unix))]
+      Proxy::Ssh(ref mut s) => {
+        s.user = Some(username.into());
+      }
     }
   }
+  /// Authenticate to an HTTP proxy with NTLM.
+  ///
+  /// When the proxy answers the `CONNECT` request with `407` offering `NTLM` or
+  /// `Negotiate`, the negotiate/challenge/authenticate exchange is run on the same
+  /// connection before the tunnel is used. Has no effect on SOCKS5 proxies.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `ntlm` feature to be enabled.
+  #[cfg(feature = "ntlm")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "ntlm")))]
+  pub fn ntlm_auth<U: Into<String>, P: Into<String>>(mut self, username: U, password: P) -> Self {
+    if let Proxy::HTTP(h) = &mut self {
+      h.ntlm = Some(NtlmAuth::new(username, password));
+    }
+    self
+  }
+  /// Set the program started for an `ssh://` jump host.
+  ///
+  /// Default is `ssh` from `PATH`. Has no effect on other proxies.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `ssh` feature to be enabled.
+  #[cfg(all(feature = "ssh", unix))]
+  #[cfg_attr(docsrs, doc(cfg(feature = "ssh")))]
+  pub fn ssh_program<P: Into<std::path::PathBuf>>(mut self, program: P) -> Self {
+    if let Proxy::Ssh(s) = &mut self {
+      s.program = program.into();
+    }
+    self
+  }
+  /// Append arguments passed to `ssh` for an `ssh://` jump host, such as
+  /// `["-i", "/path/to/key"]` or `["-F", "/path/to/config"]`.
+  ///
+  /// Has no effect on other proxies.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `ssh` feature to be enabled.
+  #[cfg(all(feature = "ssh", unix))]
+  #[cfg_attr(docsrs, doc(cfg(feature = "ssh")))]
+  pub fn ssh_args<I, S>(mut self, args: I) -> Self
+    where
+      I: IntoIterator<Item=S>,
+      S: Into<String>,
+  {
+    if let Proxy::Ssh(s) = &mut self {
+      s.args.extend(args.into_iter().map(Into::into));
+    }
+    self
+  }
   /// Convert a URL into a proxy
   ///
-  /// Supported schemes: HTTP, HTTPS, (SOCKS5, SOCKS5H).
+  /// Supported schemes: HTTP, HTTPS, (SOCKS5, SOCKS5H), and SSH with the
+  /// `ssh` feature.
   pub fn parse<U>(url: U) -> Result<Self>
     where
       http::Uri: TryFrom<U>,
@@ -86,12 +154,19 @@ impl Proxy {
           std::io::ErrorKind::InvalidData,
           "no port in url",
         ))?;
+      if let Some(addr) = ip_literal(host, port) {
+        return Ok(addr?);
+      }
       (host, port).to_socket_addrs()?.next().ok_or(new_io_error(
         std::io::ErrorKind::InvalidData,
         "no addr in url",
       ))
     };
 
+    #[cfg(all(feature = "ssh", unix))]
+    if url.scheme_str() == Some("ssh") {
+      return SshProxy::from_uri(&url).map(Proxy::Ssh);
+    }
     let mut scheme = match url.scheme_str() {
       Some("http") => Self::http(host, to_addr()?)?,
       Some("https") => Self::https(host, to_addr()?)?,
@@ -112,17 +187,55 @@ impl Proxy {
     }
     Ok(scheme)
   }
-  fn to_addr(&self) -> Result<SocketAddr> {
+  /// 对应的 curl 参数，ssh 跳板没有对应的参数
+  pub(crate) fn curl_args(&self) -> Vec<String> {
+    match self {
+      Proxy::HTTP(h) => {
+        let scheme = if h.https { "https" } else { "http" };
+        let mut args = vec![
+          "--proxy".to_string(),
+          format!("{}://{}:{}", scheme, h.host, h.addr.port()),
+        ];
+        if let Some(auth) = &h.auth {
+          args.push("--proxy-header".to_string());
+          args.push(format!(
+            "Proxy-Authorization: {}",
+            String::from_utf8_lossy(auth.as_bytes())
+          ));
+        }
+        args
+      }
+      Proxy::Socket(s) => {
+        let flag = if s.remote_dns { "--socks5-hostname" } else { "--socks5" };
+        let mut args = vec![flag.to_string(), format!("{}:{}", s.host, s.addr.port())];
+        if let Some(AuthenticationMethod::Password { username, password }) = &s.auth {
+          args.push("--proxy-user".to_string());
+          args.push(format!("{}:{}", username, password));
+        }
+        args
+      }
+      #[cfg(all(feature = "ssh", unix))]
+      Proxy::Ssh(_) => Vec::new(),
+    }
+  }
+  pub(crate) fn to_addr(&self) -> Result<SocketAddr> {
     match self.clone() {
       Proxy::HTTP(HttpProxy { addr, .. }) => Ok(addr),
       Proxy::Socket(s) => Ok(s.addr()),
+      #[cfg(all(feature = "ssh", unix))]
+      Proxy::Ssh(_) => Err(new_io_error(
+        std::io::ErrorKind::Unsupported,
+        "ssh proxy is reached through the ssh program",
+      )),
     }
   }
   #[cfg(feature = "tls")]
-  fn domain(&self) -> Result<&str> {
+  pub(crate) fn domain(&self) -> Result<&str> {
     match self {
       Proxy::HTTP(HttpProxy { host, .. }) => Ok(host.as_str()),
       Proxy::Socket(s) => Ok(s.host()),
+      #[cfg(all(feature = "ssh", unix))]
+      Proxy::Ssh(s) => Ok(s.host.as_str()),
     }
   }
 }
@@ -158,27 +271,6 @@ fn get_auth_from_authority(authority: Option<&Authority>) -> Option<(String, Opt
   }
 }
 
-pub fn encode_basic_auth<U, P>(username: U, password: Option<P>) -> HeaderValue
-  where
-    U: std::fmt::Display,
-    P: std::fmt::Display,
-{
-  use base64::prelude::BASE64_STANDARD;
-  use base64::write::EncoderWriter;
-
-  let mut buf = b"Basic ".to_vec();
-  {
-    let mut encoder = EncoderWriter::new(&mut buf, &BASE64_STANDARD);
-    let _ = write!(encoder, "{username}:");
-    if let Some(password) = password {
-      let _ = write!(encoder, "{password}");
-    }
-  }
-  let mut header = HeaderValue::from_bytes(&buf).expect("base64 is always valid HeaderValue");
-  header.set_sensitive(true);
-  header
-}
-
 /// Configuration of a proxy that a `Client` should pass requests to.
 ///
 /// A `Proxy` has a couple pieces to it:
@@ -211,12 +303,27 @@ pub fn encode_basic_auth<U, P>(username: U, password: Option<P>) -> HeaderValue
 /// # Ok(())
 /// # }
 /// ```
+///
+/// With the `"ssh"` feature on Unix, targets reachable only from a bastion
+/// can be reached through an SSH jump host:
+/// ```rust
+/// # #[cfg(all(feature = "ssh", unix))]
+/// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let proxy = slinger::Proxy::parse("ssh://scanner@bastion.example:2222")?
+///   .ssh_args(["-i", "/home/scanner/.ssh/id_ed25519"]);
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Clone, PartialEq)]
 pub enum Proxy {
   /// HTTP or HTTPS
   HTTP(HttpProxy),
   /// SOCKS5
   Socket(Socket5Proxy),
+  /// SSH jump host
+  #[cfg(all(feature = "ssh", unix))]
+  #[cfg_attr(docsrs, doc(cfg(feature = "ssh")))]
+  Ssh(SshProxy),
 }
 
 /// A particular scheme used for proxying requests.
@@ -224,33 +331,54 @@ pub enum Proxy {
 /// For example, HTTP
 #[derive(Clone, Debug, PartialEq)]
 pub struct HttpProxy {
-  https: bool,
+  pub(crate) https: bool,
   auth: Option<HeaderValue>,
+  #[cfg(feature = "ntlm")]
+  ntlm: Option<NtlmAuth>,
   addr: SocketAddr,
   host: String,
 }
 
 impl HttpProxy {
-  fn raw(&self, host_port: &str) -> Result<Bytes> {
-    // 生成隧道报文
+  fn raw(&self, host_port: &str, authorization: Option<&HeaderValue>) -> Result<Bytes> {
+    // 生成隧道报文，请求目标是 `host:port`
+    let authority = http::uri::Authority::try_from(host_port).map_err(http::Error::from)?;
     let mut br = Request::builder()
       .version(http::version::Version::HTTP_11)
-      .uri(http::Uri::builder().path_and_query(host_port).build()?)
+      .uri(http::Uri::builder().authority(authority.clone()).build()?)
       .method(http::method::Method::CONNECT)
-      .header("Host", host_port)
+      .header("Host", without_zone(host_port).as_ref())
       .header("Proxy-Connection", "Keep-Alive");
-    if let Some(auth) = &self.auth {
+    if let Some(auth) = authorization.or(self.auth.as_ref()) {
       br = br.header("Proxy-Authorization", auth);
     }
-    let br: Request = br.body(None)?.into();
+    let mut br: Request = br.body(None)?.into();
+    br.extensions_mut().insert(RequestTarget::Authority(authority));
     Ok(br.to_raw())
   }
-  #[allow(clippy::unused_io_amount)]
-  fn read_resp(&self, proxy_socket: &mut Socket) -> Result<Response> {
-    let mut buffer = [0; 128];
-    proxy_socket.read(&mut buffer[..])?;
-    let reader = BufReader::new(buffer.as_slice());
-    let proxy_response = ResponseBuilder::new(reader, Default::default()).build()?;
+  /// 发送CONNECT请求建立隧道，需要时完成NTLM认证
+  pub(crate) fn connect(&self, proxy_socket: &mut Socket, host_port: &str) -> Result<Response> {
+    proxy_socket.write_all(&self.raw(host_port, None)?)?;
+    proxy_socket.flush()?;
+    #[allow(unused_mut)]
+    let mut proxy_response = self.read_resp(proxy_socket)?;
+    #[cfg(feature = "ntlm")]
+    if let Some(ntlm) = &self.ntlm {
+      let mut rounds = 0;
+      while proxy_response.status_code() == http::StatusCode::PROXY_AUTHENTICATION_REQUIRED
+        && rounds < 2
+      {
+        let authorization =
+          match ntlm.respond(proxy_response.headers(), &http::header::PROXY_AUTHENTICATE)? {
+            Some(authorization) => authorization,
+            None => break,
+          };
+        proxy_socket.write_all(&self.raw(host_port, Some(&authorization))?)?;
+        proxy_socket.flush()?;
+        proxy_response = self.read_resp(proxy_socket)?;
+        rounds += 1;
+      }
+    }
     if proxy_response.status_code() != http::StatusCode::OK {
       return Err(new_io_error(
         std::io::ErrorKind::NotConnected,
@@ -259,118 +387,200 @@ impl HttpProxy {
     }
     Ok(proxy_response)
   }
+  fn read_resp(&self, proxy_socket: &mut Socket) -> Result<Response> {
+    // 逐字节读取响应头，避免把隧道里的数据读走
+    let mut head = Vec::new();
+    let mut one_byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+      proxy_socket.read_exact(&mut one_byte)?;
+      head.push(one_byte[0]);
+    }
+    let success = head
+      .splitn(3, |b| *b == b' ')
+      .nth(1)
+      .map(|code| code.starts_with(b"2"))
+      .unwrap_or_default();
+    if success {
+      // 隧道建立成功的响应没有body
+      let reader = BufReader::new(head.as_slice());
+      return ResponseBuilder::new(reader, Default::default()).build();
+    }
+    let reader = BufReader::new(head.as_slice().chain(proxy_socket));
+    ResponseBuilder::new(reader, Default::default()).build()
+  }
 }
 
-/// A particular scheme used for proxying requests.
+/// An SSH jump host.
+///
+/// Every connection starts `ssh -W host:port` to the jump host and talks to
+/// the target over the standard input and output of that process, the same
+/// way as OpenSSH's `ProxyJump`. Authentication, host keys and any other
+/// setting come from the ssh configuration and agent of the current user;
+/// `BatchMode` is always on so that a prompt fails the connection instead of
+/// blocking it.
 ///
-/// For example, SOCKS5
+/// The connection is returned once the jump host has opened the forwarding
+/// channel, found in the `LogLevel=DEBUG2` output of `ssh`; arguments that
+/// lower the log level make every connection fail. When `ssh` exits first,
+/// its last message is returned as a connect error, see
+/// [`Error::is_connect`](crate::Error::is_connect).
+#[cfg(all(feature = "ssh", unix))]
 #[derive(Clone, Debug, PartialEq)]
-pub struct ProxySocket {
-  target: http::Uri,
-  proxy: Option<Proxy>,
+pub struct SshProxy {
+  host: String,
+  user: Option<String>,
+  port: Option<u16>,
+  program: std::path::PathBuf,
+  args: Vec<String>,
 }
 
-impl ProxySocket {
-  /// new ProxySocket
-  pub fn new(target: &http::Uri, proxy: &Option<Proxy>) -> Self {
-    Self {
-      target: target.clone(),
-      proxy: proxy.clone(),
-    }
-  }
-  /// Connects to a target server through a connector
-  pub fn conn_with_connector(self, connector: &Connector) -> Result<Socket> {
-    let addr = self.get_conn_addr()?;
-    let mut socket = connector.connect_with_addr(addr)?;
-    match &self.proxy {
-      None => {
-        let _target_host = self.target.host().ok_or(new_io_error(
-          std::io::ErrorKind::InvalidData,
-          "no host in url",
-        ))?;
-        #[cfg(feature = "tls")]
-        if self.target.scheme() == Some(&http::uri::Scheme::HTTPS) {
-          socket = connector.upgrade_to_tls(socket, _target_host)?;
-        }
-        Ok(socket)
-      }
-      Some(proxy) => {
-        let target_host = self.target.host().ok_or(new_io_error(
-          std::io::ErrorKind::InvalidData,
-          "no host in url",
-        ))?;
-        let port = match self.target.port() {
-          Some(p) => p.as_u16(),
-          None => {
-            if self.target.scheme() == Some(&http::uri::Scheme::HTTPS) {
-              443u16
-            } else {
-              80u16
-            }
-          }
-        };
-        match proxy {
-          Proxy::HTTP(h) => {
-            #[cfg(feature = "tls")]
-            if h.https {
-              socket = connector.upgrade_to_tls(socket, proxy.domain()?)?;
-            }
-            socket.write_all(&h.raw(&format!("{}:{}", target_host, port))?)?;
-            socket.flush()?;
-            h.read_resp(&mut socket)?;
-            #[cfg(feature = "tls")]
-            if self.target.scheme() == Some(&http::uri::Scheme::HTTPS) {
-              socket = connector.upgrade_to_tls(socket, target_host)?;
-            }
-            Ok(socket)
-          }
-          Proxy::Socket(s) => {
-            s.conn(&mut socket, &self.target)?;
-            Ok(socket)
-          }
-        }
+#[cfg(all(feature = "ssh", unix))]
+impl SshProxy {
+  fn from_uri(url: &http::Uri) -> Result<Self> {
+    let host = url.host().ok_or(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "url not host",
+    ))?;
+    let userinfo = url
+      .authority()
+      .and_then(|authority| authority.as_str().rsplit_once('@'))
+      .map(|(userinfo, _)| userinfo);
+    let user = match userinfo {
+      Some(userinfo) if userinfo.contains(':') => {
+        return Err(new_io_error(
+          std::io::ErrorKind::InvalidInput,
+          "ssh proxy does not take a password, use a key or ssh-agent",
+        ));
       }
+      Some(username) => Some(percent_decode(username.as_bytes()).decode_utf8_lossy().to_string()),
+      None => None,
+    };
+    Ok(SshProxy {
+      host: bare_host(host).into_owned(),
+      user,
+      port: url.port_u16(),
+      program: "ssh".into(),
+      args: Vec::new(),
+    })
+  }
+  /// 启动 ssh 并通过 socketpair 连到目标，跳板机确认转发之后返回本地这一端
+  pub(crate) fn connect(
+    &self,
+    target: &http::Uri,
+    connect_timeout: Option<Duration>,
+  ) -> Result<socket2::Socket> {
+    use std::os::fd::OwnedFd;
+    use std::os::unix::net::UnixStream;
+    use std::process::{Command, Stdio};
+    let host = target.host().ok_or(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "no host in url",
+    ))?;
+    let port = default_port(target).ok_or(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "no port in url",
+    ))?;
+    // ssh 认识 `[fe80::1%eth0]:80` 这种写法
+    let host = match bare_host(host) {
+      host if host.contains(':') => format!("[{}]", host),
+      host => host.into_owned(),
+    };
+    reap_ssh_children();
+    let (local, remote) = UnixStream::pair()?;
+    let (log, log_remote) = UnixStream::pair()?;
+    let mut command = Command::new(&self.program);
+    // 转发通道打开时 DEBUG2 级别会打出 `open confirm`
+    command
+      .arg("-W")
+      .arg(format!("{}:{}", host, port))
+      .args(["-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes", "-o", "LogLevel=DEBUG2"]);
+    if let Some(timeout) = connect_timeout {
+      // ssh 的超时以秒为单位，至少一秒
+      let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+      command.arg("-o").arg(format!("ConnectTimeout={}", secs.max(1)));
     }
-  }
-  fn get_conn_addr(&self) -> Result<SocketAddr> {
-    // 获取连接地址，如果有代理先返回代理地址
-    match &self.proxy {
-      None => {
-        let original_host = self.target.host().ok_or(new_io_error(
-          std::io::ErrorKind::InvalidData,
-          "no host in url",
-        ))?;
-        let port = default_port(&self.target).ok_or(new_io_error(
-          std::io::ErrorKind::InvalidData,
-          "no port in url",
-        ))?;
-        let target_addr = (original_host, port)
-          .to_socket_addrs()?
-          .next()
-          .ok_or(new_io_error(
-            std::io::ErrorKind::InvalidData,
-            "no addr in url",
-          ))?;
-        Ok(target_addr)
+    if let Some(port) = self.port {
+      command.arg("-p").arg(port.to_string());
+    }
+    if let Some(user) = &self.user {
+      command.arg("-l").arg(user);
+    }
+    command
+      .args(&self.args)
+      .arg("--")
+      .arg(&self.host)
+      .stdin(Stdio::from(OwnedFd::from(remote.try_clone()?)))
+      .stdout(Stdio::from(OwnedFd::from(remote)))
+      .stderr(Stdio::from(OwnedFd::from(log_remote)));
+    let child = command.spawn();
+    // 关掉自己手里的另一端，ssh 退出时才能读到 EOF
+    drop(command);
+    let mut child = child.map_err(|err| {
+      Error::IO(std::io::Error::new(
+        std::io::ErrorKind::NotConnected,
+        format!("failed to start {}: {}", self.program.display(), err),
+      ))
+    })?;
+    log.set_read_timeout(connect_timeout)?;
+    match wait_forward(&log) {
+      Ok(()) => {
+        // 之后的日志不再读取，ssh 忽略 SIGPIPE，写不进去的日志直接丢掉
+        drop(log);
+        if let Ok(mut children) = SSH_CHILDREN.lock() {
+          children.push(child);
+        }
+        Ok(socket2::Socket::from(OwnedFd::from(local)))
       }
-      Some(proxy) => {
-        let proxy_addr = proxy.to_addr()?;
-        Ok(proxy_addr)
+      Err(err) => {
+        let _ = child.kill();
+        let _ = child.wait();
+        Err(err)
       }
     }
   }
 }
 
-fn default_port(uri: &http::Uri) -> Option<u16> {
-  match uri.port_u16() {
-    Some(p) => Some(p),
-    None => match uri.scheme_str() {
-      Some("https") => Some(443u16),
-      Some("http") => Some(80u16),
-      Some("socks5") | Some("socks5h") => Some(1080u16),
-      _ => None,
-    },
+/// 已经转发成功的 ssh 进程，之后再连接时回收已经退出的进程
+#[cfg(all(feature = "ssh", unix))]
+static SSH_CHILDREN: std::sync::Mutex<Vec<std::process::Child>> = std::sync::Mutex::new(Vec::new());
+
+/// 回收已经退出的 ssh 进程，不用为每个连接开一个线程等待
+#[cfg(all(feature = "ssh", unix))]
+fn reap_ssh_children() {
+  if let Ok(mut children) = SSH_CHILDREN.lock() {
+    children.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+  }
+}
+
+/// 读 ssh 的日志直到转发通道打开，ssh 先退出时用它最后一行不是调试信息的输出作为错误
+#[cfg(all(feature = "ssh", unix))]
+fn wait_forward(log: &std::os::unix::net::UnixStream) -> Result<()> {
+  use std::io::BufRead;
+  let mut reason = None;
+  for line in BufReader::new(log).lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+        return Err(new_io_error(std::io::ErrorKind::TimedOut, "ssh jump host timed out"));
+      }
+      Err(err) => return Err(err.into()),
+    };
+    let line = line.trim_end();
+    if line.starts_with("debug") {
+      if line.contains(": open confirm") {
+        return Ok(());
+      }
+    } else if !line.is_empty() {
+      reason = Some(line.to_string());
+    }
   }
+  Err(Error::IO(std::io::Error::new(
+    std::io::ErrorKind::NotConnected,
+    format!(
+      "ssh jump host: {}",
+      reason.as_deref().unwrap_or("ssh exited before forwarding")
+    ),
+  )))
 }
 
 /// A SOCKS5 client.
@@ -677,8 +887,8 @@ impl TargetAddr {
       std::io::ErrorKind::InvalidData,
       "not found host",
     ))?;
-    if let Ok(ip) = IpAddr::from_str(host) {
-      Ok(TargetAddr::IP(SocketAddr::new(ip, port)))
+    if let Some(addr) = ip_literal(host, port) {
+      Ok(TargetAddr::IP(addr?))
     } else {
       // 如果是使用远程DNS直接传域名过去让远程代理那边解析DNS，不是就本地解析到IP
       if !remote_dns {
@@ -0,0 +1,151 @@
+use crate::errors::{new_io_error, Result};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+
+/// 版本 2 头部固定的 12 字节签名
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// A HAProxy PROXY protocol header, sent by
+/// [`ConnectorBuilder::proxy_protocol`](crate::ConnectorBuilder::proxy_protocol)
+/// right after the TCP connection is established and before TLS.
+///
+/// `src` and `dst` are whatever the backend should believe the original
+/// connection was, they are not checked against the real addresses and must
+/// both be IPv4 or both be IPv6.
+///
+/// # Example
+///
+/// ```rust
+/// use slinger::ProxyProtocol;
+///
+/// let header = ProxyProtocol::V1 {
+///   src: "203.0.113.7:51234".parse().unwrap(),
+///   dst: "10.0.0.1:443".parse().unwrap(),
+/// };
+/// assert_eq!(
+///   header.encode().unwrap(),
+///   b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\n"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ProxyProtocol {
+  /// The human-readable version 1 header
+  V1 {
+    /// the original client address
+    src: SocketAddr,
+    /// the address the client connected to
+    dst: SocketAddr,
+  },
+  /// The binary version 2 header with the `PROXY` command over TCP
+  V2 {
+    /// the original client address
+    src: SocketAddr,
+    /// the address the client connected to
+    dst: SocketAddr,
+    /// `(type, value)` pairs appended as TLVs, in order
+    tlvs: Vec<(u8, Vec<u8>)>,
+  },
+}
+
+impl ProxyProtocol {
+  /// Encode the header as it is sent on the connection.
+  ///
+  /// # Errors
+  ///
+  /// Fails when `src` and `dst` are of different address families, or when
+  /// the TLVs of a version 2 header do not fit its 16-bit length fields.
+  pub fn encode(&self) -> Result<Vec<u8>> {
+    match self {
+      ProxyProtocol::V1 { src, dst } => {
+        let family = match (src, dst) {
+          (SocketAddr::V4(_), SocketAddr::V4(_)) => "TCP4",
+          (SocketAddr::V6(_), SocketAddr::V6(_)) => "TCP6",
+          _ => return Err(mixed_families()),
+        };
+        Ok(
+          format!(
+            "PROXY {} {} {} {} {}\r\n",
+            family,
+            src.ip(),
+            dst.ip(),
+            src.port(),
+            dst.port()
+          )
+          .into_bytes(),
+        )
+      }
+      ProxyProtocol::V2 { src, dst, tlvs } => {
+        let (family, mut addresses) = match (src, dst) {
+          (SocketAddr::V4(src), SocketAddr::V4(dst)) => {
+            (0x11, [src.ip().octets(), dst.ip().octets()].concat())
+          }
+          (SocketAddr::V6(src), SocketAddr::V6(dst)) => {
+            (0x21, [src.ip().octets(), dst.ip().octets()].concat())
+          }
+          _ => return Err(mixed_families()),
+        };
+        addresses.extend_from_slice(&src.port().to_be_bytes());
+        addresses.extend_from_slice(&dst.port().to_be_bytes());
+        for (kind, value) in tlvs {
+          let length = u16::try_from(value.len()).map_err(|_| too_long())?;
+          addresses.push(*kind);
+          addresses.extend_from_slice(&length.to_be_bytes());
+          addresses.extend_from_slice(value);
+        }
+        let length = u16::try_from(addresses.len()).map_err(|_| too_long())?;
+        let mut header = V2_SIGNATURE.to_vec();
+        // 高 4 位是版本 2，低 4 位是 PROXY 命令
+        header.push(0x21);
+        header.push(family);
+        header.extend_from_slice(&length.to_be_bytes());
+        header.extend_from_slice(&addresses);
+        Ok(header)
+      }
+    }
+  }
+}
+
+fn mixed_families() -> crate::Error {
+  new_io_error(
+    ErrorKind::InvalidInput,
+    "proxy protocol addresses must both be IPv4 or both be IPv6",
+  )
+}
+
+fn too_long() -> crate::Error {
+  new_io_error(
+    ErrorKind::InvalidInput,
+    "proxy protocol v2 header is too long",
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ProxyProtocol;
+
+  #[test]
+  fn encode_headers() {
+    let v6 = ProxyProtocol::V1 {
+      src: "[2001:db8::1]:1000".parse().unwrap(),
+      dst: "[::1]:80".parse().unwrap(),
+    };
+    assert_eq!(
+      v6.encode().unwrap(),
+      b"PROXY TCP6 2001:db8::1 ::1 1000 80\r\n"
+    );
+    let v2 = ProxyProtocol::V2 {
+      src: "192.0.2.1:1000".parse().unwrap(),
+      dst: "10.0.0.1:443".parse().unwrap(),
+      tlvs: vec![(0x02, b"example.com".to_vec())],
+    };
+    let mut expected = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x1a".to_vec();
+    expected.extend_from_slice(&[192, 0, 2, 1, 10, 0, 0, 1, 0x03, 0xe8, 0x01, 0xbb]);
+    expected.extend_from_slice(b"\x02\x00\x0bexample.com");
+    assert_eq!(v2.encode().unwrap(), expected);
+    let mixed = ProxyProtocol::V1 {
+      src: "192.0.2.1:1000".parse().unwrap(),
+      dst: "[::1]:80".parse().unwrap(),
+    };
+    assert!(mixed.encode().is_err());
+  }
+}
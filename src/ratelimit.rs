@@ -0,0 +1,427 @@
+use crate::deadline::Deadline;
+use crate::errors::{new_io_error, Result};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket shared by every clone of a `Client`.
+///
+/// The bucket holds up to `burst` tokens and is refilled at `rate` tokens per
+/// second; every request sent, redirects included, takes one token and waits
+/// for it when the bucket is empty. With `per_host` each `scheme://host:port`
+/// gets its own bucket.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+  rate: f64,
+  burst: f64,
+  per_host: bool,
+  buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+  tokens: f64,
+  last: Instant,
+}
+
+impl RateLimiter {
+  pub(crate) fn new(requests_per_second: f64, burst: u32, per_host: bool) -> Self {
+    RateLimiter {
+      rate: requests_per_second,
+      burst: f64::from(std::cmp::max(burst, 1)),
+      per_host,
+      buckets: Mutex::new(HashMap::new()),
+    }
+  }
+  /// Take one token for `uri`, sleeping until one is available.
+  pub(crate) fn acquire(&self, uri: &http::Uri, deadline: &Deadline) -> Result<()> {
+    if self.rate <= 0.0 || !self.rate.is_finite() {
+      return Ok(());
+    }
+    let key = if self.per_host {
+      host_key(uri)
+    } else {
+      String::new()
+    };
+    loop {
+      let wait = {
+        let mut buckets = self
+          .buckets
+          .lock()
+          .map_err(|_| new_io_error(std::io::ErrorKind::Other, "rate limiter poisoned"))?;
+        let now = Instant::now();
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+          tokens: self.burst,
+          last: now,
+        });
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.last = now;
+        if bucket.tokens >= 1.0 {
+          bucket.tokens -= 1.0;
+          return Ok(());
+        }
+        Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+      };
+      if let Some(remaining) = deadline.remaining() {
+        if wait >= remaining {
+          return Err(new_io_error(
+            std::io::ErrorKind::TimedOut,
+            "deadline exceeded while rate limited",
+          ));
+        }
+      }
+      std::thread::sleep(wait);
+    }
+  }
+}
+
+/// Connection slots shared by every clone of a `Client`.
+///
+/// A request waits for a free slot before opening a connection, under both
+/// the cap of its `scheme://host:port` and the cap of the whole client, and
+/// gives the slot back when the connection is closed. The wait ends with a
+/// `TimedOut` error after the queue timeout or at the request deadline.
+#[derive(Debug)]
+pub(crate) struct ConnectionLimit {
+  per_host: Option<usize>,
+  total: Option<usize>,
+  queue_timeout: Option<Duration>,
+  open: Mutex<Slots>,
+  released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Slots {
+  total: usize,
+  hosts: HashMap<String, usize>,
+}
+
+impl ConnectionLimit {
+  pub(crate) fn new(
+    per_host: Option<usize>,
+    total: Option<usize>,
+    queue_timeout: Option<Duration>,
+  ) -> Self {
+    ConnectionLimit {
+      per_host: per_host.map(|n| std::cmp::max(n, 1)),
+      total: total.map(|n| std::cmp::max(n, 1)),
+      queue_timeout,
+      open: Mutex::new(Slots::default()),
+      released: Condvar::new(),
+    }
+  }
+  /// Wait for a free slot to open a connection to `uri`.
+  pub(crate) fn acquire(
+    self: &Arc<Self>,
+    uri: &http::Uri,
+    deadline: &Deadline,
+  ) -> Result<ConnectionPermit> {
+    let host = host_key(uri);
+    let poisoned = || new_io_error(std::io::ErrorKind::Other, "connection limit poisoned");
+    // 排队超时和请求的截止时间取先到的一个
+    let give_up = match (self.queue_timeout, deadline.remaining()) {
+      (Some(timeout), Some(remaining)) => Some(std::cmp::min(timeout, remaining)),
+      (timeout, remaining) => timeout.or(remaining),
+    }
+    .map(|wait| Instant::now() + wait);
+    let mut open = self.open.lock().map_err(|_| poisoned())?;
+    loop {
+      let host_open = open.hosts.get(&host).copied().unwrap_or_default();
+      if self.total.is_none_or(|cap| open.total < cap)
+        && self.per_host.is_none_or(|cap| host_open < cap)
+      {
+        open.total += 1;
+        *open.hosts.entry(host.clone()).or_default() += 1;
+        return Ok(ConnectionPermit {
+          limit: self.clone(),
+          host,
+        });
+      }
+      open = match give_up {
+        None => self.released.wait(open).map_err(|_| poisoned())?,
+        Some(give_up) => {
+          let Some(wait) = give_up.checked_duration_since(Instant::now()).filter(|w| !w.is_zero())
+          else {
+            return Err(new_io_error(
+              std::io::ErrorKind::TimedOut,
+              "timed out waiting for a free connection slot",
+            ));
+          };
+          self.released.wait_timeout(open, wait).map_err(|_| poisoned())?.0
+        }
+      };
+    }
+  }
+}
+
+/// 按 scheme、主机和端口区分主机，省略的端口按默认端口算
+fn host_key(uri: &http::Uri) -> String {
+  format!(
+    "{}://{}:{}",
+    uri.scheme_str().unwrap_or_default(),
+    uri.host().unwrap_or_default().to_ascii_lowercase(),
+    crate::connector::default_port(uri).unwrap_or_default()
+  )
+}
+
+/// 占用的连接名额，释放时归还并唤醒排队的请求
+#[derive(Debug)]
+pub(crate) struct ConnectionPermit {
+  limit: Arc<ConnectionLimit>,
+  host: String,
+}
+
+impl Drop for ConnectionPermit {
+  fn drop(&mut self) {
+    // 锁被毒化时也要归还名额，否则排队的请求会一直等下去
+    let mut open = match self.limit.open.lock() {
+      Ok(open) => open,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    open.total = open.total.saturating_sub(1);
+    if let Some(count) = open.hosts.get_mut(&self.host) {
+      *count -= 1;
+      if *count == 0 {
+        open.hosts.remove(&self.host);
+      }
+    }
+    self.limit.released.notify_all();
+  }
+}
+
+/// 连接和它占用的名额一起交给响应，连接关闭时名额也归还
+pub(crate) struct Held<R> {
+  pub(crate) inner: R,
+  pub(crate) _permit: Option<ConnectionPermit>,
+}
+
+impl<R: Read> Read for Held<R> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    self.inner.read(buf)
+  }
+}
+
+/// Byte budget of the upload or download speed set on the `ClientBuilder`,
+/// shared by every connection of the client.
+///
+/// Transfers are cut into pieces of about a tenth of a second and wait after
+/// each piece once the budget is used up, so the average speed stays under
+/// the cap without long bursts.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+  rate: f64,
+  bucket: Mutex<Bucket>,
+}
+
+impl Throttle {
+  pub(crate) fn new(bytes_per_second: u64) -> Self {
+    Throttle {
+      rate: std::cmp::max(bytes_per_second, 1) as f64,
+      bucket: Mutex::new(Bucket {
+        tokens: 0.0,
+        last: Instant::now(),
+      }),
+    }
+  }
+  /// 每次读写最多的字节数
+  fn chunk(&self) -> usize {
+    ((self.rate / 10.0) as usize).clamp(1, 64 * 1024)
+  }
+  /// 记下传输了 `bytes` 字节，超过速度时等待
+  fn consume(&self, bytes: usize, deadline: &Deadline) -> std::io::Result<()> {
+    let wait = {
+      let mut bucket = self
+        .bucket
+        .lock()
+        .map_err(|_| std::io::Error::other("throttle poisoned"))?;
+      let now = Instant::now();
+      let elapsed = now.duration_since(bucket.last).as_secs_f64();
+      // 空闲攒下的额度最多一块，不会在空闲之后突发
+      bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.chunk() as f64);
+      bucket.last = now;
+      bucket.tokens -= bytes as f64;
+      if bucket.tokens >= 0.0 {
+        return Ok(());
+      }
+      Duration::from_secs_f64(-bucket.tokens / self.rate)
+    };
+    if deadline.remaining().is_some_and(|remaining| wait >= remaining) {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        "deadline exceeded while throttled",
+      ));
+    }
+    std::thread::sleep(wait);
+    Ok(())
+  }
+}
+
+/// 按 `Throttle` 的速度读写，没有限速时直接读写
+pub(crate) struct Throttled<'a, T> {
+  inner: T,
+  throttle: Option<&'a Throttle>,
+  deadline: Deadline,
+}
+
+impl<'a, T> Throttled<'a, T> {
+  pub(crate) fn new(inner: T, throttle: Option<&'a Throttle>, deadline: Deadline) -> Self {
+    Throttled {
+      inner,
+      throttle,
+      deadline,
+    }
+  }
+}
+
+impl<T: Read> Read for Throttled<'_, T> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let Some(throttle) = self.throttle else {
+      return self.inner.read(buf);
+    };
+    let len = std::cmp::min(buf.len(), throttle.chunk());
+    let n = self.inner.read(&mut buf[..len])?;
+    throttle.consume(n, &self.deadline)?;
+    Ok(n)
+  }
+}
+
+impl<T: Write> Write for Throttled<'_, T> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let Some(throttle) = self.throttle else {
+      return self.inner.write(buf);
+    };
+    let len = std::cmp::min(buf.len(), throttle.chunk());
+    let n = self.inner.write(&buf[..len])?;
+    throttle.consume(n, &self.deadline)?;
+    Ok(n)
+  }
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ConnectionLimit, RateLimiter};
+  use crate::deadline::Deadline;
+  use std::sync::Arc;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn burst_then_throttle() {
+    let limiter = RateLimiter::new(20.0, 2, true);
+    let uri = http::Uri::from_static("http://a.example/");
+    let other = http::Uri::from_static("http://b.example/");
+    let deadline = Deadline::default();
+    let start = Instant::now();
+    limiter.acquire(&uri, &deadline).unwrap();
+    // 显式写出默认端口也是同一个主机
+    limiter.acquire(&http::Uri::from_static("http://A.example:80/x"), &deadline).unwrap();
+    limiter.acquire(&other, &deadline).unwrap();
+    assert!(start.elapsed() < Duration::from_millis(40));
+    limiter.acquire(&uri, &deadline).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(40));
+  }
+
+  #[test]
+  fn throttle_download() {
+    let body = "x".repeat(300);
+    let mock = crate::MockTransport::new().respond(format!(
+      "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+      body.len(),
+      body
+    ));
+    let client = crate::Client::builder()
+      .transport(mock)
+      .max_download_speed(1000)
+      .build()
+      .unwrap();
+    let start = Instant::now();
+    let response = client.get("http://a.example/").send().unwrap();
+    assert_eq!(response.body().as_ref().unwrap().len(), 300);
+    // 响应头和 300 字节的响应体按 1000 字节每秒读取
+    assert!(start.elapsed() >= Duration::from_millis(300));
+  }
+
+  #[test]
+  fn connection_slots() {
+    let limit = Arc::new(ConnectionLimit::new(Some(1), Some(2), Some(Duration::from_millis(50))));
+    let deadline = Deadline::default();
+    let a = http::Uri::from_static("http://a.example/");
+    let b = http::Uri::from_static("http://b.example/");
+    let first = limit.acquire(&a, &deadline).unwrap();
+    // 默认端口和显式端口是同一个主机
+    let err = limit.acquire(&http::Uri::from_static("http://a.example:80/x"), &deadline).unwrap_err();
+    assert!(err.is_timeout());
+    let _second = limit.acquire(&b, &deadline).unwrap();
+    // 总数也满了
+    assert!(limit.acquire(&http::Uri::from_static("http://c.example/"), &deadline).is_err());
+    // 归还名额时唤醒排队的请求
+    let waiter = {
+      let limit = Arc::new(ConnectionLimit::new(Some(1), None, None));
+      let held = limit.acquire(&a, &deadline).unwrap();
+      let queued = {
+        let limit = limit.clone();
+        let a = a.clone();
+        std::thread::spawn(move || limit.acquire(&a, &Deadline::default()).is_ok())
+      };
+      std::thread::sleep(Duration::from_millis(20));
+      drop(held);
+      queued.join().unwrap()
+    };
+    assert!(waiter);
+    drop(first);
+    assert!(limit.acquire(&a, &deadline).is_ok());
+    // 跳转前关闭的连接先归还名额，同一个主机的下一跳不用等
+    let mock = crate::MockTransport::new()
+      .respond("HTTP/1.1 302 Found\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n")
+      .respond("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let client = crate::Client::builder()
+      .transport(mock)
+      .max_connections_per_host(1)
+      .connection_queue_timeout(Duration::from_millis(50))
+      .build()
+      .unwrap();
+    assert_eq!(client.get("http://a.example/").send().unwrap().text().unwrap(), "ok");
+  }
+
+  #[test]
+  fn connection_limit_cross_host_redirect() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    // 保持连接的服务器，每个请求都回复同一个响应
+    let serve = |response: String| {
+      let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+      let addr = listener.local_addr().unwrap();
+      std::thread::spawn(move || {
+        for stream in listener.incoming() {
+          let Ok(mut stream) = stream else { break };
+          let response = response.clone();
+          std::thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = stream.read(&mut buf) {
+              if n == 0 || stream.write_all(response.as_bytes()).is_err() {
+                break;
+              }
+            }
+          });
+        }
+      });
+      addr
+    };
+    let to = serve("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_string());
+    let from = serve(format!(
+      "HTTP/1.1 302 Found\r\nLocation: http://{}/\r\nContent-Length: 0\r\n\r\n",
+      to
+    ));
+    let client = crate::Client::builder()
+      .max_connections(1)
+      .timeout(Duration::from_secs(5))
+      .build()
+      .unwrap();
+    let response = client.get(format!("http://{}/", from)).send().unwrap();
+    assert_eq!(response.text().unwrap(), "ok");
+  }
+}
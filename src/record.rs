@@ -1,48 +1,645 @@
+use crate::errors::{new_io_error, Result};
 use crate::{Request, Response};
+#[cfg(feature = "record")]
 use bytes::Bytes;
 use socket2::SockAddr;
+#[cfg(feature = "record")]
+use std::io::{Cursor, Read, Write};
+#[cfg(feature = "record")]
+use std::path::PathBuf;
+#[cfg(feature = "record")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "record")]
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "record")]
+use std::time::SystemTime;
 
 /// http peer_addr and local_addr
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalPeerRecord {
   /// peer_addr
+  #[cfg_attr(feature = "serde", serde(with = "sock_addr_serde"))]
   pub remote_addr: SockAddr,
   /// local_addr
+  #[cfg_attr(feature = "serde", serde(with = "sock_addr_serde"))]
   pub local_addr: SockAddr,
+  /// whether the connection was already open and reused
+  pub reused: bool,
+}
+
+/// How the connection of one exchange ended, see
+/// [`Response::connection_close`](crate::Response::connection_close).
+///
+/// Only recorded with
+/// [`ClientBuilder::probe_connection_close`](crate::ClientBuilder::probe_connection_close).
+/// The connection is checked once right after the response, without waiting
+/// and without consuming data; on TLS connections a `close_notify` alert
+/// counts as [`ConnectionClose::ServerFin`]. A server that closes a moment
+/// after answering with `Connection: close` is seen as
+/// [`ConnectionClose::ClientClosed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConnectionClose {
+  /// the connection was still open and kept for the next request
+  KeepAlive,
+  /// the server closed the connection with a FIN
+  ServerFin,
+  /// the server reset the connection
+  Reset,
+  /// a read timed out waiting for the server before the body was complete
+  IdleTimeout,
+  /// the connection was still open and the client closed it
+  ClientClosed,
 }
 
 /// redirect info
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RedirectRecord {
   /// should_redirect
   pub should_redirect: bool,
-  /// the next redirect url
+  /// the `Location` header as received
+  #[cfg_attr(feature = "serde", serde(with = "header_value_serde"))]
+  pub location: Option<http::HeaderValue>,
+  /// the next redirect url, `location` resolved and normalized with the
+  /// client's [`LocationPolicy`](crate::redirect::LocationPolicy)
+  #[cfg_attr(feature = "serde", serde(with = "http_serde::option::uri"))]
   pub next: Option<http::Uri>,
 }
 
+/// ALPN info
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlpnRecord {
+  /// the protocols offered in the handshake, in order
+  pub offered: Vec<String>,
+  /// the protocol selected by the server
+  pub negotiated: Option<Vec<u8>>,
+}
+
+/// Per-phase durations of one request/response exchange.
+///
+/// The connection phases are zero when an already open connection is reused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timing {
+  /// DNS resolution of the target or proxy host
+  pub dns: Duration,
+  /// TCP connect, plus the proxy handshake when a proxy is used
+  pub connect: Duration,
+  /// TLS handshakes, with the proxy and with the target
+  pub tls: Duration,
+  /// from the first byte written to the first byte of the response
+  pub first_byte: Duration,
+  /// from the first byte of the response until it has been fully read
+  pub download: Duration,
+}
+
+impl Timing {
+  /// Sum of all phases.
+  pub fn total(&self) -> Duration {
+    self.dns + self.connect + self.tls + self.first_byte + self.download
+  }
+}
+
+#[cfg(feature = "record")]
+/// Byte caps applied to the raw request and raw response kept by [`HTTPRecord`].
+///
+/// Raw bytes beyond the cap are dropped and replaced by a truncation marker,
+/// while the original length is kept in the record, so long scans don't keep
+/// every body in memory but the evidence stays honest about what was cut.
+///
+/// With [`RecordLimit::spill_to_disk`] the kept bytes beyond a threshold are
+/// moved to a temporary file instead, see [`HTTPRecord::raw_response_reader`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecordLimit {
+  /// maximum raw request bytes kept, `None` keeps everything
+  pub request: Option<usize>,
+  /// maximum raw response bytes kept, `None` keeps everything
+  pub response: Option<usize>,
+  /// raw bytes kept in memory before the rest is written to a temporary file,
+  /// `None` keeps everything in memory
+  pub spill_threshold: Option<usize>,
+}
+
+#[cfg(feature = "record")]
+impl RecordLimit {
+  /// new a record limit
+  pub fn new(request: Option<usize>, response: Option<usize>) -> Self {
+    RecordLimit {
+      request,
+      response,
+      spill_threshold: None,
+    }
+  }
+  /// Keep at most `threshold` raw bytes of each request and response in
+  /// memory and write the rest to a file in [`std::env::temp_dir`].
+  ///
+  /// The file is removed when the last clone of its `HTTPRecord` is dropped.
+  /// When it can not be written the bytes stay in memory.
+  pub fn spill_to_disk(mut self, threshold: usize) -> Self {
+    self.spill_threshold = Some(threshold);
+    self
+  }
+}
+
+#[cfg(feature = "record")]
 /// HTTPRecord
+///
+/// With the `serde` feature a record serializes with its whole raw request and
+/// response, the parts spilled to disk included, and deserializes with them
+/// held in memory.
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HTTPRecord {
+  /// when the request was sent
+  pub started: Option<SystemTime>,
   /// request
   pub request: Request,
-  #[cfg_attr(feature = "serde", serde(skip))]
-  /// raw_request
+  /// raw_request, only the part kept in memory when it was spilled to disk
   pub raw_request: Bytes,
+  /// original length of raw_request before truncation
+  pub raw_request_length: usize,
   /// response
   pub response: Response,
-  #[cfg_attr(feature = "serde", serde(skip))]
-  /// raw_response
+  /// raw_response, only the part kept in memory when it was spilled to disk
   pub raw_response: Bytes,
+  /// original length of raw_response before truncation
+  pub raw_response_length: usize,
+  limit: RecordLimit,
+  request_spill: Option<Arc<SpillFile>>,
+  response_spill: Option<Arc<SpillFile>>,
 }
 
+#[cfg(feature = "record")]
 impl HTTPRecord {
+  pub(crate) fn with_limit(limit: RecordLimit) -> Self {
+    HTTPRecord {
+      limit,
+      ..Default::default()
+    }
+  }
   pub(crate) fn record_request(&mut self, irq: &Request) {
-    self.raw_request = irq.to_raw();
+    let raw = irq.to_raw();
+    self.raw_request_length = raw.len();
+    (self.raw_request, self.request_spill) =
+      spill(truncate(raw, self.limit.request), self.limit.spill_threshold);
     self.request = irq.clone();
+    self.started = Some(SystemTime::now());
   }
   pub(crate) fn record_response(&mut self, irp: &Response) {
-    self.raw_response = irp.to_raw();
+    let raw = irp.to_raw();
+    self.raw_response_length = raw.len();
+    (self.raw_response, self.response_spill) =
+      spill(truncate(raw, self.limit.response), self.limit.spill_threshold);
     self.response = irp.clone();
   }
+  /// Returns true if `raw_request` was cut by the record limit.
+  pub fn request_truncated(&self) -> bool {
+    self
+      .limit
+      .request
+      .is_some_and(|limit| self.raw_request_length > limit)
+  }
+  /// Returns true if `raw_response` was cut by the record limit.
+  pub fn response_truncated(&self) -> bool {
+    self
+      .limit
+      .response
+      .is_some_and(|limit| self.raw_response_length > limit)
+  }
+  /// Returns true if part of the raw request was written to a temporary file.
+  pub fn request_spilled(&self) -> bool {
+    self.request_spill.is_some()
+  }
+  /// Returns true if part of the raw response was written to a temporary file.
+  pub fn response_spilled(&self) -> bool {
+    self.response_spill.is_some()
+  }
+  /// Read the whole kept raw request, from memory and then from its
+  /// temporary file when it was spilled to disk.
+  pub fn raw_request_reader(&self) -> Result<Box<dyn Read + Send>> {
+    raw_reader(&self.raw_request, &self.request_spill)
+  }
+  /// Read the whole kept raw response, from memory and then from its
+  /// temporary file when it was spilled to disk.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// use slinger::record::RecordLimit;
+  ///
+  /// let client = slinger::Client::builder()
+  ///   .record_limit(RecordLimit::default().spill_to_disk(1024 * 1024))
+  ///   .build()?;
+  /// let resp = client.get("http://httpbin.org/bytes/10000000").send()?;
+  /// if let Some(records) = resp.http_record() {
+  ///   for record in records {
+  ///     let mut raw = record.raw_response_reader()?;
+  ///     std::io::copy(&mut raw, &mut std::io::sink())?;
+  ///   }
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn raw_response_reader(&self) -> Result<Box<dyn Read + Send>> {
+    raw_reader(&self.raw_response, &self.response_spill)
+  }
+}
+
+/// 序列化时读出完整的原始字节，包括写到磁盘的部分
+#[cfg(all(feature = "record", feature = "serde"))]
+#[derive(serde::Serialize)]
+struct HTTPRecordRef<'a> {
+  started: &'a Option<SystemTime>,
+  request: &'a Request,
+  #[serde(with = "crate::body::bytes_serde")]
+  raw_request: &'a [u8],
+  raw_request_length: usize,
+  response: &'a Response,
+  #[serde(with = "crate::body::bytes_serde")]
+  raw_response: &'a [u8],
+  raw_response_length: usize,
+}
+
+#[cfg(all(feature = "record", feature = "serde"))]
+#[derive(serde::Deserialize)]
+struct HTTPRecordOwned {
+  started: Option<SystemTime>,
+  request: Request,
+  #[serde(with = "crate::body::bytes_serde")]
+  raw_request: Bytes,
+  raw_request_length: usize,
+  response: Response,
+  #[serde(with = "crate::body::bytes_serde")]
+  raw_response: Bytes,
+  raw_response_length: usize,
+}
+
+#[cfg(all(feature = "record", feature = "serde"))]
+impl serde::Serialize for HTTPRecord {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let read_all = |reader: Result<Box<dyn Read + Send>>| {
+      let mut raw = Vec::new();
+      reader?.read_to_end(&mut raw)?;
+      Ok::<_, crate::Error>(raw)
+    };
+    let raw_request = read_all(self.raw_request_reader()).map_err(serde::ser::Error::custom)?;
+    let raw_response = read_all(self.raw_response_reader()).map_err(serde::ser::Error::custom)?;
+    HTTPRecordRef {
+      started: &self.started,
+      request: &self.request,
+      raw_request: &raw_request,
+      raw_request_length: self.raw_request_length,
+      response: &self.response,
+      raw_response: &raw_response,
+      raw_response_length: self.raw_response_length,
+    }
+    .serialize(serializer)
+  }
+}
+
+#[cfg(all(feature = "record", feature = "serde"))]
+impl<'de> serde::Deserialize<'de> for HTTPRecord {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+    let owned = HTTPRecordOwned::deserialize(deserializer)?;
+    Ok(HTTPRecord {
+      started: owned.started,
+      request: owned.request,
+      raw_request: owned.raw_request,
+      raw_request_length: owned.raw_request_length,
+      response: owned.response,
+      raw_response: owned.raw_response,
+      raw_response_length: owned.raw_response_length,
+      ..Default::default()
+    })
+  }
+}
+
+/// `SockAddr` 按 `SocketAddr` 序列化
+#[cfg(feature = "serde")]
+mod sock_addr_serde {
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+  use socket2::SockAddr;
+  use std::net::SocketAddr;
+
+  pub fn serialize<S: Serializer>(addr: &SockAddr, s: S) -> Result<S::Ok, S::Error> {
+    match addr.as_socket() {
+      Some(addr) => addr.serialize(s),
+      None => Err(serde::ser::Error::custom("not an IP socket address")),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<SockAddr, D::Error> {
+    SocketAddr::deserialize(d).map(SockAddr::from)
+  }
+}
+
+/// 请求头的值可能不是 UTF-8，按字节序列化
+#[cfg(feature = "serde")]
+mod header_value_serde {
+  use http::HeaderValue;
+  use serde::{Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(value: &Option<HeaderValue>, s: S) -> Result<S::Ok, S::Error> {
+    match value {
+      Some(value) => s.serialize_some(&Bytes(value.as_bytes())),
+      None => s.serialize_none(),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<HeaderValue>, D::Error> {
+    match Option::<Vec<u8>>::deserialize(d)? {
+      Some(bytes) => HeaderValue::from_bytes(&bytes)
+        .map(Some)
+        .map_err(serde::de::Error::custom),
+      None => Ok(None),
+    }
+  }
+
+  struct Bytes<'a>(&'a [u8]);
+
+  impl serde::Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+      s.serialize_bytes(self.0)
+    }
+  }
+}
+
+/// 写到磁盘的原始字节，最后一个引用释放时删除文件
+#[cfg(feature = "record")]
+#[derive(Debug)]
+struct SpillFile {
+  path: PathBuf,
+}
+
+#[cfg(feature = "record")]
+impl Drop for SpillFile {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+#[cfg(feature = "record")]
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 超过阈值的部分写入临时文件，写入失败时保留在内存中
+#[cfg(feature = "record")]
+fn spill(raw: Bytes, threshold: Option<usize>) -> (Bytes, Option<Arc<SpillFile>>) {
+  let threshold = match threshold {
+    Some(threshold) if raw.len() > threshold => threshold,
+    _ => return (raw, None),
+  };
+  let path = std::env::temp_dir().join(format!(
+    "slinger-record-{}-{}",
+    std::process::id(),
+    SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+  ));
+  let write = || -> std::io::Result<SpillFile> {
+    let mut file = std::fs::OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .open(&path)?;
+    // 先创建 SpillFile，写入失败时由 Drop 删除文件
+    let spilled = SpillFile { path: path.clone() };
+    file.write_all(&raw[threshold..])?;
+    Ok(spilled)
+  };
+  match write() {
+    Ok(spilled) => (raw.slice(..threshold), Some(Arc::new(spilled))),
+    Err(_) => (raw, None),
+  }
+}
+
+/// 持有文件引用，读取期间文件不会被删除
+#[cfg(feature = "record")]
+struct SpillReader {
+  inner: std::io::Chain<Cursor<Bytes>, std::fs::File>,
+  _spilled: Arc<SpillFile>,
+}
+
+#[cfg(feature = "record")]
+impl Read for SpillReader {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    self.inner.read(buf)
+  }
+}
+
+#[cfg(feature = "record")]
+fn raw_reader(head: &Bytes, spilled: &Option<Arc<SpillFile>>) -> Result<Box<dyn Read + Send>> {
+  let head = Cursor::new(head.clone());
+  match spilled {
+    Some(spilled) => Ok(Box::new(SpillReader {
+      inner: head.chain(std::fs::File::open(&spilled.path)?),
+      _spilled: spilled.clone(),
+    })),
+    None => Ok(Box::new(head)),
+  }
+}
+
+#[cfg(feature = "record")]
+impl HTTPRecord {
+  /// A stable, deterministic text rendering of this record for snapshot tests.
+  ///
+  /// Includes the request, the response and the next redirect URI if any,
+  /// see [`Request::snapshot`] and [`Response::snapshot`]. Volatile metadata
+  /// such as socket addresses and certificates is left out.
+  pub fn snapshot(&self) -> String {
+    let mut out = self.request.snapshot();
+    out.push_str(&self.response.snapshot());
+    if let Some(next) = self
+      .response
+      .redirect_record()
+      .and_then(|redirect| redirect.next.as_ref())
+    {
+      out.push_str(&format!("redirect {}\n", next));
+    }
+    out
+  }
+}
+
+/// 收集每一跳的请求和响应，没有 record 特性时只保留最后一个响应
+#[derive(Default)]
+pub(crate) struct Recorder {
+  #[cfg(feature = "record")]
+  current: HTTPRecord,
+  #[cfg(feature = "record")]
+  records: Vec<HTTPRecord>,
+  last: Option<Response>,
+}
+
+impl Recorder {
+  #[cfg(feature = "record")]
+  pub(crate) fn with_limit(limit: RecordLimit) -> Self {
+    Recorder {
+      current: HTTPRecord::with_limit(limit),
+      ..Default::default()
+    }
+  }
+  pub(crate) fn record_request(&mut self, _irq: &Request) {
+    #[cfg(feature = "record")]
+    self.current.record_request(_irq);
+  }
+  pub(crate) fn record_response(&mut self, irp: &Response) {
+    #[cfg(feature = "record")]
+    {
+      let mut record = HTTPRecord::with_limit(self.current.limit);
+      std::mem::swap(&mut record, &mut self.current);
+      record.record_response(irp);
+      self.records.push(record);
+    }
+    self.last = Some(irp.clone());
+  }
+  pub(crate) fn finish(self) -> Result<Response> {
+    #[allow(unused_mut)]
+    let mut last_response = self.last.ok_or(new_io_error(
+      std::io::ErrorKind::NotFound,
+      "not found record",
+    ))?;
+    #[cfg(feature = "record")]
+    last_response.extensions_mut().insert(self.records);
+    Ok(last_response)
+  }
+}
+
+/// 按名称排序输出头部，同名头部保持原有顺序
+pub(crate) fn snapshot_headers(out: &mut String, headers: &http::HeaderMap) {
+  let mut names: Vec<&http::HeaderName> = headers.keys().collect();
+  names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+  for name in names {
+    for value in headers.get_all(name) {
+      out.push_str(&format!(
+        "header {}: {}\n",
+        name,
+        value.as_bytes().escape_ascii()
+      ));
+    }
+  }
+}
+
+/// 先写长度再写转义后的内容，保证单行且可以还原
+pub(crate) fn snapshot_bytes(out: &mut String, label: &str, bytes: &[u8]) {
+  out.push_str(&format!("{} {}\n{}\n", label, bytes.len(), bytes.escape_ascii()));
+}
+
+#[cfg(feature = "record")]
+fn truncate(raw: Bytes, limit: Option<usize>) -> Bytes {
+  match limit {
+    Some(limit) if raw.len() > limit => {
+      let mut kept = raw.slice(..limit).to_vec();
+      let marker = format!(
+        "\r\n[slinger: truncated {} of {} bytes]",
+        raw.len() - limit,
+        raw.len()
+      );
+      kept.extend(marker.as_bytes());
+      Bytes::from(kept)
+    }
+    _ => raw,
+  }
+}
+
+#[cfg(all(test, feature = "record"))]
+mod tests {
+  use super::{HTTPRecord, RecordLimit};
+  use crate::{Request, Response};
+  use std::io::Read;
+
+  #[test]
+  fn truncate_raw_response() {
+    let response: Response = Response::builder().body(vec![b'a'; 1024]).unwrap().into();
+    let mut record = HTTPRecord::with_limit(RecordLimit::new(None, Some(64)));
+    record.record_response(&response);
+    assert!(record.response_truncated());
+    assert!(record.raw_response.ends_with(b"bytes]"));
+    assert!(record.raw_response_length > 1024);
+    assert!(!record.request_truncated());
+  }
+
+  #[test]
+  fn spill_raw_response() {
+    let response: Response = Response::builder().body(vec![b'a'; 4096]).unwrap().into();
+    let mut record = HTTPRecord::with_limit(RecordLimit::default().spill_to_disk(100));
+    record.record_response(&response);
+    assert!(record.response_spilled());
+    assert_eq!(record.raw_response.len(), 100);
+    let mut raw = Vec::new();
+    record.raw_response_reader().unwrap().read_to_end(&mut raw).unwrap();
+    assert_eq!(raw, response.to_raw());
+    let path = record.response_spill.as_ref().unwrap().path.clone();
+    let copy = record.clone();
+    drop(record);
+    assert!(path.exists());
+    drop(copy);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn snapshot_is_sorted_and_length_prefixed() {
+    let request: Request = Request::builder()
+      .uri("http://example.com/a?b=1")
+      .header("x-b", "2")
+      .header("accept", "*/*")
+      .body("hi\n")
+      .unwrap()
+      .into();
+    let response: Response = Response::builder()
+      .header("server", "t")
+      .header("content-type", "text/plain")
+      .body(vec![0u8, b'o', b'k'])
+      .unwrap()
+      .into();
+    let mut record = HTTPRecord::default();
+    record.record_request(&request);
+    record.record_response(&response);
+    assert_eq!(
+      record.snapshot(),
+      "request GET http://example.com/a?b=1 HTTP/1.1\n\
+       header accept: */*\n\
+       header x-b: 2\n\
+       body 3\n\
+       hi\\n\n\
+       response HTTP/1.1 200 OK\n\
+       header content-type: text/plain\n\
+       header server: t\n\
+       body 3\n\
+       \\x00ok\n"
+    );
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serde_round_trip() {
+    // 响应体不是 UTF-8，序列化后也要原样还原
+    let raw = [
+      &b"HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n"[..],
+      b"Content-Length: 4\r\n\r\n\x00\xffok",
+    ];
+    let mock = crate::MockTransport::new().respond(raw.concat());
+    let client = crate::Client::builder().transport(mock).build().unwrap();
+    let response = client.get("http://example.com/a").header("x-tag", "1").send().unwrap();
+    let json = serde_json::to_string(&response).unwrap();
+    let loaded: Response = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.status_code(), response.status_code());
+    assert_eq!(loaded.headers(), response.headers());
+    assert_eq!(loaded.body(), response.body());
+    assert_eq!(loaded.timing(), response.timing());
+    assert_eq!(loaded.remote_addr(), response.remote_addr());
+    assert!(loaded.remote_addr().is_some());
+    // 记录带着完整的原始请求和响应
+    let records = loaded.http_record().unwrap();
+    assert_eq!(records.len(), 1);
+    let mut raw = Vec::new();
+    records[0].raw_request_reader().unwrap().read_to_end(&mut raw).unwrap();
+    assert!(raw.starts_with(b"GET /a HTTP/1.1\r\n"));
+    assert!(String::from_utf8_lossy(&raw).contains("x-tag: 1\r\n"));
+    let mut raw = Vec::new();
+    records[0].raw_response_reader().unwrap().read_to_end(&mut raw).unwrap();
+    assert!(raw.ends_with(b"\r\n\r\n\x00\xffok"));
+    let json = serde_json::to_string(&records[0]).unwrap();
+    let record: HTTPRecord = serde_json::from_str(&json).unwrap();
+    assert_eq!(record.raw_request_length, records[0].raw_request_length);
+  }
 }
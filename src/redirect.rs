@@ -1,16 +1,20 @@
+use crate::connector::default_port;
+use crate::deadline::Deadline;
 use http::header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE};
 use http::{HeaderMap, StatusCode};
 
 /// A type that controls the policy on how to handle the following of redirects.
 ///
-/// The default value will catch redirect loops, and has a maximum of 10
-/// redirects it will follow in a chain before returning an error.
+/// The default value has a maximum of 10 redirects it will follow in a chain.
+/// Redirect loops are caught by the client for every policy, see
+/// [`ClientBuilder::detect_redirect_loops`](crate::ClientBuilder::detect_redirect_loops).
 ///
 /// - `limited` can be used have the same as the default behavior, but adjust
 ///   the allowed maximum redirect hops in a chain.
 /// - `none` can be used to disable all redirect behavior.
 /// - `custom` can be used to create a customized policy.
 #[derive(Clone, Debug, PartialEq)]
+#[allow(unpredictable_function_pointer_comparisons)]
 pub enum Policy {
   /// - `custom` can be used to create a customized policy. see [only_same_host].
   Custom(fn(Attempt) -> Action),
@@ -27,6 +31,7 @@ pub struct Attempt<'a> {
   status: StatusCode,
   next: &'a http::Uri,
   previous: &'a [http::Uri],
+  deadline: &'a Deadline,
 }
 
 /// An action to perform when a redirect status code is found.
@@ -36,6 +41,11 @@ pub enum Action {
   Follow,
   /// Stop
   Stop,
+  /// Fail with [`Error::TooManyRedirects`](crate::Error::TooManyRedirects)
+  TooManyRedirects,
+  /// Fail with [`Error::Redirect`](crate::Error::Redirect) and
+  /// [`RedirectError::Loop`](crate::RedirectError::Loop)
+  Loop,
 }
 
 impl Policy {
@@ -114,11 +124,13 @@ impl Policy {
     status: StatusCode,
     next: &http::Uri,
     previous: &[http::Uri],
+    deadline: &Deadline,
   ) -> Action {
     self.redirect(Attempt {
       status,
       next,
       previous,
+      deadline,
     })
   }
 }
@@ -142,6 +154,26 @@ impl<'a> Attempt<'a> {
   pub fn previous(&self) -> &[http::Uri] {
     self.previous
   }
+  /// Get the deadline shared by the whole redirect chain.
+  ///
+  /// Policies can use [`Deadline::remaining`] to stop following redirects
+  /// when too little time is left.
+  pub fn deadline(&self) -> &Deadline {
+    self.deadline
+  }
+  /// Returns true if following the next URL repeats a redirect already
+  /// followed in this chain, the same URL sending to the same next URL.
+  ///
+  /// Going back to a URL once, such as a login page that sets a cookie and
+  /// sends back to the page asked for, is not a loop; the chain is a loop
+  /// when that page sends to the login page again.
+  pub fn is_loop(&self) -> bool {
+    is_loop(self.next, self.previous)
+  }
+  /// Returns true if the next URL is `http` while the current one is `https`.
+  pub fn is_downgrade(&self) -> bool {
+    is_downgrade(self.next, self.previous)
+  }
   /// Returns an action meaning slinger should follow the next URL.
   pub fn follow(self) -> Action {
     Action::Follow
@@ -152,6 +184,214 @@ impl<'a> Attempt<'a> {
   pub fn stop(self) -> Action {
     Action::Stop
   }
+  /// Returns an action meaning slinger should give up on the chain.
+  ///
+  /// The request fails with an error of kind
+  /// [`ErrorKind::TooManyRedirects`](crate::ErrorKind::TooManyRedirects)
+  /// instead of returning the 30x response.
+  pub fn too_many_redirects(self) -> Action {
+    Action::TooManyRedirects
+  }
+  /// Returns an action meaning slinger should give up on a looping chain.
+  ///
+  /// The request fails with [`Error::Redirect`](crate::Error::Redirect),
+  /// which holds every URL of the chain.
+  pub fn redirect_loop(self) -> Action {
+    Action::Loop
+  }
+}
+
+/// 同一个地址又跳到了同一个下一跳，说明在循环
+pub(crate) fn is_loop(next: &http::Uri, previous: &[http::Uri]) -> bool {
+  let Some(current) = previous.last() else {
+    return false;
+  };
+  previous.windows(2).any(|hop| hop[0] == *current && hop[1] == *next)
+}
+
+/// 从 https 跳到 http
+pub(crate) fn is_downgrade(next: &http::Uri, previous: &[http::Uri]) -> bool {
+  previous.last().is_some_and(|current| {
+    current.scheme() == Some(&http::uri::Scheme::HTTPS)
+      && next.scheme() == Some(&http::uri::Scheme::HTTP)
+  })
+}
+
+/// How the `Location` header of a redirect is turned into the next URL.
+///
+/// Relative references are resolved against the current URL as described in
+/// RFC 3986, dot segments included, and the fragment is dropped since it is
+/// never sent. Only `http` and `https` targets are followed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LocationPolicy {
+  /// Only follow a `Location` that is a valid URI reference as received.
+  Strict,
+  /// Fix the value up the way browsers do before resolving it: drop
+  /// surrounding whitespace and embedded tabs and newlines, turn `\` into `/`
+  /// and percent-encode spaces, non-ASCII bytes and other characters not
+  /// allowed in a URI.
+  #[default]
+  Lenient,
+}
+
+impl LocationPolicy {
+  /// Resolve the raw value of a `Location` header against `base`, the URL
+  /// of the response it came with.
+  ///
+  /// Returns `None` when the value can not be followed.
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// use slinger::redirect::LocationPolicy;
+  ///
+  /// let base = "http://example.com/a/b".parse().unwrap();
+  /// let next = LocationPolicy::Lenient.resolve(b"../caf\xc3\xa9 menu", &base);
+  /// assert_eq!(next.unwrap(), "http://example.com/caf%C3%A9%20menu");
+  /// assert!(LocationPolicy::Strict.resolve(b"../caf\xc3\xa9 menu", &base).is_none());
+  /// ```
+  pub fn resolve(&self, location: &[u8], base: &http::Uri) -> Option<http::Uri> {
+    let location = match self {
+      LocationPolicy::Strict => std::str::from_utf8(location).ok()?.to_string(),
+      LocationPolicy::Lenient => normalize_location(location),
+    };
+    // 片段不会发给服务器
+    let location = location.split('#').next().unwrap_or_default();
+    if location.is_empty() {
+      return None;
+    }
+    let base_scheme = base.scheme_str()?.to_ascii_lowercase();
+    let (scheme, rest) = match split_scheme(location) {
+      // 宽松模式下 `http:path` 这种和当前 scheme 相同又没有主机的写法按相对地址处理
+      Some((scheme, rest))
+        if *self == LocationPolicy::Lenient && scheme == base_scheme && !rest.starts_with("//") =>
+      {
+        (base_scheme, rest)
+      }
+      Some((scheme, rest)) => {
+        if !rest.starts_with("//") {
+          return None;
+        }
+        (scheme, rest)
+      }
+      None => (base_scheme, location),
+    };
+    if scheme != "http" && scheme != "https" {
+      return None;
+    }
+    let (rest, query) = match rest.split_once('?') {
+      Some((rest, query)) => (rest, Some(query)),
+      None => (rest, None),
+    };
+    let (authority, path, query) = if let Some(rest) = rest.strip_prefix("//") {
+      let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+      (authority.to_string(), remove_dot_segments(path), query)
+    } else {
+      let authority = base.authority()?.as_str().to_string();
+      if rest.is_empty() {
+        (authority, base.path().to_string(), query.or(base.query()))
+      } else if rest.starts_with('/') {
+        (authority, remove_dot_segments(rest), query)
+      } else {
+        let base_path = base.path();
+        let dir = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+        let dir = if dir.is_empty() { "/" } else { dir };
+        (authority, remove_dot_segments(&format!("{}{}", dir, rest)), query)
+      }
+    };
+    if authority.is_empty() {
+      return None;
+    }
+    #[cfg(feature = "url")]
+    let authority = match *self == LocationPolicy::Lenient && authority.contains('%') {
+      true => idna_authority(&authority)?,
+      false => authority,
+    };
+    let mut url = format!("{}://{}{}", scheme, authority, path);
+    if let Some(query) = query {
+      url.push('?');
+      url.push_str(query);
+    }
+    http::Uri::try_from(url).ok()
+  }
+}
+
+/// 修正后的主机名里非 ASCII 字符已经被百分号编码，解码后按 IDNA 转成 punycode
+#[cfg(feature = "url")]
+fn idna_authority(authority: &str) -> Option<String> {
+  let (userinfo, host_port) = match authority.rsplit_once('@') {
+    Some((userinfo, host_port)) => (Some(userinfo), host_port),
+    None => (None, authority),
+  };
+  let (host, port) = match host_port.rsplit_once(':') {
+    Some((host, port)) if !host.starts_with('[') && port.bytes().all(|b| b.is_ascii_digit()) => {
+      (host, Some(port))
+    }
+    _ => (host_port, None),
+  };
+  let mut out = String::new();
+  if let Some(userinfo) = userinfo {
+    out.push_str(userinfo);
+    out.push('@');
+  }
+  out.push_str(&url::Host::parse(host).ok()?.to_string());
+  if let Some(port) = port {
+    out.push(':');
+    out.push_str(port);
+  }
+  Some(out)
+}
+
+/// 拆出 `scheme:` 前缀，scheme 转小写
+fn split_scheme(location: &str) -> Option<(String, &str)> {
+  let (scheme, rest) = location.split_once(':')?;
+  let mut chars = scheme.chars();
+  let valid = chars.next()?.is_ascii_alphabetic()
+    && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+  valid.then(|| (scheme.to_ascii_lowercase(), rest))
+}
+
+/// 按浏览器的方式修正 `Location`：去掉空白、反斜杠换成斜杠、编码 URI 中不允许的字符
+fn normalize_location(location: &[u8]) -> String {
+  let trimmed = location.trim_ascii();
+  let mut out = String::with_capacity(trimmed.len());
+  for (i, &b) in trimmed.iter().enumerate() {
+    match b {
+      b'\t' | b'\n' | b'\r' => {}
+      b'\\' => out.push('/'),
+      b'%' if trimmed.get(i + 1..i + 3).is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit)) => {
+        out.push('%')
+      }
+      b'!'..=b'~' if !matches!(b, b'"' | b'<' | b'>' | b'`' | b'{' | b'}' | b'|' | b'^' | b'%') => {
+        out.push(b as char)
+      }
+      _ => out.push_str(&format!("%{:02X}", b)),
+    }
+  }
+  out
+}
+
+/// RFC 3986 5.2.4，去掉路径中的 `.` 和 `..`
+fn remove_dot_segments(path: &str) -> String {
+  let segments: Vec<&str> = path.split('/').skip(1).collect();
+  let mut out: Vec<&str> = Vec::new();
+  for (i, segment) in segments.iter().enumerate() {
+    let last = i + 1 == segments.len();
+    match *segment {
+      "." => {}
+      ".." => {
+        out.pop();
+      }
+      segment => {
+        out.push(segment);
+        continue;
+      }
+    }
+    if last {
+      out.push("");
+    }
+  }
+  format!("/{}", out.join("/"))
 }
 
 /// only_same_host
@@ -174,19 +414,177 @@ pub fn only_same_host(attempt: Attempt) -> Action {
   }
 }
 
+/// 下一跳的主机或端口和上一跳不同，没写端口时按 scheme 的默认端口比较
+pub(crate) fn is_cross_host(next: &http::Uri, previous: &[http::Uri]) -> bool {
+  previous
+    .last()
+    .is_some_and(|previous| next.host() != previous.host() || default_port(next) != default_port(previous))
+}
+
 pub(crate) fn remove_sensitive_headers(
   headers: &mut HeaderMap,
   next: &http::Uri,
   previous: &[http::Uri],
 ) {
-  if let Some(previous) = previous.last() {
-    let cross_host = next.host() != previous.host() || next.port_u16() != previous.port_u16();
-    if cross_host {
-      headers.remove(AUTHORIZATION);
-      headers.remove(COOKIE);
-      headers.remove("cookie2");
-      headers.remove(PROXY_AUTHORIZATION);
-      headers.remove(WWW_AUTHENTICATE);
+  if is_cross_host(next, previous) {
+    headers.remove(AUTHORIZATION);
+    headers.remove(COOKIE);
+    headers.remove("cookie2");
+    headers.remove(PROXY_AUTHORIZATION);
+    headers.remove(WWW_AUTHENTICATE);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{remove_sensitive_headers, LocationPolicy, Policy};
+  use http::header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION};
+  use http::{HeaderMap, HeaderValue};
+
+  /// 跳到 `location` 的 302 响应
+  fn to(location: &str) -> String {
+    format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n", location)
+  }
+
+  fn credential_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic Zm9vOmJhcg=="));
+    headers.insert(COOKIE, HeaderValue::from_static("session=1"));
+    headers.insert(PROXY_AUTHORIZATION, HeaderValue::from_static("Basic Zm9vOmJhcg=="));
+    headers
+  }
+
+  #[test]
+  fn strip_on_cross_host() {
+    let previous = [http::Uri::from_static("http://a.example/login")];
+    for next in ["http://b.example/", "http://a.example:8080/", "https://a.example/"] {
+      let mut headers = credential_headers();
+      remove_sensitive_headers(&mut headers, &http::Uri::from_static(next), &previous);
+      assert!(headers.is_empty(), "{}", next);
+    }
+  }
+
+  #[test]
+  fn keep_on_same_host() {
+    let previous = [http::Uri::from_static("http://a.example/login")];
+    // 写出默认端口还是同一个主机
+    for next in ["http://a.example/home", "http://a.example:80/home"] {
+      let mut headers = credential_headers();
+      remove_sensitive_headers(&mut headers, &http::Uri::from_static(next), &previous);
+      assert_eq!(headers.len(), 3, "{}", next);
+    }
+    let previous = [http::Uri::from_static("https://a.example:443/login")];
+    let mut headers = credential_headers();
+    remove_sensitive_headers(&mut headers, &http::Uri::from_static("https://a.example/"), &previous);
+    assert_eq!(headers.len(), 3);
+  }
+
+  #[test]
+  fn too_many_redirects() {
+    let moved = "HTTP/1.1 302 Found\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n";
+    let mock = crate::MockTransport::new().respond(moved).respond(moved);
+    let policy = super::Policy::custom(|attempt| {
+      if attempt.previous().len() > 1 {
+        attempt.too_many_redirects()
+      } else {
+        attempt.follow()
+      }
+    });
+    let client = crate::Client::builder()
+      .transport(mock)
+      .redirect(policy)
+      .build()
+      .unwrap();
+    let err = client.get("http://a.example/").send().unwrap_err();
+    assert_eq!(err.kind(), crate::ErrorKind::TooManyRedirects);
+    assert_eq!(err.url().map(|u| u.to_string()).as_deref(), Some("http://a.example/next"));
+  }
+
+  #[test]
+  fn redirect_loop() {
+    let mock = crate::MockTransport::new()
+      .respond(to("/b"))
+      .respond(to("/a"))
+      .respond(to("/b"));
+    let client = crate::Client::builder().transport(mock).build().unwrap();
+    let err = client.get("http://a.example/a").send().unwrap_err();
+    assert_eq!(err.kind(), crate::ErrorKind::RedirectLoop);
+    assert_eq!(
+      err.to_string(),
+      "http://a.example/b: redirect loop: http://a.example/a -> http://a.example/b -> \
+       http://a.example/a -> http://a.example/b"
+    );
+    // 关掉检测后跟随到跳转次数用完，返回最后一个跳转响应
+    let mock = crate::MockTransport::new().respond(to("/b")).respond(to("/a")).respond(to("/b"));
+    let client = crate::Client::builder()
+      .transport(mock)
+      .detect_redirect_loops(false)
+      .redirect(Policy::Limit(2))
+      .build()
+      .unwrap();
+    let response = client.get("http://a.example/a").send().unwrap();
+    assert_eq!(response.status_code(), 302);
+    assert_eq!(response.uri(), "http://a.example/b");
+  }
+
+  #[test]
+  fn redirect_back_once() {
+    // 回到原地址一次不算循环
+    let mock = crate::MockTransport::new()
+      .respond(to("/login"))
+      .respond(to("/a"))
+      .respond("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    let client = crate::Client::builder().transport(mock).build().unwrap();
+    assert_eq!(client.get("http://a.example/a").send().unwrap().status_code(), 200);
+  }
+
+  #[test]
+  fn redirect_downgrade() {
+    // 默认跟随
+    let mock = crate::MockTransport::new()
+      .respond(to("http://a.example/"))
+      .respond("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    let client = crate::Client::builder().transport(mock).build().unwrap();
+    assert_eq!(client.get("https://a.example/").send().unwrap().status_code(), 200);
+    let mock = crate::MockTransport::new().respond(to("http://a.example/"));
+    let client = crate::Client::builder()
+      .transport(mock)
+      .deny_redirect_downgrade(true)
+      .build()
+      .unwrap();
+    let err = client.get("https://a.example/").send().unwrap_err();
+    assert_eq!(err.kind(), crate::ErrorKind::InsecureRedirect);
+  }
+
+  #[test]
+  fn resolve_location() {
+    let base = http::Uri::from_static("https://example.com/a/b/c?x=1");
+    let lenient = |l: &str| LocationPolicy::Lenient.resolve(l.as_bytes(), &base).map(|u| u.to_string());
+    let strict = |l: &str| LocationPolicy::Strict.resolve(l.as_bytes(), &base).map(|u| u.to_string());
+    for (location, expected) in [
+      ("d", "https://example.com/a/b/d"),
+      ("../d/./e", "https://example.com/a/d/e"),
+      ("..", "https://example.com/a/"),
+      ("/x/../y#frag", "https://example.com/y"),
+      ("?y=2", "https://example.com/a/b/c?y=2"),
+      ("//other.example/p", "https://other.example/p"),
+      ("HTTP://Other.example", "http://Other.example/"),
+    ] {
+      assert_eq!(lenient(location).as_deref(), Some(expected), "{}", location);
+      assert_eq!(strict(location).as_deref(), Some(expected), "{}", location);
     }
+    assert_eq!(lenient(" /a b\\c ").as_deref(), Some("https://example.com/a%20b/c"));
+    assert_eq!(lenient("/\\evil.example/").as_deref(), Some("https://evil.example/"));
+    assert_eq!(lenient("https:d").as_deref(), Some("https://example.com/a/b/d"));
+    assert_eq!(lenient("/100%/%41").as_deref(), Some("https://example.com/100%25/%41"));
+    assert_eq!(strict("/a b"), None);
+    assert_eq!(strict("https:d"), None);
+    assert_eq!(lenient("javascript:alert(1)"), None);
+    assert_eq!(lenient(""), None);
+    #[cfg(feature = "url")]
+    assert_eq!(
+      lenient("//Bücher.example:81/").as_deref(),
+      Some("https://xn--bcher-kva.example:81/")
+    );
   }
 }
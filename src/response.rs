@@ -1,9 +1,27 @@
 use crate::body::Body;
+use crate::cache::{CacheControl, ETag};
 #[cfg(feature = "cookie")]
 use crate::cookies;
-use crate::errors::Result;
-use crate::record::{HTTPRecord, LocalPeerRecord, RedirectRecord};
-use crate::{Error, COLON_SPACE, CR_LF, SPACE};
+use crate::early_hints::{EarlyHintHook, Preloads};
+use crate::errors::{new_io_error, ResponseLimit, Result};
+use crate::fingerprint;
+#[cfg(feature = "record")]
+use crate::har;
+#[cfg(feature = "record")]
+use crate::record::HTTPRecord;
+use crate::record::{
+  snapshot_bytes, snapshot_headers, AlpnRecord, ConnectionClose, LocalPeerRecord, RedirectRecord, Timing,
+};
+#[cfg(feature = "record")]
+use crate::COLON_SPACE;
+use crate::progress::Progress;
+use crate::retry::parse_http_date;
+use crate::schedule::Scheduled;
+use crate::shard::Shard;
+use crate::spill::SpillFile;
+use crate::sse::{self, Events, LiveBody, PendingStream};
+use crate::waf::WafDetection;
+use crate::{Error, CR_LF, SPACE};
 use bytes::Bytes;
 #[cfg(feature = "charset")]
 use encoding_rs::{Encoding, UTF_8};
@@ -12,7 +30,11 @@ use flate2::read::MultiGzDecoder;
 use http::Response as HttpResponse;
 #[cfg(feature = "charset")]
 use mime::Mime;
-use std::io::{BufRead, BufReader, Read};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 /// A Response to a submitted `Request`.
 #[derive(Debug, Default, Clone)]
@@ -26,7 +48,7 @@ pub struct Response {
   status_code: http::StatusCode,
   #[cfg_attr(feature = "serde", serde(with = "http_serde::header_map"))]
   headers: http::HeaderMap<http::HeaderValue>,
-  #[cfg_attr(feature = "serde", serde(skip))]
+  #[cfg_attr(feature = "serde", serde(default, with = "extensions_serde"))]
   extensions: http::Extensions,
   body: Option<Body>,
 }
@@ -59,11 +81,20 @@ impl<T> From<HttpResponse<T>> for Response
 }
 
 impl Response {
+  #[cfg(feature = "record")]
   pub(crate) fn to_raw(&self) -> Bytes {
     let mut http_response = Vec::new();
     http_response.extend(format!("{:?}", self.version).as_bytes());
     http_response.extend(SPACE);
-    http_response.extend(format!("{}", self.status_code).as_bytes());
+    http_response.extend(self.raw_status().unwrap_or(self.status_code.as_str()).as_bytes());
+    // 优先使用服务器发来的原因短语
+    let reason = self
+      .reason_raw()
+      .or_else(|| self.status_code.canonical_reason().map(str::as_bytes));
+    if let Some(reason) = reason {
+      http_response.extend(SPACE);
+      http_response.extend(reason);
+    }
     http_response.extend(CR_LF);
     for (k, v) in self.headers.iter() {
       http_response.extend(k.as_str().as_bytes());
@@ -80,6 +111,17 @@ impl Response {
     }
     Bytes::from(http_response)
   }
+  /// A stable, deterministic text rendering of this response for snapshot tests.
+  ///
+  /// Same layout as [`Request::snapshot`](crate::Request::snapshot), starting
+  /// with a `response <version> <status>` line.
+  pub fn snapshot(&self) -> String {
+    let mut out = format!("response {:?} {}\n", self.version, self.status_code);
+    snapshot_headers(&mut out, &self.headers);
+    let body = self.body.as_ref().map(|b| b.as_ref()).unwrap_or_default();
+    snapshot_bytes(&mut out, "body", body);
+    out
+  }
   /// An HTTP response builder
   ///
   /// This type can be used to construct an instance of `Response` through a
@@ -87,6 +129,122 @@ impl Response {
   pub fn builder() -> http::response::Builder {
     http::response::Builder::new()
   }
+  /// Parse a complete raw HTTP/1.x response captured elsewhere, such as a
+  /// packet capture extract or the output of a server, without any socket.
+  ///
+  /// The body is framed and decoded as if it was read from a connection:
+  /// `Content-Length`, chunked transfer coding with trailers, or everything
+  /// after the headers. Interim `1xx` responses before the final one are kept
+  /// in [`Response::informational`].
+  ///
+  /// # Errors
+  ///
+  /// Fails when `raw` is not a valid HTTP/1.x response or ends before the
+  /// body announced by its headers.
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n";
+  /// let resp = slinger::Response::from_bytes(raw)?;
+  /// assert_eq!(resp.status_code(), 200);
+  /// assert_eq!(resp.text()?, "ok");
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn from_bytes(raw: &[u8]) -> Result<Response> {
+    // 读取连接时的解析比较宽松，离线解析至少要求以状态行开头
+    if !raw.starts_with(b"HTTP/") {
+      return Err(new_io_error(
+        std::io::ErrorKind::InvalidData,
+        "raw response does not start with a status line",
+      ));
+    }
+    ResponseBuilder::new(BufReader::new(raw), ResponseConfig::new(false, None)).build()
+  }
+  /// Convert into a standard `http::Response`, choosing how the body is materialized.
+  ///
+  /// The final `http::Uri` of this `Response` is kept in the extensions.
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// use std::io::Read;
+  /// use slinger::BodyAs;
+  /// let resp = slinger::get("http://httpbin.org/get")?;
+  /// let mut http_resp = resp.into_http(BodyAs::Reader);
+  /// let mut body = String::new();
+  /// http_resp.body_mut().read_to_string(&mut body)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn into_http(self, body_as: BodyAs) -> HttpResponse<HttpBody> {
+    let body: Bytes = self.body.map(|b| Bytes::clone(&b)).unwrap_or_default();
+    let body = match body_as {
+      BodyAs::Bytes => HttpBody::Bytes(body),
+      BodyAs::Reader => HttpBody::Reader(Cursor::new(body)),
+      BodyAs::Discard => HttpBody::Empty,
+    };
+    let mut resp = HttpResponse::new(body);
+    *resp.version_mut() = self.version;
+    *resp.status_mut() = self.status_code;
+    *resp.headers_mut() = self.headers;
+    *resp.extensions_mut() = self.extensions;
+    resp.extensions_mut().insert(self.uri);
+    resp
+  }
+}
+
+/// How the body is materialized by [`Response::into_http`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyAs {
+  /// Keep the body as `Bytes`
+  Bytes,
+  /// Expose the body through `std::io::Read`
+  Reader,
+  /// Drop the body
+  Discard,
+}
+
+/// The body of an `http::Response` produced by [`Response::into_http`].
+#[derive(Debug, Clone)]
+pub enum HttpBody {
+  /// [`BodyAs::Bytes`]
+  Bytes(Bytes),
+  /// [`BodyAs::Reader`]
+  Reader(Cursor<Bytes>),
+  /// [`BodyAs::Discard`]
+  Empty,
+}
+
+impl HttpBody {
+  /// Returns the remaining body as `Bytes`, reading the reader variant to the end.
+  pub fn into_bytes(self) -> Bytes {
+    match self {
+      HttpBody::Bytes(b) => b,
+      HttpBody::Reader(r) => {
+        let position = std::cmp::min(r.position() as usize, r.get_ref().len());
+        r.into_inner().slice(position..)
+      }
+      HttpBody::Empty => Bytes::new(),
+    }
+  }
+}
+
+impl Read for HttpBody {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    match self {
+      HttpBody::Bytes(b) => {
+        let n = std::cmp::min(buf.len(), b.len());
+        buf[..n].copy_from_slice(&b.split_to(n));
+        Ok(n)
+      }
+      HttpBody::Reader(r) => r.read(buf),
+      HttpBody::Empty => Ok(0),
+    }
+  }
 }
 
 impl Response {
@@ -99,10 +257,35 @@ impl Response {
   /// This requires the optional `cookie` feature to be enabled.
   #[cfg(feature = "cookie")]
   #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
-  pub fn cookies(&self) -> impl Iterator<Item=cookies::Cookie> {
+  pub fn cookies(&self) -> impl Iterator<Item=cookies::Cookie<'_>> {
     cookies::extract_response_cookies(&self.headers).filter_map(|x| x.ok())
   }
 
+  /// The encoding of the body: from its byte order mark, the `charset`
+  /// parameter of `Content-Type`, or a `<meta>` charset declaration in the
+  /// first 1024 bytes of an HTML body, in that order.
+  ///
+  /// # Optional
+  ///
+  /// This requires the optional `charset` feature to be enabled.
+  #[cfg(feature = "charset")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "charset")))]
+  pub fn charset(&self) -> Option<&'static Encoding> {
+    let body = self.body().as_ref().map(|b| b.as_ref()).unwrap_or_default();
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+      return Some(encoding);
+    }
+    let content_type = self
+      .headers
+      .get(http::header::CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse::<Mime>().ok());
+    content_type
+      .as_ref()
+      .and_then(|mime| mime.get_param("charset"))
+      .and_then(|charset| Encoding::for_label(charset.as_str().as_bytes()))
+      .or_else(|| Encoding::for_label(meta_charset(body)?))
+  }
   /// 获取编码并且尝试解码
   #[cfg(feature = "charset")]
   pub fn text_with_charset(&self, default_encoding: &str) -> Result<String> {
@@ -111,32 +294,24 @@ impl Response {
     } else {
       return Ok(String::new());
     };
-    let content_type = self
-      .headers
-      .get(http::header::CONTENT_TYPE)
-      .and_then(|value| value.to_str().ok())
-      .and_then(|value| value.parse::<Mime>().ok());
-    let header_encoding = content_type
-      .as_ref()
-      .and_then(|mime| mime.get_param("charset").map(|charset| charset.as_str()))
-      .unwrap_or(default_encoding);
-    let mut decode_text = String::new();
-    for encoding_name in &[header_encoding, &default_encoding] {
-      let encoding = Encoding::for_label(encoding_name.as_bytes()).unwrap_or(UTF_8);
+    let default = Encoding::for_label(default_encoding.as_bytes()).unwrap_or(UTF_8);
+    let encoding = self.charset().unwrap_or(default);
+    // 声明的编码解不开时试一下默认编码，都不行就替换掉错误的字节
+    for encoding in [encoding, default] {
       let (text, _, is_errors) = encoding.decode(body);
       if !is_errors {
-        decode_text = text.to_string();
-        break;
+        return Ok(text.into_owned());
       }
     }
-    Ok(decode_text)
+    Ok(encoding.decode(body).0.into_owned())
   }
   /// Get the response text.
   ///
   /// This method decodes the response body with BOM sniffing
   /// and with malformed sequences replaced with the REPLACEMENT CHARACTER.
   /// Encoding is determined from the `charset` parameter of `Content-Type` header,
-  /// and defaults to `utf-8` if not presented.
+  /// or a `<meta>` declaration of an HTML body, and defaults to `utf-8` if not
+  /// presented, see [`Response::charset`].
   ///
   /// # Note
   ///
@@ -152,7 +327,6 @@ impl Response {
   /// # Ok(())
   /// # }
   /// ```
-
   pub fn text(&self) -> Result<String> {
     #[cfg(feature = "charset")]
     {
@@ -162,6 +336,111 @@ impl Response {
     #[cfg(not(feature = "charset"))]
     Ok(String::from_utf8_lossy(&self.body().clone().unwrap_or_default()).to_string())
   }
+  /// Get the response text like [`Response::text`], but fail instead of
+  /// replacing malformed sequences.
+  ///
+  /// # Errors
+  ///
+  /// Fails with an `InvalidData` error when the body is not valid in its
+  /// encoding.
+  pub fn text_strict(&self) -> Result<String> {
+    let body = self.body().as_ref().map(|b| b.as_ref()).unwrap_or_default();
+    #[cfg(feature = "charset")]
+    {
+      let encoding = self.charset().unwrap_or(UTF_8);
+      let (encoding, body) = match Encoding::for_bom(body) {
+        Some((encoding, bom)) => (encoding, &body[bom..]),
+        None => (encoding, body),
+      };
+      encoding
+        .decode_without_bom_handling_and_without_replacement(body)
+        .map(|text| text.into_owned())
+        .ok_or_else(|| {
+          new_io_error(
+            std::io::ErrorKind::InvalidData,
+            &format!("body is not valid {}", encoding.name()),
+          )
+        })
+    }
+    #[cfg(not(feature = "charset"))]
+    String::from_utf8(body.to_vec())
+      .map_err(|_| new_io_error(std::io::ErrorKind::InvalidData, "body is not valid UTF-8"))
+  }
+  /// Write the body to the file at `path`, replacing it, and return the
+  /// number of bytes written.
+  ///
+  /// The body was already read into memory. To write a large body to a file
+  /// while it is received instead, set
+  /// [`RequestBuilder::download_to`](crate::RequestBuilder::download_to) on
+  /// the request.
+  pub fn copy_to_file<P: AsRef<Path>>(&self, path: P) -> Result<u64> {
+    let body = self.body.as_ref().map(|b| b.as_ref()).unwrap_or_default();
+    let mut file = File::create(path)?;
+    file.write_all(body)?;
+    file.flush()?;
+    Ok(body.len() as u64)
+  }
+  /// Get the text of the HTML `<title>` of the body, with whitespace
+  /// collapsed and common character references decoded.
+  ///
+  /// The title is decoded with the [`Response::charset`] when the `charset`
+  /// feature is enabled, as UTF-8 otherwise.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let resp = slinger::get("http://example.com/")?;
+  /// println!(
+  ///   "{} [{}] lines={} words={} sha256={}",
+  ///   resp.status_code(),
+  ///   resp.title().unwrap_or_default(),
+  ///   resp.line_count(),
+  ///   resp.word_count(),
+  ///   resp.body_hash()
+  /// );
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn title(&self) -> Option<String> {
+    let raw = fingerprint::title(self.body_bytes())?;
+    #[cfg(feature = "charset")]
+    let raw = self.charset().unwrap_or(UTF_8).decode(raw).0;
+    #[cfg(not(feature = "charset"))]
+    let raw = String::from_utf8_lossy(raw);
+    Some(fingerprint::clean_title(&raw))
+  }
+  /// Get the favicon hash of the body as computed by Shodan, the signed
+  /// 32-bit MurmurHash3 of its base64 with a line break every 76 characters.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let resp = slinger::get("http://example.com/favicon.ico")?;
+  /// println!("http.favicon.hash:{}", resp.favicon_hash());
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn favicon_hash(&self) -> i32 {
+    fingerprint::favicon_hash(self.body_bytes())
+  }
+  /// Get the lowercase hex SHA-256 of the body.
+  pub fn body_hash(&self) -> String {
+    fingerprint::body_hash(self.body_bytes())
+  }
+  /// Count the words of the body, separated by ASCII whitespace.
+  pub fn word_count(&self) -> usize {
+    fingerprint::word_count(self.body_bytes())
+  }
+  /// Count the lines of the body, a last line without a line break included.
+  pub fn line_count(&self) -> usize {
+    fingerprint::line_count(self.body_bytes())
+  }
+  /// 响应体的字节，没有响应体时为空
+  fn body_bytes(&self) -> &[u8] {
+    self.body.as_ref().map(|b| b.as_ref()).unwrap_or_default()
+  }
   /// Get the `StatusCode` of this `Response`.
   ///
   /// # Examples
@@ -259,6 +538,29 @@ impl Response {
       .get(http::header::CONTENT_LENGTH)
       .and_then(|x| x.to_str().ok()?.parse().ok())
   }
+  /// The entity tag of the `ETag` header, for
+  /// [`RequestBuilder::if_none_match`](crate::RequestBuilder::if_none_match).
+  pub fn etag(&self) -> Option<ETag> {
+    ETag::parse(self.headers.get(http::header::ETAG)?.to_str().ok()?)
+  }
+  /// The time of the `Last-Modified` header, for
+  /// [`RequestBuilder::if_modified_since`](crate::RequestBuilder::if_modified_since).
+  pub fn last_modified(&self) -> Option<SystemTime> {
+    parse_http_date(self.headers.get(http::header::LAST_MODIFIED)?.to_str().ok()?.trim())
+  }
+  /// The directives of the `Cache-Control` headers, `None` when there is
+  /// none.
+  pub fn cache_control(&self) -> Option<CacheControl> {
+    CacheControl::from_headers(&self.headers)
+  }
+  /// Get the final URL of this `Response` as a `url::Url`, `None` when it
+  /// is not absolute, for example for a response parsed with
+  /// [`Response::from_bytes`].
+  #[cfg(feature = "url")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+  pub fn url(&self) -> Option<url::Url> {
+    url::Url::parse(&self.uri.to_string()).ok()
+  }
   /// Get the final `http::Uri` of this `Response`.
   ///
   /// # Example
@@ -323,6 +625,185 @@ impl Response {
   pub fn local_peer_record(&self) -> Option<&LocalPeerRecord> {
     self.extensions().get::<LocalPeerRecord>()
   }
+  /// Get the remote address that answered this `Response`.
+  ///
+  /// With a proxy this is the address of the proxy.
+  pub fn remote_addr(&self) -> Option<SocketAddr> {
+    self.local_peer_record()?.remote_addr.as_socket()
+  }
+  /// Get the local address of the connection used to get this `Response`.
+  pub fn local_addr(&self) -> Option<SocketAddr> {
+    self.local_peer_record()?.local_addr.as_socket()
+  }
+  /// Get how the connection ended after this `Response`.
+  ///
+  /// `None` unless the client was built with
+  /// [`ClientBuilder::probe_connection_close`](crate::ClientBuilder::probe_connection_close),
+  /// and for event streams, whose connection stays with the `Response`.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// use slinger::record::ConnectionClose;
+  ///
+  /// let client = slinger::Client::builder().probe_connection_close(true).build()?;
+  /// let resp = client.get("http://httpbin.org/get").send()?;
+  /// if resp.connection_close() == Some(ConnectionClose::Reset) {
+  ///   println!("the server reset the connection");
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn connection_close(&self) -> Option<ConnectionClose> {
+    self.extensions().get::<ConnectionClose>().copied()
+  }
+  /// Returns true if this `Response` was read from a connection opened for an
+  /// earlier request, such as the one before a redirect on the same host.
+  pub fn connection_reused(&self) -> bool {
+    self
+      .local_peer_record()
+      .is_some_and(|record| record.reused)
+  }
+  /// Close the connection this `Response` still holds and return whether
+  /// there was one.
+  ///
+  /// Only an event stream whose body was not taken by [`Response::events`]
+  /// keeps its connection, the connection of any other `Response` is shut
+  /// down before it is returned, see [`Response::connection_close`]. The
+  /// connection is closed for every clone of this `Response`, instead of
+  /// when the last of them is dropped.
+  pub fn close_connection(&self) -> bool {
+    self
+      .extensions()
+      .get::<LiveBody>()
+      .and_then(LiveBody::take)
+      .is_some()
+  }
+  /// Get the WAF or rate limit detected on this `Response`, see
+  /// [`ClientBuilder::detect_waf`](crate::ClientBuilder::detect_waf).
+  pub fn waf(&self) -> Option<&WafDetection> {
+    self.extensions().get::<WafDetection>()
+  }
+  /// Get what was repaired to read this response with
+  /// [`ClientBuilder::lenient_parsing`](crate::ClientBuilder::lenient_parsing),
+  /// empty for a well-formed response.
+  pub fn repairs(&self) -> &[Repair] {
+    self.extensions().get::<Repairs>().map_or(&[], |r| r.0.as_slice())
+  }
+  /// Get the shard the request was tagged with by
+  /// [`RequestBuilder::shard`](crate::RequestBuilder::shard).
+  pub fn shard(&self) -> Option<Shard> {
+    self.extensions().get::<Shard>().copied()
+  }
+  /// Get the reason phrase of the status line exactly as received, such as
+  /// `b"Not Found"`, a localized text or an empty phrase.
+  ///
+  /// [`StatusCode`](http::StatusCode) only knows the standard phrases, this
+  /// keeps what the server sent for fingerprinting. `None` when the status
+  /// line had no reason phrase, or for HTTP/2 which has none.
+  pub fn reason_raw(&self) -> Option<&[u8]> {
+    self.extensions().get::<ReasonPhrase>().map(|r| r.0.as_ref())
+  }
+  /// Get the number of request bytes, head included, written before the
+  /// server started answering a request sent with
+  /// [`RequestBuilder::early_response`](crate::RequestBuilder::early_response).
+  ///
+  /// `None` when the whole request was sent.
+  pub fn upload_interrupted(&self) -> Option<usize> {
+    self.extensions().get::<UploadInterrupted>().map(|u| u.0)
+  }
+  /// Get the trailer fields sent after a chunked body or at the end of an
+  /// HTTP/2 stream, such as `grpc-status`.
+  ///
+  /// Trailers are kept apart from [`Response::headers`]; `None` when the
+  /// server sent none.
+  pub fn trailers(&self) -> Option<&http::HeaderMap> {
+    self.extensions().get::<Trailers>().map(|t| &t.0)
+  }
+  /// Get the interim `1xx` responses received before this `Response`, in order.
+  ///
+  /// `101 Switching Protocols` is a final response and is never listed here.
+  pub fn informational(&self) -> &[Informational] {
+    self
+      .extensions()
+      .get::<Vec<Informational>>()
+      .map(Vec::as_slice)
+      .unwrap_or_default()
+  }
+  /// Take the handles of the preloads started for the `103 Early Hints` of
+  /// this response, see [`ClientBuilder::early_hints`](crate::ClientBuilder::early_hints).
+  ///
+  /// Returns an empty list once taken.
+  pub fn take_preloads(&mut self) -> Vec<Scheduled> {
+    self
+      .extensions()
+      .get::<Preloads>()
+      .and_then(|preloads| preloads.0.lock().ok().map(|mut p| std::mem::take(&mut *p)))
+      .unwrap_or_default()
+  }
+  /// Iterate over the events of a `text/event-stream` response.
+  ///
+  /// Requests sent with [`RequestBuilder::event_stream`](crate::RequestBuilder::event_stream)
+  /// over HTTP/1.1 get a successful event stream response without its body,
+  /// and the events are read from the connection as they arrive. Otherwise
+  /// the events are parsed from the body that was already read.
+  ///
+  /// # Errors
+  ///
+  /// Fails when the events of a streamed response have already been taken by
+  /// a previous call, on this `Response` or one of its clones.
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// let resp = slinger::Client::new()
+  ///   .get("http://example.com/events")
+  ///   .event_stream()
+  ///   .send()?;
+  /// for event in resp.events()? {
+  ///   let event = event?;
+  ///   println!("{}: {}", event.event, event.data);
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn events(&self) -> Result<Events> {
+    match self.extensions().get::<LiveBody>() {
+      Some(live) => live.take().map(Events::new).ok_or_else(sse::consumed),
+      None => Ok(Events::new(std::io::Cursor::new(
+        self.body().as_deref().cloned().unwrap_or_default(),
+      ))),
+    }
+  }
+  /// Get the status code token exactly as the server sent it, when it was not
+  /// a valid three digit code and kept by [`StatusCodePolicy::Raw`].
+  pub fn raw_status(&self) -> Option<&str> {
+    self.extensions().get::<RawStatus>().map(|raw| raw.0.as_str())
+  }
+  /// Get the ALPN protocols offered and negotiated to get this `Response`.
+  ///
+  /// Only set for TLS connections.
+  pub fn alpn_record(&self) -> Option<&AlpnRecord> {
+    self.extensions().get::<AlpnRecord>()
+  }
+  /// Get the DNS, connect, TLS, first byte and download durations of this `Response`.
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// let resp = slinger::get("http://httpbin.org/get")?;
+  /// if let Some(timing) = resp.timing() {
+  ///   println!("ttfb {:?}, total {:?}", timing.first_byte, timing.total());
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn timing(&self) -> Option<&Timing> {
+    self.extensions().get::<Timing>()
+  }
   /// Get the http record used to get this `Response`.
   ///
   /// # Example
@@ -334,9 +815,39 @@ impl Response {
   /// # Ok(())
   /// # }
   /// ```
+  #[cfg(feature = "record")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "record")))]
   pub fn http_record(&self) -> Option<&Vec<HTTPRecord>> {
     self.extensions().get::<Vec<HTTPRecord>>()
   }
+  /// Serialize every hop of the exchange that got this `Response`, redirects
+  /// included, as an HTTP Archive (HAR 1.2) JSON document.
+  ///
+  /// To archive every request of a client, see
+  /// [`HarRecorder`](crate::HarRecorder).
+  ///
+  /// # Example
+  ///
+  /// ```rust,no_run
+  /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+  /// let resp = slinger::get("http://httpbin.org/redirect/1")?;
+  /// std::fs::write("exchange.har", resp.to_har())?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "record")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+  pub fn to_har(&self) -> String {
+    har::from_records(self.http_record().map(|r| r.as_slice()).unwrap_or_default())
+  }
+  /// Get the last request sent to get this `Response`, after redirects,
+  /// cookies and authentication were applied, for example to turn it into a
+  /// curl command with [`Request::to_curl`](crate::Request::to_curl).
+  #[cfg(feature = "record")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "record")))]
+  pub fn request(&self) -> Option<&crate::Request> {
+    self.http_record()?.last().map(|record| &record.request)
+  }
   /// Get the redirect record used to get this `Response`.
   ///
   /// # Example
@@ -361,13 +872,299 @@ pub struct ResponseBuilder<T: Read> {
   builder: http::response::Builder,
   reader: BufReader<T>,
   config: ResponseConfig,
+  partial: Option<PartialBody>,
+  trailers: Option<http::HeaderMap>,
+  repairs: Vec<Repair>,
+  sink: Option<File>,
+  spilled: Option<SpillFile>,
+  received: u64,
+  total: Option<u64>,
 }
 
 /// response config
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ResponseConfig {
   unsafe_response: bool,
   max_read: Option<u64>,
+  allow_partial: bool,
+  method: Option<http::Method>,
+  limits: ResponseLimits,
+  status_code_policy: StatusCodePolicy,
+  event_stream: bool,
+  stream_body: bool,
+  early_hints: Option<EarlyHintHook>,
+  http09: bool,
+  lenient: bool,
+  body_termination: BodyTermination,
+  progress: Option<Progress>,
+  download_to: Option<PathBuf>,
+}
+
+/// A deviation from HTTP/1.1 repaired while reading a response with
+/// [`ClientBuilder::lenient_parsing`](crate::ClientBuilder::lenient_parsing),
+/// see [`Response::repairs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Repair {
+  /// Bytes before the status line that were skipped, with their count
+  LeadingGarbage(usize),
+  /// The status line as received, when it had to be normalized, such as
+  /// `http/1.1  200OK`
+  StatusLine(String),
+  /// The status line had no reason phrase
+  MissingReason,
+  /// Lines ended with a bare `\n` instead of `\r\n`
+  BareLineFeed,
+  /// A header was continued on an indented line (obs-fold), with its name
+  FoldedHeader(http::HeaderName),
+  /// Whitespace between a header name and the colon was removed, with the name
+  HeaderNameWhitespace(http::HeaderName),
+}
+
+/// 宽松解析时做过的修复
+#[derive(Clone, Debug)]
+pub(crate) struct Repairs(Vec<Repair>);
+
+/// 宽松解析时在这么多字节内查找状态行
+const MAX_LEADING_GARBAGE: usize = 4096;
+/// 读取响应体时每次最多读取的字节数
+const READ_CHUNK: u64 = 64 * 1024;
+
+/// An interim `1xx` response received before the final response, such as
+/// `100 Continue` or `103 Early Hints`, see [`Response::informational`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Informational {
+  /// The HTTP version of the status line
+  pub version: http::Version,
+  /// The status code
+  pub status: http::StatusCode,
+  /// The headers, for example the `Link` headers of `103 Early Hints`
+  pub headers: http::HeaderMap,
+}
+
+/// How a status line with a malformed status code is handled, such as
+/// `HTTP/1.1 0200 OK` sent by broken devices or a code outside `100..=999`.
+///
+/// A valid code is always used as is. Set with
+/// [`ClientBuilder::status_code_policy`](crate::ClientBuilder::status_code_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatusCodePolicy {
+  /// Fail the request with [`Error::Http`].
+  Error,
+  /// Read the leading digits as a number and clamp it to `100..=999`, so
+  /// `0200` becomes `200` and `42` becomes `100`. Fails like
+  /// [`StatusCodePolicy::Error`] when the token does not start with a digit.
+  Clamp,
+  /// Use `200 OK` and keep the original token, see [`Response::raw_status`].
+  #[default]
+  Raw,
+}
+
+impl StatusCodePolicy {
+  fn parse(&self, token: &str) -> Result<(http::StatusCode, Option<RawStatus>)> {
+    let invalid = match http::StatusCode::from_bytes(token.as_bytes()) {
+      Ok(code) => return Ok((code, None)),
+      Err(err) => err,
+    };
+    match self {
+      StatusCodePolicy::Error => Err(Error::Http(http::Error::from(invalid))),
+      StatusCodePolicy::Clamp => {
+        let digits = token.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+          return Err(Error::Http(http::Error::from(invalid)));
+        }
+        // 数字太长时解析会溢出，直接当作最大值
+        let code = token[..digits].parse::<u16>().unwrap_or(u16::MAX).clamp(100, 999);
+        Ok((http::StatusCode::from_u16(code).unwrap_or_default(), None))
+      }
+      StatusCodePolicy::Raw => Ok((
+        http::StatusCode::default(),
+        Some(RawStatus(token.to_string())),
+      )),
+    }
+  }
+}
+
+/// How the end of a response body is found, set per request with
+/// [`RequestBuilder::body_termination`](crate::RequestBuilder::body_termination).
+///
+/// Every strategy other than [`BodyTermination::Auto`] ignores the framing
+/// headers it does not name, which shows what a server really sends after a
+/// `Content-Length` it lies about or how a desync leaves the connection. The
+/// body size limits of the client still apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyTermination {
+  /// `Transfer-Encoding: chunked`, then `Content-Length`, then until the
+  /// connection closes.
+  #[default]
+  Auto,
+  /// Exactly `Content-Length` bytes, an empty body without it; a
+  /// `Transfer-Encoding` header is ignored.
+  ContentLength,
+  /// Decode chunked encoding whatever the headers say.
+  Chunked,
+  /// Everything until the server closes the connection.
+  UntilClose,
+  /// At most this many bytes, stopping earlier when the connection closes.
+  Bytes(u64),
+  /// Until nothing arrives for this long, or the connection closes.
+  Idle(Duration),
+}
+
+/// 序列化时只保留已知的记录类扩展，其它扩展丢弃
+#[cfg(feature = "serde")]
+mod extensions_serde {
+  use super::{RawStatus, ReasonPhrase, Trailers};
+  #[cfg(feature = "record")]
+  use crate::record::HTTPRecord;
+  use crate::record::{AlpnRecord, ConnectionClose, LocalPeerRecord, RedirectRecord, Timing};
+  use bytes::Bytes;
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+  #[derive(Serialize)]
+  struct KnownRef<'a> {
+    #[cfg(feature = "record")]
+    http_record: Option<&'a Vec<HTTPRecord>>,
+    redirect_record: Option<&'a RedirectRecord>,
+    local_peer_record: Option<&'a LocalPeerRecord>,
+    alpn_record: Option<&'a AlpnRecord>,
+    timing: Option<&'a Timing>,
+    connection_close: Option<&'a ConnectionClose>,
+    reason: Option<&'a [u8]>,
+    raw_status: Option<&'a str>,
+    #[serde(with = "http_serde::option::header_map")]
+    trailers: Option<http::HeaderMap>,
+  }
+
+  #[derive(Deserialize, Default)]
+  #[serde(default)]
+  struct Known {
+    #[cfg(feature = "record")]
+    http_record: Option<Vec<HTTPRecord>>,
+    redirect_record: Option<RedirectRecord>,
+    local_peer_record: Option<LocalPeerRecord>,
+    alpn_record: Option<AlpnRecord>,
+    timing: Option<Timing>,
+    connection_close: Option<ConnectionClose>,
+    reason: Option<Vec<u8>>,
+    raw_status: Option<String>,
+    #[serde(with = "http_serde::option::header_map")]
+    trailers: Option<http::HeaderMap>,
+  }
+
+  pub fn serialize<S: Serializer>(extensions: &http::Extensions, s: S) -> Result<S::Ok, S::Error> {
+    KnownRef {
+      #[cfg(feature = "record")]
+      http_record: extensions.get(),
+      redirect_record: extensions.get(),
+      local_peer_record: extensions.get(),
+      alpn_record: extensions.get(),
+      timing: extensions.get(),
+      connection_close: extensions.get(),
+      reason: extensions.get::<ReasonPhrase>().map(|r| r.0.as_ref()),
+      raw_status: extensions.get::<RawStatus>().map(|r| r.0.as_str()),
+      trailers: extensions.get::<Trailers>().map(|t| t.0.clone()),
+    }
+    .serialize(s)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<http::Extensions, D::Error> {
+    let known = Known::deserialize(d)?;
+    let mut extensions = http::Extensions::new();
+    #[cfg(feature = "record")]
+    if let Some(records) = known.http_record {
+      extensions.insert(records);
+    }
+    if let Some(record) = known.redirect_record {
+      extensions.insert(record);
+    }
+    if let Some(record) = known.local_peer_record {
+      extensions.insert(record);
+    }
+    if let Some(record) = known.alpn_record {
+      extensions.insert(record);
+    }
+    if let Some(timing) = known.timing {
+      extensions.insert(timing);
+    }
+    if let Some(close) = known.connection_close {
+      extensions.insert(close);
+    }
+    if let Some(reason) = known.reason {
+      extensions.insert(ReasonPhrase(Bytes::from(reason)));
+    }
+    if let Some(raw) = known.raw_status {
+      extensions.insert(RawStatus(raw));
+    }
+    if let Some(trailers) = known.trailers {
+      extensions.insert(Trailers(trailers));
+    }
+    Ok(extensions)
+  }
+}
+
+/// 解析后的状态行
+struct StatusLine {
+  version: http::Version,
+  code: http::StatusCode,
+  raw: Option<RawStatus>,
+  /// 状态码本身合法，没有经过 `StatusCodePolicy` 处理
+  valid: bool,
+  reason: Option<Bytes>,
+}
+
+/// 状态行中的原始原因短语
+#[derive(Clone, Debug)]
+pub(crate) struct ReasonPhrase(pub(crate) Bytes);
+
+/// 请求没有发送完服务器就开始响应时已经写入的字节数
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UploadInterrupted(pub(crate) usize);
+
+/// 分块响应体或 HTTP/2 响应结束后收到的 trailer
+#[derive(Clone, Debug)]
+pub(crate) struct Trailers(pub(crate) http::HeaderMap);
+
+/// 按 `StatusCodePolicy::Raw` 保留的原始状态码
+#[derive(Clone, Debug)]
+pub(crate) struct RawStatus(String);
+
+/// 读取响应时的大小限制，由 `ClientBuilder::max_*` 设置
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "gzip"), derive(Default))]
+pub(crate) struct ResponseLimits {
+  pub(crate) body_size: Option<u64>,
+  pub(crate) header_size: Option<usize>,
+  pub(crate) header_count: Option<usize>,
+  /// 超过这个大小的响应体写到临时文件
+  pub(crate) spill_threshold: Option<u64>,
+  #[cfg(feature = "gzip")]
+  pub(crate) decompressed_size: u64,
+  #[cfg(feature = "gzip")]
+  pub(crate) compression_ratio: u64,
+}
+
+/// 默认解压后最多 100 MiB
+#[cfg(feature = "gzip")]
+const DEFAULT_DECOMPRESSED_SIZE: u64 = 100 * 1024 * 1024;
+/// 默认压缩比最多 100 倍
+#[cfg(feature = "gzip")]
+const DEFAULT_COMPRESSION_RATIO: u64 = 100;
+/// 解压后小于 1 MiB 时不检查压缩比，避免误伤重复度高的小响应
+#[cfg(feature = "gzip")]
+const RATIO_FLOOR: u64 = 1024 * 1024;
+
+#[cfg(feature = "gzip")]
+impl Default for ResponseLimits {
+  fn default() -> Self {
+    ResponseLimits {
+      body_size: None,
+      header_size: None,
+      header_count: None,
+      spill_threshold: None,
+      decompressed_size: DEFAULT_DECOMPRESSED_SIZE,
+      compression_ratio: DEFAULT_COMPRESSION_RATIO,
+    }
+  }
 }
 
 impl ResponseConfig {
@@ -376,10 +1173,149 @@ impl ResponseConfig {
     ResponseConfig {
       unsafe_response,
       max_read,
+      allow_partial: false,
+      method: None,
+      limits: ResponseLimits::default(),
+      status_code_policy: StatusCodePolicy::default(),
+      event_stream: false,
+      stream_body: false,
+      early_hints: None,
+      http09: false,
+      lenient: false,
+      body_termination: BodyTermination::Auto,
+      progress: None,
+      download_to: None,
+    }
+  }
+  /// 请求指定的响应体结束方式
+  pub(crate) fn body_termination(mut self, termination: BodyTermination) -> Self {
+    self.body_termination = termination;
+    self
+  }
+  /// 容忍格式错误的响应并记录修复
+  pub(crate) fn lenient(mut self, enable: bool) -> Self {
+    self.lenient = enable;
+    self
+  }
+  /// 请求是 HTTP/0.9 时响应可能没有状态行和响应头
+  pub(crate) fn http09(mut self, enable: bool) -> Self {
+    self.http09 = enable;
+    self
+  }
+  /// 连接中断时保留已读取的 Content-Length 响应体，而不是返回错误
+  pub(crate) fn allow_partial(mut self, allow: bool) -> Self {
+    self.allow_partial = allow;
+    self
+  }
+  /// 请求方法决定响应是否带有body：HEAD 没有，CONNECT 成功后是隧道
+  pub(crate) fn request_method(mut self, method: &http::Method) -> Self {
+    self.method = Some(method.clone());
+    self
+  }
+  pub(crate) fn limits(mut self, limits: ResponseLimits) -> Self {
+    self.limits = limits;
+    self
+  }
+  pub(crate) fn status_code_policy(mut self, policy: StatusCodePolicy) -> Self {
+    self.status_code_policy = policy;
+    self
+  }
+  /// 成功的事件流响应只读取响应头，响应体留给 `Response::events`
+  pub(crate) fn event_stream(mut self, enable: bool) -> Self {
+    self.event_stream = enable;
+    self
+  }
+  /// 最终响应只读取响应头，响应体留给 `BodyReader`，跳转响应照常读取
+  pub(crate) fn stream_body(mut self, enable: bool) -> Self {
+    self.stream_body = enable;
+    self
+  }
+  pub(crate) fn early_hints(mut self, hook: EarlyHintHook) -> Self {
+    self.early_hints = Some(hook);
+    self
+  }
+  /// 每次读到响应体的数据时报告进度
+  pub(crate) fn progress(mut self, progress: Option<Progress>) -> Self {
+    self.progress = progress;
+    self
+  }
+  /// 成功响应的响应体写到文件里
+  pub(crate) fn download_to(mut self, path: Option<PathBuf>) -> Self {
+    self.download_to = path;
+    self
+  }
+  /// 收到 103 时立即交给客户端的策略处理，不等最终响应
+  pub(crate) fn on_early_hints(&self, status: http::StatusCode, headers: &http::HeaderMap) {
+    if let (Some(hook), 103) = (&self.early_hints, status.as_u16()) {
+      (hook.0)(headers);
+    }
+  }
+  /// 按 Content-Encoding 解码响应体
+  #[cfg_attr(not(feature = "gzip"), allow(unused_variables))]
+  pub(crate) fn decode_body(&self, header: &http::HeaderMap, body: Bytes) -> Result<Bytes> {
+    #[cfg(feature = "gzip")]
+    if let Some(ce) = header.get(http::header::CONTENT_ENCODING) {
+      if ce == "gzip" {
+        return self.gunzip(&body).map(Bytes::from);
+      }
+    }
+    Ok(body)
+  }
+  /// 解压时同时限制解压后的大小和压缩比，超过任意一个就停止读取
+  #[cfg(feature = "gzip")]
+  fn gunzip(&self, body: &[u8]) -> Result<Vec<u8>> {
+    let limits = &self.limits;
+    let ratio_limit = (body.len() as u64)
+      .saturating_mul(limits.compression_ratio)
+      .max(RATIO_FLOOR);
+    let mut gzip_body = Vec::new();
+    MultiGzDecoder::new(body)
+      .take(limits.decompressed_size.min(ratio_limit).saturating_add(1))
+      .read_to_end(&mut gzip_body)?;
+    let size = gzip_body.len() as u64;
+    if size > ratio_limit {
+      return Err(Error::ResponseLimit(
+        ResponseLimit::CompressionRatio,
+        limits.compression_ratio,
+      ));
+    }
+    if size > limits.decompressed_size {
+      return Err(Error::ResponseLimit(
+        ResponseLimit::DecompressedSize,
+        limits.decompressed_size,
+      ));
+    }
+    Ok(gzip_body)
+  }
+
+  pub(crate) fn check_body_size(&self, size: u64) -> Result<()> {
+    match self.limits.body_size {
+      Some(max) if size > max => Err(Error::ResponseLimit(ResponseLimit::BodySize, max)),
+      _ => Ok(()),
+    }
+  }
+  fn has_body(&self, status: http::StatusCode) -> bool {
+    match self.method.as_ref() {
+      Some(&http::Method::HEAD) => false,
+      Some(&http::Method::CONNECT) => !status.is_success(),
+      _ => true,
     }
   }
 }
 
+/// 读取中断的响应体，放在响应的 extensions 中，用于断点续传
+#[derive(Clone, Debug)]
+pub(crate) struct PartialBody {
+  pub(crate) kind: std::io::ErrorKind,
+  pub(crate) message: String,
+}
+
+impl From<PartialBody> for Error {
+  fn from(partial: PartialBody) -> Self {
+    Error::IO(std::io::Error::new(partial.kind, partial.message))
+  }
+}
+
 impl<T: Read> ResponseBuilder<T> {
   /// Constructs a new response.
   pub fn new(reader: BufReader<T>, config: ResponseConfig) -> ResponseBuilder<T> {
@@ -387,14 +1323,116 @@ impl<T: Read> ResponseBuilder<T> {
       builder: Default::default(),
       reader,
       config,
+      partial: None,
+      trailers: None,
+      repairs: Vec::new(),
+      sink: None,
+      spilled: None,
+      received: 0,
+      total: None,
+    }
+  }
+  fn repair(&mut self, repair: Repair) {
+    if !self.repairs.contains(&repair) {
+      self.repairs.push(repair);
+    }
+  }
+  /// 跳过状态行之前的空行和垃圾数据，只在缓冲区里查找
+  fn skip_garbage(&mut self, used: &mut usize) -> Result<()> {
+    let buffer = self.reader.fill_buf()?;
+    let window = &buffer[..buffer.len().min(MAX_LEADING_GARBAGE)];
+    let start = window
+      .windows(5)
+      .position(|w| w.eq_ignore_ascii_case(b"HTTP/"));
+    if let Some(skip) = start.filter(|skip| *skip > 0) {
+      self.reader.consume(skip);
+      *used += skip;
+      self.repair(Repair::LeadingGarbage(skip));
+    }
+    Ok(())
+  }
+  /// 把不规范的状态行整理成 `HTTP/1.1 200 OK` 的形式
+  fn normalize_status_line(&mut self, line: &[u8]) -> Vec<u8> {
+    let text = line.strip_suffix(b"\n").unwrap_or(line);
+    let text = text.strip_suffix(b"\r").unwrap_or(text);
+    let is_space = |b: &u8| *b == b' ' || *b == b'\t';
+    let mut fields = text.split(is_space).filter(|field| !field.is_empty());
+    let version = fields.next().unwrap_or_default().to_ascii_uppercase();
+    let mut code = fields.next().unwrap_or_default().to_vec();
+    // 原因短语保留原始字节，从第二个字段之后开始
+    let mut reason = match (version.len(), code.len()) {
+      (0, _) | (_, 0) => Vec::new(),
+      _ => {
+        let code_end = text
+          .windows(code.len())
+          .enumerate()
+          .skip(version.len())
+          .find(|(_, w)| *w == code.as_slice())
+          .map_or(text.len(), |(i, _)| i + code.len());
+        let rest = &text[code_end..];
+        rest[rest.iter().take_while(|b| is_space(b)).count()..].to_vec()
+      }
+    };
+    // `200OK` 这样状态码和原因短语连在一起
+    if code.len() > 3 && code[..3].iter().all(u8::is_ascii_digit) && !code[3].is_ascii_digit() {
+      let mut joined = code.split_off(3);
+      if !reason.is_empty() {
+        joined.push(b' ');
+        joined.extend(reason);
+      }
+      reason = joined;
     }
+    let mut normalized = version;
+    normalized.push(b' ');
+    normalized.extend(&code);
+    if !reason.is_empty() {
+      normalized.push(b' ');
+      normalized.extend(&reason);
+    }
+    if normalized != text {
+      self.repair(Repair::StatusLine(String::from_utf8_lossy(text).into_owned()));
+    }
+    if reason.is_empty() {
+      self.repair(Repair::MissingReason);
+    }
+    if !line.ends_with(b"\r\n") && line.ends_with(b"\n") {
+      self.repair(Repair::BareLineFeed);
+    }
+    normalized.extend(CR_LF);
+    normalized
+  }
+  /// 读取一行响应头，状态行和所有头部共用 `header_size` 限制
+  fn read_head_line(&mut self, line: &mut Vec<u8>, used: &mut usize) -> Result<usize> {
+    let length = match self.config.limits.header_size {
+      Some(max) => {
+        let remaining = max.saturating_sub(*used);
+        let length = (&mut self.reader)
+          .take(remaining as u64 + 1)
+          .read_until(b'\n', line)?;
+        if length > remaining {
+          return Err(Error::ResponseLimit(ResponseLimit::HeaderSize, max as u64));
+        }
+        length
+      }
+      None => self.reader.read_until(b'\n', line)?,
+    };
+    *used += length;
+    Ok(length)
   }
-  fn parser_version(&mut self) -> Result<(http::Version, http::StatusCode)> {
-    let mut buffer = String::new();
-    self.reader.read_line(&mut buffer)?;
+  fn parser_version(&mut self, used: &mut usize) -> Result<StatusLine> {
+    if self.config.lenient {
+      self.skip_garbage(used)?;
+    }
+    let mut line = Vec::new();
+    self.read_head_line(&mut line, used)?;
+    if self.config.lenient {
+      line = self.normalize_status_line(&line);
+    }
+    let buffer = String::from_utf8_lossy(&line);
+    let buffer = buffer.trim_end_matches(['\r', '\n']);
     let mut version = http::Version::default();
-    let mut code = http::StatusCode::default();
-    for (index, vc) in buffer.splitn(3, |c| c == ' ').enumerate() {
+    let mut token = "";
+    for (index, vc) in buffer.splitn(3, ' ').enumerate() {
       match index {
         0 => {
           version = match vc {
@@ -407,58 +1445,281 @@ impl<T: Read> ResponseBuilder<T> {
           };
         }
         1 => {
-          code = http::StatusCode::try_from(vc).unwrap_or_default();
+          token = vc;
         }
         _ => {}
       }
     }
-    Ok((version, code))
+    let (code, raw) = self.config.status_code_policy.parse(token)?;
+    // 原因短语按原始字节保存，不做 UTF-8 转换
+    let status_line = line.strip_suffix(b"\n").unwrap_or(&line);
+    let status_line = status_line.strip_suffix(b"\r").unwrap_or(status_line);
+    let reason = status_line
+      .splitn(3, |b| *b == b' ')
+      .nth(2)
+      .map(Bytes::copy_from_slice);
+    Ok(StatusLine {
+      version,
+      code,
+      raw,
+      valid: http::StatusCode::from_bytes(token.as_bytes()).is_ok(),
+      reason,
+    })
   }
-  fn read_headers(&mut self) -> http::HeaderMap {
+  fn read_headers(&mut self, used: &mut usize) -> Result<http::HeaderMap> {
     // 读取请求头
     let mut headers = http::HeaderMap::new();
     let mut header_line = Vec::new();
-    while let Ok(length) = self.reader.read_until(b'\n', &mut header_line) {
+    let mut count = 0;
+    // 上一个请求头的名字，折叠行接在它的值后面
+    let mut last: Option<http::HeaderName> = None;
+    loop {
+      let length = match self.read_head_line(&mut header_line, used) {
+        Ok(length) => length,
+        Err(err @ Error::ResponseLimit(..)) => return Err(err),
+        Err(_) => break,
+      };
       if length == 0 || header_line == b"\r\n" {
         break;
       }
+      if self.config.lenient {
+        if header_line == b"\n" {
+          self.repair(Repair::BareLineFeed);
+          break;
+        }
+        if self.lenient_header(&mut headers, &mut last, &mut header_line) {
+          header_line.clear();
+          continue;
+        }
+      }
+      count += 1;
+      if let Some(max) = self.config.limits.header_count {
+        if count > max {
+          return Err(Error::ResponseLimit(ResponseLimit::HeaderCount, max as u64));
+        }
+      }
       if let Ok((Some(k), Some(v))) = parser_headers(&header_line) {
-        headers.insert(k, v);
+        last = Some(k.clone());
+        headers.append(k, v);
       };
       header_line.clear();
     }
-    headers
+    Ok(headers)
+  }
+  /// 修复一行请求头：折叠行、单独的 `\n`、名字和冒号之间的空白。
+  /// 返回 `true` 时这一行已经处理完，不再按普通请求头解析
+  fn lenient_header(
+    &mut self,
+    headers: &mut http::HeaderMap,
+    last: &mut Option<http::HeaderName>,
+    line: &mut Vec<u8>,
+  ) -> bool {
+    if !line.ends_with(b"\r\n") && line.ends_with(b"\n") {
+      self.repair(Repair::BareLineFeed);
+      line.pop();
+      line.extend(CR_LF);
+    }
+    // 以空白开头的行是上一个请求头的延续
+    if line.first().is_some_and(|b| *b == b' ' || *b == b'\t') {
+      let Some(name) = last.clone() else {
+        return true;
+      };
+      let continuation = line.trim_ascii();
+      if let http::header::Entry::Occupied(mut entry) = headers.entry(&name) {
+        if let Some(value) = entry.iter_mut().last() {
+          let mut joined = value.as_bytes().to_vec();
+          joined.push(b' ');
+          joined.extend(continuation);
+          if let Ok(joined) = http::HeaderValue::from_bytes(&joined) {
+            *value = joined;
+          }
+        }
+      }
+      self.repair(Repair::FoldedHeader(name));
+      return true;
+    }
+    if let Some(colon) = line.iter().position(|b| *b == b':') {
+      let name = &line[..colon];
+      let trimmed = name.trim_ascii_end();
+      if trimmed.len() != name.len() {
+        if let Ok(parsed) = http::HeaderName::from_bytes(trimmed) {
+          self.repair(Repair::HeaderNameWhitespace(parsed));
+          line.drain(trimmed.len()..colon);
+        }
+      }
+    }
+    false
   }
-  fn read_body(&mut self, header: &http::HeaderMap) -> Result<Vec<u8>> {
+  fn read_body(&mut self, status: http::StatusCode, header: &http::HeaderMap) -> Result<Bytes> {
     let mut content_length: Option<u64> = header
       .get(http::header::CONTENT_LENGTH)
       .and_then(|x| x.to_str().ok()?.parse().ok());
     if self.config.unsafe_response {
       content_length = None;
     }
+    let encoded = header.contains_key(http::header::CONTENT_ENCODING);
+    // 只有成功响应写到文件，压缩过的响应体解压之后再写
+    let mut download = match &self.config.download_to {
+      Some(path) if status.is_success() => Some(File::create(path)?),
+      _ => None,
+    };
+    if !encoded {
+      self.sink = download.take();
+    }
+    // 已知长度超过阈值时直接写到临时文件
+    if let (None, Some(length), Some(threshold)) =
+      (&self.sink, content_length, self.config.limits.spill_threshold)
+    {
+      if length > threshold {
+        self.spill(&mut Vec::new())?;
+      }
+    }
+    let body = match self.config.body_termination {
+      BodyTermination::Auto => match (
+        header.get(http::header::TRANSFER_ENCODING),
+        content_length,
+      ) {
+        (Some(te), _) if te == "chunked" => self.read_chunked_body()?,
+        (Some(_), _) => Vec::new(),
+        (None, Some(cl)) => self.read_sized(header, cl)?,
+        (None, None) => self.read_to_close(None)?,
+      },
+      BodyTermination::ContentLength => match content_length {
+        Some(cl) => self.read_sized(header, cl)?,
+        None => Vec::new(),
+      },
+      BodyTermination::Chunked => self.read_chunked_body()?,
+      BodyTermination::UntilClose => self.read_to_close(None)?,
+      BodyTermination::Bytes(cap) => self.read_to_close(Some(cap))?,
+      BodyTermination::Idle(_) => self.read_until_idle()?,
+    };
+    let body = match (self.sink.take(), self.spilled.take()) {
+      (Some(file), Some(spilled)) => spilled.into_bytes(&file)?,
+      (Some(mut file), None) => {
+        file.flush()?;
+        return Ok(Bytes::new());
+      }
+      (None, _) => Bytes::from(body),
+    };
+    let body = self.config.decode_body(header, body)?;
+    match download {
+      Some(mut file) => {
+        file.write_all(&body)?;
+        Ok(Bytes::new())
+      }
+      None => Ok(body),
+    }
+  }
+  /// 把已经读到的部分写进临时文件，之后的数据都写到文件里
+  fn spill(&mut self, body: &mut Vec<u8>) -> Result<()> {
+    let (spilled, mut file) = SpillFile::create()?;
+    file.write_all(body)?;
+    *body = Vec::new();
+    self.sink = Some(file);
+    self.spilled = Some(spilled);
+    Ok(())
+  }
+  /// 读到的响应体写到下载文件或者留在内存里，同时报告进度
+  fn emit(&mut self, body: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    self.received += data.len() as u64;
+    if let (None, Some(threshold)) = (&self.sink, self.config.limits.spill_threshold) {
+      if self.received > threshold {
+        self.spill(body)?;
+      }
+    }
+    match &mut self.sink {
+      Some(file) => file.write_all(data)?,
+      None => body.extend_from_slice(data),
+    }
+    if let Some(progress) = &self.config.progress {
+      progress.report(self.received, self.total);
+    }
+    Ok(())
+  }
+  /// 按 Content-Length 读取
+  fn read_sized(&mut self, header: &http::HeaderMap, mut cl: u64) -> Result<Vec<u8>> {
+    // 如果有最大读取限制，取一个最小的长度
+    if let Some(max_read) = self.config.max_read {
+      cl = std::cmp::min(cl, max_read);
+    }
+    self.config.check_body_size(cl)?;
+    self.total = Some(cl);
+    // 压缩过的响应体无法按字节续传
+    let partial = self.config.allow_partial && !header.contains_key(http::header::CONTENT_ENCODING);
     let mut body = Vec::new();
-    if let Some(te) = header.get(http::header::TRANSFER_ENCODING) {
-      if te == "chunked" {
-        body = self.read_chunked_body()?;
+    if self.sink.is_none() {
+      body.reserve(cl as usize);
+    }
+    let mut buf = vec![0; cl.min(READ_CHUNK) as usize];
+    let mut remaining = cl;
+    while remaining > 0 {
+      let want = remaining.min(buf.len() as u64) as usize;
+      let err = match self.reader.read(&mut buf[..want]) {
+        Ok(0) => std::io::Error::new(
+          std::io::ErrorKind::UnexpectedEof,
+          "connection closed before the body was complete",
+        ),
+        Ok(n) => {
+          self.emit(&mut body, &buf[..n])?;
+          remaining -= n as u64;
+          continue;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+        Err(err) => err,
+      };
+      if !partial {
+        return Err(err.into());
       }
-    } else if let Some(mut cl) = content_length {
-      // 如果有最大读取限制，取一个最小的长度
-      if let Some(max_read) = self.config.max_read {
-        cl = std::cmp::min(cl, max_read);
+      self.partial = Some(PartialBody {
+        kind: err.kind(),
+        message: err.to_string(),
+      });
+      break;
+    }
+    Ok(body)
+  }
+  /// 读到连接关闭，最多读取 `cap` 字节
+  fn read_to_close(&mut self, cap: Option<u64>) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let over_limit = self.config.limits.body_size.map(|max| max + 1);
+    let mut remaining = cap.into_iter().chain(over_limit).min().unwrap_or(u64::MAX);
+    let mut buf = vec![0; READ_CHUNK as usize];
+    while remaining > 0 {
+      let want = remaining.min(buf.len() as u64) as usize;
+      match self.reader.read(&mut buf[..want]) {
+        Ok(0) => break,
+        Ok(n) => {
+          self.emit(&mut body, &buf[..n])?;
+          remaining -= n as u64;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+        Err(err) => return Err(err.into()),
       }
-      let mut buf = vec![0; cl as usize];
-      self.reader.read_exact(&mut buf)?;
-      body = buf;
-    } else {
-      self.reader.read_to_end(&mut body)?;
     }
-    #[cfg(feature = "gzip")]
-    if let Some(ce) = header.get(http::header::CONTENT_ENCODING) {
-      if ce == "gzip" {
-        let mut gzip_body = Vec::new();
-        let mut d = MultiGzDecoder::new(&body[..]);
-        d.read_to_end(&mut gzip_body)?;
-        body = gzip_body;
+    self.config.check_body_size(self.received)?;
+    Ok(body)
+  }
+  /// 读到一段时间没有数据，超时由读取器按 `BodyTermination::Idle` 设置
+  fn read_until_idle(&mut self) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+      match self.reader.read(&mut buf) {
+        Ok(0) => break,
+        Ok(n) => {
+          self.emit(&mut body, &buf[..n])?;
+          self.config.check_body_size(self.received)?;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+        Err(err)
+          if matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+          ) =>
+        {
+          break
+        }
+        Err(err) => return Err(err.into()),
       }
     }
     Ok(body)
@@ -487,30 +1748,224 @@ impl<T: Read> ResponseBuilder<T> {
         }
       }
       if chunk == "0" || chunk.is_empty() {
+        // 最后一个分块之后是 trailer 和结束的空行
+        let trailers = self.read_headers(&mut 0)?;
+        if !trailers.is_empty() {
+          self.trailers = Some(trailers);
+        }
         break;
       }
       let chunk = usize::from_str_radix(&chunk, 16)?;
+      self.config.check_body_size(self.received.saturating_add(chunk as u64))?;
       let mut chunk_of_bytes = vec![0; chunk];
       self.reader.read_exact(&mut chunk_of_bytes)?;
-      body.append(&mut chunk_of_bytes);
+      self.emit(&mut body, &chunk_of_bytes)?;
     }
     Ok(body)
   }
 
   /// Build a `Response`, which can be inspected, modified and executed with
   /// `Client::execute()`.
-  pub fn build(mut self) -> Result<Response> {
-    let (v, c) = self.parser_version()?;
+  pub fn build(self) -> Result<Response> {
+    self.build_with_reader().map(|(resp, _)| resp)
+  }
+  /// 读取一个响应并交回读取器，用于同一个连接上连续读取多个响应
+  pub(crate) fn build_with_reader(mut self) -> Result<(Response, BufReader<T>)> {
+    // 一个字节都没收到连接就断开了，复用空闲连接时多半是服务器已经把它关掉
+    match self.reader.fill_buf() {
+      Ok([]) => return Err(closed_before_response(std::io::ErrorKind::UnexpectedEof)),
+      Err(err) if matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted
+      ) => {
+        return Err(closed_before_response(err.kind()));
+      }
+      Err(err) => return Err(err.into()),
+      Ok(_) => {}
+    }
+    if self.config.http09 && !self.reader.fill_buf()?.starts_with(b"HTTP/") {
+      return self.build_http09();
+    }
+    let mut header_size = 0;
+    let mut informational = Vec::new();
+    let (StatusLine { version: v, code: c, raw, reason, .. }, header) = loop {
+      let line = self.parser_version(&mut header_size)?;
+      let header = self.read_headers(&mut header_size)?;
+      // 101 之后连接换成了其他协议，当作最终响应；修正过的状态码也不当作临时响应
+      if !line.valid || !line.code.is_informational() || line.code == http::StatusCode::SWITCHING_PROTOCOLS {
+        break (line, header);
+      }
+      self.config.on_early_hints(line.code, &header);
+      push_informational(&mut informational, line.version, line.code, header)?;
+    };
     self.builder = self.builder.version(v).status(c);
-    let header = self.read_headers();
+    let streamed = (self.config.event_stream && c.is_success() && is_event_stream(&header))
+      || (self.config.stream_body
+        && self.config.has_body(c)
+        && self.config.download_to.is_none()
+        && !c.is_redirection()
+        && c != http::StatusCode::NO_CONTENT);
+    let stream = streamed.then(|| {
+      let chunked = header
+        .get(http::header::TRANSFER_ENCODING)
+        .is_some_and(|te| te == "chunked");
+      PendingStream {
+        buffered: self.reader.buffer().to_vec(),
+        chunked,
+        length: header
+          .get(http::header::CONTENT_LENGTH)
+          .and_then(|x| x.to_str().ok()?.parse().ok())
+          .filter(|_| !chunked && !self.config.unsafe_response),
+      }
+    });
     // 读取body
-    let body = self.read_body(&header)?;
+    let body = if stream.is_none() && self.config.has_body(c) {
+      self.read_body(c, &header)?
+    } else {
+      Bytes::new()
+    };
     if let Some(h) = self.builder.headers_mut() {
       *h = header;
     }
-    let resp = self.builder.body(body)?;
-    Ok(resp.into())
+    let mut resp: Response = self.builder.body(body)?.into();
+    if let Some(partial) = self.partial {
+      resp.extensions_mut().insert(partial);
+    }
+    if let Some(raw) = raw {
+      resp.extensions_mut().insert(raw);
+    }
+    if let Some(reason) = reason {
+      resp.extensions_mut().insert(ReasonPhrase(reason));
+    }
+    if let Some(stream) = stream {
+      resp.extensions_mut().insert(stream);
+    }
+    if !informational.is_empty() {
+      resp.extensions_mut().insert(informational);
+    }
+    if let Some(trailers) = self.trailers {
+      resp.extensions_mut().insert(Trailers(trailers));
+    }
+    if !self.repairs.is_empty() {
+      resp.extensions_mut().insert(Repairs(self.repairs));
+    }
+    Ok((resp, self.reader))
+  }
+}
+
+impl<T: Read> ResponseBuilder<T> {
+  /// HTTP/0.9 的响应只有响应体，直到连接关闭
+  fn build_http09(mut self) -> Result<(Response, BufReader<T>)> {
+    let mut body = Vec::new();
+    match self.config.limits.body_size {
+      Some(max) => {
+        (&mut self.reader).take(max + 1).read_to_end(&mut body)?;
+        self.config.check_body_size(body.len() as u64)?;
+      }
+      None => {
+        self.reader.read_to_end(&mut body)?;
+      }
+    }
+    let resp = self
+      .builder
+      .version(http::Version::HTTP_09)
+      .status(http::StatusCode::OK)
+      .body(body)?
+      .into();
+    Ok((resp, self.reader))
+  }
+}
+
+/// 连接在收到响应的第一个字节之前就断开了
+#[derive(Debug)]
+struct ClosedBeforeResponse;
+
+impl std::fmt::Display for ClosedBeforeResponse {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("connection closed without a response")
+  }
+}
+
+impl std::error::Error for ClosedBeforeResponse {}
+
+fn closed_before_response(kind: std::io::ErrorKind) -> Error {
+  Error::IO(std::io::Error::new(kind, ClosedBeforeResponse))
+}
+
+/// 错误是否发生在收到任何响应字节之前，这时重新发送请求不会重复处理响应
+pub(crate) fn is_closed_before_response(err: &Error) -> bool {
+  match err.root() {
+    Error::IO(err) => {
+      err.kind() == std::io::ErrorKind::BrokenPipe
+        || err.get_ref().is_some_and(|inner| inner.is::<ClosedBeforeResponse>())
+    }
+    _ => false,
+  }
+}
+
+/// 最多保留的 1xx 临时响应数量，避免服务器无限发送
+const MAX_INFORMATIONAL: usize = 32;
+
+pub(crate) fn push_informational(
+  informational: &mut Vec<Informational>,
+  version: http::Version,
+  status: http::StatusCode,
+  headers: http::HeaderMap,
+) -> Result<()> {
+  if informational.len() >= MAX_INFORMATIONAL {
+    return Err(new_io_error(
+      std::io::ErrorKind::InvalidData,
+      "too many informational responses",
+    ));
+  }
+  informational.push(Informational {
+    version,
+    status,
+    headers,
+  });
+  Ok(())
+}
+
+fn is_event_stream(header: &http::HeaderMap) -> bool {
+  header
+    .get(http::header::CONTENT_TYPE)
+    .and_then(|ct| ct.to_str().ok())
+    .is_some_and(|ct| ct.trim_start().to_ascii_lowercase().starts_with("text/event-stream"))
+}
+
+/// 在 HTML 的前 1024 个字节里找 `<meta charset=...>` 或
+/// `<meta http-equiv="Content-Type" content="...; charset=...">` 声明的编码
+#[cfg(feature = "charset")]
+fn meta_charset(body: &[u8]) -> Option<&[u8]> {
+  let head = &body[..body.len().min(1024)];
+  let mut rest = head;
+  while let Some(start) = find_ascii_ci(rest, b"<meta") {
+    let tag = &rest[start..];
+    let tag = &tag[..tag.iter().position(|b| *b == b'>').unwrap_or(tag.len())];
+    if let Some(at) = find_ascii_ci(tag, b"charset") {
+      let value = tag[at + 7..].trim_ascii_start();
+      if let Some(value) = value.strip_prefix(b"=") {
+        let value = value.trim_ascii_start();
+        let value = value.strip_prefix(b"\"").or_else(|| value.strip_prefix(b"'")).unwrap_or(value);
+        let end = value
+          .iter()
+          .position(|b| !(b.is_ascii_alphanumeric() || b"-_:.".contains(b)))
+          .unwrap_or(value.len());
+        if end > 0 {
+          return Some(&value[..end]);
+        }
+      }
+    }
+    rest = &rest[start + 5..];
   }
+  None
+}
+
+#[cfg(feature = "charset")]
+fn find_ascii_ci(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window.eq_ignore_ascii_case(needle))
 }
 
 pub(crate) fn parser_headers(
@@ -539,3 +1994,361 @@ pub(crate) fn parser_headers(
   }
   Ok((k, v))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    BodyAs, BodyTermination, Repair, Response, ResponseBuilder, ResponseConfig, ResponseLimits,
+    StatusCodePolicy,
+  };
+  use crate::{Error, ResponseLimit};
+  use std::io::{BufReader, Read};
+  use std::time::Duration;
+
+  fn read_with(raw: &'static [u8], limits: ResponseLimits) -> crate::Result<Response> {
+    let config = ResponseConfig::new(false, None).limits(limits);
+    ResponseBuilder::new(BufReader::new(raw), config).build()
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn decompression_bomb() {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&vec![0; 4 * 1024 * 1024]).unwrap();
+    let gz = encoder.finish().unwrap();
+    let mut raw = format!(
+      "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+      gz.len()
+    )
+    .into_bytes();
+    raw.extend(gz);
+    let read = |limits| {
+      let config = ResponseConfig::new(false, None).limits(limits);
+      ResponseBuilder::new(BufReader::new(&raw[..]), config).build()
+    };
+    assert!(matches!(
+      read(ResponseLimits::default()),
+      Err(Error::ResponseLimit(ResponseLimit::CompressionRatio, 100))
+    ));
+    let limits = ResponseLimits {
+      compression_ratio: u64::MAX,
+      decompressed_size: 1024,
+      ..Default::default()
+    };
+    assert!(matches!(
+      read(limits),
+      Err(Error::ResponseLimit(ResponseLimit::DecompressedSize, 1024))
+    ));
+    let limits = ResponseLimits {
+      compression_ratio: u64::MAX,
+      ..Default::default()
+    };
+    assert_eq!(read(limits).unwrap().body().as_ref().unwrap().len(), 4 * 1024 * 1024);
+  }
+
+  #[test]
+  fn response_limits() {
+    let raw = b"HTTP/1.1 200 OK\r\nA: 1\r\nB: 2\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+    assert!(read_with(raw, ResponseLimits::default()).is_ok());
+    let exceeded = |limits, kind| {
+      matches!(read_with(raw, limits), Err(Error::ResponseLimit(k, _)) if k == kind)
+    };
+    let limits = ResponseLimits {
+      body_size: Some(4),
+      ..Default::default()
+    };
+    assert!(exceeded(limits, ResponseLimit::BodySize));
+    let limits = ResponseLimits {
+      header_size: Some(30),
+      ..Default::default()
+    };
+    assert!(exceeded(limits, ResponseLimit::HeaderSize));
+    let limits = ResponseLimits {
+      header_count: Some(2),
+      ..Default::default()
+    };
+    assert!(exceeded(limits, ResponseLimit::HeaderCount));
+  }
+
+  #[test]
+  fn spill_body() {
+    let spilled = || {
+      let prefix = format!("slinger-body-{}-", std::process::id());
+      std::fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .filter(|entry| {
+          entry
+            .as_ref()
+            .is_ok_and(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        })
+        .count()
+    };
+    let limits = ResponseLimits {
+      spill_threshold: Some(4),
+      ..Default::default()
+    };
+    // 分块的响应体读到一半超过阈值，已知长度的一开始就写到文件
+    let chunked = read_with(
+      b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n",
+      limits,
+    )
+    .unwrap();
+    let sized = read_with(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", limits).unwrap();
+    let small = read_with(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nfour", limits).unwrap();
+    assert_eq!(spilled(), 2);
+    assert_eq!(chunked.text().unwrap(), "abcdef");
+    assert_eq!(sized.clone().body().as_deref().unwrap(), b"hello".as_slice());
+    assert_eq!(small.text().unwrap(), "four");
+    drop((chunked, sized, small));
+    assert_eq!(spilled(), 0);
+  }
+
+  #[test]
+  fn malformed_status_code() {
+    let read = |raw: &'static [u8], policy| {
+      let config = ResponseConfig::new(false, None).status_code_policy(policy);
+      ResponseBuilder::new(BufReader::new(raw), config).build()
+    };
+    let raw = b"HTTP/1.1 0404 Not Found\r\nContent-Length: 0\r\n\r\n";
+    assert!(matches!(read(raw, StatusCodePolicy::Error), Err(Error::Http(_))));
+    assert_eq!(read(raw, StatusCodePolicy::Clamp).unwrap().status_code(), 404);
+    let resp = read(raw, StatusCodePolicy::Raw).unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.raw_status(), Some("0404"));
+    let raw = b"HTTP/1.1 42\r\nContent-Length: 0\r\n\r\n";
+    assert_eq!(read(raw, StatusCodePolicy::Clamp).unwrap().status_code(), 100);
+    let raw = b"HTTP/1.1 OK\r\nContent-Length: 0\r\n\r\n";
+    assert!(read(raw, StatusCodePolicy::Clamp).is_err());
+    let raw = b"HTTP/1.1 204\r\n\r\n";
+    let resp = read(raw, StatusCodePolicy::Error).unwrap();
+    assert_eq!(resp.status_code(), 204);
+    assert_eq!(resp.raw_status(), None);
+  }
+
+  #[test]
+  fn parse_from_bytes() {
+    let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\nX-Sum: 1\r\n\r\n";
+    let resp = Response::from_bytes(raw).unwrap();
+    assert_eq!(resp.text().unwrap(), "abcde");
+    assert_eq!(resp.trailers().unwrap()["x-sum"], "1");
+    let resp = Response::from_bytes(b"HTTP/1.0 404 Not Found\r\nServer: x\r\n\r\ngone").unwrap();
+    assert_eq!(resp.status_code(), 404);
+    assert_eq!(resp.text().unwrap(), "gone");
+    assert!(Response::from_bytes(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort").is_err());
+    assert!(Response::from_bytes(b"not a response").is_err());
+  }
+
+  #[cfg(feature = "charset")]
+  #[test]
+  fn decode_charsets() {
+    let sjis = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=Shift_JIS\r\nContent-Length: 4\r\n\r\n\x93\xfa\x96\x7b";
+    let resp = Response::from_bytes(sjis).unwrap();
+    assert_eq!(resp.text().unwrap(), "日本");
+    let gbk = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html><META http-equiv=Content-Type content='text/html; charset=gbk'>\xd6\xd0\xce\xc4";
+    let resp = Response::from_bytes(gbk).unwrap();
+    assert_eq!(resp.charset().unwrap().name(), "GBK");
+    assert!(resp.text().unwrap().ends_with(">中文"));
+    let latin = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=iso-8859-1\r\n\r\ncaf\xe9";
+    assert_eq!(Response::from_bytes(latin).unwrap().text().unwrap(), "café");
+    let broken = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nok\xff";
+    let resp = Response::from_bytes(broken).unwrap();
+    assert_eq!(resp.text().unwrap(), "ok\u{fffd}");
+    assert!(resp.text_strict().is_err());
+  }
+
+  #[test]
+  fn validators() {
+    let raw = b"HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nLast-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\nCache-Control: max-age=0, must-revalidate\r\n\r\n";
+    let resp = Response::from_bytes(raw).unwrap();
+    assert_eq!(resp.etag().unwrap().tag(), "v1");
+    let modified = resp.last_modified().unwrap();
+    assert_eq!(modified.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(), 784111777);
+    let cache_control = resp.cache_control().unwrap();
+    assert_eq!(cache_control.max_age, Some(Duration::ZERO));
+    assert!(cache_control.must_revalidate);
+  }
+
+  #[test]
+  fn informational_responses() {
+    let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+    let resp = read_with(raw, ResponseLimits::default()).unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.text().unwrap(), "ok");
+    let interim = resp.informational();
+    assert_eq!(interim.len(), 2);
+    assert_eq!(interim[0].status, 100);
+    assert_eq!(interim[1].status, 103);
+    assert_eq!(interim[1].headers["link"], "</style.css>; rel=preload");
+  }
+
+  #[test]
+  fn reason_phrase() {
+    let raw = b"HTTP/1.1 404 Nicht Gefunden \xe2\x9c\x97\r\nContent-Length: 0\r\n\r\n";
+    let resp = read_with(raw, ResponseLimits::default()).unwrap();
+    assert_eq!(resp.reason_raw(), Some("Nicht Gefunden \u{2717}".as_bytes()));
+    #[cfg(feature = "record")]
+    assert!(resp.to_raw().starts_with(b"HTTP/1.1 404 Nicht Gefunden \xe2\x9c\x97\r\n"));
+    let raw = b"HTTP/1.1 599\r\nContent-Length: 0\r\n\r\n";
+    let resp = read_with(raw, ResponseLimits::default()).unwrap();
+    assert_eq!(resp.reason_raw(), None);
+    #[cfg(feature = "record")]
+    assert!(resp.to_raw().starts_with(b"HTTP/1.1 599\r\n"));
+    let raw = b"HTTP/1.1 200 \r\nContent-Length: 0\r\n\r\n";
+    assert_eq!(read_with(raw, ResponseLimits::default()).unwrap().reason_raw(), Some(&b""[..]));
+  }
+
+  #[test]
+  fn chunked_trailers() {
+    let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: grpc-status\r\n\r\n2\r\nok\r\n0\r\ngrpc-status: 0\r\ngrpc-message: done\r\n\r\n";
+    let resp = read_with(raw, ResponseLimits::default()).unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+    let trailers = resp.trailers().unwrap();
+    assert_eq!(trailers["grpc-status"], "0");
+    assert_eq!(trailers["grpc-message"], "done");
+    assert!(resp.headers().get("grpc-status").is_none());
+    let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n";
+    assert!(read_with(raw, ResponseLimits::default()).unwrap().trailers().is_none());
+  }
+
+  #[test]
+  fn lenient_parsing_repairs() {
+    let raw = b"\r\n\x00junk\nhttp/1.1  200OK\nServer: box\nX-Long: a\n\tb\nContent-Type : text/plain\nContent-Length: 2\n\nok";
+    let config = ResponseConfig::new(false, None).lenient(true);
+    let resp = ResponseBuilder::new(BufReader::new(&raw[..]), config).build().unwrap();
+    assert_eq!(resp.status_code(), http::StatusCode::OK);
+    assert_eq!(resp.version(), http::Version::HTTP_11);
+    assert_eq!(resp.reason_raw(), Some(&b"OK"[..]));
+    assert_eq!(resp.headers()["x-long"], "a b");
+    assert_eq!(resp.headers()["content-type"], "text/plain");
+    assert_eq!(resp.text().unwrap(), "ok");
+    assert_eq!(
+      resp.repairs(),
+      [
+        Repair::LeadingGarbage(8),
+        Repair::StatusLine("http/1.1  200OK".to_string()),
+        Repair::BareLineFeed,
+        Repair::FoldedHeader(http::HeaderName::from_static("x-long")),
+        Repair::HeaderNameWhitespace(http::header::CONTENT_TYPE),
+      ]
+    );
+    let resp = read_with(b"HTTP/1.1 204\r\n\r\n", ResponseLimits::default()).unwrap();
+    assert!(resp.repairs().is_empty());
+  }
+
+  #[test]
+  fn body_termination_strategies() {
+    let read = |raw: &'static [u8], termination| {
+      let config = ResponseConfig::new(false, None).body_termination(termination);
+      ResponseBuilder::new(BufReader::new(raw), config).build().unwrap().text().unwrap()
+    };
+    let lying =
+      b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+    assert_eq!(read(lying, BodyTermination::Auto), "abc");
+    assert_eq!(read(lying, BodyTermination::ContentLength), "3\r");
+    assert_eq!(read(lying, BodyTermination::Bytes(6)), "3\r\nabc");
+    assert_eq!(read(lying, BodyTermination::UntilClose), "3\r\nabc\r\n0\r\n\r\n");
+    let plain = b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n2\r\nok\r\n0\r\n\r\n";
+    assert_eq!(read(plain, BodyTermination::Chunked), "ok");
+    assert_eq!(
+      read(plain, BodyTermination::Idle(Duration::from_millis(10))),
+      "2\r\nok\r\n0\r\n\r\n"
+    );
+  }
+
+  #[test]
+  fn http09_response() {
+    let read = |raw: &'static [u8]| {
+      let config = ResponseConfig::new(false, None).http09(true);
+      ResponseBuilder::new(BufReader::new(raw), config).build()
+    };
+    let resp = read(b"<html>legacy</html>").unwrap();
+    assert_eq!(resp.version(), http::Version::HTTP_09);
+    assert_eq!(resp.status_code(), http::StatusCode::OK);
+    assert_eq!(resp.text().unwrap(), "<html>legacy</html>");
+    let resp = read(b"HTTP/1.0 404 Not Found\r\nContent-Length: 2\r\n\r\nno").unwrap();
+    assert_eq!(resp.version(), http::Version::HTTP_10);
+    assert_eq!(resp.status_code(), http::StatusCode::NOT_FOUND);
+    assert!(read(b"").is_err());
+  }
+
+  #[test]
+  fn into_http_body_dispositions() {
+    let resp: Response = Response::builder()
+      .status(201)
+      .header("X-Test", "1")
+      .body("hello")
+      .unwrap()
+      .into();
+    let http_resp = resp.clone().into_http(BodyAs::Bytes);
+    assert_eq!(http_resp.status(), http::StatusCode::CREATED);
+    assert_eq!(http_resp.headers()["x-test"], "1");
+    assert_eq!(http_resp.into_body().into_bytes(), "hello");
+    let mut body = String::new();
+    resp
+      .clone()
+      .into_http(BodyAs::Reader)
+      .body_mut()
+      .read_to_string(&mut body)
+      .unwrap();
+    assert_eq!(body, "hello");
+    assert!(resp.into_http(BodyAs::Discard).into_body().into_bytes().is_empty());
+  }
+
+  #[test]
+  fn connection_addresses() {
+    use std::io::Write;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // 同一个连接上先跳转再响应
+    let server = std::thread::spawn(move || {
+      let (mut stream, peer) = listener.accept().unwrap();
+      let mut buf = [0u8; 1024];
+      let _ = stream.read(&mut buf).unwrap();
+      stream
+        .write_all(b"HTTP/1.1 302 Found\r\nLocation: /b\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+      let _ = stream.read(&mut buf).unwrap();
+      stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+        .unwrap();
+      peer
+    });
+    let client = crate::Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let resp = client.get(format!("http://{}/a", addr)).send().unwrap();
+    let peer = server.join().unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.remote_addr(), Some(addr));
+    assert_eq!(resp.local_addr(), Some(peer));
+    assert!(resp.connection_reused());
+  }
+
+  #[test]
+  fn phase_timing() {
+    use std::io::Write;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // 先等一会儿才响应，响应体分两次发
+    std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let _ = stream.read(&mut [0u8; 1024]).unwrap();
+      std::thread::sleep(Duration::from_millis(200));
+      stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nok").unwrap();
+      std::thread::sleep(Duration::from_millis(200));
+      stream.write_all(b"ok").unwrap();
+    });
+    let client = crate::Client::builder().timeout(Duration::from_secs(5)).build().unwrap();
+    let resp = client.get(format!("http://localhost:{}/", addr.port())).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "okok");
+    let timing = *resp.timing().unwrap();
+    assert!(timing.first_byte >= Duration::from_millis(150), "{:?}", timing);
+    assert!(timing.download >= Duration::from_millis(150), "{:?}", timing);
+    assert!(timing.first_byte < Duration::from_secs(2), "{:?}", timing);
+    assert_eq!(timing.tls, Duration::ZERO);
+    assert_eq!(
+      timing.total(),
+      timing.dns + timing.connect + timing.first_byte + timing.download
+    );
+  }
+}
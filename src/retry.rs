@@ -0,0 +1,405 @@
+use crate::errors::{Error, Result};
+use crate::{Request, Response};
+use http::{Method, StatusCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+static JITTER_STATE: AtomicU64 = AtomicU64::new(0);
+
+/// A type that controls when and how often a failed request is sent again.
+///
+/// Retries happen on connect errors, timeouts and the configured status codes
+/// (`429`, `502`, `503` and `504` by default). The delay between attempts grows
+/// exponentially from `base_delay` up to `max_delay` with random jitter, and a
+/// `Retry-After` header sent by the server takes precedence. Only idempotent
+/// methods are retried unless [`RetryPolicy::retry_non_idempotent`] is enabled.
+///
+/// Responses tagged by [`ClientBuilder::detect_waf`](crate::ClientBuilder::detect_waf)
+/// are handled by what was detected: rate limits are retried whatever their
+/// status, block pages are not retried unless [`RetryPolicy::retry_waf_blocks`]
+/// is enabled.
+///
+/// # Example
+///
+/// ```rust
+/// # fn run() -> Result<(), slinger::Error> {
+/// use std::time::Duration;
+/// use slinger::RetryPolicy;
+///
+/// let policy = RetryPolicy::new(5)
+///   .base_delay(Duration::from_millis(200))
+///   .max_delay(Duration::from_secs(5));
+/// let client = slinger::Client::builder().retry(policy).build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[allow(unpredictable_function_pointer_comparisons)]
+pub struct RetryPolicy {
+  max_attempts: usize,
+  base_delay: Duration,
+  max_delay: Duration,
+  jitter: bool,
+  statuses: Vec<StatusCode>,
+  retry_non_idempotent: bool,
+  honor_retry_after: bool,
+  retry_waf_blocks: bool,
+  retry_if: Option<fn(&Request, &Result<Response>) -> bool>,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy::new(3)
+  }
+}
+
+impl RetryPolicy {
+  /// Create a `RetryPolicy` allowing at most `max_attempts` attempts, the first one included.
+  pub fn new(max_attempts: usize) -> Self {
+    RetryPolicy {
+      max_attempts,
+      base_delay: Duration::from_millis(100),
+      max_delay: Duration::from_secs(10),
+      jitter: true,
+      statuses: vec![
+        StatusCode::TOO_MANY_REQUESTS,
+        StatusCode::BAD_GATEWAY,
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::GATEWAY_TIMEOUT,
+      ],
+      retry_non_idempotent: false,
+      honor_retry_after: true,
+      retry_waf_blocks: false,
+      retry_if: None,
+    }
+  }
+  /// Delay before the first retry, doubled for every following one.
+  ///
+  /// Default is 100 milliseconds.
+  pub fn base_delay(mut self, delay: Duration) -> Self {
+    self.base_delay = delay;
+    self
+  }
+  /// Upper bound of the delay between two attempts, `Retry-After` included.
+  ///
+  /// Default is 10 seconds.
+  pub fn max_delay(mut self, delay: Duration) -> Self {
+    self.max_delay = delay;
+    self
+  }
+  /// Enable or disable random jitter on the backoff delay.
+  ///
+  /// Default is `true`.
+  pub fn jitter(mut self, enable: bool) -> Self {
+    self.jitter = enable;
+    self
+  }
+  /// Set the response status codes that trigger a retry.
+  pub fn statuses<I: IntoIterator<Item=StatusCode>>(mut self, statuses: I) -> Self {
+    self.statuses = statuses.into_iter().collect();
+    self
+  }
+  /// Allow retrying non-idempotent methods such as `POST` and `PATCH`.
+  ///
+  /// Default is `false`.
+  pub fn retry_non_idempotent(mut self, enable: bool) -> Self {
+    self.retry_non_idempotent = enable;
+    self
+  }
+  /// Enable or disable honoring the `Retry-After` response header.
+  ///
+  /// Default is `true`.
+  pub fn honor_retry_after(mut self, enable: bool) -> Self {
+    self.honor_retry_after = enable;
+    self
+  }
+  /// Retry responses detected as WAF block pages like any other response.
+  ///
+  /// Default is `false`, a block page is returned at once.
+  pub fn retry_waf_blocks(mut self, enable: bool) -> Self {
+    self.retry_waf_blocks = enable;
+    self
+  }
+  /// Only retry when `predicate` returns `true`, on top of the other rules.
+  ///
+  /// The request is passed with its extensions, so a tag set with
+  /// [`RequestBuilder::extension`](crate::RequestBuilder::extension) can
+  /// decide whether it is worth retrying.
+  ///
+  /// # Example
+  ///
+  /// ```rust
+  /// # fn run() -> Result<(), slinger::Error> {
+  /// #[derive(Clone)]
+  /// struct Probe;
+  ///
+  /// // failed probes are not worth retrying
+  /// let policy = slinger::RetryPolicy::new(3)
+  ///   .retry_if(|request, _| request.extensions().get::<Probe>().is_none());
+  /// let client = slinger::Client::builder().retry(policy).build()?;
+  /// let resp = client.get("http://example.com").extension(Probe).send();
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn retry_if(mut self, predicate: fn(&Request, &Result<Response>) -> bool) -> Self {
+    self.retry_if = Some(predicate);
+    self
+  }
+  /// Returns the delay to wait before sending `request` again after `attempt`
+  /// attempts ended with `result`, or `None` if it should not be retried.
+  pub fn next_delay(
+    &self,
+    request: &Request,
+    result: &Result<Response>,
+    attempt: usize,
+  ) -> Option<Duration> {
+    if attempt >= self.max_attempts {
+      return None;
+    }
+    if !self.retry_non_idempotent && !is_idempotent(request.method()) {
+      return None;
+    }
+    if self.retry_if.is_some_and(|retry_if| !retry_if(request, result)) {
+      return None;
+    }
+    let retry_after = match result {
+      Ok(response) => {
+        let retryable = match response.waf() {
+          Some(detection) if detection.rate_limited => true,
+          Some(_) if !self.retry_waf_blocks => false,
+          _ => self.statuses.contains(&response.status_code()),
+        };
+        if !retryable {
+          return None;
+        }
+        if self.honor_retry_after {
+          retry_after(response)
+        } else {
+          None
+        }
+      }
+      Err(err) => {
+        if !is_retryable_error(err) {
+          return None;
+        }
+        None
+      }
+    };
+    let delay = retry_after.unwrap_or_else(|| self.backoff(attempt));
+    Some(std::cmp::min(delay, self.max_delay))
+  }
+  fn backoff(&self, attempt: usize) -> Duration {
+    let exp = u32::try_from(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+    let backoff = self
+      .base_delay
+      .checked_mul(2u32.saturating_pow(exp))
+      .unwrap_or(self.max_delay);
+    let backoff = std::cmp::min(backoff, self.max_delay);
+    if self.jitter {
+      // 一半固定一半随机，避免多个客户端同时重试
+      let half = backoff / 2;
+      half + half.mul_f64(random_unit())
+    } else {
+      backoff
+    }
+  }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+  matches!(
+    *method,
+    Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+  )
+}
+
+fn is_retryable_error(err: &Error) -> bool {
+  use std::io::ErrorKind;
+  match err.root() {
+    Error::IO(e) => matches!(
+      e.kind(),
+      ErrorKind::ConnectionRefused
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::NotConnected
+        | ErrorKind::BrokenPipe
+        | ErrorKind::TimedOut
+        | ErrorKind::WouldBlock
+        | ErrorKind::UnexpectedEof
+    ),
+    _ => false,
+  }
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+  let value = response
+    .headers()
+    .get(http::header::RETRY_AFTER)?
+    .to_str()
+    .ok()?
+    .trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let date = parse_http_date(value)?;
+  Some(
+    date
+      .duration_since(SystemTime::now())
+      .unwrap_or(Duration::ZERO),
+  )
+}
+
+/// 解析 IMF-fixdate 格式的时间，例如 `Sun, 06 Nov 1994 08:49:37 GMT`
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+  let (_, rest) = value.split_once(", ")?;
+  let mut parts = rest.split_whitespace();
+  let day: u64 = parts.next()?.parse().ok()?;
+  let month = match parts.next()? {
+    "Jan" => 1,
+    "Feb" => 2,
+    "Mar" => 3,
+    "Apr" => 4,
+    "May" => 5,
+    "Jun" => 6,
+    "Jul" => 7,
+    "Aug" => 8,
+    "Sep" => 9,
+    "Oct" => 10,
+    "Nov" => 11,
+    "Dec" => 12,
+    _ => return None,
+  };
+  let year: i64 = parts.next()?.parse().ok()?;
+  let mut hms = parts.next()?.splitn(3, ':');
+  let hour: u64 = hms.next()?.parse().ok()?;
+  let minute: u64 = hms.next()?.parse().ok()?;
+  let second: u64 = hms.next()?.parse().ok()?;
+  if parts.next()? != "GMT" || day == 0 || day > 31 || hour > 23 || minute > 59 || second > 60 {
+    return None;
+  }
+  // days from civil, 见 http://howardhinnant.github.io/date_algorithms.html
+  let y = if month <= 2 { year - 1 } else { year };
+  let era = y.div_euclid(400);
+  let yoe = y - era * 400;
+  let mp = (month + 9) % 12;
+  let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  let days = era * 146097 + doe - 719468;
+  let secs = u64::try_from(days).ok()? * 86400 + hour * 3600 + minute * 60 + second;
+  Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// 格式化为 IMF-fixdate，例如 `Sun, 06 Nov 1994 08:49:37 GMT`
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+  let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+  let days = (secs / 86400) as i64;
+  let rest = secs % 86400;
+  let (year, month, day) = civil_from_days(days);
+  // 1970-01-01 是星期四
+  const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+  const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+  ];
+  format!(
+    "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[days.rem_euclid(7) as usize],
+    day,
+    MONTHS[(month - 1) as usize],
+    year,
+    rest / 3600,
+    rest % 3600 / 60,
+    rest % 60
+  )
+}
+
+/// 1970-01-01 之后的天数换算成公历的年、月、日
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// 简单的 xorshift 伪随机数，范围 [0, 1)
+fn random_unit() -> f64 {
+  let mut x = JITTER_STATE.load(Ordering::Relaxed);
+  if x == 0 {
+    x = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_nanos() as u64)
+      .unwrap_or(0x2545_f491_4f6c_dd1d)
+      | 1;
+  }
+  x ^= x << 13;
+  x ^= x >> 7;
+  x ^= x << 17;
+  JITTER_STATE.store(x, Ordering::Relaxed);
+  (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn http_date() {
+    let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    assert_eq!(
+      date.duration_since(UNIX_EPOCH).unwrap().as_secs(),
+      784111777
+    );
+    assert!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT").is_none());
+    assert_eq!(format_http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+  }
+
+  #[test]
+  fn retry_status_and_method() {
+    let policy = RetryPolicy::new(3).jitter(false);
+    let get: Request = Request::builder().body("").unwrap().into();
+    let post: Request = Request::builder()
+      .method(Method::POST)
+      .body("")
+      .unwrap()
+      .into();
+    let unavailable: Result<Response> = Ok(
+      Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header(http::header::RETRY_AFTER, "2")
+        .body("")
+        .unwrap()
+        .into(),
+    );
+    let ok: Result<Response> = Ok(Response::builder().body("").unwrap().into());
+    assert_eq!(
+      policy.next_delay(&get, &unavailable, 1),
+      Some(Duration::from_secs(2))
+    );
+    assert_eq!(policy.next_delay(&get, &unavailable, 3), None);
+    assert_eq!(policy.next_delay(&post, &unavailable, 1), None);
+    assert_eq!(policy.next_delay(&get, &ok, 1), None);
+    let mut challenge: Response = Response::builder()
+      .status(StatusCode::SERVICE_UNAVAILABLE)
+      .header("cf-ray", "1")
+      .body("")
+      .unwrap()
+      .into();
+    let detection = crate::waf::detect(&challenge).unwrap();
+    challenge.extensions_mut().insert(detection);
+    assert_eq!(policy.next_delay(&get, &Ok(challenge), 1), None);
+    let refused: Result<Response> = Err(Error::IO(std::io::ErrorKind::ConnectionRefused.into()));
+    assert_eq!(
+      policy.next_delay(&get, &refused, 2),
+      Some(Duration::from_millis(200))
+    );
+    #[derive(Clone)]
+    struct NoRetry;
+    let policy = policy.retry_if(|request, _| request.extensions().get::<NoRetry>().is_none());
+    let tagged: Request = Request::builder().extension(NoRetry).body("").unwrap().into();
+    assert_eq!(policy.next_delay(&tagged, &refused, 1), None);
+    assert!(policy.next_delay(&get, &refused, 1).is_some());
+  }
+}
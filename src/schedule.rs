@@ -0,0 +1,102 @@
+use crate::errors::Result;
+use crate::{Client, Request, Response};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+/// A request waiting to be sent at a later time, returned by
+/// [`Client::send_at`] and [`Client::send_after`].
+///
+/// Each scheduled request waits on its own thread. Dropping the handle does
+/// not cancel the request, it is still sent and its response discarded.
+#[derive(Debug)]
+pub struct Scheduled {
+  at: Instant,
+  cancelled: Arc<(Mutex<bool>, Condvar)>,
+  handle: JoinHandle<Option<Result<Response>>>,
+}
+
+impl Scheduled {
+  pub(crate) fn spawn(client: Client, request: Request, at: Instant) -> Scheduled {
+    let cancelled = Arc::new((Mutex::new(false), Condvar::new()));
+    let flag = cancelled.clone();
+    let handle = std::thread::spawn(move || {
+      let (lock, condvar) = &*flag;
+      let mut cancelled = lock.lock().ok()?;
+      // 等到发送时间，期间被取消则直接返回
+      while !*cancelled {
+        let now = Instant::now();
+        if now >= at {
+          break;
+        }
+        cancelled = condvar.wait_timeout(cancelled, at - now).ok()?.0;
+      }
+      if *cancelled {
+        return None;
+      }
+      drop(cancelled);
+      Some(client.execute(request))
+    });
+    Scheduled {
+      at,
+      cancelled,
+      handle,
+    }
+  }
+  /// When the request is due to be sent.
+  pub fn at(&self) -> Instant {
+    self.at
+  }
+  /// Cancel the request if it has not been sent yet.
+  ///
+  /// Returns `false` when it is too late, the request is already on its way.
+  pub fn cancel(&self) -> bool {
+    let (lock, condvar) = &*self.cancelled;
+    let Ok(mut cancelled) = lock.lock() else {
+      return false;
+    };
+    if Instant::now() >= self.at || self.handle.is_finished() {
+      return *cancelled;
+    }
+    *cancelled = true;
+    condvar.notify_all();
+    true
+  }
+  /// Whether the request was sent and answered, failed or was cancelled.
+  pub fn is_finished(&self) -> bool {
+    self.handle.is_finished()
+  }
+  /// Block until the request has been sent and return its result, or `None`
+  /// when it was cancelled.
+  pub fn wait(self) -> Option<Result<Response>> {
+    self.handle.join().ok().flatten()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Client, Next, Request, Response};
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn delayed_and_cancelled() {
+    let client = Client::builder()
+      .with(|_request: Request, _next: Next<'_>| Ok(Response::builder().body("").unwrap().into()))
+      .build()
+      .unwrap();
+    let start = Instant::now();
+    let sent = client.send_after(
+      client.get("http://127.0.0.1:1/").build().unwrap(),
+      Duration::from_millis(50),
+    );
+    let cancelled = client.send_after(
+      client.get("http://127.0.0.1:1/").build().unwrap(),
+      Duration::from_secs(30),
+    );
+    assert!(cancelled.cancel());
+    assert!(cancelled.wait().is_none());
+    assert!(sent.wait().unwrap().is_ok());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(start.elapsed() < Duration::from_secs(30));
+  }
+}
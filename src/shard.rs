@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// One of `count` slices of a target list, see
+/// [`Client::assign_shard`](crate::Client::assign_shard).
+///
+/// A request tagged with [`RequestBuilder::shard`](crate::RequestBuilder::shard)
+/// carries its shard to the [`Response`](crate::Response::shard) and to the
+/// [`Activity`](crate::Activity) extensions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Shard {
+  /// The index of the shard, below `count`
+  pub index: u32,
+  /// The number of shards the targets are split into
+  pub count: u32,
+}
+
+impl Shard {
+  /// The shard of `target` when targets are split into `count` shards.
+  ///
+  /// The assignment only depends on the bytes of `target` and `count`, so
+  /// every worker agrees on it without talking to the others. Growing `count`
+  /// by one only moves targets to the new shard, about `1 / count` of them.
+  /// A `count` of `0` is treated as `1`.
+  pub fn of(target: &str, count: u32) -> Shard {
+    let count = count.max(1);
+    Shard {
+      index: jump_hash(fnv1a(target.as_bytes()), count),
+      count,
+    }
+  }
+}
+
+impl std::fmt::Display for Shard {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}/{}", self.index, self.count)
+  }
+}
+
+/// Counters of one shard, see [`Client::shard_stats`](crate::Client::shard_stats).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShardStats {
+  /// Targets `Client::assign_shard` assigned to this shard
+  pub assigned: u64,
+  /// Targets `Client::assign_shard` left to other shards
+  pub skipped: u64,
+  /// Requests tagged with this shard that were sent, retries not counted
+  pub requests: u64,
+  /// Tagged requests that ended with an error
+  pub errors: u64,
+}
+
+/// 每个分片的计数，所有克隆的 `Client` 共用
+#[derive(Debug, Default)]
+pub(crate) struct ShardLog(Mutex<BTreeMap<u32, ShardStats>>);
+
+impl ShardLog {
+  pub(crate) fn update<F: FnOnce(&mut ShardStats)>(&self, index: u32, f: F) {
+    if let Ok(mut stats) = self.0.lock() {
+      f(stats.entry(index).or_default());
+    }
+  }
+  pub(crate) fn snapshot(&self) -> BTreeMap<u32, ShardStats> {
+    self.0.lock().map(|stats| stats.clone()).unwrap_or_default()
+  }
+}
+
+/// FNV-1a，标准库的哈希在不同版本之间不保证稳定
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+  })
+}
+
+/// Lamping 和 Veach 的一致性哈希，分片数增加时只有移到新分片的目标
+fn jump_hash(mut key: u64, buckets: u32) -> u32 {
+  let mut bucket = -1i64;
+  let mut next = 0i64;
+  while next < i64::from(buckets) {
+    bucket = next;
+    key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+    next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+  }
+  bucket as u32
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Shard;
+
+  #[test]
+  fn shards_are_stable_and_consistent() {
+    let targets: Vec<String> = (0..2000).map(|i| format!("https://host{}.example/", i)).collect();
+    let mut sizes = [0usize; 8];
+    for target in &targets {
+      let shard = Shard::of(target, 8);
+      assert_eq!(shard, Shard::of(target, 8));
+      sizes[shard.index as usize] += 1;
+      // 增加一个分片时目标要么不动，要么移到新的分片
+      let grown = Shard::of(target, 9);
+      assert!(grown.index == shard.index || grown.index == 8);
+    }
+    assert!(sizes.iter().all(|size| (150..350).contains(size)), "{:?}", sizes);
+    assert_eq!(Shard::of("example.com", 0), Shard { index: 0, count: 1 });
+  }
+}
@@ -0,0 +1,338 @@
+//! Request signing
+use crate::auth::to_hex;
+use crate::errors::{new_io_error, Result};
+use crate::request::AutoHeaders;
+use crate::retry::civil_from_days;
+use crate::{ChunkedBody, Request};
+use hmac::{Hmac, Mac};
+use http::header::{HeaderName, HeaderValue};
+use http::Version;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Signs every request of a `Client` right before it is written.
+///
+/// A signer set with [`ClientBuilder::sign`](crate::ClientBuilder::sign) runs
+/// after default headers, cookies and credentials were added, for every hop
+/// of a redirect chain and every authentication round, so the signature
+/// covers the headers that go on the wire. Raw requests are not signed.
+///
+/// Closures with the signature `Fn(&mut Request) -> Result<()>` are signers
+/// too, for schemes such as an HMAC of the body.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use hmac::{Hmac, Mac};
+/// use slinger::Request;
+///
+/// let client = slinger::Client::builder()
+///   .sign(|request: &mut Request| {
+///     let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"secret").unwrap();
+///     if let Some(body) = request.body() {
+///       mac.update(body);
+///     }
+///     let signature = format!("{:x}", mac.finalize().into_bytes());
+///     request.headers_mut().insert("x-signature", signature.parse()?);
+///     Ok(())
+///   })
+///   .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Signer: Send + Sync + 'static {
+  /// Add the signature to `request`.
+  fn sign(&self, request: &mut Request) -> Result<()>;
+}
+
+impl<F> Signer for F
+  where
+    F: Fn(&mut Request) -> Result<()> + Send + Sync + 'static,
+{
+  fn sign(&self, request: &mut Request) -> Result<()> {
+    self(request)
+  }
+}
+
+impl std::fmt::Debug for dyn Signer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Signer")
+  }
+}
+
+/// 不签名的请求头，代理和客户端可能会改写它们
+const UNSIGNED_HEADERS: [&str; 6] = [
+  "authorization",
+  "user-agent",
+  "expect",
+  "connection",
+  "transfer-encoding",
+  "x-amzn-trace-id",
+];
+
+/// RFC 3986 中不需要编码的字符之外都编码
+const URI_ENCODE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Signs requests with AWS Signature Version 4.
+///
+/// The canonical request is computed from the method, path, query, headers
+/// and body of the request as it is written, `Host` included. `X-Amz-Date`,
+/// `X-Amz-Security-Token` with temporary credentials, and for S3
+/// `X-Amz-Content-Sha256` are added before signing, then the `Authorization`
+/// header is set. Streamed [`ChunkedBody`] uploads are signed as
+/// `UNSIGNED-PAYLOAD`, a `X-Amz-Content-Sha256` header set on the request is
+/// used as the payload hash as is.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::SigV4;
+///
+/// let client = slinger::Client::builder()
+///   .sign(SigV4::new("AKIDEXAMPLE", "secret", "us-east-1", "s3"))
+///   .build()?;
+/// let resp = client.get("https://bucket.s3.amazonaws.com/key").send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SigV4 {
+  access_key: String,
+  secret_key: String,
+  session_token: Option<String>,
+  region: String,
+  service: String,
+}
+
+impl std::fmt::Debug for SigV4 {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("SigV4")
+      .field("access_key", &self.access_key)
+      .field("region", &self.region)
+      .field("service", &self.service)
+      .finish()
+  }
+}
+
+impl SigV4 {
+  /// Sign with the access key and secret key of `region` and `service`,
+  /// such as `us-east-1` and `s3`.
+  pub fn new<A, S, R, V>(access_key: A, secret_key: S, region: R, service: V) -> SigV4
+    where
+      A: Into<String>,
+      S: Into<String>,
+      R: Into<String>,
+      V: Into<String>,
+  {
+    SigV4 {
+      access_key: access_key.into(),
+      secret_key: secret_key.into(),
+      session_token: None,
+      region: region.into(),
+      service: service.into(),
+    }
+  }
+  /// Send the session token of temporary credentials in
+  /// `X-Amz-Security-Token`.
+  pub fn session_token<T: Into<String>>(mut self, token: T) -> SigV4 {
+    self.session_token = Some(token.into());
+    self
+  }
+  fn sign_at(&self, request: &mut Request, time: SystemTime) -> Result<()> {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let rest = secs % 86400;
+    let timestamp = format!(
+      "{}T{:02}{:02}{:02}Z",
+      date,
+      rest / 3600,
+      rest % 3600 / 60,
+      rest % 60
+    );
+    let payload_hash = match request.headers().get("x-amz-content-sha256") {
+      Some(hash) => hash.to_str().map_err(|_| invalid("invalid x-amz-content-sha256"))?.to_string(),
+      None if request.extensions().get::<ChunkedBody>().is_some() => "UNSIGNED-PAYLOAD".to_string(),
+      None => to_hex(&Sha256::digest(request.body().map_or(&[][..], |b| b.as_ref()))),
+    };
+    let headers = request.headers_mut();
+    headers.insert("x-amz-date", HeaderValue::from_str(&timestamp)?);
+    if let Some(token) = &self.session_token {
+      headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+    }
+    if self.service == "s3" {
+      headers.insert("x-amz-content-sha256", HeaderValue::from_str(&payload_hash)?);
+    }
+    let (signed_headers, canonical_headers) = canonical_headers(request)?;
+    let canonical_request = format!(
+      "{}\n{}\n{}\n{}\n{}\n{}",
+      request.method(),
+      canonical_uri(request.uri().path(), self.service != "s3"),
+      canonical_query(request.uri().query().unwrap_or_default()),
+      canonical_headers,
+      signed_headers,
+      payload_hash
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+    let string_to_sign = format!(
+      "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+      timestamp,
+      scope,
+      to_hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut key = hmac_sha256(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+    for part in [&self.region, &self.service, "aws4_request"] {
+      key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = to_hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    let authorization = format!(
+      "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+      self.access_key, scope, signed_headers, signature
+    );
+    request
+      .headers_mut()
+      .insert(http::header::AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+    Ok(())
+  }
+}
+
+impl Signer for SigV4 {
+  fn sign(&self, request: &mut Request) -> Result<()> {
+    self.sign_at(request, SystemTime::now())
+  }
+}
+
+fn invalid(message: &str) -> crate::Error {
+  new_io_error(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+  mac.update(data);
+  mac.finalize().into_bytes().to_vec()
+}
+
+/// 按请求实际发送的主机头签名；没有主机头又不会自动添加时补上
+fn canonical_headers(request: &mut Request) -> Result<(String, String)> {
+  let auto = request.extensions().get::<AutoHeaders>().copied().unwrap_or_default();
+  let mut lines: Vec<(String, String)> = Vec::new();
+  if !request.headers().contains_key(http::header::HOST) {
+    let authority = request
+      .uri()
+      .authority()
+      .map(|a| a.as_str().to_string())
+      .ok_or_else(|| invalid("request to sign has no host"))?;
+    if auto.allows(&http::header::HOST) && request.version() > Version::HTTP_10 {
+      lines.push(("host".to_string(), authority));
+    } else {
+      request
+        .headers_mut()
+        .insert(http::header::HOST, HeaderValue::from_str(&authority)?);
+    }
+  }
+  let mut names: Vec<&HeaderName> = request
+    .headers()
+    .keys()
+    .filter(|name| !UNSIGNED_HEADERS.contains(&name.as_str()))
+    .collect();
+  names.sort_by_key(|name| name.as_str());
+  for name in names {
+    let values: Vec<String> = request
+      .headers()
+      .get_all(name)
+      .iter()
+      .map(|v| {
+        let value = String::from_utf8_lossy(v.as_bytes());
+        value.split_whitespace().collect::<Vec<_>>().join(" ")
+      })
+      .collect();
+    lines.push((name.as_str().to_string(), values.join(",")));
+  }
+  lines.sort();
+  let signed = lines.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+  let canonical = lines
+    .iter()
+    .map(|(name, value)| format!("{}:{}\n", name, value))
+    .collect();
+  Ok((signed, canonical))
+}
+
+/// 路径的每一段解码后重新编码，S3 以外的服务再编码一次
+fn canonical_uri(path: &str, double_encode: bool) -> String {
+  if path.is_empty() {
+    return "/".to_string();
+  }
+  path
+    .split('/')
+    .map(|segment| {
+      let decoded = percent_decode_str(segment).collect::<Vec<u8>>();
+      let encoded = percent_encoding::percent_encode(&decoded, URI_ENCODE).to_string();
+      match double_encode {
+        true => utf8_percent_encode(&encoded, URI_ENCODE).to_string(),
+        false => encoded,
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// 查询参数解码后重新编码，按参数名和值排序
+fn canonical_query(query: &str) -> String {
+  let encode = |part: &str| {
+    let decoded = percent_decode_str(part).collect::<Vec<u8>>();
+    percent_encoding::percent_encode(&decoded, URI_ENCODE).to_string()
+  };
+  let mut pairs: Vec<(String, String)> = query
+    .split('&')
+    .filter(|pair| !pair.is_empty())
+    .map(|pair| {
+      let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+      (encode(name), encode(value))
+    })
+    .collect();
+  pairs.sort();
+  pairs
+    .iter()
+    .map(|(name, value)| format!("{}={}", name, value))
+    .collect::<Vec<_>>()
+    .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{canonical_query, canonical_uri, SigV4};
+  use crate::Request;
+  use std::time::{Duration, UNIX_EPOCH};
+
+  #[test]
+  fn sigv4() {
+    // AWS 文档中 IAM ListUsers 的示例
+    let mut request: Request = http::Request::builder()
+      .uri("https://iam.amazonaws.com/?Version=2010-05-08&Action=ListUsers")
+      .header("content-type", "application/x-www-form-urlencoded; charset=utf-8")
+      .body(bytes::Bytes::new())
+      .unwrap()
+      .into();
+    let time = UNIX_EPOCH + Duration::from_secs(1440938160);
+    SigV4::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "us-east-1", "iam")
+      .sign_at(&mut request, time)
+      .unwrap();
+    assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+    assert_eq!(
+      request.headers()["authorization"],
+      concat!(
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, ",
+        "SignedHeaders=content-type;host;x-amz-date, ",
+        "Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+      )
+    );
+    // 自动添加主机头时不写进请求头
+    assert!(!request.headers().contains_key("host"));
+    assert_eq!(canonical_uri("/a b/%41", false), "/a%20b/A");
+    assert_eq!(canonical_uri("/a%20b", true), "/a%2520b");
+    assert_eq!(canonical_query("b=2&a=x y&a=1&c"), "a=1&a=x%20y&b=2&c=");
+  }
+}
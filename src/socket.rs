@@ -1,80 +1,117 @@
+use crate::ratelimit::ConnectionPermit;
 #[cfg(feature = "tls")]
-use native_tls::TlsStream;
+use openssl::ssl::{SslRef, SslStream};
 #[cfg(feature = "tls")]
 use openssl::x509::X509;
 use socket2::Socket as RawSocket;
 use std::fmt::Arguments;
 use std::io;
 use std::io::{IoSlice, IoSliceMut, Read, Write};
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 /// Socket
 #[derive(Debug)]
 pub enum Socket {
   /// TCP
   TCP(RawSocket),
+  /// UDP, connected to a single peer with [`Connector::connect_udp`](crate::Connector::connect_udp)
+  ///
+  /// Every read returns one datagram and every write sends one.
+  UDP(RawSocket),
   #[cfg(feature = "tls")]
   /// TLS
-  TLS(TlsStream<RawSocket>),
+  TLS(SslStream<RawSocket>),
+  #[cfg(feature = "tls")]
+  /// TLS inside another TLS connection, such as a `CONNECT` tunnel through an
+  /// `https://` proxy
+  NestedTLS(SslStream<Box<Socket>>),
 }
 
 impl Socket {
   #[cfg(feature = "tls")]
   /// get peer_certificate
   pub fn peer_certificate(&self) -> Option<X509> {
+    self.ssl().and_then(SslRef::peer_certificate)
+  }
+  /// 最外层 TLS 连接的会话，嵌套时是到目标的那一层
+  #[cfg(feature = "tls")]
+  pub(crate) fn ssl(&self) -> Option<&SslRef> {
     match &self {
-      Socket::TCP(_) => None,
-      Socket::TLS(stream) => {
-        if let Ok(Some(peer_certificate)) = stream.peer_certificate() {
-          if let Ok(x509) = X509::from_der(&peer_certificate.to_der().unwrap_or_default()) {
-            return Some(x509);
-          }
-        };
-        None
-      }
+      Socket::TCP(_) | Socket::UDP(_) => None,
+      Socket::TLS(stream) => Some(stream.ssl()),
+      Socket::NestedTLS(stream) => Some(stream.ssl()),
     }
   }
 }
 
+impl Socket {
+  #[cfg(feature = "tls")]
+  /// get the protocol negotiated with ALPN
+  pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+    self.ssl().and_then(SslRef::selected_alpn_protocol).map(<[u8]>::to_vec)
+  }
+  #[cfg(feature = "tls")]
+  /// whether the TLS session was resumed instead of a full handshake, see
+  /// [`ConnectorBuilder::tls_session_cache`](crate::ConnectorBuilder::tls_session_cache)
+  pub fn session_reused(&self) -> bool {
+    self.ssl().is_some_and(SslRef::session_reused)
+  }
+}
+
 // 实现socket的读写
 impl Read for Socket {
   #[inline]
   fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
     match self {
       Socket::TCP(s) => s.read(buf),
+      Socket::UDP(s) => s.read(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.read(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.read(buf),
     }
   }
   #[inline]
   fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
     match self {
       Socket::TCP(s) => s.read_vectored(buf),
+      Socket::UDP(s) => s.read_vectored(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.read_vectored(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.read_vectored(buf),
     }
   }
   #[inline]
   fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
     match self {
       Socket::TCP(s) => s.read_to_end(buf),
+      Socket::UDP(s) => s.read_to_end(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.read_to_end(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.read_to_end(buf),
     }
   }
   #[inline]
   fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
     match self {
       Socket::TCP(s) => s.read_to_string(buf),
+      Socket::UDP(s) => s.read_to_string(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.read_to_string(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.read_to_string(buf),
     }
   }
   #[inline]
   fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
     match self {
       Socket::TCP(s) => s.read_exact(buf),
+      Socket::UDP(s) => s.read_exact(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.read_exact(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.read_exact(buf),
     }
   }
 }
@@ -84,40 +121,55 @@ impl Write for Socket {
   fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
     match self {
       Socket::TCP(s) => s.write(buf),
+      Socket::UDP(s) => s.write(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.write(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.write(buf),
     }
   }
   #[inline]
   fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> io::Result<usize> {
     match self {
       Socket::TCP(s) => s.write_vectored(buf),
+      Socket::UDP(s) => s.write_vectored(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.write_vectored(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.write_vectored(buf),
     }
   }
   #[inline]
   fn flush(&mut self) -> io::Result<()> {
     match self {
       Socket::TCP(s) => s.flush(),
+      Socket::UDP(s) => s.flush(),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.flush(),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.flush(),
     }
   }
   #[inline]
   fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
     match self {
       Socket::TCP(s) => s.write_all(buf),
+      Socket::UDP(s) => s.write_all(buf),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.write_all(buf),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.write_all(buf),
     }
   }
   #[inline]
   fn write_fmt(&mut self, fmt: Arguments<'_>) -> io::Result<()> {
     match self {
       Socket::TCP(s) => s.write_fmt(fmt),
+      Socket::UDP(s) => s.write_fmt(fmt),
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.write_fmt(fmt),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.write_fmt(fmt),
     }
   }
 }
@@ -128,9 +180,52 @@ impl Deref for Socket {
 
   fn deref(&self) -> &Self::Target {
     match self {
-      Socket::TCP(s) => s,
+      Socket::TCP(s) | Socket::UDP(s) => s,
       #[cfg(feature = "tls")]
       Socket::TLS(t) => t.get_ref(),
+      #[cfg(feature = "tls")]
+      Socket::NestedTLS(t) => t.get_ref(),
     }
   }
 }
+
+/// A connection opened with [`Client::tunnel`](crate::Client::tunnel).
+///
+/// It reads and writes like the [`Socket`] it derefs to, and keeps its slot
+/// under [`ClientBuilder::max_connections_per_host`](crate::ClientBuilder::max_connections_per_host)
+/// and [`ClientBuilder::max_connections`](crate::ClientBuilder::max_connections)
+/// until it is dropped.
+#[derive(Debug)]
+pub struct Tunnel {
+  pub(crate) socket: Socket,
+  pub(crate) _permit: Option<ConnectionPermit>,
+}
+
+impl Read for Tunnel {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.socket.read(buf)
+  }
+}
+
+impl Write for Tunnel {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.socket.write(buf)
+  }
+  fn flush(&mut self) -> io::Result<()> {
+    self.socket.flush()
+  }
+}
+
+impl Deref for Tunnel {
+  type Target = Socket;
+
+  fn deref(&self) -> &Self::Target {
+    &self.socket
+  }
+}
+
+impl DerefMut for Tunnel {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.socket
+  }
+}
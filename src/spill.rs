@@ -0,0 +1,58 @@
+use bytes::Bytes;
+use memmap2::Mmap;
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 响应体的临时文件，映射释放后删除
+#[derive(Debug)]
+pub(crate) struct SpillFile {
+  path: PathBuf,
+}
+
+impl Drop for SpillFile {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 映射的内存在文件之前释放，字段顺序不能调换
+struct Mapped {
+  map: Mmap,
+  _spilled: SpillFile,
+}
+
+impl AsRef<[u8]> for Mapped {
+  fn as_ref(&self) -> &[u8] {
+    &self.map
+  }
+}
+
+impl SpillFile {
+  /// 在临时目录创建一个新文件
+  pub(crate) fn create() -> std::io::Result<(SpillFile, File)> {
+    let path = std::env::temp_dir().join(format!(
+      "slinger-body-{}-{}",
+      std::process::id(),
+      SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create_new(true)
+      .open(&path)?;
+    Ok((SpillFile { path }, file))
+  }
+  /// 把写完的文件映射成 `Bytes`，最后一个引用释放时删除文件
+  pub(crate) fn into_bytes(self, file: &File) -> std::io::Result<Bytes> {
+    // 文件是这里独占创建的临时文件，映射期间只有它自己读取
+    let map = unsafe { Mmap::map(file)? };
+    Ok(Bytes::from_owner(Mapped {
+      map,
+      _spilled: self,
+    }))
+  }
+}
+
@@ -0,0 +1,319 @@
+use crate::errors::{new_io_error, Result};
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One event received from a `text/event-stream` response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+  /// The last event id seen on the stream, including on previous events
+  pub id: Option<String>,
+  /// The event type, `message` when the server did not set one
+  pub event: String,
+  /// The `data` lines joined with `\n`
+  pub data: String,
+  /// The reconnection time set by this event
+  pub retry: Option<Duration>,
+}
+
+/// An iterator over the events of a `text/event-stream` response, returned by
+/// [`Response::events`](crate::Response::events).
+///
+/// Comments and incomplete events at the end of the stream are skipped. The
+/// iterator ends when the stream ends or after the first read error, a read
+/// timeout included, so the timeout of the `Client` or
+/// [`RequestBuilder::io_timeout`](crate::RequestBuilder::io_timeout) is the
+/// longest time allowed between two events or keep-alive comments.
+pub struct Events {
+  reader: Box<dyn BufRead + Send>,
+  last_event_id: Option<String>,
+  retry: Option<Duration>,
+  started: bool,
+  done: bool,
+}
+
+impl std::fmt::Debug for Events {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Events")
+      .field("last_event_id", &self.last_event_id)
+      .field("retry", &self.retry)
+      .field("done", &self.done)
+      .finish()
+  }
+}
+
+impl Events {
+  pub(crate) fn new<R: BufRead + Send + 'static>(reader: R) -> Events {
+    Events {
+      reader: Box::new(reader),
+      last_event_id: None,
+      retry: None,
+      started: false,
+      done: false,
+    }
+  }
+  /// The last event id seen, to send as `Last-Event-ID` when reconnecting.
+  pub fn last_event_id(&self) -> Option<&str> {
+    self.last_event_id.as_deref()
+  }
+  /// The last reconnection time sent by the server.
+  pub fn retry(&self) -> Option<Duration> {
+    self.retry
+  }
+  fn read_event(&mut self) -> Result<Option<Event>> {
+    let mut event = Event::default();
+    let mut has_data = false;
+    let mut line = Vec::new();
+    loop {
+      line.clear();
+      if self.reader.read_until(b'\n', &mut line)? == 0 {
+        // 流结束时丢弃没有空行结尾的事件
+        return Ok(None);
+      }
+      let mut text = String::from_utf8_lossy(&line).into_owned();
+      if !self.started {
+        self.started = true;
+        if let Some(stripped) = text.strip_prefix('\u{feff}') {
+          text = stripped.to_string();
+        }
+      }
+      let text = text.trim_end_matches('\n').trim_end_matches('\r');
+      if text.is_empty() {
+        if !has_data {
+          event = Event::default();
+          continue;
+        }
+        if event.event.is_empty() {
+          event.event = "message".to_string();
+        }
+        event.id = self.last_event_id.clone();
+        return Ok(Some(event));
+      }
+      if text.starts_with(':') {
+        continue;
+      }
+      let (field, value) = match text.split_once(':') {
+        Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+        None => (text, ""),
+      };
+      match field {
+        "event" => event.event = value.to_string(),
+        "data" => {
+          if has_data {
+            event.data.push('\n');
+          }
+          event.data.push_str(value);
+          has_data = true;
+        }
+        "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+        "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+          if let Ok(millis) = value.parse() {
+            let retry = Duration::from_millis(millis);
+            self.retry = Some(retry);
+            event.retry = Some(retry);
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+}
+
+impl Iterator for Events {
+  type Item = Result<Event>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    match self.read_event() {
+      Ok(Some(event)) => Some(Ok(event)),
+      Ok(None) => {
+        self.done = true;
+        None
+      }
+      Err(err) => {
+        self.done = true;
+        Some(Err(err))
+      }
+    }
+  }
+}
+
+/// 请求标记：响应是事件流时不读取响应体，把连接交给 `Response::events`
+#[derive(Clone, Debug)]
+pub(crate) struct EventStream;
+
+/// 读取响应头时已经缓冲的响应体开头
+#[derive(Clone, Debug)]
+pub(crate) struct PendingStream {
+  pub(crate) buffered: Vec<u8>,
+  pub(crate) chunked: bool,
+  /// `Content-Length`，没有时读到连接关闭
+  pub(crate) length: Option<u64>,
+}
+
+impl PendingStream {
+  /// 接上连接得到未解码的响应体
+  pub(crate) fn into_body<R: Read + Send + 'static>(self, reader: R) -> LiveBody {
+    let raw = BufReader::new(Cursor::new(self.buffered).chain(reader));
+    let body: Box<dyn BufRead + Send> = if self.chunked {
+      Box::new(BufReader::new(ChunkedReader {
+        inner: raw,
+        remaining: 0,
+        done: false,
+      }))
+    } else if let Some(length) = self.length {
+      Box::new(raw.take(length))
+    } else {
+      Box::new(raw)
+    };
+    LiveBody(Arc::new(Mutex::new(Some(body))))
+  }
+}
+
+/// 还没有读取的响应体，只能取出一次
+#[derive(Clone)]
+pub(crate) struct LiveBody(Arc<Mutex<Option<Box<dyn BufRead + Send>>>>);
+
+impl LiveBody {
+  pub(crate) fn take(&self) -> Option<Box<dyn BufRead + Send>> {
+    self.0.lock().ok()?.take()
+  }
+}
+
+/// 边读边解码 `Transfer-Encoding: chunked`
+struct ChunkedReader<R> {
+  inner: R,
+  remaining: u64,
+  done: bool,
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    if self.done || buf.is_empty() {
+      return Ok(0);
+    }
+    if self.remaining == 0 {
+      let mut line = String::new();
+      // 跳过上一个分块结尾的空行
+      while line.trim().is_empty() {
+        line.clear();
+        if self.inner.read_line(&mut line)? == 0 {
+          self.done = true;
+          return Ok(0);
+        }
+      }
+      let size = line.trim().split(';').next().unwrap_or_default();
+      self.remaining = u64::from_str_radix(size, 16)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid chunk size"))?;
+      if self.remaining == 0 {
+        self.done = true;
+        return Ok(0);
+      }
+    }
+    let max = buf.len().min(self.remaining as usize);
+    let n = self.inner.read(&mut buf[..max])?;
+    if n == 0 {
+      return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    self.remaining -= n as u64;
+    Ok(n)
+  }
+}
+
+/// 响应体已经被读取过时返回的错误
+pub(crate) fn consumed() -> crate::Error {
+  new_io_error(
+    std::io::ErrorKind::Unsupported,
+    "event stream has already been consumed",
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Event, Events, PendingStream};
+  use std::io::Cursor;
+  use std::time::Duration;
+
+  #[test]
+  fn parse_events() {
+    let stream = "\u{feff}: keep-alive\n\ndata: first\ndata:second\n\nevent: update\r\nid: 7\r\nretry: 3000\r\ndata\r\n\r\nid\ndata: last\n\ndata: incomplete";
+    let mut events = Events::new(Cursor::new(stream));
+    let all: Vec<Event> = events.by_ref().map(|e| e.unwrap()).collect();
+    assert_eq!(
+      all,
+      vec![
+        Event {
+          id: None,
+          event: "message".to_string(),
+          data: "first\nsecond".to_string(),
+          retry: None,
+        },
+        Event {
+          id: Some("7".to_string()),
+          event: "update".to_string(),
+          data: String::new(),
+          retry: Some(Duration::from_secs(3)),
+        },
+        Event {
+          id: Some(String::new()),
+          event: "message".to_string(),
+          data: "last".to_string(),
+          retry: None,
+        },
+      ]
+    );
+    assert_eq!(events.retry(), Some(Duration::from_secs(3)));
+  }
+
+  #[test]
+  fn chunked_stream() {
+    let pending = PendingStream {
+      buffered: b"c\r\ndata: hello\n\r\n".to_vec(),
+      chunked: true,
+      length: None,
+    };
+    let body = pending.into_body(Cursor::new(b"1\r\n\n\r\n0\r\n\r\n".to_vec()));
+    let mut events = Events::new(body.take().unwrap());
+    assert_eq!(events.next().unwrap().unwrap().data, "hello");
+    assert!(events.next().is_none());
+    assert!(body.take().is_none());
+  }
+
+  #[test]
+  fn close_connection() {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/events", listener.local_addr().unwrap());
+    // 发出一个事件后保持连接，直到客户端关闭
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+      let mut request = Vec::new();
+      let mut buf = [0u8; 1024];
+      while !request.ends_with(b"\r\n\r\n") {
+        let n = stream.read(&mut buf).unwrap();
+        request.extend_from_slice(&buf[..n]);
+      }
+      stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\ndata: one\n\n")
+        .unwrap();
+      let eof = stream.read(&mut buf).unwrap() == 0;
+      (String::from_utf8(request).unwrap().to_ascii_lowercase(), eof)
+    });
+    let resp = crate::Client::new()
+      .get(url)
+      .event_stream()
+      .close_connection()
+      .send()
+      .unwrap();
+    let copy = resp.clone();
+    assert!(resp.close_connection());
+    assert!(!copy.close_connection());
+    let (request, eof) = server.join().unwrap();
+    assert!(request.contains("\r\nconnection: close\r\n"));
+    assert!(eof);
+    assert_eq!(crate::Client::new().close_idle(), 0);
+  }
+}
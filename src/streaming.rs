@@ -0,0 +1,103 @@
+use crate::sse::LiveBody;
+use crate::Response;
+use std::io::{BufRead, Cursor, Read};
+
+/// 请求标记：最终响应只读取响应头，响应体交给 `BodyReader`
+#[derive(Clone, Debug)]
+pub(crate) struct StreamBody;
+
+/// The body of a response sent with
+/// [`Client::send_streaming`](crate::Client::send_streaming), read from the
+/// connection on demand.
+///
+/// The body is read as sent after `Transfer-Encoding: chunked` is removed,
+/// a `Content-Encoding` is not decoded and the size limits of the `Client`
+/// don't apply. The connection is closed when the `BodyReader` is dropped,
+/// whether the body was read to its end or not.
+pub struct BodyReader {
+  inner: Box<dyn BufRead + Send>,
+}
+
+impl BodyReader {
+  /// 取出响应里还在连接上的响应体，没有时读已经读好的响应体
+  pub(crate) fn take(response: &mut Response) -> BodyReader {
+    let live = response
+      .extensions_mut()
+      .remove::<LiveBody>()
+      .and_then(|live| live.take());
+    let inner = match live {
+      Some(inner) => inner,
+      None => {
+        let body = response.body_mut().take().map(|b| b.to_vec()).unwrap_or_default();
+        Box::new(Cursor::new(body))
+      }
+    };
+    BodyReader { inner }
+  }
+  /// Read the rest of the body into memory.
+  pub fn bytes(mut self) -> crate::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    self.inner.read_to_end(&mut body)?;
+    Ok(body)
+  }
+  /// Read the rest of the body without keeping it and return its size, so
+  /// the server sees the whole response consumed before the connection is
+  /// closed.
+  pub fn discard(mut self) -> crate::Result<u64> {
+    Ok(std::io::copy(&mut self.inner, &mut std::io::sink())?)
+  }
+  /// Close the connection without reading the rest of the body.
+  pub fn abort(self) {
+    drop(self)
+  }
+}
+
+impl Read for BodyReader {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    self.inner.read(buf)
+  }
+}
+
+impl BufRead for BodyReader {
+  fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+    self.inner.fill_buf()
+  }
+  fn consume(&mut self, amt: usize) {
+    self.inner.consume(amt)
+  }
+}
+
+impl std::fmt::Debug for BodyReader {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("BodyReader").finish_non_exhaustive()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Client, MockTransport};
+  use std::io::Read;
+
+  #[test]
+  fn send_streaming() {
+    let mock = MockTransport::new()
+      .respond("HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 5\r\n\r\nhelloEXTRA")
+      .respond("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n")
+      .respond("HTTP/1.1 302 Found\r\nLocation: /html\r\nContent-Length: 3\r\n\r\nfoo")
+      .respond("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html>");
+    let client = Client::builder().transport(mock).build().unwrap();
+    let (head, mut body) = client.send_streaming(client.get("http://a.example/").build().unwrap()).unwrap();
+    assert_eq!(head.headers()["content-type"], "image/png");
+    assert!(head.body().is_none());
+    let mut start = [0u8; 2];
+    body.read_exact(&mut start).unwrap();
+    assert_eq!(&start, b"he");
+    assert_eq!(body.discard().unwrap(), 3);
+    let (_, body) = client.send_streaming(client.get("http://a.example/").build().unwrap()).unwrap();
+    assert_eq!(body.bytes().unwrap(), b"abcde");
+    // 跳转响应照常读取，只有最终响应是流式的
+    let (head, body) = client.send_streaming(client.get("http://a.example/").build().unwrap()).unwrap();
+    assert_eq!(head.uri(), "http://a.example/html");
+    assert_eq!(body.bytes().unwrap(), b"<html>");
+  }
+}
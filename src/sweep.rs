@@ -0,0 +1,96 @@
+use crate::errors::Result;
+use crate::{Client, RequestBuilder, Response};
+use http::{HeaderValue, Uri};
+
+/// The outcome of one request of [`Client::host_sweep`].
+#[derive(Debug)]
+pub struct HostProbe {
+  /// The URL the request was resolved, connected and sent to
+  pub target: Uri,
+  /// The value sent in the `Host` header
+  pub host: String,
+  /// The response, or the error that ended the request
+  pub result: Result<Response>,
+}
+
+impl HostProbe {
+  /// The `(connect target, host value)` pair identifying this probe.
+  pub fn key(&self) -> (&Uri, &str) {
+    (&self.target, &self.host)
+  }
+}
+
+/// 请求标记：Host 头探测不跟随跳转，Location 本身就是要观察的结果
+#[derive(Clone, Debug)]
+pub(crate) struct NoRedirect;
+
+pub(crate) fn run<T, H, S>(client: &Client, targets: T, hosts: H) -> Vec<HostProbe>
+where
+  T: IntoIterator<Item = Uri>,
+  H: IntoIterator<Item = S>,
+  S: AsRef<str>,
+{
+  let hosts: Vec<String> = hosts.into_iter().map(|h| h.as_ref().to_string()).collect();
+  let mut probes = Vec::new();
+  for target in targets {
+    for host in &hosts {
+      let result = HeaderValue::try_from(host.as_str())
+        .map_err(|err| crate::Error::Http(err.into()))
+        .and_then(|value| {
+          let builder = http::Request::get(target.clone())
+            .header(http::header::HOST, value)
+            .extension(NoRedirect);
+          RequestBuilder::new(client.clone(), builder).send()
+        });
+      probes.push(HostProbe {
+        target: target.clone(),
+        host: host.clone(),
+        result,
+      });
+    }
+  }
+  probes
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::MockTransport;
+
+  #[test]
+  fn host_sweep() {
+    let ok = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+    let moved = "HTTP/1.1 302 Found\r\nLocation: http://evil.example/\r\nContent-Length: 0\r\n\r\n";
+    let mock = MockTransport::new().respond(ok).respond(moved).respond(ok).respond(ok);
+    let client = crate::Client::builder().transport(mock.clone()).build().unwrap();
+    let targets = vec!["http://a.example/x".parse().unwrap(), "http://b.example/".parse().unwrap()];
+    // 非法的 Host 值直接失败，不发送请求
+    let probes = client.host_sweep(targets, ["a.example", "evil.example", "bad\nhost"]);
+    let keys: Vec<_> = probes.iter().map(|p| (p.target.to_string(), p.host.clone())).collect();
+    assert_eq!(
+      keys,
+      [
+        ("http://a.example/x", "a.example"),
+        ("http://a.example/x", "evil.example"),
+        ("http://a.example/x", "bad\nhost"),
+        ("http://b.example/", "a.example"),
+        ("http://b.example/", "evil.example"),
+        ("http://b.example/", "bad\nhost"),
+      ]
+      .map(|(t, h)| (t.to_string(), h.to_string()))
+    );
+    // 跳转不跟随，Location 留给调用方
+    let moved = probes[1].result.as_ref().unwrap();
+    assert_eq!(moved.status_code(), 302);
+    assert_eq!(moved.headers()["location"], "http://evil.example/");
+    assert!(probes[2].result.is_err());
+    assert!(probes[5].result.is_err());
+    let requests: Vec<String> =
+      mock.requests().iter().map(|r| String::from_utf8_lossy(r).to_lowercase()).collect();
+    assert_eq!(requests.len(), 4);
+    assert!(requests[0].starts_with("get /x http/1.1\r\n"));
+    assert!(requests[0].contains("\r\nhost: a.example\r\n"));
+    assert!(requests[1].contains("\r\nhost: evil.example\r\n"));
+    assert!(requests[3].starts_with("get / http/1.1\r\n"));
+    assert!(requests[3].contains("\r\nhost: evil.example\r\n"));
+  }
+}
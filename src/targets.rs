@@ -0,0 +1,313 @@
+use crate::errors::{new_io_error, Result};
+use crate::Request;
+use http::{Method, Uri};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+
+/// Turns mixed target lists into deduplicated candidate URLs.
+///
+/// Every non-empty line that does not start with `#` is one of:
+///
+/// - a URL with a scheme such as `https://example.com:8443/admin`, kept as is;
+/// - a hostname or IP address with an optional port and path, such as
+///   `example.com`, `10.0.0.1:8080` or `[::1]/status`;
+/// - a CIDR range such as `192.168.1.0/30`, expanded to every address in it.
+///
+/// Hosts without a port get one URL per scheme and port, hosts with a port one
+/// URL per scheme. The default port of a scheme is left out of the URL, so
+/// `example.com` and `http://example.com:80` end up as the same URL.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::targets::Targets;
+///
+/// let urls = Targets::new()
+///   .ports([80, 8080])
+///   .parse("example.com\n10.0.0.0/31\nhttps://example.org/login")?;
+/// assert_eq!(urls.len(), 13);
+/// let client = slinger::Client::new();
+/// for request in Targets::requests(slinger::Method::GET, urls) {
+///   let _ = client.execute(request);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Targets {
+  schemes: Vec<String>,
+  ports: Vec<u16>,
+  max_hosts: u128,
+}
+
+impl Default for Targets {
+  fn default() -> Self {
+    Targets::new()
+  }
+}
+
+impl Targets {
+  /// Expand to `http` and `https` on their default ports, with CIDR ranges
+  /// limited to 65536 addresses.
+  pub fn new() -> Self {
+    Targets {
+      schemes: vec!["http".to_string(), "https".to_string()],
+      ports: Vec::new(),
+      max_hosts: 65536,
+    }
+  }
+  /// Set the schemes every host is tried with.
+  ///
+  /// Default is `http` and `https`.
+  pub fn schemes<I, S>(mut self, schemes: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    self.schemes = schemes
+      .into_iter()
+      .map(|s| s.as_ref().to_ascii_lowercase())
+      .collect();
+    self
+  }
+  /// Set the ports every host without an explicit port is tried on.
+  ///
+  /// Default is an empty list, only the default port of each scheme.
+  pub fn ports<I: IntoIterator<Item = u16>>(mut self, ports: I) -> Self {
+    self.ports = ports.into_iter().collect();
+    self
+  }
+  /// Fail when a single CIDR range holds more than `max` addresses.
+  ///
+  /// Default is `65536`, a `/16` IPv4 range.
+  pub fn max_hosts(mut self, max: u128) -> Self {
+    self.max_hosts = max;
+    self
+  }
+  /// Parse `input` line by line, see [`Targets::parse_lines`].
+  pub fn parse(&self, input: &str) -> Result<Vec<Uri>> {
+    self.parse_lines(input.lines())
+  }
+  /// Expand every line to its candidate URLs, in input order and without
+  /// duplicates.
+  ///
+  /// # Errors
+  ///
+  /// Fails on the first line that is not a valid URL, host or CIDR range, or
+  /// a range larger than [`Targets::max_hosts`].
+  pub fn parse_lines<I, S>(&self, lines: I) -> Result<Vec<Uri>>
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let mut seen = HashSet::new();
+    let mut urls = Vec::new();
+    for line in lines {
+      let line = line.as_ref().trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      for url in self.expand(line)? {
+        if seen.insert(url.clone()) {
+          urls.push(url);
+        }
+      }
+    }
+    Ok(urls)
+  }
+  /// Build one request per URL, ready for [`Client::execute`](crate::Client::execute).
+  pub fn requests<I: IntoIterator<Item = Uri>>(
+    method: Method,
+    urls: I,
+  ) -> impl Iterator<Item = Request> {
+    urls.into_iter().map(move |url| {
+      let mut request = Request::default();
+      *request.method_mut() = method.clone();
+      *request.uri_mut() = url;
+      request
+    })
+  }
+  fn expand(&self, line: &str) -> Result<Vec<Uri>> {
+    if line.contains("://") {
+      return Ok(vec![parse_uri(line, line)?]);
+    }
+    let (host, path) = match line.find('/') {
+      Some(index) => line.split_at(index),
+      None => (line, ""),
+    };
+    // 斜杠后面是数字并且前面是 IP 时按 CIDR 处理
+    if let (Ok(ip), Ok(prefix)) = (
+      host.parse::<IpAddr>(),
+      path[1.min(path.len())..].parse::<u8>(),
+    ) {
+      let mut urls = Vec::new();
+      for ip in cidr_hosts(ip, prefix, self.max_hosts, line)? {
+        urls.extend(self.with_schemes(&format_ip(ip), None, "", line)?);
+      }
+      return Ok(urls);
+    }
+    let (host, port) = split_port(host, line)?;
+    self.with_schemes(&host, port, path, line)
+  }
+  fn with_schemes(
+    &self,
+    host: &str,
+    port: Option<u16>,
+    path: &str,
+    line: &str,
+  ) -> Result<Vec<Uri>> {
+    let ports = match port {
+      Some(port) => vec![Some(port)],
+      None if self.ports.is_empty() => vec![None],
+      None => self.ports.iter().copied().map(Some).collect(),
+    };
+    let path = if path.is_empty() { "/" } else { path };
+    let mut urls = Vec::new();
+    for scheme in &self.schemes {
+      for port in &ports {
+        let url = match port {
+          Some(port) => format!("{}://{}:{}{}", scheme, host, port, path),
+          None => format!("{}://{}{}", scheme, host, path),
+        };
+        urls.push(parse_uri(&url, line)?);
+      }
+    }
+    Ok(urls)
+  }
+}
+
+fn invalid(line: &str) -> crate::Error {
+  new_io_error(
+    ErrorKind::InvalidInput,
+    &format!("invalid target: {}", line),
+  )
+}
+
+/// 解析 URL，主机名转小写并去掉 scheme 默认端口，便于去重
+fn parse_uri(url: &str, line: &str) -> Result<Uri> {
+  let uri = Uri::try_from(url).map_err(|_| invalid(line))?;
+  let (scheme, authority) = match (uri.scheme_str(), uri.authority()) {
+    (Some(scheme), Some(authority)) => (scheme.to_ascii_lowercase(), authority),
+    _ => return Err(invalid(line)),
+  };
+  let host = authority.host().to_ascii_lowercase();
+  let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+  let url = match authority.port_u16() {
+    Some(port) if Some(port) != default_port(&scheme) => {
+      format!("{}://{}:{}{}", scheme, host, port, path)
+    }
+    _ => format!("{}://{}{}", scheme, host, path),
+  };
+  Uri::try_from(url).map_err(|_| invalid(line))
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+  match scheme {
+    "http" | "ws" => Some(80),
+    "https" | "wss" => Some(443),
+    _ => None,
+  }
+}
+
+/// 拆分 `host:port`，支持 `[::1]:8080` 和不带括号的 IPv6 地址
+fn split_port(host: &str, line: &str) -> Result<(String, Option<u16>)> {
+  if let Some(rest) = host.strip_prefix('[') {
+    let (ip, rest) = rest.split_once(']').ok_or_else(|| invalid(line))?;
+    let ip: IpAddr = ip.parse().map_err(|_| invalid(line))?;
+    let port = match rest.strip_prefix(':') {
+      Some(port) => Some(port.parse().map_err(|_| invalid(line))?),
+      None if rest.is_empty() => None,
+      None => return Err(invalid(line)),
+    };
+    return Ok((format_ip(ip), port));
+  }
+  if let Ok(ip) = host.parse::<IpAddr>() {
+    return Ok((format_ip(ip), None));
+  }
+  match host.rsplit_once(':') {
+    Some((name, port)) => Ok((
+      name.to_string(),
+      Some(port.parse().map_err(|_| invalid(line))?),
+    )),
+    None => Ok((host.to_string(), None)),
+  }
+}
+
+fn format_ip(ip: IpAddr) -> String {
+  match ip {
+    IpAddr::V4(ip) => ip.to_string(),
+    IpAddr::V6(ip) => format!("[{}]", ip),
+  }
+}
+
+/// 展开 CIDR 中的所有地址，包括网络地址和广播地址
+fn cidr_hosts(ip: IpAddr, prefix: u8, max: u128, line: &str) -> Result<Vec<IpAddr>> {
+  let bits = if ip.is_ipv4() { 32 } else { 128 };
+  if prefix > bits {
+    return Err(invalid(line));
+  }
+  let size = 1u128
+    .checked_shl(u32::from(bits - prefix))
+    .unwrap_or(u128::MAX);
+  if size > max {
+    return Err(new_io_error(
+      ErrorKind::InvalidInput,
+      &format!("target range {} has more than {} addresses", line, max),
+    ));
+  }
+  let hosts = match ip {
+    IpAddr::V4(ip) => {
+      let start = u32::from(ip) & !(u32::MAX.checked_shr(u32::from(prefix)).unwrap_or(0));
+      (0..size as u32)
+        .map(|i| IpAddr::from(std::net::Ipv4Addr::from(start + i)))
+        .collect()
+    }
+    IpAddr::V6(ip) => {
+      let start = u128::from(ip) & !(u128::MAX.checked_shr(u32::from(prefix)).unwrap_or(0));
+      (0..size)
+        .map(|i| IpAddr::from(std::net::Ipv6Addr::from(start + i)))
+        .collect()
+    }
+  };
+  Ok(hosts)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Targets;
+
+  #[test]
+  fn expand_and_dedupe() {
+    let urls = Targets::new()
+      .ports([80, 8443])
+      .parse(
+        "# targets\nexample.com\nhttp://EXAMPLE.com:80/\n10.0.0.0/31\n[::1]:9000/status\nhttps://example.org/login\n",
+      )
+      .unwrap();
+    let urls: Vec<String> = urls.iter().map(|u| u.to_string()).collect();
+    assert_eq!(
+      urls,
+      vec![
+        "http://example.com/",
+        "http://example.com:8443/",
+        "https://example.com:80/",
+        "https://example.com:8443/",
+        "http://10.0.0.0/",
+        "http://10.0.0.0:8443/",
+        "https://10.0.0.0:80/",
+        "https://10.0.0.0:8443/",
+        "http://10.0.0.1/",
+        "http://10.0.0.1:8443/",
+        "https://10.0.0.1:80/",
+        "https://10.0.0.1:8443/",
+        "http://[::1]:9000/status",
+        "https://[::1]:9000/status",
+        "https://example.org/login",
+      ]
+    );
+    assert!(Targets::new().parse("10.0.0.0/8").is_err());
+    assert!(Targets::new().parse("example.com:http").is_err());
+  }
+}
@@ -0,0 +1,300 @@
+use crate::errors::{new_io_error, Result};
+use bytes::{Bytes, BytesMut};
+use std::io::ErrorKind;
+
+/// A raw request with named placeholders, parsed once and rendered for every
+/// payload.
+///
+/// A placeholder is written either as `§name§` or as `{{name}}`, both forms
+/// name the same variable and a variable may appear any number of times. The
+/// text around the placeholders is sent byte for byte, as with
+/// [`Client::raw`](crate::Client::raw).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// use slinger::template::{Payloads, RawTemplate};
+///
+/// let template = RawTemplate::parse(
+///   "POST /login HTTP/1.1\r\nHost: example.com\r\nContent-Length: 0\r\n\r\nuser=§user§&pass={{pass}}",
+/// )?
+/// .update_content_length(true);
+/// let payloads = Payloads::new()
+///   .set("user", ["admin", "root"])
+///   .set("pass", ["123456", "password", "letmein"]);
+/// let client = slinger::Client::new();
+/// let requests = client.raw_template("http://example.com", &template, &payloads, true)?;
+/// for (values, request) in payloads.combinations().zip(requests) {
+///   let resp = request.send()?;
+///   println!("{:?} {}", values, resp.status_code());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawTemplate {
+  parts: Vec<Part>,
+  names: Vec<String>,
+  content_length: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+  Literal(Bytes),
+  /// `names` 中的下标
+  Variable(usize),
+}
+
+const SECTION: &[u8] = "§".as_bytes();
+
+impl RawTemplate {
+  /// Split `raw` into literal text and placeholders.
+  ///
+  /// # Errors
+  ///
+  /// Fails on a placeholder that is not closed or has an empty name.
+  pub fn parse<R: Into<Bytes>>(raw: R) -> Result<RawTemplate> {
+    let raw: Bytes = raw.into();
+    let mut template = RawTemplate {
+      parts: Vec::new(),
+      names: Vec::new(),
+      content_length: false,
+    };
+    let mut literal = 0;
+    let mut pos = 0;
+    while pos < raw.len() {
+      let (open, close): (&[u8], &[u8]) = if raw[pos..].starts_with(SECTION) {
+        (SECTION, SECTION)
+      } else if raw[pos..].starts_with(b"{{") {
+        (b"{{", b"}}")
+      } else {
+        pos += 1;
+        continue;
+      };
+      let start = pos + open.len();
+      let end = find(&raw[start..], close)
+        .map(|end| start + end)
+        .ok_or_else(|| invalid("unterminated placeholder", pos))?;
+      let name = std::str::from_utf8(&raw[start..end])
+        .map(str::trim)
+        .ok()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| invalid("invalid placeholder name", pos))?;
+      if literal < pos {
+        template.parts.push(Part::Literal(raw.slice(literal..pos)));
+      }
+      let index = match template.names.iter().position(|n| n == name) {
+        Some(index) => index,
+        None => {
+          template.names.push(name.to_string());
+          template.names.len() - 1
+        }
+      };
+      template.parts.push(Part::Variable(index));
+      pos = end + close.len();
+      literal = pos;
+    }
+    if literal < raw.len() {
+      template.parts.push(Part::Literal(raw.slice(literal..)));
+    }
+    Ok(template)
+  }
+  /// The names of the placeholders, in order of first appearance.
+  pub fn names(&self) -> &[String] {
+    &self.names
+  }
+  /// Rewrite the `Content-Length` header of every rendered request to the
+  /// length of its body, so payloads of any size keep the request framed.
+  ///
+  /// Only an existing header is rewritten, none is added. Default is `false`.
+  pub fn update_content_length(mut self, enable: bool) -> Self {
+    self.content_length = enable;
+    self
+  }
+  /// Render the request with `values`, pairs of a placeholder name and the
+  /// bytes it is replaced with.
+  ///
+  /// # Errors
+  ///
+  /// Fails when a placeholder of the template has no value.
+  pub fn render<I, K, V>(&self, values: I) -> Result<Bytes>
+  where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+  {
+    let mut slots: Vec<Option<V>> = self.names.iter().map(|_| None).collect();
+    for (name, value) in values {
+      if let Some(index) = self.names.iter().position(|n| n == name.as_ref()) {
+        slots[index] = Some(value);
+      }
+    }
+    let mut out = BytesMut::new();
+    for part in &self.parts {
+      match part {
+        Part::Literal(literal) => out.extend_from_slice(literal),
+        Part::Variable(index) => match &slots[*index] {
+          Some(value) => out.extend_from_slice(value.as_ref()),
+          None => return Err(missing(&self.names[*index])),
+        },
+      }
+    }
+    if self.content_length {
+      return Ok(Bytes::from(fix_content_length(&out)));
+    }
+    Ok(out.freeze())
+  }
+  /// 检查每个占位符都有对应的 payload
+  pub(crate) fn check(&self, payloads: &Payloads) -> Result<()> {
+    match self
+      .names
+      .iter()
+      .find(|name| !payloads.sets.iter().any(|(n, _)| n == *name))
+    {
+      Some(name) => Err(missing(name)),
+      None => Ok(()),
+    }
+  }
+}
+
+/// Named lists of payloads whose every combination is rendered into a
+/// [`RawTemplate`].
+///
+/// Combinations are the cartesian product of the lists, in the order the
+/// lists were set with the last one changing fastest, like a cluster bomb
+/// attack. A single list simply walks through its values.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Payloads {
+  sets: Vec<(String, Vec<Bytes>)>,
+}
+
+impl Payloads {
+  /// No payload lists.
+  pub fn new() -> Self {
+    Payloads::default()
+  }
+  /// Set the values of the placeholder `name`, replacing earlier values.
+  pub fn set<N, I, V>(mut self, name: N, values: I) -> Self
+  where
+    N: Into<String>,
+    I: IntoIterator<Item = V>,
+    V: Into<Bytes>,
+  {
+    let name = name.into();
+    let values = values.into_iter().map(Into::into).collect();
+    match self.sets.iter_mut().find(|(n, _)| *n == name) {
+      Some(set) => set.1 = values,
+      None => self.sets.push((name, values)),
+    }
+    self
+  }
+  /// The number of combinations, zero when any list is empty.
+  pub fn len(&self) -> usize {
+    if self.sets.is_empty() {
+      return 0;
+    }
+    self
+      .sets
+      .iter()
+      .map(|(_, values)| values.len())
+      .fold(1, usize::saturating_mul)
+  }
+  /// Returns true if there is no combination at all.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+  /// Every combination as `(name, value)` pairs, in the order requests are
+  /// generated by [`Client::raw_template`](crate::Client::raw_template).
+  pub fn combinations(&self) -> impl Iterator<Item = Vec<(&str, &Bytes)>> + '_ {
+    (0..self.len()).map(move |mut n| {
+      let mut combination: Vec<(&str, &Bytes)> = self
+        .sets
+        .iter()
+        .rev()
+        .map(|(name, values)| {
+          let value = &values[n % values.len()];
+          n /= values.len();
+          (name.as_str(), value)
+        })
+        .collect();
+      combination.reverse();
+      combination
+    })
+  }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn invalid(reason: &str, offset: usize) -> crate::Error {
+  new_io_error(
+    ErrorKind::InvalidInput,
+    &format!("{} at byte {} of raw template", reason, offset),
+  )
+}
+
+fn missing(name: &str) -> crate::Error {
+  new_io_error(
+    ErrorKind::InvalidInput,
+    &format!("no payload for placeholder {}", name),
+  )
+}
+
+/// 把已有的 Content-Length 改成请求头之后的实际长度
+fn fix_content_length(raw: &[u8]) -> Vec<u8> {
+  let head_end = match find(raw, b"\r\n\r\n") {
+    Some(end) => end + 4,
+    None => return raw.to_vec(),
+  };
+  let body_len = (raw.len() - head_end).to_string();
+  let mut out = Vec::with_capacity(raw.len() + body_len.len());
+  for line in raw[..head_end].split_inclusive(|b| *b == b'\n') {
+    match line.iter().position(|b| *b == b':') {
+      Some(colon) if line[..colon].eq_ignore_ascii_case(b"content-length") => {
+        out.extend_from_slice(&line[..=colon]);
+        out.push(b' ');
+        out.extend_from_slice(body_len.as_bytes());
+        out.extend_from_slice(b"\r\n");
+      }
+      _ => out.extend_from_slice(line),
+    }
+  }
+  out.extend_from_slice(&raw[head_end..]);
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Payloads, RawTemplate};
+
+  #[test]
+  fn render_combinations() {
+    let template = RawTemplate::parse(
+      "POST /§path§ HTTP/1.1\r\nContent-Length: 1\r\n\r\na={{ user }}&b=§user§",
+    )
+    .unwrap()
+    .update_content_length(true);
+    assert_eq!(template.names(), ["path", "user"]);
+    let payloads = Payloads::new()
+      .set("path", ["x", "login"])
+      .set("user", ["admin", "root"]);
+    assert_eq!(payloads.len(), 4);
+    let rendered: Vec<_> = payloads
+      .combinations()
+      .map(|values| template.render(values).unwrap())
+      .collect();
+    assert_eq!(
+      rendered[1],
+      "POST /x HTTP/1.1\r\nContent-Length: 13\r\n\r\na=root&b=root"
+    );
+    assert_eq!(
+      rendered[2],
+      "POST /login HTTP/1.1\r\nContent-Length: 15\r\n\r\na=admin&b=admin"
+    );
+    assert!(template.render([("path", "x")]).is_err());
+    assert!(RawTemplate::parse("GET /{{path HTTP/1.1\r\n\r\n").is_err());
+    assert!(RawTemplate::parse("GET /§§ HTTP/1.1\r\n\r\n").is_err());
+  }
+}
@@ -0,0 +1,454 @@
+use crate::errors::{new_io_error, Error, Result};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use openssl::ex_data::Index;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{Ssl, SslConnectorBuilder, SslRef, SslSession};
+use openssl::x509::{X509Ref, X509};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// 缓存的主机数量上限，扫描大量主机时不会无限增长
+const MAX_SESSIONS: usize = 1024;
+
+/// 按主机缓存的 TLS 会话，重连同一个主机时恢复会话而不是完整握手；克隆共享同一份缓存
+#[derive(Clone, Default)]
+pub(crate) struct SessionCache(Arc<Mutex<Sessions>>);
+
+/// 会话和它们的插入顺序，满了以后先淘汰最早保存的主机
+#[derive(Default)]
+struct Sessions {
+  entries: HashMap<String, SslSession>,
+  order: VecDeque<String>,
+}
+
+impl std::fmt::Debug for SessionCache {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let hosts = self.0.lock().map(|sessions| sessions.entries.len()).unwrap_or_default();
+    f.debug_struct("SessionCache").field("hosts", &hosts).finish()
+  }
+}
+
+impl PartialEq for SessionCache {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl SessionCache {
+  pub(crate) fn get(&self, key: &str) -> Option<SslSession> {
+    self.0.lock().ok()?.entries.get(key).cloned()
+  }
+  pub(crate) fn insert(&self, key: String, session: SslSession) {
+    // 连接没有发送 close_notify 就关闭时 OpenSSL 会把连接上的会话标记为不可恢复，
+    // 保存一份独立的副本
+    let session = match session.to_der().and_then(|der| SslSession::from_der(&der)) {
+      Ok(session) => session,
+      Err(_) => return,
+    };
+    if let Ok(mut sessions) = self.0.lock() {
+      let sessions = &mut *sessions;
+      if sessions.entries.insert(key.clone(), session).is_some() {
+        sessions.order.retain(|k| *k != key);
+      }
+      sessions.order.push_back(key);
+      while sessions.order.len() > MAX_SESSIONS {
+        if let Some(evicted) = sessions.order.pop_front() {
+          sessions.entries.remove(&evicted);
+        }
+      }
+    }
+  }
+}
+
+/// 缓存的键：校验设置不同的连接器共享缓存时，不校验证书得到的会话不能被校验证书的连接恢复
+pub(crate) fn session_key(host: &str, certs_verification: bool, hostname_verification: bool) -> String {
+  format!("{}{}:{}", u8::from(certs_verification), u8::from(hostname_verification), host)
+}
+
+/// 连接上保存的缓存键，新会话回调里用它保存会话
+pub(crate) fn session_key_index() -> Index<Ssl, String> {
+  static INDEX: OnceLock<Index<Ssl, String>> = OnceLock::new();
+  *INDEX.get_or_init(|| Ssl::new_ex_index().expect("failed to allocate ssl ex data index"))
+}
+
+/// 和 native-tls 一样加载系统的根证书，加载失败的位置直接跳过
+pub(crate) fn load_system_roots(builder: &mut SslConnectorBuilder) {
+  static PROBE: OnceLock<openssl_probe::ProbeResult> = OnceLock::new();
+  let probe = PROBE.get_or_init(openssl_probe::probe);
+  if let Some(cert_file) = &probe.cert_file {
+    let _ = builder.load_verify_locations(Some(cert_file), None);
+  }
+  for cert_dir in &probe.cert_dir {
+    let _ = builder.load_verify_locations(None, Some(cert_dir));
+  }
+}
+
+/// 密钥日志的位置：显式配置的路径优先，其次是 `SSLKEYLOGFILE` 环境变量
+pub(crate) fn keylog_path(configured: Option<&Path>) -> Option<PathBuf> {
+  configured
+    .map(Path::to_path_buf)
+    .or_else(|| std::env::var_os("SSLKEYLOGFILE").filter(|p| !p.is_empty()).map(PathBuf::from))
+}
+
+/// 在 TLS 握手时以 NSS 密钥日志格式追加写入，每行一条
+pub(crate) fn keylog_callback(path: &Path) -> Result<impl Fn(&openssl::ssl::SslRef, &str) + Send + Sync + 'static> {
+  let file: Mutex<File> = Mutex::new(OpenOptions::new().create(true).append(true).open(path)?);
+  Ok(move |_: &openssl::ssl::SslRef, line: &str| {
+    if let Ok(mut file) = file.lock() {
+      let _ = writeln!(file, "{}", line);
+    }
+  })
+}
+
+/// 按主机固定的公钥哈希，握手完成后证书链中至少一个证书的 SPKI 哈希必须匹配
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Pins(HashMap<String, Vec<[u8; 32]>>);
+
+impl Pins {
+  /// 解析 base64 的哈希，可以带 `sha256/` 前缀
+  pub(crate) fn new(pins: &[(String, Vec<String>)]) -> Result<Pins> {
+    let mut hosts: HashMap<String, Vec<[u8; 32]>> = HashMap::new();
+    for (host, hashes) in pins {
+      let entry = hosts.entry(host.to_ascii_lowercase()).or_default();
+      for hash in hashes {
+        let encoded = hash.trim();
+        let encoded = encoded.strip_prefix("sha256/").unwrap_or(encoded);
+        let hash = BASE64_STANDARD
+          .decode(encoded)
+          .ok()
+          .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+          .ok_or_else(|| new_io_error(std::io::ErrorKind::InvalidInput, "invalid sha256 pin"))?;
+        entry.push(hash);
+      }
+    }
+    Ok(Pins(hosts))
+  }
+  pub(crate) fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+  /// 没有为这个主机固定哈希时直接通过
+  pub(crate) fn check(&self, host: &str, ssl: &SslRef) -> Result<()> {
+    let Some(pins) = self.0.get(&host.to_ascii_lowercase()) else {
+      return Ok(());
+    };
+    // 不校验证书时没有验证过的链，退回到服务器发送的链；AWS-LC 只有服务器发送的链
+    #[cfg(not(any(feature = "aws-lc", feature = "aws-lc-fips")))]
+    let chain = ssl.verified_chain().or_else(|| ssl.peer_cert_chain());
+    #[cfg(any(feature = "aws-lc", feature = "aws-lc-fips"))]
+    let chain = ssl.peer_cert_chain();
+    let matched = chain
+      .into_iter()
+      .flatten()
+      .chain(ssl.peer_certificate().as_deref())
+      .filter_map(|cert| spki_sha256(cert).ok())
+      .any(|hash| pins.contains(&hash));
+    if matched {
+      Ok(())
+    } else {
+      Err(Error::CertificatePin(host.to_string()))
+    }
+  }
+}
+
+/// 证书中 SubjectPublicKeyInfo 的 DER 编码的 SHA-256
+pub(crate) fn spki_sha256(cert: &X509Ref) -> Result<[u8; 32]> {
+  let der = cert.public_key()?.public_key_to_der()?;
+  Ok(Sha256::digest(der).into())
+}
+
+/// ALPN 协议列表的线路格式：每个协议前面是一个字节的长度
+pub(crate) fn alpn_wire_format(protocols: &[String]) -> Result<Vec<u8>> {
+  let mut wire = Vec::new();
+  for protocol in protocols {
+    let len = u8::try_from(protocol.len()).map_err(|_| {
+      new_io_error(std::io::ErrorKind::InvalidInput, "alpn protocol is too long")
+    })?;
+    wire.push(len);
+    wire.extend_from_slice(protocol.as_bytes());
+  }
+  Ok(wire)
+}
+
+/// 测试用的 TLS 服务端，证书是自签名的 `localhost`
+/// A root certificate trusted in addition to the system store, see
+/// [`ClientBuilder::add_root_certificate`](crate::ClientBuilder::add_root_certificate).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Certificate(X509);
+
+impl Certificate {
+  /// Parse a DER encoded certificate.
+  pub fn from_der(der: &[u8]) -> Result<Certificate> {
+    Ok(Certificate(X509::from_der(der)?))
+  }
+  /// Parse a PEM encoded certificate, the first one when there are several.
+  pub fn from_pem(pem: &[u8]) -> Result<Certificate> {
+    Ok(Certificate(X509::from_pem(pem)?))
+  }
+  /// Parse every certificate of a PEM bundle, such as a CA file.
+  pub fn stack_from_pem(pem: &[u8]) -> Result<Vec<Certificate>> {
+    Ok(X509::stack_from_pem(pem)?.into_iter().map(Certificate).collect())
+  }
+  pub(crate) fn x509(&self) -> &X509 {
+    &self.0
+  }
+}
+
+impl From<X509> for Certificate {
+  fn from(cert: X509) -> Certificate {
+    Certificate(cert)
+  }
+}
+
+/// A client certificate with its private key, sent when the server asks for
+/// one, see [`ClientBuilder::identity`](crate::ClientBuilder::identity).
+#[derive(Clone, Debug)]
+pub struct Identity {
+  cert: X509,
+  key: PKey<Private>,
+  chain: Vec<X509>,
+}
+
+impl Identity {
+  /// Parse a PEM file holding the certificate, its private key and
+  /// optionally the intermediate certificates, in any order.
+  pub fn from_pem(pem: &[u8]) -> Result<Identity> {
+    Identity::from_pkcs8_pem(pem, pem)
+  }
+  /// Use a PEM certificate chain, leaf first, with a PEM private key.
+  pub fn from_pkcs8_pem(pem: &[u8], key: &[u8]) -> Result<Identity> {
+    let mut certs = X509::stack_from_pem(pem)?.into_iter();
+    let cert = certs
+      .next()
+      .ok_or_else(|| new_io_error(std::io::ErrorKind::InvalidData, "no certificate in the pem"))?;
+    Ok(Identity {
+      cert,
+      key: PKey::private_key_from_pem(key)?,
+      chain: certs.collect(),
+    })
+  }
+  /// Parse a DER encoded PKCS #12 archive protected by `password`.
+  pub fn from_pkcs12_der(der: &[u8], password: &str) -> Result<Identity> {
+    let parsed = Pkcs12::from_der(der)?.parse2(password)?;
+    let (Some(cert), Some(key)) = (parsed.cert, parsed.pkey) else {
+      return Err(new_io_error(
+        std::io::ErrorKind::InvalidData,
+        "pkcs12 archive without a certificate and private key",
+      ));
+    };
+    let chain = parsed.ca.map(|ca| ca.into_iter().collect()).unwrap_or_default();
+    Ok(Identity { cert, key, chain })
+  }
+  /// 握手时把证书、私钥和中间证书交给服务器
+  pub(crate) fn configure(&self, builder: &mut SslConnectorBuilder) -> Result<()> {
+    builder.set_certificate(&self.cert)?;
+    builder.set_private_key(&self.key)?;
+    for cert in &self.chain {
+      builder.add_extra_chain_cert(cert.clone())?;
+    }
+    Ok(())
+  }
+}
+
+impl PartialEq for Identity {
+  fn eq(&self, other: &Self) -> bool {
+    self.cert == other.cert && self.key.public_eq(&other.key) && self.chain == other.chain
+  }
+}
+
+#[cfg(test)]
+pub(crate) fn test_acceptor(alpn: &'static [u8]) -> openssl::ssl::SslAcceptor {
+  use openssl::asn1::Asn1Time;
+  use openssl::ec::{EcGroup, EcKey};
+  use openssl::hash::MessageDigest;
+  use openssl::nid::Nid;
+  use openssl::pkey::PKey;
+  use openssl::ssl::{AlpnError, SslAcceptor, SslMethod};
+  use openssl::x509::{X509Builder, X509NameBuilder};
+
+  let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+  let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+  let mut name = X509NameBuilder::new().unwrap();
+  name.append_entry_by_text("CN", "localhost").unwrap();
+  let name = name.build();
+  let mut cert = X509Builder::new().unwrap();
+  cert.set_subject_name(&name).unwrap();
+  cert.set_issuer_name(&name).unwrap();
+  cert.set_pubkey(&key).unwrap();
+  cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+  cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+  cert.sign(&key, MessageDigest::sha256()).unwrap();
+  let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+  acceptor.set_private_key(&key).unwrap();
+  acceptor.set_certificate(&cert.build()).unwrap();
+  if !alpn.is_empty() {
+    acceptor.set_alpn_select_callback(move |_, offered| {
+      openssl::ssl::select_next_proto(alpn, offered).ok_or(AlpnError::NOACK)
+    });
+  }
+  acceptor.build()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{spki_sha256, test_acceptor, Identity, Pins, SessionCache, MAX_SESSIONS};
+  use base64::prelude::BASE64_STANDARD;
+  use base64::Engine;
+  use openssl::asn1::Asn1Time;
+  use openssl::hash::MessageDigest;
+  use openssl::pkey::PKey;
+  use openssl::rsa::Rsa;
+  use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+  use openssl::x509::{X509Builder, X509NameBuilder};
+  use std::io::{Read, Write};
+  use std::net::{TcpListener, TcpStream};
+
+  #[test]
+  fn parse_pins_and_hash_spki() {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "example.com").unwrap();
+    let name = name.build();
+    let mut cert = X509Builder::new().unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+    let cert = cert.build();
+    let hash = spki_sha256(&cert).unwrap();
+    let expected = openssl::sha::sha256(&key.public_key_to_der().unwrap());
+    assert_eq!(hash, expected);
+    let pin = format!("sha256/{}", BASE64_STANDARD.encode(hash));
+    let pins = Pins::new(&[("Example.com".to_string(), vec![pin])]).unwrap();
+    assert_eq!(pins.0["example.com"], vec![hash]);
+    assert!(Pins::new(&[("example.com".to_string(), vec!["c2hvcnQ=".to_string()])]).is_err());
+  }
+
+  #[test]
+  fn evict_oldest_session() {
+    let acceptor = test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut stream = acceptor.accept(stream).unwrap();
+      stream.write_all(b"ok").unwrap();
+    });
+    let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+    connector.set_verify(SslVerifyMode::NONE);
+    let mut stream = connector
+      .build()
+      .connect("localhost", TcpStream::connect(addr).unwrap())
+      .unwrap();
+    let mut buf = [0u8; 2];
+    stream.read_exact(&mut buf).unwrap();
+    let session = stream.ssl().session().unwrap().to_owned();
+    let cache = SessionCache::default();
+    for host in 0..MAX_SESSIONS {
+      cache.insert(host.to_string(), session.clone());
+    }
+    // 重新保存的主机排到最后，满了以后淘汰最早保存的
+    cache.insert("0".to_string(), session.clone());
+    cache.insert("new".to_string(), session.clone());
+    assert!(cache.get("0").is_some());
+    assert!(cache.get("1").is_none());
+    assert!(cache.get("2").is_some());
+    assert!(cache.get("new").is_some());
+    assert_eq!(cache.0.lock().unwrap().entries.len(), MAX_SESSIONS);
+  }
+
+  #[test]
+  fn identity_formats() {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "client").unwrap();
+    let name = name.build();
+    let mut cert = X509Builder::new().unwrap();
+    cert.set_subject_name(&name).unwrap();
+    cert.set_issuer_name(&name).unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+    let cert = cert.build();
+    let cert_pem = cert.to_pem().unwrap();
+    let key_pem = key.private_key_to_pem_pkcs8().unwrap();
+    // 私钥在证书前面也能解析
+    let pem = Identity::from_pem(&[key_pem.clone(), cert_pem.clone()].concat()).unwrap();
+    assert_eq!(pem, Identity::from_pkcs8_pem(&cert_pem, &key_pem).unwrap());
+    let pkcs12 = openssl::pkcs12::Pkcs12::builder()
+      .name("client")
+      .pkey(&key)
+      .cert(&cert)
+      .build2("secret")
+      .unwrap();
+    let der = pkcs12.to_der().unwrap();
+    assert_eq!(Identity::from_pkcs12_der(&der, "secret").unwrap(), pem);
+    assert!(Identity::from_pkcs12_der(&der, "wrong").is_err());
+    assert!(Identity::from_pem(&key_pem).is_err());
+  }
+
+  #[cfg(any(feature = "aws-lc", feature = "aws-lc-fips"))]
+  #[test]
+  fn aws_lc_backend() {
+    assert!(openssl::version::version().contains("AWS-LC"), "{}", openssl::version::version());
+    let acceptor = test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut stream = acceptor.accept(stream).unwrap();
+      let mut buf = [0u8; 1024];
+      let _ = stream.read(&mut buf);
+      stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap();
+    });
+    let response = crate::Client::builder()
+      .impersonate(crate::Browser::Chrome120)
+      .build()
+      .unwrap()
+      .get(format!("https://{}/", addr))
+      .send()
+      .unwrap();
+    assert_eq!(response.text().unwrap(), "ok");
+  }
+
+  #[test]
+  fn keylog_file() {
+    let path = std::env::temp_dir().join(format!("slinger-keylog-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let acceptor = test_acceptor(b"");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut stream = acceptor.accept(stream).unwrap();
+      let _ = stream.read(&mut [0u8; 1]);
+    });
+    let connector = crate::ConnectorBuilder::default().keylog(&path).build().unwrap();
+    let socket = connector.connect_with_uri(&format!("https://{}/", addr).parse().unwrap()).unwrap();
+    let mut random = [0u8; 32];
+    let len = socket.ssl().unwrap().client_random(&mut random);
+    let random: String = random[..len].iter().map(|b| format!("{:02x}", b)).collect();
+    drop(socket);
+    // 每行是 `标签 客户端随机数 密钥`，Wireshark 按客户端随机数找到连接
+    let log = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(!log.is_empty());
+    for line in log.lines() {
+      let fields: Vec<_> = line.split(' ').collect();
+      assert_eq!(fields.len(), 3, "{}", line);
+      assert_eq!(fields[1], random, "{}", line);
+    }
+    assert!(log.contains("CLIENT_HANDSHAKE_TRAFFIC_SECRET ") || log.contains("CLIENT_RANDOM "));
+    // 显式配置的文件打不开时构建失败
+    let missing = std::env::temp_dir().join("slinger-keylog-missing").join("keys.log");
+    assert!(crate::ConnectorBuilder::default().keylog(missing).build().is_err());
+  }
+}
@@ -0,0 +1,188 @@
+use crate::errors::{new_io_error, Result};
+use crate::{Connector, Socket};
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Opens the connection a request is sent on.
+///
+/// [`Connector`] is the transport every `Client` uses by default. A custom
+/// transport set with [`ClientBuilder::transport`](crate::ClientBuilder::transport)
+/// replaces it for every connection of the client, for example to answer
+/// requests from memory in tests with [`MockTransport`]. The socket it returns
+/// is used as is: no proxy, PROXY protocol or TLS handshake happens on top of
+/// it.
+pub trait Transport: Send + Sync + 'static {
+  /// Open a connection to `target`.
+  fn connect(&self, target: &http::Uri) -> Result<Socket>;
+}
+
+impl Transport for Connector {
+  fn connect(&self, target: &http::Uri) -> Result<Socket> {
+    self.connect_with_uri(target)
+  }
+}
+
+/// 自定义传输层，按指针比较，让 `ConnectorBuilder` 保留 `PartialEq`
+#[derive(Clone)]
+pub(crate) struct TransportHandle(pub(crate) Arc<dyn Transport>);
+
+impl std::fmt::Debug for TransportHandle {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Transport")
+  }
+}
+
+impl PartialEq for TransportHandle {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+/// A [`Transport`] answering every connection with canned bytes, for tests
+/// that must not touch the network.
+///
+/// Each connection gets the next queued response, written byte for byte
+/// before the connection is half closed, and fails when none is left. The
+/// bytes the client sent are kept for [`MockTransport::requests`]. Clones
+/// share the same queue.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::MockTransport;
+///
+/// let mock = MockTransport::new().respond("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+/// let client = slinger::Client::builder().transport(mock.clone()).build()?;
+/// let resp = client.get("http://example.com/").send()?;
+/// assert_eq!(resp.text()?, "ok");
+/// assert!(mock.requests()[0].starts_with(b"GET / HTTP/1.1\r\n"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockTransport {
+  responses: Arc<Mutex<VecDeque<Bytes>>>,
+  peers: Arc<Mutex<Vec<JoinHandle<Vec<u8>>>>>,
+}
+
+impl MockTransport {
+  /// A transport without any response queued.
+  pub fn new() -> Self {
+    MockTransport::default()
+  }
+  /// Queue the raw response of the next connection.
+  pub fn respond<R: Into<Bytes>>(self, raw: R) -> Self {
+    if let Ok(mut responses) = self.responses.lock() {
+      responses.push_back(raw.into());
+    }
+    self
+  }
+  /// The bytes sent on every connection so far, in order of connection.
+  ///
+  /// Waits until the client closed those connections.
+  pub fn requests(&self) -> Vec<Vec<u8>> {
+    let peers = match self.peers.lock() {
+      Ok(mut peers) => std::mem::take(&mut *peers),
+      Err(_) => return Vec::new(),
+    };
+    peers
+      .into_iter()
+      .map(|peer| peer.join().unwrap_or_default())
+      .collect()
+  }
+}
+
+impl Transport for MockTransport {
+  fn connect(&self, _target: &http::Uri) -> Result<Socket> {
+    let raw = self
+      .responses
+      .lock()
+      .map_err(|_| new_io_error(ErrorKind::Other, "mock transport lock poisoned"))?
+      .pop_front()
+      .ok_or_else(|| new_io_error(ErrorKind::ConnectionRefused, "no mock response left"))?;
+    // 用回环地址上的一对连接，客户端看到的是普通的 TCP 套接字
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let client = TcpStream::connect(listener.local_addr()?)?;
+    let (mut server, _) = listener.accept()?;
+    let peer = std::thread::spawn(move || {
+      let mut received = Vec::new();
+      if server.write_all(&raw).is_ok() {
+        let _ = server.shutdown(Shutdown::Write);
+        let _ = server.read_to_end(&mut received);
+      }
+      received
+    });
+    if let Ok(mut peers) = self.peers.lock() {
+      peers.push(peer);
+    }
+    Ok(Socket::TCP(client.into()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::MockTransport;
+
+  #[test]
+  fn mock_transport() {
+    let mock = MockTransport::new()
+      .respond("HTTP/1.1 201 Created\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello")
+      .respond("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    let client = crate::Client::builder()
+      .transport(mock.clone())
+      .build()
+      .unwrap();
+    let resp = client.post("http://example.com/a").body("k=v").send().unwrap();
+    assert_eq!(resp.status_code(), 201);
+    assert_eq!(resp.text().unwrap(), "hello");
+    let resp = client.get("https://example.com/b").send().unwrap();
+    assert_eq!(resp.status_code(), 404);
+    assert!(client.get("http://example.com/c").send().is_err());
+    drop(client);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].starts_with(b"POST /a HTTP/1.1\r\n"));
+    assert!(requests[0].ends_with(b"\r\n\r\nk=v"));
+    assert!(requests[1].starts_with(b"GET /b HTTP/1.1\r\n"));
+  }
+
+  #[test]
+  fn stale_connection_retry() {
+    use std::io::{Read, Write};
+    // 服务器回复跳转后不等下一个请求的响应就关闭了连接，客户端换新连接重发一次
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let mut buf = [0; 1024];
+      let (mut first, _) = listener.accept().unwrap();
+      let n = first.read(&mut buf).unwrap();
+      assert!(buf[..n].starts_with(b"GET /a HTTP/1.1\r\n"));
+      first
+        .write_all(b"HTTP/1.1 302 Found\r\nLocation: /b\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+      let n = first.read(&mut buf).unwrap();
+      let stale = buf[..n].to_vec();
+      drop(first);
+      let (mut second, _) = listener.accept().unwrap();
+      let n = second.read(&mut buf).unwrap();
+      let retried = buf[..n].to_vec();
+      second
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+        .unwrap();
+      (stale, retried)
+    });
+    let client = crate::Client::new();
+    let resp = client.get(format!("http://{}/a", addr)).send().unwrap();
+    assert_eq!(resp.status_code(), 200);
+    assert_eq!(resp.text().unwrap(), "ok");
+    assert!(!resp.local_peer_record().unwrap().reused);
+    let (stale, retried) = server.join().unwrap();
+    assert!(stale.starts_with(b"GET /b HTTP/1.1\r\n"));
+    assert!(retried.starts_with(b"GET /b HTTP/1.1\r\n"));
+  }
+}
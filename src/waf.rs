@@ -0,0 +1,202 @@
+use crate::Response;
+use http::StatusCode;
+
+/// A web application firewall or CDN vendor recognized by [`detect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Waf {
+  /// Cloudflare
+  Cloudflare,
+  /// Akamai
+  Akamai,
+  /// AWS WAF and CloudFront
+  Aws,
+  /// Imperva Incapsula
+  Imperva,
+  /// Sucuri Website Firewall
+  Sucuri,
+  /// F5 BIG-IP ASM
+  F5BigIp,
+  /// ModSecurity
+  ModSecurity,
+  /// Fortinet FortiWeb
+  FortiWeb,
+  /// Barracuda
+  Barracuda,
+  /// Wordfence
+  Wordfence,
+  /// Azure Front Door
+  AzureFrontDoor,
+}
+
+/// What [`detect`] found on a blocked response.
+///
+/// With [`ClientBuilder::detect_waf`](crate::ClientBuilder::detect_waf) it is
+/// put in the extensions of every blocked `Response`, see
+/// [`Response::waf`](crate::Response::waf), and
+/// [`RetryPolicy`](crate::RetryPolicy) uses it to back off on rate limits and
+/// to stop retrying block pages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WafDetection {
+  /// The suspected vendor, `None` for a generic block or rate limit.
+  pub vendor: Option<Waf>,
+  /// Whether the response looks like a rate limit rather than a block.
+  pub rate_limited: bool,
+  /// The signature that matched, e.g. `header cf-ray`.
+  pub evidence: String,
+}
+
+enum Marker {
+  /// 响应头存在
+  Header(&'static str),
+  /// 响应头的值包含（不区分大小写）
+  HeaderValue(&'static str, &'static str),
+  /// Set-Cookie 中的 cookie 名前缀
+  Cookie(&'static str),
+  /// 响应体包含（不区分大小写）
+  Body(&'static str),
+}
+
+const SIGNATURES: &[(Waf, Marker)] = &[
+  (Waf::Cloudflare, Marker::Body("attention required! | cloudflare")),
+  (Waf::Cloudflare, Marker::Body("cf-error-details")),
+  (Waf::Cloudflare, Marker::HeaderValue("server", "cloudflare")),
+  (Waf::Cloudflare, Marker::Header("cf-ray")),
+  (Waf::Akamai, Marker::HeaderValue("server", "akamaighost")),
+  (Waf::Akamai, Marker::Body("errors.edgesuite.net")),
+  (Waf::Imperva, Marker::Body("incapsula incident id")),
+  (Waf::Imperva, Marker::Header("x-iinfo")),
+  (Waf::Imperva, Marker::Cookie("incap_ses")),
+  (Waf::Imperva, Marker::Cookie("visid_incap")),
+  (Waf::Sucuri, Marker::Header("x-sucuri-block")),
+  (Waf::Sucuri, Marker::Header("x-sucuri-id")),
+  (Waf::Sucuri, Marker::Body("sucuri website firewall")),
+  (Waf::F5BigIp, Marker::Body("the requested url was rejected. please consult with your administrator")),
+  (Waf::F5BigIp, Marker::Cookie("TS01")),
+  (Waf::F5BigIp, Marker::Cookie("BIGipServer")),
+  (Waf::ModSecurity, Marker::Body("mod_security")),
+  (Waf::ModSecurity, Marker::Body("modsecurity")),
+  (Waf::ModSecurity, Marker::HeaderValue("server", "mod_security")),
+  (Waf::FortiWeb, Marker::Cookie("FORTIWAFSID")),
+  (Waf::FortiWeb, Marker::Body("fortigate application control")),
+  (Waf::Barracuda, Marker::Cookie("barra_counter_session")),
+  (Waf::Barracuda, Marker::Body("barracuda networks")),
+  (Waf::Wordfence, Marker::Body("generated by wordfence")),
+  (Waf::AzureFrontDoor, Marker::Header("x-azure-ref")),
+  (Waf::Aws, Marker::Body("request blocked.")),
+  (Waf::Aws, Marker::HeaderValue("server", "awselb")),
+  (Waf::Aws, Marker::Header("x-amz-cf-id")),
+  (Waf::Aws, Marker::Header("x-amzn-waf-action")),
+];
+
+/// 只检查响应体开头这么多字节
+const BODY_PREFIX: usize = 64 * 1024;
+
+/// Look for WAF, block page and rate limit signatures on `response`.
+///
+/// Only error responses are considered: `403`, `406`, `429`, `501` and `503`,
+/// which WAFs use for block and challenge pages, plus any response carrying
+/// `x-ratelimit-remaining: 0`. Vendors are recognized from their headers,
+/// cookies and block page markers; a block without a known signature is
+/// reported with no vendor.
+///
+/// # Example
+///
+/// ```rust
+/// let response: slinger::Response = http::Response::builder()
+///   .status(403)
+///   .header("cf-ray", "8a1b2c3d4e5f-AMS")
+///   .body("<title>Attention Required! | Cloudflare</title>")
+///   .unwrap()
+///   .into();
+/// let detection = slinger::waf::detect(&response).unwrap();
+/// assert_eq!(detection.vendor, Some(slinger::waf::Waf::Cloudflare));
+/// ```
+pub fn detect(response: &Response) -> Option<WafDetection> {
+  let headers = response.headers();
+  let quota_exhausted = headers
+    .get("x-ratelimit-remaining")
+    .is_some_and(|v| v.as_bytes().trim_ascii() == b"0");
+  let rate_limited = response.status_code() == StatusCode::TOO_MANY_REQUESTS || quota_exhausted;
+  let blocking = matches!(response.status_code().as_u16(), 403 | 406 | 429 | 501 | 503);
+  if !blocking && !quota_exhausted {
+    return None;
+  }
+  let body = response
+    .body()
+    .as_ref()
+    .map(|b| &b.as_ref()[..b.len().min(BODY_PREFIX)])
+    .unwrap_or_default()
+    .to_ascii_lowercase();
+  let found = SIGNATURES.iter().find_map(|(waf, marker)| {
+    let evidence = match marker {
+      Marker::Header(name) => headers.contains_key(*name).then(|| format!("header {}", name)),
+      Marker::HeaderValue(name, value) => headers
+        .get_all(*name)
+        .iter()
+        .any(|v| contains(&v.as_bytes().to_ascii_lowercase(), value.as_bytes()))
+        .then(|| format!("header {}: {}", name, value)),
+      Marker::Cookie(prefix) => headers
+        .get_all(http::header::SET_COOKIE)
+        .iter()
+        .any(|v| v.as_bytes().starts_with(prefix.as_bytes()))
+        .then(|| format!("cookie {}", prefix)),
+      Marker::Body(text) => contains(&body, text.as_bytes()).then(|| format!("body {:?}", text)),
+    };
+    evidence.map(|evidence| (*waf, evidence))
+  });
+  match found {
+    Some((waf, evidence)) => Some(WafDetection {
+      vendor: Some(waf),
+      rate_limited,
+      evidence,
+    }),
+    None if rate_limited => Some(WafDetection {
+      vendor: None,
+      rate_limited,
+      evidence: if quota_exhausted {
+        "header x-ratelimit-remaining: 0".to_string()
+      } else {
+        "status 429".to_string()
+      },
+    }),
+    None => None,
+  }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+  haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{detect, Waf};
+  use crate::Response;
+
+  #[test]
+  fn vendors_and_rate_limits() {
+    let response = |status: u16, header: (&str, &str), body: &str| -> Response {
+      http::Response::builder()
+        .status(status)
+        .header(header.0, header.1)
+        .body(body.to_string())
+        .unwrap()
+        .into()
+    };
+    let ok = response(200, ("cf-ray", "1"), "");
+    assert!(detect(&ok).is_none());
+    let blocked = response(403, ("set-cookie", "visid_incap_123=x"), "");
+    assert_eq!(detect(&blocked).unwrap().vendor, Some(Waf::Imperva));
+    let rejected = response(
+      200,
+      ("x-ratelimit-remaining", "0"),
+      "The requested URL was rejected. Please consult with your administrator.",
+    );
+    let detection = detect(&rejected).unwrap();
+    assert_eq!(detection.vendor, Some(Waf::F5BigIp));
+    assert!(detection.rate_limited);
+    let generic = detect(&response(429, ("x-test", "1"), "slow down")).unwrap();
+    assert_eq!(generic.vendor, None);
+    assert!(detect(&response(403, ("x-test", "1"), "forbidden")).is_none());
+  }
+}
@@ -0,0 +1,201 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Logs every byte a `Client` writes to and reads from its connections, in
+/// the style of `curl -v`.
+///
+/// Set with [`ClientBuilder::wire_log`](crate::ClientBuilder::wire_log), or
+/// [`ClientBuilder::verbose`](crate::ClientBuilder::verbose) for standard
+/// error. Connection events start with `*`, sent bytes with `>` and received
+/// bytes with `<`. Bytes are logged as they go over the wire, before TLS
+/// encryption and after decryption, so an HTTP/1.x exchange reads like the
+/// raw request and response. Bytes that are not text are summarized, or
+/// written as a hexdump with [`WireLog::hexdump`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # fn run() -> Result<(), slinger::Error> {
+/// use slinger::WireLog;
+///
+/// let log = WireLog::to_writer(std::fs::File::create("wire.log")?).hexdump(true);
+/// let client = slinger::Client::builder().wire_log(log).build()?;
+/// client.get("http://httpbin.org/bytes/64").send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WireLog {
+  sink: Arc<Mutex<Box<dyn Write + Send>>>,
+  hexdump: bool,
+}
+
+impl std::fmt::Debug for WireLog {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("WireLog")
+      .field("hexdump", &self.hexdump)
+      .finish()
+  }
+}
+
+impl WireLog {
+  /// Log to standard error.
+  pub fn stderr() -> Self {
+    WireLog::to_writer(std::io::stderr())
+  }
+  /// Log to `writer`, for example a file.
+  pub fn to_writer<W: Write + Send + 'static>(writer: W) -> Self {
+    WireLog {
+      sink: Arc::new(Mutex::new(Box::new(writer))),
+      hexdump: false,
+    }
+  }
+  /// Write bytes that are not text as a hexdump instead of only their
+  /// length.
+  ///
+  /// Default is `false`.
+  pub fn hexdump(mut self, enable: bool) -> Self {
+    self.hexdump = enable;
+    self
+  }
+  /// 连接相关的事件
+  pub(crate) fn info(&self, text: &str) {
+    self.write(format!("* {}\n", text).as_bytes());
+  }
+  pub(crate) fn sent(&self, bytes: &[u8]) {
+    self.write(&self.render(b'>', bytes));
+  }
+  pub(crate) fn received(&self, bytes: &[u8]) {
+    self.write(&self.render(b'<', bytes));
+  }
+  fn write(&self, text: &[u8]) {
+    // 日志写不出去不影响请求
+    if let Ok(mut sink) = self.sink.lock() {
+      let _ = sink.write_all(text);
+      let _ = sink.flush();
+    }
+  }
+  /// 文本逐行加上方向标记，遇到第一行不是文本的内容时剩下的按二进制处理
+  fn render(&self, marker: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + 16);
+    let mut rest = bytes;
+    while !rest.is_empty() {
+      let end = rest
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(rest.len(), |n| n + 1);
+      let line = &rest[..end];
+      let text = line.strip_suffix(b"\n").unwrap_or(line);
+      let text = text.strip_suffix(b"\r").unwrap_or(text);
+      if !is_text(text) {
+        break;
+      }
+      out.extend_from_slice(&[marker, b' ']);
+      out.extend_from_slice(text);
+      out.push(b'\n');
+      rest = &rest[end..];
+    }
+    if rest.is_empty() {
+      return out;
+    }
+    if !self.hexdump {
+      let summary = format!("{} [{} bytes data]\n", marker as char, rest.len());
+      out.extend_from_slice(summary.as_bytes());
+      return out;
+    }
+    for (n, row) in rest.chunks(16).enumerate() {
+      let hex: Vec<String> = row.iter().map(|b| format!("{:02x}", b)).collect();
+      let ascii: String = row
+        .iter()
+        .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+        .collect();
+      let line = format!("{} {:04x}: {:<47}  {}\n", marker as char, n * 16, hex.join(" "), ascii);
+      out.extend_from_slice(line.as_bytes());
+    }
+    out
+  }
+}
+
+fn is_text(line: &[u8]) -> bool {
+  std::str::from_utf8(line).is_ok_and(|s| !s.chars().any(|c| c.is_control() && c != '\t'))
+}
+
+/// 写入时把字节记到日志里，用于分块发送的请求体
+pub(crate) struct LoggedWriter<'a, W: Write> {
+  inner: W,
+  log: Option<&'a WireLog>,
+}
+
+impl<'a, W: Write> LoggedWriter<'a, W> {
+  pub(crate) fn new(inner: W, log: Option<&'a WireLog>) -> Self {
+    LoggedWriter { inner, log }
+  }
+}
+
+impl<W: Write> Write for LoggedWriter<'_, W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let n = self.inner.write(buf)?;
+    if let Some(log) = self.log {
+      log.sent(&buf[..n]);
+    }
+    Ok(n)
+  }
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::WireLog;
+  use std::sync::{Arc, Mutex};
+
+  #[derive(Clone, Default)]
+  struct Shared(Arc<Mutex<Vec<u8>>>);
+
+  impl std::io::Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn render_wire_bytes() {
+    let log = WireLog::to_writer(std::io::sink());
+    assert_eq!(
+      log.render(b'>', b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"),
+      b"> GET / HTTP/1.1\n> Host: a\n> \n"
+    );
+    let raw = b"HTTP/1.1 200 OK\r\n\r\n\x1f\x8b\x08\x00abcdefghijklmnop";
+    assert_eq!(log.render(b'<', raw), b"< HTTP/1.1 200 OK\n< \n< [20 bytes data]\n");
+    let dump = String::from_utf8(log.hexdump(true).render(b'<', raw)).unwrap();
+    assert_eq!(
+      dump.lines().nth(2).unwrap(),
+      "< 0000: 1f 8b 08 00 61 62 63 64 65 66 67 68 69 6a 6b 6c  ....abcdefghijkl"
+    );
+    assert_eq!(
+      dump.lines().nth(3).unwrap(),
+      "< 0010: 6d 6e 6f 70                                      mnop"
+    );
+  }
+
+  #[test]
+  fn log_client_exchange() {
+    let shared = Shared::default();
+    let mock =
+      crate::MockTransport::new().respond("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let client = crate::Client::builder()
+      .transport(mock)
+      .wire_log(WireLog::to_writer(shared.clone()))
+      .build()
+      .unwrap();
+    client.get("http://example.com/x").send().unwrap();
+    let log = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+    assert!(log.starts_with("* Connected to example.com (127.0.0.1:"));
+    assert!(log.contains("\n> GET /x HTTP/1.1\n"));
+    assert!(log.ends_with("< HTTP/1.1 200 OK\n< Content-Length: 2\n< \n< ok\n"));
+  }
+}